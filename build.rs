@@ -0,0 +1,18 @@
+// Only does anything with the `header` feature: writes include/nova.h for
+// the C API in src/capi.rs.
+fn main() {
+    #[cfg(feature = "header")]
+    {
+        println!("cargo:rerun-if-changed=src/capi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+            .expect("cbindgen.toml");
+        cbindgen::Builder::new()
+            .with_src(format!("{}/src/capi.rs", crate_dir))
+            .with_config(config)
+            .generate()
+            .expect("generating nova.h")
+            .write_to_file(format!("{}/include/nova.h", crate_dir));
+    }
+}
@@ -0,0 +1,76 @@
+/* Generated by cbindgen from src/capi.rs; do not edit. */
+
+#ifndef NOVA_H
+#define NOVA_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define NOVA_OK 0
+
+/*
+ The source didn't parse; the result string holds the diagnostics
+ */
+#define NOVA_ERR_PARSE 1
+
+/*
+ NULL handle/source, or source that isn't UTF-8
+ */
+#define NOVA_ERR_ARGUMENT 2
+
+/*
+ The source didn't type-check, or evaluating it failed; the result
+ string holds the error
+ */
+#define NOVA_ERR_RUNTIME 3
+
+/*
+ Opaque to C; only ever handled through a pointer
+ */
+typedef struct NovaInterpreter NovaInterpreter;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Creates an interpreter with an empty environment. Release it with
+ nova_free.
+ */
+struct NovaInterpreter *nova_new(void);
+
+/*
+ Evaluates a NUL-terminated source string. The printed form of the
+ value (or what went wrong) is available from nova_get_string_result.
+
+ # Safety
+ `interp` must be NULL or a live pointer from `nova_new`, and `source`
+ NULL or a NUL-terminated string.
+ */
+int nova_eval(struct NovaInterpreter *interp, const char *source);
+
+/*
+ Result of the last nova_eval. The string belongs to the interpreter and
+ stays valid until the next nova_eval or nova_free; copy it to keep it.
+
+ # Safety
+ `interp` must be NULL or a live pointer from `nova_new`.
+ */
+const char *nova_get_string_result(const struct NovaInterpreter *interp);
+
+/*
+ Releases the interpreter and its last result.
+
+ # Safety
+ `interp` must be NULL or a pointer from `nova_new` that hasn't been
+ freed yet.
+ */
+void nova_free(struct NovaInterpreter *interp);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* NOVA_H */
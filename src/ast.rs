@@ -1,4 +1,15 @@
+use std::fmt;
+use std::sync::Arc;
 use crate::token::TokenType;
+use crate::span::Span;
+use crate::decimal::Decimal;
+
+mod diff;
+pub use diff::{diff, Change};
+
+// Statements carry an id so tools (incremental reparse, LSP) can track
+// them across edits. Ids are unique within one parse.
+pub type NodeId = usize;
 
 pub trait Node {
     fn token_literal(&self) -> String;
@@ -9,11 +20,25 @@ pub trait Node {
 #[derive(Debug, Clone, PartialEq)] 
 pub struct Program {
     pub statements: Vec<Statement>,
+    // Names from the `#pragma` lines at the top ("strict")
+    pub pragmas: Vec<String>,
+    // Experimental features a `#pragma feature(...)` turned on (see
+    // features.rs)
+    pub features: Vec<String>,
+    // The macros it defines, by name and where; the calls to them are
+    // expanded already (see macros.rs)
+    pub macros: Vec<(String, Span)>,
+}
+
+impl Program {
+    pub fn max_id(&self) -> NodeId {
+        self.statements.iter().map(|s| s.max_id()).max().unwrap_or(0)
+    }
 }
 
 impl Node for Program {
     fn token_literal(&self) -> String {
-        if self.statements.len() > 0 {
+        if !self.statements.is_empty() {
             self.statements[0].token_literal()
         } else {
             String::from("")
@@ -28,22 +53,502 @@ impl Node for Program {
 #[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
     Let(LetStatement),
+    LetTuple(LetTupleStatement),
     Return(ReturnStatement),
+    Yield(YieldStatement),
+    Defer(DeferStatement),
+    Break(BreakStatement),
+    Continue(ContinueStatement),
     Expression(ExpressionStatement),
 }
 
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Let(s) => s.span,
+            Statement::LetTuple(s) => s.span,
+            Statement::Return(s) => s.span,
+            Statement::Yield(s) => s.span,
+            Statement::Defer(s) => s.span,
+            Statement::Break(s) => s.span,
+            Statement::Continue(s) => s.span,
+            Statement::Expression(s) => s.span,
+        }
+    }
+
+    pub fn id(&self) -> NodeId {
+        match self {
+            Statement::Let(s) => s.id,
+            Statement::LetTuple(s) => s.id,
+            Statement::Return(s) => s.id,
+            Statement::Yield(s) => s.id,
+            Statement::Defer(s) => s.id,
+            Statement::Break(s) => s.id,
+            Statement::Continue(s) => s.id,
+            Statement::Expression(s) => s.id,
+        }
+    }
+
+    pub fn set_id(&mut self, id: NodeId) {
+        match self {
+            Statement::Let(s) => s.id = id,
+            Statement::LetTuple(s) => s.id = id,
+            Statement::Return(s) => s.id = id,
+            Statement::Yield(s) => s.id = id,
+            Statement::Defer(s) => s.id = id,
+            Statement::Break(s) => s.id = id,
+            Statement::Continue(s) => s.id = id,
+            Statement::Expression(s) => s.id = id,
+        }
+    }
+
+    // Moves every span in this subtree by `delta` chars
+    pub fn shift_spans(&mut self, delta: isize) {
+        match self {
+            Statement::Let(s) => {
+                s.span = shift(s.span, delta);
+                s.name.span = shift(s.name.span, delta);
+                s.value.shift_spans(delta);
+            },
+            Statement::LetTuple(s) => {
+                s.span = shift(s.span, delta);
+                for name in &mut s.names {
+                    name.span = shift(name.span, delta);
+                }
+                s.value.shift_spans(delta);
+            },
+            Statement::Return(s) => {
+                s.span = shift(s.span, delta);
+                s.return_value.shift_spans(delta);
+            },
+            Statement::Yield(s) => {
+                s.span = shift(s.span, delta);
+                s.value.shift_spans(delta);
+            },
+            Statement::Defer(s) => {
+                s.span = shift(s.span, delta);
+                s.value.shift_spans(delta);
+            },
+            Statement::Break(s) => {
+                s.span = shift(s.span, delta);
+                if let Some(value) = &mut s.value {
+                    value.shift_spans(delta);
+                }
+            },
+            Statement::Continue(s) => s.span = shift(s.span, delta),
+            Statement::Expression(s) => {
+                s.span = shift(s.span, delta);
+                s.expression.shift_spans(delta);
+            },
+        }
+    }
+
+    // Every variable this subtree binds or uses, not counting the insides
+    // of nested functions; `binds` is true for the names a let or for
+    // introduces
+    pub fn visit_variables_mut(&mut self, f: &mut impl FnMut(&mut Identifier, bool)) {
+        match self {
+            Statement::Let(s) => {
+                s.value.visit_variables_mut(f);
+                f(&mut s.name, true);
+            },
+            Statement::LetTuple(s) => {
+                s.value.visit_variables_mut(f);
+                for name in &mut s.names {
+                    f(name, true);
+                }
+            },
+            Statement::Return(s) => s.return_value.visit_variables_mut(f),
+            Statement::Yield(s) => s.value.visit_variables_mut(f),
+            Statement::Defer(s) => s.value.visit_variables_mut(f),
+            Statement::Break(s) => {
+                if let Some(value) = &mut s.value {
+                    value.visit_variables_mut(f);
+                }
+            },
+            Statement::Continue(_) => {},
+            Statement::Expression(s) => s.expression.visit_variables_mut(f),
+        }
+    }
+
+    // Largest id used in this subtree
+    pub fn max_id(&self) -> NodeId {
+        let nested = match self {
+            Statement::Let(s) => s.value.max_id(),
+            Statement::LetTuple(s) => s.value.max_id(),
+            Statement::Return(s) => s.return_value.max_id(),
+            Statement::Yield(s) => s.value.max_id(),
+            Statement::Defer(s) => s.value.max_id(),
+            Statement::Break(s) => s.value.as_ref().map_or(0, |v| v.max_id()),
+            Statement::Continue(_) => 0,
+            Statement::Expression(s) => s.expression.max_id(),
+        };
+        self.id().max(nested)
+    }
+
+    // Whether a `yield` is reachable without entering another function
+    pub fn contains_yield(&self) -> bool {
+        self.suspends_at(Suspension::Yield)
+    }
+
+    pub fn contains_await(&self) -> bool {
+        self.suspends_at(Suspension::Await)
+    }
+
+    pub fn suspends_at(&self, point: Suspension) -> bool {
+        match self {
+            Statement::Yield(s) => point == Suspension::Yield || s.value.suspends_at(point),
+            Statement::Let(s) => s.value.suspends_at(point),
+            Statement::LetTuple(s) => s.value.suspends_at(point),
+            Statement::Return(s) => s.return_value.suspends_at(point),
+            Statement::Break(s) => s.value.as_ref().is_some_and(|v| v.suspends_at(point)),
+            // Runs on the way out, not where it's written
+            Statement::Defer(_) | Statement::Continue(_) => false,
+            Statement::Expression(s) => s.expression.suspends_at(point),
+        }
+    }
+}
+
+fn shift(span: Span, delta: isize) -> Span {
+    Span::new(span.start.saturating_add_signed(delta), span.end.saturating_add_signed(delta))
+}
+
+// `outer: loop { ... }`
+fn labelled(label: &Option<String>, code: String) -> String {
+    match label {
+        Some(label) => format!("{}: {}", label, code),
+        None => code,
+    }
+}
+
+impl Expression {
+    pub fn shift_spans(&mut self, delta: isize) {
+        match self {
+            Expression::Identifier(e) => e.span = shift(e.span, delta),
+            Expression::IntegerLiteral(_) | Expression::StringLiteral(_) | Expression::CharLiteral(_) |
+            Expression::DecimalLiteral(_) |
+            Expression::Boolean(_) => {},
+            Expression::Prefix(e) => e.right.shift_spans(delta),
+            Expression::Await(e) => e.value.shift_spans(delta),
+            Expression::Infix(e) => {
+                e.left.shift_spans(delta);
+                e.right.shift_spans(delta);
+            },
+            Expression::Comparison(e) => {
+                for operand in &mut e.operands {
+                    operand.shift_spans(delta);
+                }
+            },
+            Expression::If(e) => {
+                e.condition.shift_spans(delta);
+                e.consequence.shift_spans(delta);
+                if let Some(alt) = &mut e.alternative {
+                    alt.shift_spans(delta);
+                }
+            },
+            Expression::Function(e) => {
+                for p in e.parameters.iter_mut().chain(&mut e.captures) {
+                    p.span = shift(p.span, delta);
+                }
+                e.body.shift_spans(delta);
+            },
+            Expression::Call(e) => {
+                e.function.shift_spans(delta);
+                for a in &mut e.arguments {
+                    a.shift_spans(delta);
+                }
+                for (name, value) in &mut e.keywords {
+                    name.span = shift(name.span, delta);
+                    value.shift_spans(delta);
+                }
+                if let Some(block) = &mut e.block {
+                    block.shift_spans(delta);
+                }
+            },
+            Expression::Array(e) => {
+                for el in &mut e.elements {
+                    el.shift_spans(delta);
+                }
+            },
+            Expression::Tuple(e) => {
+                for el in &mut e.elements {
+                    el.shift_spans(delta);
+                }
+            },
+            Expression::Hash(e) => {
+                for (key, value) in &mut e.pairs {
+                    key.shift_spans(delta);
+                    value.shift_spans(delta);
+                }
+            },
+            Expression::Index(e) => {
+                e.left.shift_spans(delta);
+                e.index.shift_spans(delta);
+            },
+            Expression::Slice(e) => {
+                e.left.shift_spans(delta);
+                for bound in [&mut e.start, &mut e.end].into_iter().flatten() {
+                    bound.shift_spans(delta);
+                }
+            },
+            Expression::Unsafe(e) => e.body.shift_spans(delta),
+            Expression::For(e) => {
+                e.variable.span = shift(e.variable.span, delta);
+                e.iterable.shift_spans(delta);
+                e.body.shift_spans(delta);
+            },
+            Expression::Loop(e) => e.body.shift_spans(delta),
+            Expression::DoWhile(e) => {
+                e.body.shift_spans(delta);
+                e.condition.shift_spans(delta);
+            },
+            Expression::Match(e) => {
+                e.subject.shift_spans(delta);
+                for arm in &mut e.arms {
+                    arm.pattern.visit_bindings_mut(&mut |name| name.span = shift(name.span, delta));
+                    if let Some(guard) = &mut arm.guard {
+                        guard.shift_spans(delta);
+                    }
+                    arm.body.shift_spans(delta);
+                }
+            },
+            Expression::Switch(e) => {
+                e.subject.shift_spans(delta);
+                for body in e.bodies_mut() {
+                    body.shift_spans(delta);
+                }
+            },
+            Expression::Zone(e) => {
+                for (name, value) in &mut e.limits {
+                    name.span = shift(name.span, delta);
+                    value.shift_spans(delta);
+                }
+                e.body.shift_spans(delta);
+            },
+        }
+    }
+
+    pub fn visit_variables_mut(&mut self, f: &mut impl FnMut(&mut Identifier, bool)) {
+        match self {
+            Expression::Identifier(e) => f(e, false),
+            Expression::IntegerLiteral(_) | Expression::StringLiteral(_) | Expression::CharLiteral(_) |
+            Expression::DecimalLiteral(_) | Expression::Boolean(_) => {},
+            // The body has its own frame; only what it captures is read here
+            Expression::Function(e) => {
+                for c in &mut e.captures {
+                    f(c, false);
+                }
+            },
+            Expression::Prefix(e) => e.right.visit_variables_mut(f),
+            Expression::Await(e) => e.value.visit_variables_mut(f),
+            Expression::Infix(e) => {
+                e.left.visit_variables_mut(f);
+                e.right.visit_variables_mut(f);
+            },
+            Expression::Comparison(e) => {
+                for operand in &mut e.operands {
+                    operand.visit_variables_mut(f);
+                }
+            },
+            Expression::If(e) => {
+                e.condition.visit_variables_mut(f);
+                e.consequence.visit_variables_mut(f);
+                if let Some(alt) = &mut e.alternative {
+                    alt.visit_variables_mut(f);
+                }
+            },
+            Expression::Call(e) => {
+                e.function.visit_variables_mut(f);
+                for a in &mut e.arguments {
+                    a.visit_variables_mut(f);
+                }
+                for (_, value) in &mut e.keywords {
+                    value.visit_variables_mut(f);
+                }
+                if let Some(block) = &mut e.block {
+                    block.visit_variables_mut(f);
+                }
+            },
+            Expression::Array(e) => {
+                for el in &mut e.elements {
+                    el.visit_variables_mut(f);
+                }
+            },
+            Expression::Tuple(e) => {
+                for el in &mut e.elements {
+                    el.visit_variables_mut(f);
+                }
+            },
+            Expression::Hash(e) => {
+                for (key, value) in &mut e.pairs {
+                    key.visit_variables_mut(f);
+                    value.visit_variables_mut(f);
+                }
+            },
+            Expression::Index(e) => {
+                e.left.visit_variables_mut(f);
+                e.index.visit_variables_mut(f);
+            },
+            Expression::Slice(e) => {
+                e.left.visit_variables_mut(f);
+                for bound in [&mut e.start, &mut e.end].into_iter().flatten() {
+                    bound.visit_variables_mut(f);
+                }
+            },
+            Expression::Unsafe(e) => e.body.visit_variables_mut(f),
+            Expression::For(e) => {
+                e.iterable.visit_variables_mut(f);
+                f(&mut e.variable, true);
+                e.body.visit_variables_mut(f);
+            },
+            Expression::Loop(e) => e.body.visit_variables_mut(f),
+            Expression::DoWhile(e) => {
+                e.body.visit_variables_mut(f);
+                e.condition.visit_variables_mut(f);
+            },
+            Expression::Match(e) => {
+                e.subject.visit_variables_mut(f);
+                for arm in &mut e.arms {
+                    arm.pattern.visit_bindings_mut(&mut |name| f(name, true));
+                    if let Some(guard) = &mut arm.guard {
+                        guard.visit_variables_mut(f);
+                    }
+                    arm.body.visit_variables_mut(f);
+                }
+            },
+            Expression::Switch(e) => {
+                e.subject.visit_variables_mut(f);
+                for body in e.bodies_mut() {
+                    body.visit_variables_mut(f);
+                }
+            },
+            Expression::Zone(e) => {
+                for (_, value) in &mut e.limits {
+                    value.visit_variables_mut(f);
+                }
+                e.body.visit_variables_mut(f);
+            },
+        }
+    }
+
+    pub fn contains_yield(&self) -> bool {
+        self.suspends_at(Suspension::Yield)
+    }
+
+    pub fn contains_await(&self) -> bool {
+        self.suspends_at(Suspension::Await)
+    }
+
+    pub fn suspends_at(&self, point: Suspension) -> bool {
+        match self {
+            Expression::Identifier(_) | Expression::IntegerLiteral(_) | Expression::StringLiteral(_) |
+            Expression::CharLiteral(_) | Expression::DecimalLiteral(_) | Expression::Boolean(_) |
+            Expression::Function(_) => false,
+            Expression::Prefix(e) => e.right.suspends_at(point),
+            Expression::Await(e) => point == Suspension::Await || e.value.suspends_at(point),
+            Expression::Infix(e) => e.left.suspends_at(point) || e.right.suspends_at(point),
+            Expression::Comparison(e) => e.operands.iter().any(|o| o.suspends_at(point)),
+            Expression::If(e) => {
+                e.condition.suspends_at(point) || e.consequence.suspends_at(point) ||
+                    e.alternative.as_ref().is_some_and(|a| a.suspends_at(point))
+            },
+            Expression::Call(e) => e.function.suspends_at(point) || e.all_arguments().any(|a| a.suspends_at(point)),
+            Expression::Array(e) => e.elements.iter().any(|el| el.suspends_at(point)),
+            Expression::Tuple(e) => e.elements.iter().any(|el| el.suspends_at(point)),
+            Expression::Hash(e) => e.pairs.iter().any(|(k, v)| k.suspends_at(point) || v.suspends_at(point)),
+            Expression::Index(e) => e.left.suspends_at(point) || e.index.suspends_at(point),
+            Expression::Slice(e) => {
+                e.left.suspends_at(point) || [&e.start, &e.end].into_iter().flatten().any(|b| b.suspends_at(point))
+            },
+            Expression::Unsafe(e) => e.body.suspends_at(point),
+            Expression::Loop(e) => e.body.suspends_at(point),
+            Expression::DoWhile(e) => e.body.suspends_at(point) || e.condition.suspends_at(point),
+            Expression::For(e) => e.iterable.suspends_at(point) || e.body.suspends_at(point),
+            Expression::Match(e) => {
+                e.subject.suspends_at(point) ||
+                    e.arms.iter().any(|arm| arm.guard.as_ref().is_some_and(|g| g.suspends_at(point)) || arm.body.suspends_at(point))
+            },
+            Expression::Switch(e) => e.subject.suspends_at(point) || e.bodies().any(|b| b.suspends_at(point)),
+            Expression::Zone(e) => e.body.suspends_at(point),
+        }
+    }
+
+    pub fn max_id(&self) -> NodeId {
+        match self {
+            Expression::Identifier(_) | Expression::IntegerLiteral(_) |
+            Expression::StringLiteral(_) | Expression::CharLiteral(_) | Expression::DecimalLiteral(_) |
+            Expression::Boolean(_) => 0,
+            Expression::Prefix(e) => e.right.max_id(),
+            Expression::Await(e) => e.value.max_id(),
+            Expression::Infix(e) => e.left.max_id().max(e.right.max_id()),
+            Expression::Comparison(e) => e.operands.iter().map(|o| o.max_id()).max().unwrap_or(0),
+            Expression::If(e) => {
+                let alt = e.alternative.as_ref().map(|a| a.max_id()).unwrap_or(0);
+                e.condition.max_id().max(e.consequence.max_id()).max(alt)
+            },
+            Expression::Function(e) => e.body.max_id(),
+            Expression::Call(e) => {
+                let block = e.block.as_ref().map_or(0, |b| b.max_id());
+                e.all_arguments().map(|a| a.max_id()).fold(e.function.max_id().max(block), usize::max)
+            },
+            Expression::Array(e) => e.elements.iter().map(|el| el.max_id()).max().unwrap_or(0),
+            Expression::Tuple(e) => e.elements.iter().map(|el| el.max_id()).max().unwrap_or(0),
+            Expression::Hash(e) => e.pairs.iter().map(|(k, v)| k.max_id().max(v.max_id())).max().unwrap_or(0),
+            Expression::Index(e) => e.left.max_id().max(e.index.max_id()),
+            Expression::Slice(e) => {
+                [&e.start, &e.end].into_iter().flatten().map(|b| b.max_id()).fold(e.left.max_id(), usize::max)
+            },
+            Expression::Unsafe(e) => e.body.max_id(),
+            Expression::Loop(e) => e.body.max_id(),
+            Expression::DoWhile(e) => e.body.max_id().max(e.condition.max_id()),
+            Expression::For(e) => e.iterable.max_id().max(e.body.max_id()),
+            Expression::Match(e) => {
+                e.arms.iter()
+                    .map(|arm| arm.guard.as_ref().map_or(0, |g| g.max_id()).max(arm.body.max_id()))
+                    .fold(e.subject.max_id(), usize::max)
+            },
+            Expression::Switch(e) => e.bodies().map(|b| b.max_id()).fold(e.subject.max_id(), usize::max),
+            Expression::Zone(e) => {
+                e.limits.iter().map(|(_, v)| v.max_id()).fold(e.body.max_id(), usize::max)
+            },
+        }
+    }
+}
+
 impl Node for Statement {
     fn token_literal(&self) -> String {
         match self {
             Statement::Let(s) => s.token.to_string(),
+            Statement::LetTuple(s) => s.token.to_string(),
             Statement::Return(s) => s.token.to_string(),
+            Statement::Yield(s) => s.token.to_string(),
+            Statement::Defer(s) => s.token.to_string(),
+            Statement::Break(s) => s.token.to_string(),
+            Statement::Continue(s) => s.token.to_string(),
             Statement::Expression(s) => s.token.to_string(),
         }
     }
     fn string(&self) -> String {
         match self {
-            Statement::Let(s) => format!("let {} = {};", s.name.value, s.value.string()),
+            Statement::Let(s) => match &s.annotation {
+                Some(ty) => format!("let {}: {} = {};", s.name.value, ty, s.value.string()),
+                None => format!("let {} = {};", s.name.value, s.value.string()),
+            },
+            Statement::LetTuple(s) => {
+                let names: Vec<&str> = s.names.iter().map(|n| n.value.as_str()).collect();
+                format!("let ({}) = {};", names.join(", "), s.value.string())
+            },
             Statement::Return(s) => format!("return {};", s.return_value.string()),
+            Statement::Yield(s) => format!("yield {};", s.value.string()),
+            Statement::Defer(s) => format!("defer {};", s.value.string()),
+            Statement::Break(s) => {
+                let target: Vec<String> = s.label.iter().cloned().chain(s.value.as_ref().map(|v| v.string())).collect();
+                if target.is_empty() { "break;".to_string() } else { format!("break {};", target.join(" ")) }
+            },
+            Statement::Continue(s) => match &s.label {
+                Some(label) => format!("continue {};", label),
+                None => "continue;".to_string(),
+            },
             Statement::Expression(s) => s.expression.string(),
         }
     }
@@ -56,12 +561,29 @@ impl Node for Statement {
 pub enum Expression {
     Identifier(Identifier),
     IntegerLiteral(IntegerLiteral),
+    StringLiteral(StringLiteral),
+    CharLiteral(CharLiteral),
+    DecimalLiteral(DecimalLiteral),
     Boolean(BooleanLiteral), 
     Prefix(PrefixExpression),
+    Await(AwaitExpression),
     Infix(InfixExpression),
+    Comparison(ComparisonChain),
     If(IfExpression),
     Function(FunctionLiteral),
     Call(CallExpression),
+    Array(ArrayLiteral),
+    Tuple(TupleLiteral),
+    Hash(HashLiteral),
+    Index(IndexExpression),
+    Slice(SliceExpression),
+    Unsafe(UnsafeExpression),
+    Zone(ZoneExpression),
+    Loop(LoopExpression),
+    DoWhile(DoWhileExpression),
+    For(ForExpression),
+    Match(MatchExpression),
+    Switch(SwitchExpression),
 }
 
 impl Node for Expression {
@@ -69,21 +591,50 @@ impl Node for Expression {
         match self {
             Expression::Identifier(e) => e.token.to_string(),
             Expression::IntegerLiteral(e) => e.token.to_string(),
+            Expression::StringLiteral(e) => e.token.to_string(),
+            Expression::CharLiteral(e) => e.token.to_string(),
+            Expression::DecimalLiteral(e) => e.token.to_string(),
             Expression::Boolean(e) => e.token.to_string(),
             Expression::Prefix(e) => e.token.to_string(),
+            Expression::Await(e) => e.token.to_string(),
             Expression::Infix(e) => e.token.to_string(),
+            Expression::Comparison(e) => e.token.to_string(),
             Expression::If(e) => e.token.to_string(),
             Expression::Function(e) => e.token.to_string(),
             Expression::Call(e) => e.token.to_string(),
+            Expression::Array(e) => e.token.to_string(),
+            Expression::Tuple(e) => e.token.to_string(),
+            Expression::Hash(e) => e.token.to_string(),
+            Expression::Index(e) => e.token.to_string(),
+            Expression::Slice(e) => e.token.to_string(),
+            Expression::Unsafe(e) => e.token.to_string(),
+            Expression::Zone(e) => e.token.to_string(),
+            Expression::Loop(e) => e.token.to_string(),
+            Expression::DoWhile(e) => e.token.to_string(),
+            Expression::For(e) => e.token.to_string(),
+            Expression::Match(e) => e.token.to_string(),
+            Expression::Switch(e) => e.token.to_string(),
         }
     }
     fn string(&self) -> String {
         match self {
             Expression::Identifier(e) => e.value.clone(),
             Expression::IntegerLiteral(e) => e.value.to_string(),
+            Expression::StringLiteral(e) => format!("{:?}", e.value),
+            Expression::CharLiteral(e) => format!("{:?}", e.value),
+            Expression::DecimalLiteral(e) => format!("{}d", e.value),
             Expression::Boolean(e) => e.token.to_string(),
             Expression::Prefix(e) => format!("({}{})", e.operator, e.right.string()),
+            Expression::Await(e) => format!("(await {})", e.value.string()),
             Expression::Infix(e) => format!("({} {} {})", e.left.string(), e.operator, e.right.string()),
+            Expression::Comparison(e) => {
+                let mut out = format!("({}", e.operands[0].string());
+                for (operator, operand) in e.operators.iter().zip(&e.operands[1..]) {
+                    out.push_str(&format!(" {} {}", operator, operand.string()));
+                }
+                out.push(')');
+                out
+            },
             Expression::If(e) => {
                 let mut out = format!("if {} {{ {} }}", e.condition.string(), e.consequence.string());
                 if let Some(alt) = &e.alternative {
@@ -91,13 +642,69 @@ impl Node for Expression {
                 }
                 out
             },
-            Expression::Function(e) => {
-                let params: Vec<String> = e.parameters.iter().map(|p| p.value.clone()).collect();
-                format!("fn({}) {}", params.join(", "), e.body.string())
-            },
+            Expression::Function(e) => format!("{} {{ {} }}", e.signature(), e.body.string()),
             Expression::Call(e) => {
-                let args: Vec<String> = e.arguments.iter().map(|a| a.string()).collect();
-                format!("{}({})", e.function.string(), args.join(", "))
+                let mut args: Vec<String> = e.arguments.iter().map(|a| a.string()).collect();
+                args.extend(e.keywords.iter().map(|(name, value)| format!("{}: {}", name.value, value.string())));
+                match &e.block {
+                    Some(block) => format!("{}({}) {{ {} }}", e.function.string(), args.join(", "), block.string()),
+                    None => format!("{}({})", e.function.string(), args.join(", ")),
+                }
+            },
+            Expression::Array(e) => {
+                let elements: Vec<String> = e.elements.iter().map(|el| el.string()).collect();
+                format!("[{}]", elements.join(", "))
+            },
+            Expression::Tuple(e) => {
+                let elements: Vec<String> = e.elements.iter().map(|el| el.string()).collect();
+                if elements.len() == 1 {
+                    format!("({},)", elements[0])
+                } else {
+                    format!("({})", elements.join(", "))
+                }
+            },
+            Expression::Hash(e) => {
+                let pairs: Vec<String> = e.pairs.iter().map(|(k, v)| format!("{}: {}", k.string(), v.string())).collect();
+                format!("{{{}}}", pairs.join(", "))
+            },
+            Expression::Index(e) => format!("({}[{}])", e.left.string(), e.index.string()),
+            Expression::Slice(e) => {
+                let start = e.start.as_ref().map(|b| b.string()).unwrap_or_default();
+                let end = e.end.as_ref().map(|b| b.string()).unwrap_or_default();
+                format!("({}[{}:{}])", e.left.string(), start, end)
+            },
+            Expression::Unsafe(e) => format!("unsafe {{ {} }}", e.body.string()),
+            Expression::Loop(e) => labelled(&e.label, format!("loop {{ {} }}", e.body.string())),
+            Expression::DoWhile(e) => labelled(&e.label, format!("do {{ {} }} while ({})", e.body.string(), e.condition.string())),
+            Expression::For(e) => {
+                labelled(&e.label, format!("for {} in {} {{ {} }}", e.variable.value, e.iterable.string(), e.body.string()))
+            },
+            Expression::Match(e) => {
+                let arms: Vec<String> = e.arms.iter().map(|arm| match &arm.guard {
+                    Some(guard) => format!("{} if {} -> {{ {} }}", arm.pattern, guard.string(), arm.body.string()),
+                    None => format!("{} -> {{ {} }}", arm.pattern, arm.body.string()),
+                }).collect();
+                format!("match ({}) {{ {} }}", e.subject.string(), arms.join(", "))
+            },
+            Expression::Switch(e) => {
+                let mut cases: Vec<String> = e.cases.iter().map(|case| {
+                    let values: Vec<String> = case.values.iter().map(|v| v.string()).collect();
+                    format!("case {}: {}", values.join(", "), case.body.string())
+                }).collect();
+                if let Some(default) = &e.default {
+                    cases.push(format!("default: {}", default.string()));
+                }
+                format!("switch ({}) {{ {} }}", e.subject.string(), cases.join(" "))
+            },
+            Expression::Zone(e) => {
+                let limits: Vec<String> = e.limits.iter()
+                    .map(|(name, value)| format!("{}: {}", name.value, value.string()))
+                    .collect();
+                if limits.is_empty() {
+                    format!("zone {{ {} }}", e.body.string())
+                } else {
+                    format!("zone({}) {{ {} }}", limits.join(", "), e.body.string())
+                }
             },
         }
     }
@@ -109,21 +716,89 @@ impl Node for Expression {
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct LetStatement {
+    pub id: NodeId,
     pub token: TokenType,
     pub name: Identifier,
+    pub annotation: Option<TypeExpr>, // `let x: int = ...`
+    pub doc: Option<String>,          // `///` lines above the let
+    pub value: Expression,
+    pub span: Span,
+}
+
+// `let (a, b) = pair;`: the value must be a tuple with one element per name
+#[derive(Debug, Clone, PartialEq)]
+pub struct LetTupleStatement {
+    pub id: NodeId,
+    pub token: TokenType,
+    pub names: Vec<Identifier>,
     pub value: Expression,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ReturnStatement {
+    pub id: NodeId,
     pub token: TokenType,
     pub return_value: Expression,
+    pub span: Span,
+}
+
+// Where a function body can stop and carry on later: at a `yield` in a
+// generator, at an `await` in an async function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Suspension {
+    Yield,
+    Await,
+}
+
+// `yield value;`: hands a value to whoever is iterating the generator
+#[derive(Debug, Clone, PartialEq)]
+pub struct YieldStatement {
+    pub id: NodeId,
+    pub token: TokenType,
+    pub value: Expression,
+    pub span: Span,
+}
+
+// `defer expr;`: evaluates expr when the function call it's in (or the
+// program, at top level) finishes, however it finishes. Deferred
+// expressions run last first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeferStatement {
+    pub id: NodeId,
+    pub token: TokenType,
+    pub value: Expression,
+    pub span: Span,
+}
+
+// `break;` or `break value;`: leaves the innermost loop, which evaluates
+// to the value (null without one). `break outer;` leaves the loop
+// labelled `outer:` instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BreakStatement {
+    pub id: NodeId,
+    pub token: TokenType,
+    pub label: Option<String>,
+    pub value: Option<Expression>,
+    pub span: Span,
+}
+
+// `continue;` or `continue outer;`: skips to the next pass of the
+// innermost (or labelled) loop
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContinueStatement {
+    pub id: NodeId,
+    pub token: TokenType,
+    pub label: Option<String>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ExpressionStatement {
+    pub id: NodeId,
     pub token: TokenType,
     pub expression: Expression,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -132,14 +807,42 @@ pub struct BlockStatement {
     pub statements: Vec<Statement>,
 }
 
+impl BlockStatement {
+    pub fn shift_spans(&mut self, delta: isize) {
+        for s in &mut self.statements {
+            s.shift_spans(delta);
+        }
+    }
+
+    pub fn max_id(&self) -> NodeId {
+        self.statements.iter().map(|s| s.max_id()).max().unwrap_or(0)
+    }
+
+    pub fn visit_variables_mut(&mut self, f: &mut impl FnMut(&mut Identifier, bool)) {
+        for s in &mut self.statements {
+            s.visit_variables_mut(f);
+        }
+    }
+
+    // A function whose body yields is a generator
+    pub fn contains_yield(&self) -> bool {
+        self.statements.iter().any(|s| s.contains_yield())
+    }
+
+    pub fn contains_await(&self) -> bool {
+        self.statements.iter().any(|s| s.contains_await())
+    }
+
+    pub fn suspends_at(&self, point: Suspension) -> bool {
+        self.statements.iter().any(|s| s.suspends_at(point))
+    }
+}
+
 impl Node for BlockStatement {
     fn token_literal(&self) -> String { self.token.to_string() }
     fn string(&self) -> String {
-        let mut out = String::new();
-        for s in &self.statements {
-            out.push_str(&s.string());
-        }
-        out
+        let statements: Vec<String> = self.statements.iter().map(|s| s.string()).collect();
+        statements.join(" ")
     }
 }
 
@@ -155,6 +858,10 @@ pub struct IfExpression {
 pub struct Identifier {
     pub token: TokenType,
     pub value: String,
+    pub span: Span,
+    // Index into the enclosing function's locals (resolver::assign_slots);
+    // None for top-level code and names the function doesn't bind
+    pub slot: Option<usize>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -163,6 +870,24 @@ pub struct IntegerLiteral {
     pub value: i64,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringLiteral {
+    pub token: TokenType,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharLiteral {
+    pub token: TokenType,
+    pub value: char,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecimalLiteral {
+    pub token: TokenType,
+    pub value: Decimal,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct BooleanLiteral {
     pub token: TokenType,
@@ -176,6 +901,16 @@ pub struct PrefixExpression {
     pub right: Box<Expression>,
 }
 
+// `await value`: the result of a future (or task) once it's done. In an
+// async function, one that is the whole value of a `let`, a `return` or an
+// expression statement lets other work run in the meantime; any other
+// `await` waits on the spot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AwaitExpression {
+    pub token: TokenType,
+    pub value: Box<Expression>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct InfixExpression {
     pub token: TokenType, 
@@ -184,16 +919,318 @@ pub struct InfixExpression {
     pub right: Box<Expression>,
 }
 
+// `a < b < c`: neighbours are compared left to right until one comparison
+// fails, so it means `a < b` and `b < c`, with `b` evaluated once. Written
+// `(a < b) < c` it's an ordinary comparison of a bool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComparisonChain {
+    pub token: TokenType,
+    pub operands: Vec<Expression>,
+    pub operators: Vec<String>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct FunctionLiteral {
     pub token: TokenType,
+    pub type_params: Vec<Identifier>,           // `fn<T>`
     pub parameters: Vec<Identifier>,
+    pub param_types: Vec<Option<TypeExpr>>,     // One per parameter
+    pub return_type: Option<TypeExpr>,          // `-> T`
     pub body: BlockStatement,
+    // Names of the slots a call's frame has: parameters first, then the
+    // body's lets and loop variables
+    pub locals: Arc<[String]>,
+    // Names the body uses without binding them, its nested functions'
+    // included. Numbered against the enclosing function's slots: a
+    // function made inside another copies these from its frame.
+    pub captures: Vec<Identifier>,
+    // `async fn`: a call returns a future (see future.rs)
+    pub is_async: bool,
+}
+
+impl FunctionLiteral {
+    // `fn<T>(a: T, b) -> T`, annotations included
+    pub fn signature(&self) -> String {
+        let params: Vec<String> = self.parameters.iter().zip(&self.param_types)
+            .map(|(p, ty)| match ty {
+                Some(ty) => format!("{}: {}", p.value, ty),
+                None => p.value.clone(),
+            })
+            .collect();
+        let mut out = String::from(if self.is_async { "async fn" } else { "fn" });
+        if !self.type_params.is_empty() {
+            let names: Vec<&str> = self.type_params.iter().map(|t| t.value.as_str()).collect();
+            out.push_str(&format!("<{}>", names.join(", ")));
+        }
+        out.push_str(&format!("({})", params.join(", ")));
+        if let Some(ret) = &self.return_type {
+            out.push_str(&format!(" -> {}", ret));
+        }
+        out
+    }
+}
+
+// A type as written in an annotation: `int`, `array<T>`, `fn(T) -> bool`.
+// Annotations are only read by the type checker; evaluation ignores them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeExpr {
+    Named { name: String, args: Vec<TypeExpr> },
+    Function { params: Vec<TypeExpr>, ret: Box<TypeExpr> },
 }
 
+impl fmt::Display for TypeExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeExpr::Named { name, args } if args.is_empty() => write!(f, "{}", name),
+            TypeExpr::Named { name, args } => {
+                let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+                write!(f, "{}<{}>", name, args.join(", "))
+            },
+            TypeExpr::Function { params, ret } => {
+                let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+                write!(f, "fn({}) -> {}", params.join(", "), ret)
+            },
+        }
+    }
+}
+
+// `name: value` at a call site
+pub type KeywordArgument = (Identifier, Expression);
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct CallExpression {
     pub token: TokenType,
     pub function: Box<Expression>,
     pub arguments: Vec<Expression>,
-}
\ No newline at end of file
+    // `f(1, y: 2)`: named arguments, always after the positional ones
+    pub keywords: Vec<KeywordArgument>,
+    // `unless(done) { ... }`: a block after a call to a macro, handed to it
+    // after the arguments (expansion replaces these calls), or the code in
+    // `quote { ... }`
+    pub block: Option<BlockStatement>,
+}
+
+impl CallExpression {
+    // `quote(...)` or `quote { ... }`: the code in it is data, not run
+    // where it's written (only what it unquotes is)
+    pub fn is_quote(&self) -> bool {
+        matches!(&*self.function, Expression::Identifier(name) if name.value == "quote")
+    }
+
+    // Positional arguments, then keyword values, in source order
+    pub fn all_arguments(&self) -> impl Iterator<Item = &Expression> {
+        self.arguments.iter().chain(self.keywords.iter().map(|(_, value)| value))
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrayLiteral {
+    pub token: TokenType,
+    pub elements: Vec<Expression>,
+}
+
+// `(1, "a")`; one element needs a trailing comma, `(x)` is just x
+#[derive(Debug, Clone, PartialEq)]
+pub struct TupleLiteral {
+    pub token: TokenType,
+    pub elements: Vec<Expression>,
+}
+
+// `{"a": 1, 2: [3]}`: keys are any expression that evaluates to an int,
+// bool or string
+#[derive(Debug, Clone, PartialEq)]
+pub struct HashLiteral {
+    pub token: TokenType,
+    pub pairs: Vec<(Expression, Expression)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexExpression {
+    pub token: TokenType,
+    pub left: Box<Expression>,
+    pub index: Box<Expression>,
+}
+
+// `a[1:4]`, `a[:2]`, `a[1:]`: a missing bound means the start/end
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliceExpression {
+    pub token: TokenType,
+    pub left: Box<Expression>,
+    pub start: Option<Box<Expression>>,
+    pub end: Option<Box<Expression>>,
+}
+
+// `unsafe { ... }`: the only place capability-gated builtins may run
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsafeExpression {
+    pub token: TokenType,
+    pub body: BlockStatement,
+}
+
+// `loop { ... }`: runs the body until a `break`. Any loop can be given a
+// label (`outer: loop { ... }`) for a nested break or continue to name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopExpression {
+    pub token: TokenType,
+    pub label: Option<String>,
+    pub body: BlockStatement,
+}
+
+// `do { ... } while (cond)`: runs the body, then again for as long as the
+// condition holds afterwards. The condition sees the body's lets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DoWhileExpression {
+    pub token: TokenType,
+    pub label: Option<String>,
+    pub body: BlockStatement,
+    pub condition: Box<Expression>,
+}
+
+// `for x in xs { ... }`: runs the body once per element (or character, or
+// hash key, or generated value). Like a `let`, `x` binds in the enclosing
+// scope.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForExpression {
+    pub token: TokenType,
+    pub label: Option<String>,
+    pub variable: Identifier,
+    pub iterable: Box<Expression>,
+    pub body: BlockStatement,
+}
+
+// `match (x) { 0 -> "none", [a, ..rest] if a > 1 -> rest, _ -> x }`: the
+// first arm whose pattern fits and whose guard (if any) is truthy gives
+// the value. Like a `let`, names in a pattern bind in the enclosing scope.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchExpression {
+    pub token: TokenType,
+    pub subject: Box<Expression>,
+    pub arms: Vec<MatchArm>,
+}
+
+// An arm's body is a block even when written as one expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub body: BlockStatement,
+}
+
+// `switch (x) { case 1, 2: ... case "a": ... default: ... }`: the case
+// listing the subject's value runs, and only that one; there's no
+// fallthrough. Values are int and string literals. Without a default, a
+// value no case lists is an error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchExpression {
+    pub token: TokenType,
+    pub subject: Box<Expression>,
+    pub cases: Vec<SwitchCase>,
+    pub default: Option<BlockStatement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchCase {
+    pub values: Vec<Expression>,
+    pub body: BlockStatement,
+}
+
+impl SwitchExpression {
+    // Every case's body, then the default's
+    pub fn bodies(&self) -> impl Iterator<Item = &BlockStatement> {
+        self.cases.iter().map(|case| &case.body).chain(&self.default)
+    }
+
+    pub fn bodies_mut(&mut self) -> impl Iterator<Item = &mut BlockStatement> {
+        self.cases.iter_mut().map(|case| &mut case.body).chain(&mut self.default)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Wildcard,                 // _
+    Null,                     // null
+    Literal(Box<Expression>), // 1, -1, "a", 'c', 1.5d, true: equal values fit
+    Binding(Identifier),      // n: fits anything and names it
+    // [a, b] fits a two-element array; [a, ..rest] one with at least one,
+    // binding the others to `rest` (a bare `..` ignores them)
+    Array(Vec<Pattern>, Option<Box<Pattern>>),
+    Tuple(Vec<Pattern>),
+    // {"name": n} fits a hash with at least these keys
+    Hash(Vec<(Expression, Pattern)>),
+}
+
+impl Pattern {
+    pub fn visit_bindings_mut(&mut self, f: &mut impl FnMut(&mut Identifier)) {
+        match self {
+            Pattern::Wildcard | Pattern::Null | Pattern::Literal(_) => {},
+            Pattern::Binding(name) => f(name),
+            Pattern::Array(elements, rest) => {
+                for p in elements.iter_mut().chain(rest.as_deref_mut()) {
+                    p.visit_bindings_mut(f);
+                }
+            },
+            Pattern::Tuple(elements) => {
+                for p in elements {
+                    p.visit_bindings_mut(f);
+                }
+            },
+            Pattern::Hash(pairs) => {
+                for (_, p) in pairs {
+                    p.visit_bindings_mut(f);
+                }
+            },
+        }
+    }
+
+    // The names it binds, in order
+    pub fn bindings(&self) -> Vec<&Identifier> {
+        match self {
+            Pattern::Wildcard | Pattern::Null | Pattern::Literal(_) => vec![],
+            Pattern::Binding(name) => vec![name],
+            Pattern::Array(elements, rest) => elements.iter().chain(rest.as_deref()).flat_map(Pattern::bindings).collect(),
+            Pattern::Tuple(elements) => elements.iter().flat_map(Pattern::bindings).collect(),
+            Pattern::Hash(pairs) => pairs.iter().flat_map(|(_, p)| p.bindings()).collect(),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |patterns: &[Pattern]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        // `-1` rather than the `(-1)` an expression prints, which the
+        // parser wouldn't take back as a hash key
+        let literal = |e: &Expression| match e {
+            Expression::Prefix(p) => format!("{}{}", p.operator, p.right.string()),
+            e => e.string(),
+        };
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Null => write!(f, "null"),
+            Pattern::Literal(e) => write!(f, "{}", literal(e)),
+            Pattern::Binding(name) => write!(f, "{}", name.value),
+            Pattern::Array(elements, rest) => {
+                let mut items = list(elements);
+                match rest.as_deref() {
+                    Some(Pattern::Binding(name)) => items.push(format!("..{}", name.value)),
+                    Some(_) => items.push("..".to_string()),
+                    None => {},
+                }
+                write!(f, "[{}]", items.join(", "))
+            },
+            Pattern::Tuple(elements) if elements.len() == 1 => write!(f, "({},)", elements[0]),
+            Pattern::Tuple(elements) => write!(f, "({})", list(elements).join(", ")),
+            Pattern::Hash(pairs) => {
+                let pairs: Vec<String> = pairs.iter().map(|(k, p)| format!("{}: {}", literal(k), p)).collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            },
+        }
+    }
+}
+
+// `zone(steps: 1000) { ... }`: runs the body in its own scope, optionally
+// with resource limits. Limit names are checked by the parser.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneExpression {
+    pub token: TokenType,
+    pub limits: Vec<(Identifier, Expression)>,
+    pub body: BlockStatement,
+}
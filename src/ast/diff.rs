@@ -0,0 +1,89 @@
+// What changed between two parses of a program, statement by statement.
+// Statements are compared by what they say, not where they are, so text
+// that only moved (a line inserted above it) or was reformatted is the
+// same statement:
+//
+//   let old = parse("let a = 1;\nlet b = 2;");
+//   let new = parse("let a = 1;\nlet b = 3;\nprint(b);");
+//   diff(&old, &new)
+//   // [Modified { old: 1, new: 1, .. }, Added { new: 2, .. }]
+//
+// Unchanged statements are matched up as a longest common subsequence;
+// between two matches, removed and added statements pair up in order as
+// modifications, and whatever is left over on one side was added or
+// removed. A statement that moved past others shows up as removed in one
+// place and added in another.
+use super::{Node, Program, Statement};
+use crate::span::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    // Indices into the new program's statements, spans in its text
+    Added { new: usize, span: Span },
+    // ... and into the old one's
+    Removed { old: usize, span: Span },
+    Modified { old: usize, new: usize, old_span: Span, new_span: Span },
+}
+
+// In the order of the statements they're about
+pub fn diff(old: &Program, new: &Program) -> Vec<Change> {
+    let old_keys: Vec<String> = old.statements.iter().map(Statement::string).collect();
+    let new_keys: Vec<String> = new.statements.iter().map(Statement::string).collect();
+
+    // Edits are usually small: the common ends are matched directly, and
+    // only what's between them goes through the quadratic part
+    let prefix = old_keys.iter().zip(&new_keys).take_while(|(a, b)| a == b).count();
+    let suffix = old_keys[prefix..].iter().rev().zip(new_keys[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let old_mid = &old_keys[prefix..old_keys.len() - suffix];
+    let new_mid = &new_keys[prefix..new_keys.len() - suffix];
+
+    let mut changes = vec![];
+    let (mut i, mut j) = (0, 0);
+    let gap = |changes: &mut Vec<Change>, removed: std::ops::Range<usize>, added: std::ops::Range<usize>| {
+        let paired = removed.len().min(added.len());
+        for (o, n) in removed.clone().zip(added.clone()) {
+            let (old_span, new_span) = (old.statements[o].span(), new.statements[n].span());
+            changes.push(Change::Modified { old: o, new: n, old_span, new_span });
+        }
+        for o in removed.start + paired..removed.end {
+            changes.push(Change::Removed { old: o, span: old.statements[o].span() });
+        }
+        for n in added.start + paired..added.end {
+            changes.push(Change::Added { new: n, span: new.statements[n].span() });
+        }
+    };
+    for (a, b) in common(old_mid, new_mid) {
+        gap(&mut changes, prefix + i..prefix + a, prefix + j..prefix + b);
+        (i, j) = (a + 1, b + 1);
+    }
+    gap(&mut changes, prefix + i..prefix + old_mid.len(), prefix + j..prefix + new_mid.len());
+    changes
+}
+
+// Index pairs of a longest common subsequence, in order
+fn common(a: &[String], b: &[String]) -> Vec<(usize, usize)> {
+    // lengths[i][j]: LCS length of a[i..] and b[j..]
+    let mut lengths = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut pairs = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            pairs.push((i, j));
+            (i, j) = (i + 1, j + 1);
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
@@ -0,0 +1,228 @@
+// An indented dump of the syntax tree, for seeing how the parser read
+// something (`nova check --ast-tree file.nv`, `:ast` in the REPL):
+//
+//   Program
+//     Let x @1:1
+//       Infix +
+//         Int 1
+//         Identifier y @1:13
+//
+// One node per line: its kind, then operator, name or literal, then
+// `@line:column` where the node has a span (statements and identifiers).
+use crate::ast::{BlockStatement, Expression, Node, Program, Statement};
+use crate::span::{LineIndex, Span};
+
+pub fn render(program: &Program, source: &str) -> String {
+    let mut tree = Tree { index: LineIndex::new(source), out: String::new() };
+    tree.line(0, "Program", None);
+    for s in &program.statements {
+        tree.statement(1, s);
+    }
+    tree.out
+}
+
+struct Tree {
+    index: LineIndex,
+    out: String,
+}
+
+impl Tree {
+    fn line(&mut self, depth: usize, text: &str, span: Option<Span>) {
+        self.out.push_str(&"  ".repeat(depth));
+        self.out.push_str(text);
+        if let Some(span) = span {
+            let pos = self.index.position(span.start);
+            self.out.push_str(&format!(" @{}:{}", pos.line + 1, pos.column + 1));
+        }
+        self.out.push('\n');
+    }
+
+    // A label line ("then", "args") with the nodes under it
+    fn labelled<'a>(&mut self, depth: usize, label: &str, expressions: impl IntoIterator<Item = &'a Expression>) {
+        self.line(depth, label, None);
+        for e in expressions {
+            self.expression(depth + 1, e);
+        }
+    }
+
+    fn block(&mut self, depth: usize, label: &str, block: &BlockStatement) {
+        self.line(depth, label, None);
+        for s in &block.statements {
+            self.statement(depth + 1, s);
+        }
+    }
+
+    fn statement(&mut self, depth: usize, s: &Statement) {
+        match s {
+            Statement::Let(s) => {
+                let text = match &s.annotation {
+                    Some(ty) => format!("Let {}: {}", s.name.value, ty),
+                    None => format!("Let {}", s.name.value),
+                };
+                self.line(depth, &text, Some(s.span));
+                self.expression(depth + 1, &s.value);
+            },
+            Statement::LetTuple(s) => {
+                let names: Vec<&str> = s.names.iter().map(|n| n.value.as_str()).collect();
+                self.line(depth, &format!("LetTuple ({})", names.join(", ")), Some(s.span));
+                self.expression(depth + 1, &s.value);
+            },
+            Statement::Return(s) => {
+                self.line(depth, "Return", Some(s.span));
+                self.expression(depth + 1, &s.return_value);
+            },
+            Statement::Yield(s) => {
+                self.line(depth, "Yield", Some(s.span));
+                self.expression(depth + 1, &s.value);
+            },
+            Statement::Defer(s) => {
+                self.line(depth, "Defer", Some(s.span));
+                self.expression(depth + 1, &s.value);
+            },
+            Statement::Break(s) => {
+                let text = match &s.label {
+                    Some(label) => format!("Break {}", label),
+                    None => "Break".to_string(),
+                };
+                self.line(depth, &text, Some(s.span));
+                if let Some(value) = &s.value {
+                    self.expression(depth + 1, value);
+                }
+            },
+            Statement::Continue(s) => match &s.label {
+                Some(label) => self.line(depth, &format!("Continue {}", label), Some(s.span)),
+                None => self.line(depth, "Continue", Some(s.span)),
+            },
+            Statement::Expression(s) => {
+                self.line(depth, "Expression", Some(s.span));
+                self.expression(depth + 1, &s.expression);
+            },
+        }
+    }
+
+    fn expression(&mut self, depth: usize, e: &Expression) {
+        match e {
+            Expression::Identifier(e) => self.line(depth, &format!("Identifier {}", e.value), Some(e.span)),
+            Expression::IntegerLiteral(e) => self.line(depth, &format!("Int {}", e.value), None),
+            Expression::StringLiteral(e) => self.line(depth, &format!("String {:?}", e.value), None),
+            Expression::CharLiteral(e) => self.line(depth, &format!("Char {:?}", e.value), None),
+            Expression::DecimalLiteral(e) => self.line(depth, &format!("Decimal {}", e.value), None),
+            Expression::Boolean(e) => self.line(depth, &format!("Bool {}", e.value), None),
+            Expression::Prefix(e) => {
+                self.line(depth, &format!("Prefix {}", e.operator), None);
+                self.expression(depth + 1, &e.right);
+            },
+            Expression::Await(e) => {
+                self.line(depth, "Await", None);
+                self.expression(depth + 1, &e.value);
+            },
+            Expression::Infix(e) => {
+                self.line(depth, &format!("Infix {}", e.operator), None);
+                self.expression(depth + 1, &e.left);
+                self.expression(depth + 1, &e.right);
+            },
+            Expression::Comparison(e) => {
+                self.line(depth, &format!("Comparison {}", e.operators.join(" ")), None);
+                for operand in &e.operands {
+                    self.expression(depth + 1, operand);
+                }
+            },
+            Expression::If(e) => {
+                self.line(depth, "If", None);
+                self.labelled(depth + 1, "condition", [e.condition.as_ref()]);
+                self.block(depth + 1, "then", &e.consequence);
+                if let Some(alt) = &e.alternative {
+                    self.block(depth + 1, "else", alt);
+                }
+            },
+            Expression::Function(e) => {
+                self.line(depth, &format!("Function {}", e.signature()), None);
+                self.block(depth + 1, "body", &e.body);
+            },
+            Expression::Call(e) => {
+                self.line(depth, "Call", None);
+                self.expression(depth + 1, &e.function);
+                if !e.arguments.is_empty() {
+                    self.labelled(depth + 1, "args", &e.arguments);
+                }
+                for (name, value) in &e.keywords {
+                    self.labelled(depth + 1, &format!("{}:", name.value), [value]);
+                }
+                if let Some(block) = &e.block {
+                    self.block(depth + 1, "block", block);
+                }
+            },
+            Expression::Array(e) => self.labelled(depth, "Array", &e.elements),
+            Expression::Tuple(e) => self.labelled(depth, "Tuple", &e.elements),
+            Expression::Hash(e) => {
+                self.line(depth, "Hash", None);
+                for (key, value) in &e.pairs {
+                    self.labelled(depth + 1, "pair", [key, value]);
+                }
+            },
+            Expression::Index(e) => {
+                self.line(depth, "Index", None);
+                self.expression(depth + 1, &e.left);
+                self.expression(depth + 1, &e.index);
+            },
+            Expression::Slice(e) => {
+                self.line(depth, "Slice", None);
+                self.expression(depth + 1, &e.left);
+                if let Some(start) = &e.start {
+                    self.labelled(depth + 1, "start", [start.as_ref()]);
+                }
+                if let Some(end) = &e.end {
+                    self.labelled(depth + 1, "end", [end.as_ref()]);
+                }
+            },
+            Expression::Unsafe(e) => self.block(depth, "Unsafe", &e.body),
+            Expression::Zone(e) => {
+                self.line(depth, "Zone", None);
+                for (name, value) in &e.limits {
+                    self.labelled(depth + 1, &format!("{}:", name.value), [value]);
+                }
+                self.block(depth + 1, "body", &e.body);
+            },
+            Expression::Loop(e) => self.block(depth, &labelled(&e.label, "Loop"), &e.body),
+            Expression::DoWhile(e) => {
+                self.block(depth, &labelled(&e.label, "DoWhile"), &e.body);
+                self.labelled(depth + 1, "while", [e.condition.as_ref()]);
+            },
+            Expression::For(e) => {
+                self.line(depth, &labelled(&e.label, &format!("For {}", e.variable.value)), Some(e.variable.span));
+                self.labelled(depth + 1, "in", [e.iterable.as_ref()]);
+                self.block(depth + 1, "body", &e.body);
+            },
+            Expression::Match(e) => {
+                self.line(depth, "Match", None);
+                self.labelled(depth + 1, "subject", [e.subject.as_ref()]);
+                for arm in &e.arms {
+                    self.line(depth + 1, &format!("Arm {}", arm.pattern), None);
+                    if let Some(guard) = &arm.guard {
+                        self.labelled(depth + 2, "if", [guard]);
+                    }
+                    self.block(depth + 2, "body", &arm.body);
+                }
+            },
+            Expression::Switch(e) => {
+                self.line(depth, "Switch", None);
+                self.labelled(depth + 1, "subject", [e.subject.as_ref()]);
+                for case in &e.cases {
+                    let values: Vec<String> = case.values.iter().map(|v| v.string()).collect();
+                    self.block(depth + 1, &format!("case {}", values.join(", ")), &case.body);
+                }
+                if let Some(default) = &e.default {
+                    self.block(depth + 1, "default", default);
+                }
+            },
+        }
+    }
+}
+
+// `outer: Loop`
+fn labelled(label: &Option<String>, text: &str) -> String {
+    match label {
+        Some(label) => format!("{}: {}", label, text),
+        None => text.to_string(),
+    }
+}
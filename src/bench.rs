@@ -0,0 +1,75 @@
+// Benchmark harness (`nova bench`). Every top-level `bench_*` function that
+// takes no arguments is called for a warmup period, then called again
+// for a fixed time on the monotonic clock, and the call rate reported.
+use std::fmt;
+use std::time::{Duration, Instant};
+use crate::environment::Environment;
+use crate::evaluator::call_function;
+use crate::object::Object;
+
+pub struct Config {
+    pub warmup: Duration,
+    pub time: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config { warmup: Duration::from_millis(200), time: Duration::from_secs(1) }
+    }
+}
+
+pub struct Report {
+    pub name: String,
+    pub iterations: u64,
+    pub elapsed: Duration,
+}
+
+impl Report {
+    pub fn per_second(&self) -> f64 {
+        self.iterations as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn mean(&self) -> Duration {
+        self.elapsed.div_f64(self.iterations.max(1) as f64)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:<24} {:>12.0} iter/s {:>12.3?}/iter ({} iterations)",
+            self.name, self.per_second(), self.mean(), self.iterations)
+    }
+}
+
+// Names of the benchmarks bound in env (after running the script), sorted
+pub fn benchmarks(env: &Environment) -> Vec<String> {
+    env.iter()
+        .filter(|(name, value)| name.starts_with("bench_") && matches!(value, Object::Function(f) if f.parameters.is_empty()))
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+pub fn run(name: &str, env: &mut Environment, config: &Config) -> Result<Report, String> {
+    let function = env.get(name).ok_or_else(|| format!("{}: no such benchmark", name))?;
+    let mut call = || match call_function(function.clone(), vec![], env) {
+        Object::Error(msg) => Err(format!("{}: {}", name, msg)),
+        _ => Ok(()),
+    };
+
+    let start = Instant::now();
+    while start.elapsed() < config.warmup {
+        call()?;
+    }
+
+    // Always at least one measured call, however short the time
+    let mut iterations = 0;
+    let start = Instant::now();
+    loop {
+        call()?;
+        iterations += 1;
+        if start.elapsed() >= config.time {
+            break;
+        }
+    }
+    Ok(Report { name: name.to_string(), iterations, elapsed: start.elapsed() })
+}
@@ -0,0 +1,119 @@
+// Parsed programs, keyed by a hash of their source, so code that comes
+// around again isn't parsed again: eval() in a loop, or `nova run --watch`
+// when a save didn't change anything the parser sees.
+//
+//   let mut cache = ParseCache::new(64);
+//   let (program, errors) = cache.parse("1 + 2", &operators);   // parses
+//   let (program, errors) = cache.parse("1 + 2", &operators);   // a clone of the first
+//
+// What's cached was parsed with one set of custom operators; parsing with
+// others empties the cache first.
+//
+// The cache lives in memory, for one process. Keeping it on disk would
+// need a format for the AST, and there's no module system yet to make
+// many files worth caching across runs. What it holds counts against the
+// runtime's memory limit (see bytes()).
+use std::collections::{HashMap, VecDeque};
+use crate::ast::Program;
+use crate::diagnostics::Diagnostic;
+use crate::lexer::Lexer;
+use crate::operators::Operators;
+use crate::parser::Parser;
+
+pub const DEFAULT_CAPACITY: usize = 64;
+
+#[derive(Debug)]
+pub struct ParseCache {
+    capacity: usize,
+    entries: HashMap<u64, Entry>,
+    // Oldest first; the first to go when the cache is full
+    order: VecDeque<u64>,
+    operators: Operators,
+    bytes: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    // Checked on a hit, so two sources with the same hash can't mix up
+    source: String,
+    program: Program,
+    errors: Vec<Diagnostic>,
+}
+
+impl Default for ParseCache {
+    fn default() -> Self {
+        ParseCache::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl ParseCache {
+    // Remembers up to `capacity` programs; 0 turns caching off
+    pub fn new(capacity: usize) -> Self {
+        ParseCache { capacity, entries: HashMap::new(), order: VecDeque::new(), operators: Operators::new(), bytes: 0, hits: 0, misses: 0 }
+    }
+
+    // The program and its parse errors, as Parser::parse_program gives them
+    pub fn parse(&mut self, source: &str, operators: &Operators) -> (Program, Vec<Diagnostic>) {
+        if *operators != self.operators {
+            self.clear();
+            self.operators = operators.clone();
+        }
+        let key = hash(source);
+        if let Some(entry) = self.entries.get(&key).filter(|e| e.source == source) {
+            self.hits += 1;
+            return (entry.program.clone(), entry.errors.clone());
+        }
+        self.misses += 1;
+        let mut p = Parser::with_operators(Lexer::new(source.to_string()), operators.clone());
+        let program = p.parse_program();
+        if self.capacity > 0 {
+            if self.entries.len() >= self.capacity && !self.entries.contains_key(&key)
+                && let Some(oldest) = self.order.pop_front()
+                && let Some(entry) = self.entries.remove(&oldest) {
+                self.bytes -= entry_size(&entry.source);
+            }
+            if !self.entries.contains_key(&key) {
+                self.order.push_back(key);
+            }
+            let entry = Entry { source: source.to_string(), program: program.clone(), errors: p.errors.clone() };
+            self.bytes += entry_size(source);
+            if let Some(old) = self.entries.insert(key, entry) {
+                self.bytes -= entry_size(&old.source);
+            }
+        }
+        (program, p.errors)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Roughly what the entries take up, for charging to Runtime::memory
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.bytes = 0;
+    }
+}
+
+// A program's tree takes about this many times the bytes of its source
+const TREE_BYTES_PER_BYTE: usize = 8;
+
+fn entry_size(source: &str) -> usize {
+    std::mem::size_of::<Entry>() + source.len() * (1 + TREE_BYTES_PER_BYTE)
+}
+
+// 64-bit FNV-1a: the same for the same text, whatever the run or build
+pub fn hash(source: &str) -> u64 {
+    source.bytes().fold(0xcbf2_9ce4_8422_2325, |h, b| (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3))
+}
@@ -0,0 +1,106 @@
+// C ABI for embedding Nova in non-Rust programs (the `capi` feature).
+//
+//   NovaInterpreter *nova = nova_new();
+//   if (nova_eval(nova, "let x = 20; x * 2") == NOVA_OK) {
+//       printf("%s\n", nova_get_string_result(nova));   // "40"
+//   } else {
+//       fprintf(stderr, "%s\n", nova_get_string_result(nova));
+//   }
+//   nova_free(nova);
+//
+// Build with `cargo build --release --no-default-features --features capi`
+// to get libnova_lang.so / .dylib / .dll. The `header` feature also writes
+// include/nova.h with cbindgen.
+//
+// The interpreter keeps bindings between nova_eval calls, like the REPL.
+// Every call after nova_new takes the handle it returned; passing NULL is
+// allowed and reported as NOVA_ERR_ARGUMENT instead of crashing.
+use std::ffi::{CStr, CString, c_char, c_int};
+use std::ptr;
+use crate::interpreter::{Error, Interpreter};
+
+pub const NOVA_OK: c_int = 0;
+/// The source didn't parse; the result string holds the diagnostics
+pub const NOVA_ERR_PARSE: c_int = 1;
+/// NULL handle/source, or source that isn't UTF-8
+pub const NOVA_ERR_ARGUMENT: c_int = 2;
+/// The source didn't type-check, or evaluating it failed; the result
+/// string holds the error
+pub const NOVA_ERR_RUNTIME: c_int = 3;
+
+/// Opaque to C; only ever handled through a pointer
+pub struct NovaInterpreter {
+    interp: Interpreter,
+    result: CString,
+}
+
+/// Creates an interpreter with an empty environment. Release it with
+/// nova_free.
+#[unsafe(no_mangle)]
+pub extern "C" fn nova_new() -> *mut NovaInterpreter {
+    let interp = NovaInterpreter { interp: Interpreter::new(), result: CString::default() };
+    Box::into_raw(Box::new(interp))
+}
+
+/// Evaluates a NUL-terminated source string. The printed form of the
+/// value (or what went wrong) is available from nova_get_string_result.
+///
+/// # Safety
+/// `interp` must be NULL or a live pointer from `nova_new`, and `source`
+/// NULL or a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nova_eval(interp: *mut NovaInterpreter, source: *const c_char) -> c_int {
+    // SAFETY: the caller hands us a pointer from nova_new (or NULL)
+    let Some(interp) = (unsafe { interp.as_mut() }) else {
+        return NOVA_ERR_ARGUMENT;
+    };
+    if source.is_null() {
+        interp.result = CString::default();
+        return NOVA_ERR_ARGUMENT;
+    }
+    // SAFETY: checked for NULL above, NUL-termination is the caller's promise
+    let Ok(source) = (unsafe { CStr::from_ptr(source) }).to_str() else {
+        interp.result = to_c_string("source is not valid UTF-8".to_string());
+        return NOVA_ERR_ARGUMENT;
+    };
+
+    let (code, result) = match interp.interp.eval_source(source) {
+        Ok(value) => (NOVA_OK, value.to_string()),
+        Err(e @ Error::Parse(_)) => (NOVA_ERR_PARSE, e.to_string()),
+        Err(e) => (NOVA_ERR_RUNTIME, e.to_string()),
+    };
+    interp.result = to_c_string(result);
+    code
+}
+
+/// Result of the last nova_eval. The string belongs to the interpreter and
+/// stays valid until the next nova_eval or nova_free; copy it to keep it.
+///
+/// # Safety
+/// `interp` must be NULL or a live pointer from `nova_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nova_get_string_result(interp: *const NovaInterpreter) -> *const c_char {
+    // SAFETY: see nova_eval
+    match unsafe { interp.as_ref() } {
+        Some(interp) => interp.result.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Releases the interpreter and its last result.
+///
+/// # Safety
+/// `interp` must be NULL or a pointer from `nova_new` that hasn't been
+/// freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nova_free(interp: *mut NovaInterpreter) {
+    if !interp.is_null() {
+        // SAFETY: ownership goes back to the Box we created in nova_new
+        drop(unsafe { Box::from_raw(interp) });
+    }
+}
+
+// Interior NULs can't cross the C boundary; Nova strings may contain them
+fn to_c_string(s: String) -> CString {
+    CString::new(s.replace('\0', "\\0")).unwrap_or_default()
+}
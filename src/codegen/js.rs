@@ -0,0 +1,943 @@
+// Nova -> JavaScript.
+//
+// The goal is output a person can read, so constructs map to their
+// closest JS equivalent:
+//   let        -> const (or `let` + reassignment when a name is rebound)
+//   fn         -> arrow function, last expression becomes the return
+//                 (`function*` if the body yields, `async` for async fn)
+//   await x    -> `await x`; JS only allows it directly in an async
+//                 function (or at the top of an ES module), so an await
+//                 inside an if-expression or loop used as a value, which
+//                 become IIFEs, won't run
+//   if         -> `if` statement where possible, an IIFE inside expressions
+//   == / !=    -> === / !==
+//   && / ||    -> && / || between booleans; otherwise an arrow that keeps
+//                 the left side and tests it with `__truthy`
+//   /          -> Math.trunc(a / b)   (integers only, like the interpreter)
+//   a < b < c  -> a < b && b < c, through an IIFE if `b` isn't a plain
+//                 name or literal (so it's evaluated once)
+//   (a, b)     -> array; `let (a, b) = t` destructures it
+//   {k: v}     -> object literal (JS turns every key into a string)
+//   a[-1]      -> a.at(-1) when the index is a negative literal
+//   a[i:j]     -> a.slice(i, j) (a reversed range is empty, not an error)
+//   unsafe     -> its body; JS has no capabilities to gate
+//   defer e    -> `__deferred.push(() => e)`, with the function body (or
+//                 program) in a try whose finally runs them, last first
+//   zone       -> IIFE with its own scope (limits are not enforced)
+//   loop       -> `while (true)`; a loop used as a value is wrapped in an
+//                 IIFE and its `break v` becomes `return v`
+//   do/while   -> `do { ... } while (cond);`, like `loop` otherwise
+//   for x in v -> `for (const x of v)`, like `loop` otherwise; anything but
+//                 an array or string literal goes through an `__iter`
+//                 helper so a hash iterates over its keys
+//   switch     -> JS `switch` in an IIFE; each case returns, so none falls
+//                 through, and with no default an unknown value throws
+//   match      -> IIFE trying each arm's tests in turn, throwing if none
+//                 matches; pattern names are assigned like hoisted lets
+//   1.50d      -> 1.50, a plain JS number (so no longer exact)
+//   f(x: 1)    -> f(1), reordered to the parameter order when f is a
+//                 known function; otherwise through a `__kwargs` helper
+//                 that reads the names off the JS function's source
+//
+// Nova blocks don't open a scope, so a `let` inside an if-branch is
+// hoisted to a `let name;` at the top of the enclosing function.
+//
+// Nova only treats `false` and `null` as falsy, unlike JS. Conditions that
+// are obviously boolean are emitted as-is; anything else goes through a
+// small `__truthy` helper that is added to the output when needed.
+use std::collections::HashMap;
+use crate::ast::{
+    Program, Statement, Expression, BlockStatement, FunctionLiteral, IfExpression, LoopExpression, DoWhileExpression, ForExpression, ZoneExpression,
+    MatchExpression, Pattern, SwitchExpression, ComparisonChain,
+};
+
+const TRUTHY_HELPER: &str = "const __truthy = (v) => v !== null && v !== undefined && v !== false;\n";
+const ITER_HELPER: &str = "const __iter = (v) => typeof v === \"object\" && !(Symbol.iterator in v) ? Object.keys(v) : v;\n";
+const KWARGS_HELPER: &str = "const __kwargs = (f, args, named) => {\n  const params = f.toString().match(/^[^(]*\\(([^)]*)\\)/)[1].split(\",\").map((p) => p.trim()).filter((p) => p);\n  return f(...params.map((p, i) => i < args.length ? args[i] : named[p]));\n};\n";
+
+pub fn compile(program: &Program) -> String {
+    let mut js = JsGen { out: String::new(), indent: 0, needs_truthy: false, needs_iter: false, needs_kwargs: false, scopes: vec![], functions: HashMap::new(), loops: vec![], defers: false };
+    js.program(program);
+    let mut helpers = String::new();
+    if js.needs_truthy {
+        helpers.push_str(TRUTHY_HELPER);
+    }
+    if js.needs_iter {
+        helpers.push_str(ITER_HELPER);
+    }
+    if js.needs_kwargs {
+        helpers.push_str(KWARGS_HELPER);
+    }
+    if helpers.is_empty() {
+        js.out
+    } else {
+        format!("{}\n{}", helpers, js.out)
+    }
+}
+
+// What a Nova `break` turns into inside a given loop
+#[derive(Clone, Copy, PartialEq)]
+enum LoopExit {
+    Break,  // The loop's value isn't used
+    Return, // The loop is the value of a function or IIFE
+}
+
+// How often each name is bound in a scope, and whether we've declared it yet
+#[derive(Default)]
+struct Scope {
+    bindings: HashMap<String, usize>,
+    declared: HashMap<String, bool>,
+}
+
+struct JsGen {
+    out: String,
+    indent: usize,
+    needs_truthy: bool,
+    needs_iter: bool,
+    needs_kwargs: bool,
+    scopes: Vec<Scope>,
+    // Parameter names of names currently bound to a function literal
+    functions: HashMap<String, Vec<String>>,
+    loops: Vec<(LoopExit, Option<String>)>,
+    // Whether the function (or program) being generated has a `defer`
+    defers: bool,
+}
+
+impl JsGen {
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    // --- SCOPES ---
+
+    fn enter_scope(&mut self, statements: &[Statement], params: &[String]) {
+        let mut scope = Scope::default();
+        for p in params {
+            scope.bindings.insert(p.clone(), 1);
+            scope.declared.insert(p.clone(), true);
+        }
+        let mut hoisted = vec![];
+        count_bindings(statements, &mut scope.bindings, false, &mut hoisted);
+        for name in &hoisted {
+            scope.declared.insert(name.clone(), true);
+        }
+        self.scopes.push(scope);
+        if !hoisted.is_empty() {
+            self.line(&format!("let {};", hoisted.join(", ")));
+        }
+    }
+
+    fn let_keyword(&mut self, name: &str) -> &'static str {
+        let scope = self.scopes.last_mut().expect("scope");
+        let already = scope.declared.insert(name.to_string(), true).unwrap_or(false);
+        if already {
+            ""
+        } else if scope.bindings.get(name).copied().unwrap_or(0) > 1 {
+            "let "
+        } else {
+            "const "
+        }
+    }
+
+    // --- STATEMENTS ---
+
+    fn program(&mut self, program: &Program) {
+        self.enter_scope(&program.statements, &[]);
+        let last = program.statements.len().saturating_sub(1);
+        for (i, stmt) in program.statements.iter().enumerate() {
+            match stmt {
+                // The program's value is what the REPL would print
+                Statement::Expression(s) if i == last => {
+                    let value = bare(self.expression(&s.expression));
+                    self.line(&format!("console.log({});", value));
+                },
+                Statement::Return(s) => {
+                    let value = bare(self.expression(&s.return_value));
+                    self.line(&format!("console.log({});", value));
+                    break;
+                },
+                _ => self.statement(stmt),
+            }
+        }
+        self.scopes.pop();
+        if self.defers {
+            self.out = with_deferred(&self.out, 0);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Let(s) => {
+                match &s.value {
+                    Expression::Function(fl) => {
+                        let params = fl.parameters.iter().map(|p| p.value.clone()).collect();
+                        self.functions.insert(s.name.value.clone(), params);
+                    },
+                    _ => {
+                        self.functions.remove(&s.name.value);
+                    },
+                }
+                let value = bare(self.expression(&s.value));
+                let keyword = self.let_keyword(&s.name.value);
+                self.line(&format!("{}{} = {};", keyword, s.name.value, value));
+            },
+            Statement::LetTuple(s) => {
+                let value = bare(self.expression(&s.value));
+                let names: Vec<String> = s.names.iter().map(|n| n.value.clone()).collect();
+                let keywords: Vec<&str> = names.iter().map(|n| self.let_keyword(n)).collect();
+                if keywords.iter().all(|k| *k == keywords[0]) {
+                    self.line(&format!("{}[{}] = {};", keywords.first().unwrap_or(&""), names.join(", "), value));
+                } else {
+                    // Some names are new, some rebound: declare the new ones first
+                    let fresh: Vec<&str> = names.iter().zip(&keywords)
+                        .filter(|(_, k)| !k.is_empty())
+                        .map(|(n, _)| n.as_str())
+                        .collect();
+                    self.line(&format!("let {};", fresh.join(", ")));
+                    self.line(&format!("[{}] = {};", names.join(", "), value));
+                }
+            },
+            Statement::Return(s) => {
+                let value = bare(self.expression(&s.return_value));
+                self.line(&format!("return {};", value));
+            },
+            Statement::Yield(s) => {
+                let value = bare(self.expression(&s.value));
+                self.line(&format!("yield {};", value));
+            },
+            Statement::Defer(s) => {
+                let value = bare(self.expression(&s.value));
+                self.defers = true;
+                self.line(&format!("__deferred.push(() => {});", value));
+            },
+            Statement::Break(s) => {
+                let value = s.value.as_ref().map(|v| (is_pure(v), bare(self.expression(v))));
+                let target = match &s.label {
+                    Some(label) => self.loops.iter().rev().find(|(_, l)| l.as_ref() == Some(label)),
+                    None => self.loops.last(),
+                };
+                match (target.map(|(exit, _)| *exit), value) {
+                    (Some(LoopExit::Return), value) => {
+                        let value = value.map_or_else(|| "null".to_string(), |(_, v)| v);
+                        self.line(&format!("return {};", value));
+                    },
+                    (_, value) => {
+                        // Nobody sees the value, but it may have effects
+                        if let Some((false, value)) = value {
+                            self.line(&format!("{};", value));
+                        }
+                        match &s.label {
+                            Some(label) => self.line(&format!("break {};", label)),
+                            None => self.line("break;"),
+                        }
+                    },
+                }
+            },
+            Statement::Continue(s) => match &s.label {
+                Some(label) => self.line(&format!("continue {};", label)),
+                None => self.line("continue;"),
+            },
+            Statement::Expression(s) => match &s.expression {
+                Expression::If(ie) => self.if_statement(ie, false),
+                Expression::Loop(l) => self.while_loop(l, LoopExit::Break),
+                Expression::DoWhile(d) => self.do_while_loop(d, LoopExit::Break),
+                Expression::For(fe) => self.for_loop(fe, LoopExit::Break),
+                Expression::Unsafe(u) => self.block(&u.body),
+                Expression::Zone(z) => {
+                    let code = self.zone(z);
+                    self.line(&format!("{};", code));
+                },
+                e => {
+                    let value = bare(self.expression(e));
+                    // A statement starting with `{` would be a block in JS
+                    if value.starts_with('{') {
+                        self.line(&format!("({});", value));
+                    } else {
+                        self.line(&format!("{};", value));
+                    }
+                },
+            },
+        }
+    }
+
+    // A block in tail position: its last expression is returned
+    fn tail_block(&mut self, block: &BlockStatement) {
+        let last = block.statements.len().saturating_sub(1);
+        for (i, stmt) in block.statements.iter().enumerate() {
+            if i != last {
+                self.statement(stmt);
+                continue;
+            }
+            match stmt {
+                Statement::Expression(s) => match &s.expression {
+                    Expression::If(ie) => self.if_statement(ie, true),
+                    Expression::Unsafe(u) => self.tail_block(&u.body),
+                    Expression::Loop(l) => self.while_loop(l, LoopExit::Return),
+                    Expression::DoWhile(d) => self.do_while_loop(d, LoopExit::Return),
+                    Expression::For(fe) => self.for_loop(fe, LoopExit::Return),
+                    e => {
+                        let value = bare(self.expression(e));
+                        self.line(&format!("return {};", value));
+                    },
+                },
+                Statement::Let(s) => {
+                    self.statement(stmt);
+                    self.line(&format!("return {};", s.name.value));
+                },
+                Statement::LetTuple(s) => {
+                    self.statement(stmt);
+                    let names: Vec<&str> = s.names.iter().map(|n| n.value.as_str()).collect();
+                    self.line(&format!("return [{}];", names.join(", ")));
+                },
+                Statement::Defer(_) => {
+                    self.statement(stmt);
+                    self.line("return null;");
+                },
+                Statement::Return(_) | Statement::Yield(_) | Statement::Break(_) | Statement::Continue(_) => self.statement(stmt),
+            }
+        }
+        if block.statements.is_empty() {
+            self.line("return null;");
+        }
+    }
+
+    fn if_statement(&mut self, ie: &IfExpression, tail: bool) {
+        let cond = bare(self.condition(&ie.condition));
+        self.line(&format!("if ({}) {{", cond));
+        self.indent += 1;
+        if tail { self.tail_block(&ie.consequence) } else { self.block(&ie.consequence) }
+        self.indent -= 1;
+        match &ie.alternative {
+            Some(alt) => {
+                self.line("} else {");
+                self.indent += 1;
+                if tail { self.tail_block(alt) } else { self.block(alt) }
+                self.indent -= 1;
+                self.line("}");
+            },
+            None => {
+                self.line("}");
+                if tail {
+                    self.line("return null;");
+                }
+            },
+        }
+    }
+
+    fn while_loop(&mut self, l: &LoopExpression, exit: LoopExit) {
+        self.line(&format!("{}while (true) {{", js_label(&l.label)));
+        self.indent += 1;
+        self.loops.push((exit, l.label.clone()));
+        self.block(&l.body);
+        self.loops.pop();
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    fn do_while_loop(&mut self, d: &DoWhileExpression, exit: LoopExit) {
+        self.line(&format!("{}do {{", js_label(&d.label)));
+        self.indent += 1;
+        self.loops.push((exit, d.label.clone()));
+        self.block(&d.body);
+        self.loops.pop();
+        self.indent -= 1;
+        let condition = bare(self.condition(&d.condition));
+        self.line(&format!("}} while ({});", condition));
+        if exit == LoopExit::Return {
+            self.line("return null;");
+        }
+    }
+
+    // Running out of items is the loop's null
+    fn for_loop(&mut self, fe: &ForExpression, exit: LoopExit) {
+        let iterable = bare(self.expression(&fe.iterable));
+        let iterable = match *fe.iterable {
+            Expression::Array(_) | Expression::StringLiteral(_) => iterable,
+            _ => {
+                self.needs_iter = true;
+                format!("__iter({})", iterable)
+            },
+        };
+        self.line(&format!("{}for (const {} of {}) {{", js_label(&fe.label), fe.variable.value, iterable));
+        self.indent += 1;
+        self.loops.push((exit, fe.label.clone()));
+        self.block(&fe.body);
+        self.loops.pop();
+        self.indent -= 1;
+        self.line("}");
+        if exit == LoopExit::Return {
+            self.line("return null;");
+        }
+    }
+
+    fn block(&mut self, block: &BlockStatement) {
+        for stmt in &block.statements {
+            self.statement(stmt);
+        }
+    }
+
+    // --- EXPRESSIONS ---
+
+    fn expression(&mut self, exp: &Expression) -> String {
+        match exp {
+            Expression::Identifier(i) => i.value.clone(),
+            Expression::IntegerLiteral(i) => i.value.to_string(),
+            Expression::StringLiteral(s) => js_string(&s.value),
+            Expression::CharLiteral(c) => js_string(&c.value.to_string()),
+            Expression::DecimalLiteral(d) => d.value.to_string(),
+            Expression::Boolean(b) => b.value.to_string(),
+            Expression::Prefix(p) => {
+                if p.operator == "!" {
+                    format!("!{}", self.condition(&p.right))
+                } else {
+                    format!("({}{})", p.operator, self.expression(&p.right))
+                }
+            },
+            Expression::Infix(i) if (i.operator == "&&" || i.operator == "||") && !is_boolean(exp) => {
+                let left = self.expression(&i.left);
+                let right = self.expression(&i.right);
+                self.needs_truthy = true;
+                match i.operator.as_str() {
+                    "&&" => format!("((__l) => __truthy(__l) ? {} : __l)({})", right, bare(left)),
+                    _ => format!("((__l) => __truthy(__l) ? __l : {})({})", right, bare(left)),
+                }
+            },
+            Expression::Infix(i) => {
+                let left = self.expression(&i.left);
+                let right = self.expression(&i.right);
+                match i.operator.as_str() {
+                    "/" => format!("Math.trunc({} / {})", left, right),
+                    "==" => format!("({} === {})", left, right),
+                    "!=" => format!("({} !== {})", left, right),
+                    op => format!("({} {} {})", left, op, right),
+                }
+            },
+            Expression::Comparison(c) => self.comparison_chain(c),
+            Expression::If(ie) => {
+                // Expressions can't hold statements in JS, wrap in an IIFE
+                let saved = std::mem::take(&mut self.out);
+                let saved_indent = self.indent;
+                self.indent = 1;
+                self.if_statement(ie, true);
+                let body = std::mem::replace(&mut self.out, saved);
+                self.indent = saved_indent;
+                format!("(() => {{\n{}{}}})()", body, "  ".repeat(self.indent))
+            },
+            Expression::Function(fl) => self.function(fl),
+            Expression::Await(a) => format!("(await {})", bare(self.expression(&a.value))),
+            Expression::Call(c) => {
+                let callee = self.expression(&c.function);
+                let callee = match *c.function {
+                    Expression::Function(_) => format!("({})", callee),
+                    _ => callee,
+                };
+                let args: Vec<String> = c.arguments.iter().map(|a| bare(self.expression(a))).collect();
+                if c.keywords.is_empty() {
+                    return format!("{}({})", callee, args.join(", "));
+                }
+                let named: Vec<(String, String)> = c.keywords.iter()
+                    .map(|(name, value)| (name.value.clone(), bare(self.expression(value))))
+                    .collect();
+                let params = match &*c.function {
+                    Expression::Function(fl) => Some(fl.parameters.iter().map(|p| p.value.clone()).collect()),
+                    Expression::Identifier(id) => self.functions.get(&id.value).cloned(),
+                    _ => None,
+                };
+                match params {
+                    Some(params) if named.iter().all(|(n, _)| params.contains(n)) => {
+                        let mut ordered = args;
+                        for param in params.iter().skip(ordered.len()) {
+                            let value = named.iter().find(|(n, _)| n == param).map(|(_, v)| v.clone());
+                            ordered.push(value.unwrap_or_else(|| "undefined".to_string()));
+                        }
+                        format!("{}({})", callee, ordered.join(", "))
+                    },
+                    _ => {
+                        self.needs_kwargs = true;
+                        let pairs: Vec<String> = named.iter().map(|(n, v)| format!("{}: {}", n, v)).collect();
+                        format!("__kwargs({}, [{}], {{{}}})", callee, args.join(", "), pairs.join(", "))
+                    },
+                }
+            },
+            Expression::Unsafe(u) => {
+                let saved = std::mem::take(&mut self.out);
+                let saved_indent = self.indent;
+                self.indent = 1;
+                self.tail_block(&u.body);
+                let body = std::mem::replace(&mut self.out, saved);
+                self.indent = saved_indent;
+                format!("(() => {{\n{}{}}})()", body, "  ".repeat(self.indent))
+            },
+            Expression::Zone(z) => self.zone(z),
+            Expression::Loop(l) => {
+                let saved = std::mem::take(&mut self.out);
+                let saved_indent = self.indent;
+                self.indent += 1;
+                self.while_loop(l, LoopExit::Return);
+                let body = std::mem::replace(&mut self.out, saved);
+                self.indent = saved_indent;
+                format!("(() => {{\n{}{}}})()", body, "  ".repeat(self.indent))
+            },
+            Expression::DoWhile(d) => {
+                let saved = std::mem::take(&mut self.out);
+                let saved_indent = self.indent;
+                self.indent += 1;
+                self.do_while_loop(d, LoopExit::Return);
+                let body = std::mem::replace(&mut self.out, saved);
+                self.indent = saved_indent;
+                format!("(() => {{\n{}{}}})()", body, "  ".repeat(self.indent))
+            },
+            Expression::For(fe) => {
+                let saved = std::mem::take(&mut self.out);
+                let saved_indent = self.indent;
+                self.indent += 1;
+                self.for_loop(fe, LoopExit::Return);
+                let body = std::mem::replace(&mut self.out, saved);
+                self.indent = saved_indent;
+                format!("(() => {{\n{}{}}})()", body, "  ".repeat(self.indent))
+            },
+            Expression::Match(me) => self.match_expression(me),
+            Expression::Switch(se) => self.switch(se),
+            Expression::Index(ie) => {
+                let left = self.expression(&ie.left);
+                let index = bare(self.expression(&ie.index));
+                if matches!(&*ie.index, Expression::Prefix(p) if p.operator == "-") {
+                    format!("({}.at({}) ?? null)", left, index)
+                } else {
+                    format!("({}[{}] ?? null)", left, index)
+                }
+            },
+            Expression::Slice(se) => {
+                let left = self.expression(&se.left);
+                let start = se.start.as_ref().map(|b| bare(self.expression(b)));
+                let end = se.end.as_ref().map(|b| bare(self.expression(b)));
+                match (start, end) {
+                    (start, Some(end)) => format!("{}.slice({}, {})", left, start.unwrap_or_else(|| "0".to_string()), end),
+                    (Some(start), None) => format!("{}.slice({})", left, start),
+                    (None, None) => format!("{}.slice()", left),
+                }
+            },
+            Expression::Array(a) => {
+                let elements: Vec<String> = a.elements.iter().map(|e| bare(self.expression(e))).collect();
+                format!("[{}]", elements.join(", "))
+            },
+            Expression::Tuple(t) => {
+                let elements: Vec<String> = t.elements.iter().map(|e| bare(self.expression(e))).collect();
+                format!("[{}]", elements.join(", "))
+            },
+            Expression::Hash(h) => {
+                let pairs: Vec<String> = h.pairs.iter().map(|(k, v)| {
+                    let key = match k {
+                        Expression::StringLiteral(s) => js_string(&s.value),
+                        Expression::IntegerLiteral(i) => i.value.to_string(),
+                        k => format!("[{}]", bare(self.expression(k))),
+                    };
+                    format!("{}: {}", key, bare(self.expression(v)))
+                }).collect();
+                format!("{{{}}}", pairs.join(", "))
+            },
+        }
+    }
+
+    fn function(&mut self, fl: &FunctionLiteral) -> String {
+        let params: Vec<String> = fl.parameters.iter().map(|p| p.value.clone()).collect();
+
+        let saved = std::mem::take(&mut self.out);
+        let saved_indent = self.indent;
+        // Parameters shadow outer functions of the same name
+        let saved_functions = self.functions.clone();
+        let saved_loops = std::mem::take(&mut self.loops);
+        let saved_defers = std::mem::replace(&mut self.defers, false);
+        for param in &params {
+            self.functions.remove(param);
+        }
+        self.indent += 1;
+        self.enter_scope(&fl.body.statements, &params);
+        self.tail_block(&fl.body);
+        self.scopes.pop();
+        let mut body = std::mem::replace(&mut self.out, saved);
+        self.indent = saved_indent;
+        self.functions = saved_functions;
+        self.loops = saved_loops;
+        if std::mem::replace(&mut self.defers, saved_defers) {
+            body = with_deferred(&body, saved_indent + 1);
+        }
+
+        let head = if fl.body.contains_yield() { "function* " } else { "" };
+        let arrow = if head.is_empty() { " =>" } else { "" };
+        let asynchronous = if fl.is_async { "async " } else { "" };
+        format!("{}{}({}){} {{\n{}{}}}", asynchronous, head, params.join(", "), arrow, body, "  ".repeat(self.indent))
+    }
+
+    fn zone(&mut self, z: &ZoneExpression) -> String {
+        let saved = std::mem::take(&mut self.out);
+        let saved_indent = self.indent;
+        self.indent += 1;
+        self.enter_scope(&z.body.statements, &[]);
+        self.tail_block(&z.body);
+        self.scopes.pop();
+        let body = std::mem::replace(&mut self.out, saved);
+        self.indent = saved_indent;
+        format!("(() => {{\n{}{}}})()", body, "  ".repeat(self.indent))
+    }
+
+    // `a < b && b < c` when the middle operands can be repeated, otherwise
+    // an IIFE that evaluates each operand once, in order
+    fn comparison_chain(&mut self, c: &ComparisonChain) -> String {
+        let operands: Vec<String> = c.operands.iter().map(|o| bare(self.expression(o))).collect();
+        let middle = &c.operands[1..c.operands.len() - 1];
+        if middle.iter().all(is_pure) {
+            let pairs: Vec<String> = c.operators.iter().enumerate()
+                .map(|(i, op)| format!("{} {} {}", operands[i], op, operands[i + 1]))
+                .collect();
+            return format!("({})", pairs.join(" && "));
+        }
+        let saved = std::mem::take(&mut self.out);
+        let saved_indent = self.indent;
+        self.indent += 1;
+        self.line(&format!("let __l = {};", operands[0]));
+        for (i, op) in c.operators.iter().enumerate() {
+            self.line(&format!("const __r{} = {};", i, operands[i + 1]));
+            if i + 1 == c.operators.len() {
+                self.line(&format!("return __l {} __r{};", op, i));
+            } else {
+                self.line(&format!("if (!(__l {} __r{})) return false;", op, i));
+                self.line(&format!("__l = __r{};", i));
+            }
+        }
+        let body = std::mem::replace(&mut self.out, saved);
+        self.indent = saved_indent;
+        format!("(() => {{\n{}{}}})()", body, "  ".repeat(self.indent))
+    }
+
+    fn match_expression(&mut self, me: &MatchExpression) -> String {
+        let subject = bare(self.expression(&me.subject));
+        let saved = std::mem::take(&mut self.out);
+        let saved_indent = self.indent;
+        self.indent += 1;
+        self.line(&format!("const __m = {};", subject));
+        let mut exhaustive = false;
+        for arm in &me.arms {
+            let mut tests = vec![];
+            let mut binds = vec![];
+            self.pattern(&arm.pattern, "__m", &mut tests, &mut binds);
+            // An arm that always matches ends the chain
+            if tests.is_empty() && arm.guard.is_none() {
+                for (name, value) in binds {
+                    self.line(&format!("{} = {};", name, value));
+                }
+                self.tail_block(&arm.body);
+                exhaustive = true;
+                break;
+            }
+            let test = if tests.is_empty() { "true".to_string() } else { tests.join(" && ") };
+            self.line(&format!("if ({}) {{", test));
+            self.indent += 1;
+            for (name, value) in binds {
+                self.line(&format!("{} = {};", name, value));
+            }
+            match &arm.guard {
+                Some(guard) => {
+                    let guard = bare(self.condition(guard));
+                    self.line(&format!("if ({}) {{", guard));
+                    self.indent += 1;
+                    self.tail_block(&arm.body);
+                    self.indent -= 1;
+                    self.line("}");
+                },
+                None => self.tail_block(&arm.body),
+            }
+            self.indent -= 1;
+            self.line("}");
+        }
+        if !exhaustive {
+            self.line("throw new Error(\"match: no arm matches \" + JSON.stringify(__m));");
+        }
+        let body = std::mem::replace(&mut self.out, saved);
+        self.indent = saved_indent;
+        format!("(() => {{\n{}{}}})()", body, "  ".repeat(self.indent))
+    }
+
+    fn switch(&mut self, se: &SwitchExpression) -> String {
+        let subject = bare(self.expression(&se.subject));
+        let saved = std::mem::take(&mut self.out);
+        let saved_indent = self.indent;
+        self.indent += 1;
+        self.line(&format!("const __s = {};", subject));
+        self.line("switch (__s) {");
+        self.indent += 1;
+        for case in &se.cases {
+            for value in &case.values {
+                let value = bare(self.expression(value));
+                self.line(&format!("case {}:", value));
+            }
+            self.indent += 1;
+            self.tail_block(&case.body);
+            self.indent -= 1;
+        }
+        self.line("default:");
+        self.indent += 1;
+        match &se.default {
+            Some(body) => self.tail_block(body),
+            None => self.line("throw new Error(\"switch: no case for \" + JSON.stringify(__s));"),
+        }
+        self.indent -= 2;
+        self.line("}");
+        let body = std::mem::replace(&mut self.out, saved);
+        self.indent = saved_indent;
+        format!("(() => {{\n{}{}}})()", body, "  ".repeat(self.indent))
+    }
+
+    // The tests a value at `path` has to pass, and what it binds
+    fn pattern(&mut self, pattern: &Pattern, path: &str, tests: &mut Vec<String>, binds: &mut Vec<(String, String)>) {
+        match pattern {
+            Pattern::Wildcard => {},
+            Pattern::Null => tests.push(format!("{} === null", path)),
+            Pattern::Literal(literal) => {
+                let literal = bare(self.expression(literal));
+                tests.push(format!("{} === {}", path, literal));
+            },
+            Pattern::Binding(name) => binds.push((name.value.clone(), path.to_string())),
+            Pattern::Array(elements, rest) => {
+                let length = if rest.is_some() { ">=" } else { "===" };
+                tests.push(format!("Array.isArray({}) && {}.length {} {}", path, path, length, elements.len()));
+                for (i, element) in elements.iter().enumerate() {
+                    self.pattern(element, &format!("{}[{}]", path, i), tests, binds);
+                }
+                if let Some(rest) = rest {
+                    self.pattern(rest, &format!("{}.slice({})", path, elements.len()), tests, binds);
+                }
+            },
+            Pattern::Tuple(elements) => {
+                tests.push(format!("Array.isArray({}) && {}.length === {}", path, path, elements.len()));
+                for (i, element) in elements.iter().enumerate() {
+                    self.pattern(element, &format!("{}[{}]", path, i), tests, binds);
+                }
+            },
+            Pattern::Hash(pairs) => {
+                tests.push(format!("typeof {} === \"object\" && {} !== null && !Array.isArray({})", path, path, path));
+                for (key, value) in pairs {
+                    let key = bare(self.expression(key));
+                    tests.push(format!("{} in {}", key, path));
+                    self.pattern(value, &format!("{}[{}]", path, key), tests, binds);
+                }
+            },
+        }
+    }
+
+    // Conditions use JS truthiness unless they are clearly booleans already
+    fn condition(&mut self, exp: &Expression) -> String {
+        let code = self.expression(exp);
+        if is_boolean(exp) {
+            code
+        } else {
+            self.needs_truthy = true;
+            format!("__truthy({})", code)
+        }
+    }
+}
+
+// `outer: ` before a labelled loop
+fn js_label(label: &Option<String>) -> String {
+    label.as_ref().map_or_else(String::new, |label| format!("{}: ", label))
+}
+
+// Drops one pair of parentheses wrapping the whole expression, for
+// positions where they're just noise (`const x = a + b;`)
+fn bare(code: String) -> String {
+    if !(code.starts_with('(') && code.ends_with(')')) {
+        return code;
+    }
+    let mut depth = 0;
+    for (i, ch) in code.char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 && i != code.len() - 1 {
+                    return code; // "(a) + (b)"
+                }
+            },
+            _ => {},
+        }
+    }
+    code[1..code.len() - 1].to_string()
+}
+
+fn is_boolean(exp: &Expression) -> bool {
+    match exp {
+        Expression::Boolean(_) => true,
+        Expression::Prefix(p) => p.operator == "!",
+        Expression::Infix(i) if i.operator == "&&" || i.operator == "||" => is_boolean(&i.left) && is_boolean(&i.right),
+        Expression::Infix(i) => matches!(i.operator.as_str(), "<" | ">" | "==" | "!="),
+        Expression::Comparison(_) => true,
+        _ => false,
+    }
+}
+
+// Bindings made in this scope; names first bound inside a block are
+// collected in `hoisted`
+fn count_bindings(statements: &[Statement], counts: &mut HashMap<String, usize>, nested: bool, hoisted: &mut Vec<String>) {
+    for stmt in statements {
+        match stmt {
+            Statement::Let(s) => {
+                count_in_expression(&s.value, counts, hoisted);
+                let name = &s.name.value;
+                let count = counts.entry(name.clone()).or_insert(0);
+                if *count == 0 && nested && !hoisted.contains(name) {
+                    hoisted.push(name.clone());
+                }
+                *count += 1;
+            },
+            Statement::LetTuple(s) => {
+                count_in_expression(&s.value, counts, hoisted);
+                for name in &s.names {
+                    let count = counts.entry(name.value.clone()).or_insert(0);
+                    if *count == 0 && nested && !hoisted.contains(&name.value) {
+                        hoisted.push(name.value.clone());
+                    }
+                    *count += 1;
+                }
+            },
+            Statement::Return(s) => count_in_expression(&s.return_value, counts, hoisted),
+            Statement::Yield(s) => count_in_expression(&s.value, counts, hoisted),
+            Statement::Defer(s) => count_in_expression(&s.value, counts, hoisted),
+            Statement::Break(s) => {
+                if let Some(value) = &s.value {
+                    count_in_expression(value, counts, hoisted);
+                }
+            },
+            Statement::Continue(_) => {},
+            Statement::Expression(s) => count_in_expression(&s.expression, counts, hoisted),
+        }
+    }
+}
+
+// A body whose defers run however it ends
+fn with_deferred(body: &str, indent: usize) -> String {
+    let pad = "  ".repeat(indent);
+    let mut out = format!("{pad}const __deferred = [];\n{pad}try {{\n");
+    for line in body.lines() {
+        out.push_str(&format!("  {}\n", line));
+    }
+    out.push_str(&format!("{pad}}} finally {{\n{pad}  for (const __f of __deferred.reverse()) __f();\n{pad}}}\n"));
+    out
+}
+
+// Blocks inside an expression still bind in the enclosing scope; function
+// literals get a scope of their own
+fn count_in_expression(exp: &Expression, counts: &mut HashMap<String, usize>, hoisted: &mut Vec<String>) {
+    match exp {
+        Expression::If(ie) => {
+            count_in_expression(&ie.condition, counts, hoisted);
+            count_bindings(&ie.consequence.statements, counts, true, hoisted);
+            if let Some(alt) = &ie.alternative {
+                count_bindings(&alt.statements, counts, true, hoisted);
+            }
+        },
+        Expression::Unsafe(u) => count_bindings(&u.body.statements, counts, true, hoisted),
+        Expression::Loop(l) => count_bindings(&l.body.statements, counts, true, hoisted),
+        Expression::DoWhile(d) => {
+            count_bindings(&d.body.statements, counts, true, hoisted);
+            count_in_expression(&d.condition, counts, hoisted);
+        },
+        Expression::For(fe) => {
+            count_in_expression(&fe.iterable, counts, hoisted);
+            count_bindings(&fe.body.statements, counts, true, hoisted);
+        },
+        Expression::Match(me) => {
+            count_in_expression(&me.subject, counts, hoisted);
+            for arm in &me.arms {
+                for name in arm.pattern.bindings() {
+                    let count = counts.entry(name.value.clone()).or_insert(0);
+                    if *count == 0 && !hoisted.contains(&name.value) {
+                        hoisted.push(name.value.clone());
+                    }
+                    *count += 1;
+                }
+                if let Some(guard) = &arm.guard {
+                    count_in_expression(guard, counts, hoisted);
+                }
+                count_bindings(&arm.body.statements, counts, true, hoisted);
+            }
+        },
+        Expression::Switch(se) => {
+            count_in_expression(&se.subject, counts, hoisted);
+            for body in se.bodies() {
+                count_bindings(&body.statements, counts, true, hoisted);
+            }
+        },
+        Expression::Prefix(p) => count_in_expression(&p.right, counts, hoisted),
+        Expression::Await(a) => count_in_expression(&a.value, counts, hoisted),
+        Expression::Comparison(c) => {
+            for operand in &c.operands {
+                count_in_expression(operand, counts, hoisted);
+            }
+        },
+        Expression::Infix(i) => {
+            count_in_expression(&i.left, counts, hoisted);
+            count_in_expression(&i.right, counts, hoisted);
+        },
+        Expression::Call(c) => {
+            count_in_expression(&c.function, counts, hoisted);
+            for a in c.all_arguments() {
+                count_in_expression(a, counts, hoisted);
+            }
+        },
+        Expression::Array(a) => {
+            for e in &a.elements {
+                count_in_expression(e, counts, hoisted);
+            }
+        },
+        Expression::Tuple(t) => {
+            for e in &t.elements {
+                count_in_expression(e, counts, hoisted);
+            }
+        },
+        Expression::Hash(h) => {
+            for (k, v) in &h.pairs {
+                count_in_expression(k, counts, hoisted);
+                count_in_expression(v, counts, hoisted);
+            }
+        },
+        Expression::Index(ie) => {
+            count_in_expression(&ie.left, counts, hoisted);
+            count_in_expression(&ie.index, counts, hoisted);
+        },
+        Expression::Slice(se) => {
+            count_in_expression(&se.left, counts, hoisted);
+            for bound in [&se.start, &se.end].into_iter().flatten() {
+                count_in_expression(bound, counts, hoisted);
+            }
+        },
+        Expression::Identifier(_) | Expression::IntegerLiteral(_) | Expression::StringLiteral(_) |
+        Expression::CharLiteral(_) | Expression::DecimalLiteral(_) | Expression::Boolean(_) | Expression::Function(_) |
+        Expression::Zone(_) => {},
+    }
+}
+
+// Evaluating it can't do anything but produce the value
+fn is_pure(exp: &Expression) -> bool {
+    matches!(exp, Expression::Identifier(_) | Expression::IntegerLiteral(_) | Expression::StringLiteral(_) |
+        Expression::CharLiteral(_) | Expression::DecimalLiteral(_) | Expression::Boolean(_))
+}
+
+fn js_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
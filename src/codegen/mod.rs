@@ -0,0 +1,2 @@
+// Backends that turn a parsed Program into another language
+pub mod js;
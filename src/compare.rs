@@ -0,0 +1,60 @@
+// Equality and ordering between runtime values.
+//
+// `==` / `!=` work on any two values: composites compare element by
+// element, values of different types are simply unequal. Ordering (`<`,
+// `>`) is only defined where it means something: ints (of any size), decimals, strings, chars,
+// datetimes, bytes, and arrays or tuples of those (lexicographically, like strings). Anything else is an
+// error rather than an arbitrary answer.
+use std::cmp::Ordering;
+use crate::decimal::Decimal;
+use crate::object::Object;
+
+pub fn equals(a: &Object, b: &Object) -> bool {
+    match (a, b) {
+        (Object::Integer(x), Object::Integer(y)) => x == y,
+        (Object::Boolean(x), Object::Boolean(y)) => x == y,
+        (Object::String(x), Object::String(y)) => x == y,
+        (Object::Null, Object::Null) => true,
+        (Object::Array(x), Object::Array(y)) | (Object::Tuple(x), Object::Tuple(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(a, b)| equals(a, b))
+        },
+        (Object::Hash(x), Object::Hash(y)) => {
+            x.len() == y.len() && x.iter().all(|(k, v)| y.get(k).is_some_and(|w| equals(v, w)))
+        },
+        (Object::Set(x), Object::Set(y)) => x == y,
+        (Object::ReturnValue(x), _) => equals(x, b),
+        (_, Object::ReturnValue(y)) => equals(a, y),
+        // Functions and errors: same definition / same message
+        _ => a == b,
+    }
+}
+
+pub fn compare(a: &Object, b: &Object) -> Result<Ordering, String> {
+    match (a, b) {
+        (Object::Integer(x), Object::Integer(y)) => Ok(x.cmp(y)),
+        (Object::String(x), Object::String(y)) => Ok(x.cmp(y)),
+        (Object::Char(x), Object::Char(y)) => Ok(x.cmp(y)),
+        (Object::Decimal(x), Object::Decimal(y)) => Ok(x.cmp(y)),
+        (Object::Decimal(x), Object::Integer(y)) => Ok(x.cmp(&Decimal::from(*y))),
+        (Object::Integer(x), Object::Decimal(y)) => Ok(Decimal::from(*x).cmp(y)),
+        #[cfg(feature = "bigint")]
+        (Object::BigInt(x), Object::BigInt(y)) => Ok(x.cmp(y)),
+        #[cfg(feature = "bigint")]
+        (Object::BigInt(x), Object::Integer(y)) => Ok(x.cmp(&(*y).into())),
+        #[cfg(feature = "bigint")]
+        (Object::Integer(x), Object::BigInt(y)) => Ok(num_bigint::BigInt::from(*x).cmp(y)),
+        (Object::DateTime(x), Object::DateTime(y)) => Ok(x.cmp(y)),
+        (Object::Bytes(x), Object::Bytes(y)) => Ok(x.cmp(y)),
+        (Object::Array(x), Object::Array(y)) | (Object::Tuple(x), Object::Tuple(y)) => {
+            for (a, b) in x.iter().zip(y) {
+                match compare(a, b)? {
+                    Ordering::Equal => continue,
+                    other => return Ok(other),
+                }
+            }
+            Ok(x.len().cmp(&y.len()))
+        },
+        _ if a.type_name() == b.type_name() => Err(format!("{} values can't be ordered", a.type_name())),
+        _ => Err(format!("can't compare {} with {}", a.type_name(), b.type_name())),
+    }
+}
@@ -0,0 +1,259 @@
+// Conversions between Rust values and Nova objects.
+//
+// Host code (builtins, embedders) should not have to match on `Object`
+// by hand for every argument. `From` covers the Rust -> Nova direction,
+// `FromObject`/`TryFrom` the fallible Nova -> Rust direction, and
+// `FromNovaArgs` unpacks a whole argument list in one call:
+//
+//     let (name, count): (String, i64) = FromNovaArgs::from_args(&args)?;
+use std::collections::BTreeSet;
+use std::fmt;
+use indexmap::IndexMap;
+use crate::datetime::DateTime;
+use crate::object::{HashKey, Object};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionError {
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected {}, found {}", self.expected, self.found)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgError {
+    WrongCount { expected: usize, found: usize },
+    WrongType { index: usize, error: ConversionError },
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgError::WrongCount { expected, found } => {
+                write!(f, "wrong number of arguments: expected {}, found {}", expected, found)
+            },
+            ArgError::WrongType { index, error } => {
+                write!(f, "argument {}: {}", index + 1, error)
+            },
+        }
+    }
+}
+
+// --- RUST -> NOVA ---
+
+impl From<i64> for Object {
+    fn from(val: i64) -> Self { Object::Integer(val) }
+}
+
+// Back to a plain int when it fits, so a value has only one representation
+#[cfg(feature = "bigint")]
+impl From<num_bigint::BigInt> for Object {
+    fn from(val: num_bigint::BigInt) -> Self {
+        i64::try_from(&val).map_or(Object::BigInt(val), Object::Integer)
+    }
+}
+
+impl From<bool> for Object {
+    fn from(val: bool) -> Self { Object::Boolean(val) }
+}
+
+impl From<String> for Object {
+    fn from(val: String) -> Self { Object::String(val) }
+}
+
+impl From<&str> for Object {
+    fn from(val: &str) -> Self { Object::String(val.to_string()) }
+}
+
+impl From<char> for Object {
+    fn from(val: char) -> Self { Object::Char(val) }
+}
+
+impl From<DateTime> for Object {
+    fn from(val: DateTime) -> Self { Object::DateTime(val) }
+}
+
+impl From<()> for Object {
+    fn from(_: ()) -> Self { Object::Null }
+}
+
+impl<T: Into<Object>> From<Vec<T>> for Object {
+    fn from(val: Vec<T>) -> Self {
+        Object::Array(val.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<Object>> From<Option<T>> for Object {
+    fn from(val: Option<T>) -> Self {
+        match val {
+            Some(v) => v.into(),
+            None => Object::Null,
+        }
+    }
+}
+
+// --- NOVA -> RUST ---
+
+pub trait FromObject: Sized {
+    fn from_object(obj: &Object) -> Result<Self, ConversionError>;
+}
+
+fn mismatch(expected: &'static str, obj: &Object) -> ConversionError {
+    ConversionError { expected, found: obj.type_name() }
+}
+
+impl FromObject for Object {
+    fn from_object(obj: &Object) -> Result<Self, ConversionError> {
+        Ok(obj.clone())
+    }
+}
+
+impl FromObject for i64 {
+    fn from_object(obj: &Object) -> Result<Self, ConversionError> {
+        match obj {
+            Object::Integer(val) => Ok(*val),
+            _ => Err(mismatch("int", obj)),
+        }
+    }
+}
+
+impl FromObject for bool {
+    fn from_object(obj: &Object) -> Result<Self, ConversionError> {
+        match obj {
+            Object::Boolean(val) => Ok(*val),
+            _ => Err(mismatch("bool", obj)),
+        }
+    }
+}
+
+impl FromObject for String {
+    fn from_object(obj: &Object) -> Result<Self, ConversionError> {
+        match obj {
+            Object::String(val) => Ok(val.clone()),
+            _ => Err(mismatch("string", obj)),
+        }
+    }
+}
+
+impl FromObject for char {
+    fn from_object(obj: &Object) -> Result<Self, ConversionError> {
+        match obj {
+            Object::Char(val) => Ok(*val),
+            _ => Err(mismatch("char", obj)),
+        }
+    }
+}
+
+// An int is taken as Unix seconds, so time() works wherever a date does
+impl FromObject for DateTime {
+    fn from_object(obj: &Object) -> Result<Self, ConversionError> {
+        match obj {
+            Object::DateTime(val) => Ok(*val),
+            Object::Integer(secs) => DateTime::from_timestamp(*secs).ok_or_else(|| mismatch("datetime", obj)),
+            _ => Err(mismatch("datetime", obj)),
+        }
+    }
+}
+
+impl<T: FromObject> FromObject for Vec<T> {
+    fn from_object(obj: &Object) -> Result<Self, ConversionError> {
+        match obj {
+            Object::Array(elements) => elements.iter().map(T::from_object).collect(),
+            _ => Err(mismatch("array", obj)),
+        }
+    }
+}
+
+impl FromObject for BTreeSet<HashKey> {
+    fn from_object(obj: &Object) -> Result<Self, ConversionError> {
+        match obj {
+            Object::Set(keys) => Ok(keys.clone()),
+            _ => Err(mismatch("set", obj)),
+        }
+    }
+}
+
+impl FromObject for IndexMap<HashKey, Object> {
+    fn from_object(obj: &Object) -> Result<Self, ConversionError> {
+        match obj {
+            Object::Hash(pairs) => Ok(pairs.clone()),
+            _ => Err(mismatch("hash", obj)),
+        }
+    }
+}
+
+// `null` maps to None, anything else must convert to T
+impl<T: FromObject> FromObject for Option<T> {
+    fn from_object(obj: &Object) -> Result<Self, ConversionError> {
+        match obj {
+            Object::Null => Ok(None),
+            _ => T::from_object(obj).map(Some),
+        }
+    }
+}
+
+impl TryFrom<Object> for i64 {
+    type Error = ConversionError;
+    fn try_from(obj: Object) -> Result<Self, Self::Error> { i64::from_object(&obj) }
+}
+
+impl TryFrom<Object> for bool {
+    type Error = ConversionError;
+    fn try_from(obj: Object) -> Result<Self, Self::Error> { bool::from_object(&obj) }
+}
+
+impl TryFrom<Object> for String {
+    type Error = ConversionError;
+    fn try_from(obj: Object) -> Result<Self, Self::Error> {
+        match obj {
+            Object::String(val) => Ok(val),
+            _ => Err(mismatch("string", &obj)),
+        }
+    }
+}
+
+impl<T: FromObject> TryFrom<Object> for Vec<T> {
+    type Error = ConversionError;
+    fn try_from(obj: Object) -> Result<Self, Self::Error> { Vec::<T>::from_object(&obj) }
+}
+
+// --- ARGUMENT LISTS ---
+
+pub trait FromNovaArgs: Sized {
+    fn from_args(args: &[Object]) -> Result<Self, ArgError>;
+}
+
+fn convert_arg<T: FromObject>(args: &[Object], index: usize) -> Result<T, ArgError> {
+    T::from_object(&args[index]).map_err(|error| ArgError::WrongType { index, error })
+}
+
+macro_rules! impl_from_nova_args {
+    ($count:expr; $($name:ident => $index:tt),*) => {
+        impl<$($name: FromObject),*> FromNovaArgs for ($($name,)*) {
+            fn from_args(args: &[Object]) -> Result<Self, ArgError> {
+                if args.len() != $count {
+                    return Err(ArgError::WrongCount { expected: $count, found: args.len() });
+                }
+                Ok(($(convert_arg::<$name>(args, $index)?,)*))
+            }
+        }
+    };
+}
+
+impl_from_nova_args!(0;);
+impl_from_nova_args!(1; A => 0);
+impl_from_nova_args!(2; A => 0, B => 1);
+impl_from_nova_args!(3; A => 0, B => 1, C => 2);
+impl_from_nova_args!(4; A => 0, B => 1, C => 2, D => 3);
+impl_from_nova_args!(5; A => 0, B => 1, C => 2, D => 3, E => 4);
+
+// Variadic builtins take the whole list as-is
+impl FromNovaArgs for Vec<Object> {
+    fn from_args(args: &[Object]) -> Result<Self, ArgError> {
+        Ok(args.to_vec())
+    }
+}
@@ -0,0 +1,193 @@
+// How expensive a program looks, from its AST alone. For hosts that take
+// formulas from users and want to turn away pathological ones before
+// running anything:
+//
+//   let cost = cost::estimate(&program);
+//   if cost.work > 100_000 || cost.loop_depth > 2 || cost.recursive {
+//       return Err("formula too complex");
+//   }
+//
+// `work` counts every node weighted by how often the loops around it are
+// expected to run it: a `for` over an array literal runs once per element,
+// any other loop is guessed to run LOOP_GUESS times. A function's body is
+// counted once, where it's written, not at each call. It's an estimate
+// for screening inputs; zones and the step limit are what bound a run.
+use crate::ast::{BlockStatement, Expression, Program, Statement};
+
+// Iterations assumed for a loop whose count isn't in the source
+pub const LOOP_GUESS: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Cost {
+    // Statements and expressions
+    pub nodes: usize,
+    // Deepest nesting of loops
+    pub loop_depth: usize,
+    // Call sites, and the most of them in one function body (the top level
+    // counts as one)
+    pub calls: usize,
+    pub fan_out: usize,
+    // A function calls itself by the name it was bound to
+    pub recursive: bool,
+    pub work: u64,
+}
+
+pub fn estimate(program: &Program) -> Cost {
+    let mut e = Estimator { cost: Cost::default(), weight: 1, depth: 0, functions: vec![(None, 0)] };
+    for statement in &program.statements {
+        e.statement(statement);
+    }
+    let (_, calls) = e.functions[0];
+    e.cost.fan_out = e.cost.fan_out.max(calls);
+    e.cost
+}
+
+struct Estimator {
+    cost: Cost,
+    // How many times the node being looked at is expected to run
+    weight: u64,
+    depth: usize,
+    // The functions being looked inside of: the name each is bound to and
+    // its call sites so far
+    functions: Vec<(Option<String>, usize)>,
+}
+
+impl Estimator {
+    fn node(&mut self) {
+        self.cost.nodes += 1;
+        self.cost.work = self.cost.work.saturating_add(self.weight);
+    }
+
+    fn block(&mut self, block: &BlockStatement) {
+        for statement in &block.statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        self.node();
+        match statement {
+            Statement::Let(s) => match &s.value {
+                Expression::Function(_) => self.function(&s.value, Some(&s.name.value)),
+                value => self.expression(value),
+            },
+            Statement::LetTuple(s) => self.expression(&s.value),
+            Statement::Return(s) => self.expression(&s.return_value),
+            Statement::Yield(s) => self.expression(&s.value),
+            Statement::Defer(s) => self.expression(&s.value),
+            Statement::Break(s) => {
+                if let Some(value) = &s.value {
+                    self.expression(value);
+                }
+            },
+            Statement::Continue(_) => {},
+            Statement::Expression(s) => self.expression(&s.expression),
+        }
+    }
+
+    // The body of a loop, run `times` times for every time the loop is
+    fn repeated(&mut self, times: u64, f: impl FnOnce(&mut Self)) {
+        let weight = self.weight;
+        self.weight = weight.saturating_mul(times.max(1));
+        self.depth += 1;
+        self.cost.loop_depth = self.cost.loop_depth.max(self.depth);
+        f(self);
+        self.depth -= 1;
+        self.weight = weight;
+    }
+
+    // Its own call sites, and recursion through `name`
+    fn function(&mut self, e: &Expression, name: Option<&str>) {
+        let Expression::Function(f) = e else { return };
+        self.node();
+        self.functions.push((name.map(str::to_string), 0));
+        self.block(&f.body);
+        if let Some((_, calls)) = self.functions.pop() {
+            self.cost.fan_out = self.cost.fan_out.max(calls);
+        }
+    }
+
+    fn expression(&mut self, e: &Expression) {
+        match e {
+            Expression::Function(_) => return self.function(e, None),
+            _ => self.node(),
+        }
+        match e {
+            Expression::Identifier(_) | Expression::IntegerLiteral(_) | Expression::StringLiteral(_) |
+            Expression::CharLiteral(_) | Expression::DecimalLiteral(_) | Expression::Boolean(_) |
+            Expression::Function(_) => {},
+            Expression::Prefix(e) => self.expression(&e.right),
+            Expression::Await(e) => self.expression(&e.value),
+            Expression::Infix(e) => {
+                self.expression(&e.left);
+                self.expression(&e.right);
+            },
+            Expression::Comparison(e) => e.operands.iter().for_each(|o| self.expression(o)),
+            Expression::If(e) => {
+                self.expression(&e.condition);
+                self.block(&e.consequence);
+                if let Some(alternative) = &e.alternative {
+                    self.block(alternative);
+                }
+            },
+            Expression::Call(e) => {
+                self.cost.calls += 1;
+                if let Some((_, calls)) = self.functions.last_mut() {
+                    *calls += 1;
+                }
+                if let Expression::Identifier(callee) = &*e.function
+                    && self.functions.iter().any(|(name, _)| name.as_deref() == Some(callee.value.as_str())) {
+                    self.cost.recursive = true;
+                }
+                self.expression(&e.function);
+                e.all_arguments().for_each(|a| self.expression(a));
+            },
+            Expression::Array(e) => e.elements.iter().for_each(|el| self.expression(el)),
+            Expression::Tuple(e) => e.elements.iter().for_each(|el| self.expression(el)),
+            Expression::Hash(e) => e.pairs.iter().for_each(|(k, v)| {
+                self.expression(k);
+                self.expression(v);
+            }),
+            Expression::Index(e) => {
+                self.expression(&e.left);
+                self.expression(&e.index);
+            },
+            Expression::Slice(e) => {
+                self.expression(&e.left);
+                [&e.start, &e.end].into_iter().flatten().for_each(|b| self.expression(b));
+            },
+            Expression::Unsafe(e) => self.block(&e.body),
+            Expression::Zone(e) => {
+                e.limits.iter().for_each(|(_, v)| self.expression(v));
+                self.block(&e.body);
+            },
+            Expression::Loop(e) => self.repeated(LOOP_GUESS, |s| s.block(&e.body)),
+            Expression::DoWhile(e) => self.repeated(LOOP_GUESS, |s| {
+                s.block(&e.body);
+                s.expression(&e.condition);
+            }),
+            Expression::For(e) => {
+                self.expression(&e.iterable);
+                let times = match &*e.iterable {
+                    Expression::Array(a) => a.elements.len() as u64,
+                    _ => LOOP_GUESS,
+                };
+                self.repeated(times, |s| s.block(&e.body));
+            },
+            Expression::Match(e) => {
+                self.expression(&e.subject);
+                for arm in &e.arms {
+                    if let Some(guard) = &arm.guard {
+                        self.expression(guard);
+                    }
+                    self.block(&arm.body);
+                }
+            },
+            Expression::Switch(e) => {
+                self.expression(&e.subject);
+                e.cases.iter().flat_map(|c| &c.values).for_each(|v| self.expression(v));
+                e.bodies().for_each(|b| self.block(b));
+            },
+        }
+    }
+}
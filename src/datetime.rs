@@ -0,0 +1,406 @@
+// Points in time, to the millisecond, in UTC. A datetime is milliseconds
+// since the Unix epoch; the calendar (proleptic Gregorian) only comes in
+// when it's built from or broken into parts, formatted or parsed.
+//
+//   date(2024, 2, 29, 13, 5)                   // 2024-02-29T13:05:00Z
+//   date_format(d, "%d %b %Y, %H:%M")          // "29 Feb 2024, 13:05"
+//   date_parse("29/02/2024", "%d/%m/%Y")       // 2024-02-29T00:00:00Z
+//   date_parse("2024-02-29T14:05:00+01:00")    // ISO 8601 without a format
+//
+// Format directives (strftime's, minus the locale-dependent ones):
+//
+//   %Y year   %m month 01-12   %d day 01-31   %H hour 00-23   %M minute
+//   %S second   %f millisecond 000-999   %y two-digit year   %j day of year
+//   %b %B month name (Jan, January)   %a %A weekday name (Mon, Monday)
+//   %s Unix seconds   %z offset (+0000)   %Z UTC   %F %Y-%m-%d   %T %H:%M:%S
+//   %% a literal %
+//
+// Parsing takes the same directives. Names match in any case, numbers may
+// have fewer digits than they print with, and %z accepts Z, +hh:mm or
+// +hhmm. A parsed offset is applied, so the result is always UTC.
+use std::fmt;
+
+const MS_PER_DAY: i64 = 86_400_000;
+
+const MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December",
+];
+const WEEKDAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DateTime {
+    millis: i64,
+}
+
+// A datetime broken into calendar fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parts {
+    pub year: i64,
+    pub month: i64,       // 1-12
+    pub day: i64,         // 1-31
+    pub hour: i64,
+    pub minute: i64,
+    pub second: i64,
+    pub millisecond: i64,
+    pub weekday: i64,     // 1 is Monday, 7 Sunday
+    pub yearday: i64,     // 1-366
+}
+
+impl DateTime {
+    pub fn from_millis(millis: i64) -> DateTime {
+        DateTime { millis }
+    }
+
+    pub fn from_timestamp(seconds: i64) -> Option<DateTime> {
+        Some(DateTime { millis: seconds.checked_mul(1000)? })
+    }
+
+    pub fn millis(&self) -> i64 {
+        self.millis
+    }
+
+    // Whole seconds since the epoch, rounded down
+    pub fn timestamp(&self) -> i64 {
+        self.millis.div_euclid(1000)
+    }
+
+    // None unless every field is in range (no February 30th, no hour 24)
+    pub fn from_parts(year: i64, month: i64, day: i64, hour: i64, minute: i64, second: i64, millisecond: i64) -> Option<DateTime> {
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+        if !(0..24).contains(&hour) || !(0..60).contains(&minute) || !(0..60).contains(&second) || !(0..1000).contains(&millisecond) {
+            return None;
+        }
+        let days = days_from_civil(year, month, day)?;
+        let millis = days.checked_mul(MS_PER_DAY)?
+            .checked_add(((hour * 60 + minute) * 60 + second) * 1000 + millisecond)?;
+        Some(DateTime { millis })
+    }
+
+    pub fn parts(&self) -> Parts {
+        let days = self.millis.div_euclid(MS_PER_DAY);
+        let ms = self.millis.rem_euclid(MS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        Parts {
+            year,
+            month,
+            day,
+            hour: ms / 3_600_000,
+            minute: ms / 60_000 % 60,
+            second: ms / 1000 % 60,
+            millisecond: ms % 1000,
+            // 1970-01-01 was a Thursday
+            weekday: (days + 3).rem_euclid(7) + 1,
+            yearday: days - days_from_civil(year, 1, 1).unwrap_or(days) + 1,
+        }
+    }
+
+    pub fn checked_add_millis(self, millis: i64) -> Option<DateTime> {
+        Some(DateTime { millis: self.millis.checked_add(millis)? })
+    }
+
+    // Calendar months; the day is clamped, so Jan 31 + 1 month is Feb 28/29
+    pub fn checked_add_months(self, months: i64) -> Option<DateTime> {
+        let p = self.parts();
+        let total = p.year.checked_mul(12)?.checked_add(p.month - 1)?.checked_add(months)?;
+        let (year, month) = (total.div_euclid(12), total.rem_euclid(12) + 1);
+        let day = p.day.min(days_in_month(year, month));
+        DateTime::from_parts(year, month, day, p.hour, p.minute, p.second, p.millisecond)
+    }
+
+    pub fn format(&self, format: &str) -> Result<String, String> {
+        let p = self.parts();
+        let mut out = String::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => out.push_str(&format!("{:04}", p.year)),
+                Some('y') => out.push_str(&format!("{:02}", p.year.rem_euclid(100))),
+                Some('m') => out.push_str(&format!("{:02}", p.month)),
+                Some('d') => out.push_str(&format!("{:02}", p.day)),
+                Some('H') => out.push_str(&format!("{:02}", p.hour)),
+                Some('M') => out.push_str(&format!("{:02}", p.minute)),
+                Some('S') => out.push_str(&format!("{:02}", p.second)),
+                Some('f') => out.push_str(&format!("{:03}", p.millisecond)),
+                Some('j') => out.push_str(&format!("{:03}", p.yearday)),
+                Some('b') => out.push_str(&MONTHS[p.month as usize - 1][..3]),
+                Some('B') => out.push_str(MONTHS[p.month as usize - 1]),
+                Some('a') => out.push_str(&WEEKDAYS[p.weekday as usize - 1][..3]),
+                Some('A') => out.push_str(WEEKDAYS[p.weekday as usize - 1]),
+                Some('s') => out.push_str(&self.timestamp().to_string()),
+                Some('z') => out.push_str("+0000"),
+                Some('Z') => out.push_str("UTC"),
+                Some('F') => out.push_str(&format!("{:04}-{:02}-{:02}", p.year, p.month, p.day)),
+                Some('T') => out.push_str(&format!("{:02}:{:02}:{:02}", p.hour, p.minute, p.second)),
+                Some('%') => out.push('%'),
+                Some(other) => return Err(format!("unknown directive %{}", other)),
+                None => return Err("format ends with a lone %".to_string()),
+            }
+        }
+        Ok(out)
+    }
+
+    // ISO 8601: a date, optionally a time after `T` or a space, optionally
+    // an offset
+    pub fn parse_iso(text: &str) -> Result<DateTime, String> {
+        let text = text.trim();
+        let has_time = text.len() > 10;
+        let has_offset = has_time && (text.ends_with(['Z', 'z']) || text[11..].contains(['+', '-']));
+        let has_seconds = has_time && text[11..].split(['Z', 'z', '+', '-']).next().is_some_and(|t| t.matches(':').count() == 2);
+        let mut format = String::from("%Y-%m-%d");
+        if has_time {
+            format.push_str(&text[10..11]);
+            format.push_str(if has_seconds { "%H:%M:%S" } else { "%H:%M" });
+            if text[11..].contains('.') {
+                format.push_str(".%f");
+            }
+        }
+        if has_offset {
+            format.push_str("%z");
+        }
+        DateTime::parse(text, &format).map_err(|_| format!("{:?} is not an ISO 8601 date", text))
+    }
+
+    pub fn parse(text: &str, format: &str) -> Result<DateTime, String> {
+        let mut p = Parser { text, pos: 0 };
+        let (mut year, mut month, mut day) = (1970, 1, 1);
+        let (mut hour, mut minute, mut second, mut millisecond) = (0, 0, 0, 0);
+        let mut yearday = None;
+        let mut offset = 0;
+        let mut timestamp = None;
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                if c.is_whitespace() {
+                    p.skip_whitespace();
+                } else {
+                    p.literal(c)?;
+                }
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => year = p.signed(4)?,
+                Some('y') => year = match p.number(2)? {
+                    // POSIX: 69-99 are 1969-1999, 00-68 are 2000-2068
+                    y if y >= 69 => 1900 + y,
+                    y => 2000 + y,
+                },
+                Some('m') => month = p.number(2)?,
+                Some('d') => day = p.number(2)?,
+                Some('H') => hour = p.number(2)?,
+                Some('M') => minute = p.number(2)?,
+                Some('S') => second = p.number(2)?,
+                Some('f') => {
+                    let start = p.pos;
+                    let digits = p.number(9)?;
+                    let len = p.pos - start;
+                    millisecond = if len >= 3 { digits / 10i64.pow(len as u32 - 3) } else { digits * 10i64.pow(3 - len as u32) };
+                },
+                Some('j') => yearday = Some(p.number(3)?),
+                Some('b' | 'B') => month = p.name(&MONTHS)? + 1,
+                Some('a' | 'A') => {
+                    p.name(&WEEKDAYS)?;
+                },
+                Some('s') => timestamp = Some(p.signed(19)?),
+                Some('z') => offset = p.offset()?,
+                Some('Z') => p.word(&["UTC", "GMT", "Z"])?,
+                Some('F') => {
+                    year = p.signed(4)?;
+                    p.literal('-')?;
+                    month = p.number(2)?;
+                    p.literal('-')?;
+                    day = p.number(2)?;
+                },
+                Some('T') => {
+                    hour = p.number(2)?;
+                    p.literal(':')?;
+                    minute = p.number(2)?;
+                    p.literal(':')?;
+                    second = p.number(2)?;
+                },
+                Some('%') => p.literal('%')?,
+                Some(other) => return Err(format!("unknown directive %{}", other)),
+                None => return Err("format ends with a lone %".to_string()),
+            }
+        }
+        if p.pos < text.len() {
+            return Err(format!("unexpected {:?} at position {}", &text[p.pos..], p.pos));
+        }
+
+        if let Some(ts) = timestamp {
+            return DateTime::from_timestamp(ts).ok_or_else(|| format!("timestamp {} is out of range", ts));
+        }
+        let date = match yearday {
+            Some(n) => DateTime::from_parts(year, 1, 1, hour, minute, second, millisecond)
+                .filter(|_| (1..=days_in_year(year)).contains(&n))
+                .and_then(|d| d.checked_add_millis((n - 1) * MS_PER_DAY)),
+            None => DateTime::from_parts(year, month, day, hour, minute, second, millisecond),
+        };
+        date.and_then(|d| d.checked_add_millis(-offset * 60_000))
+            .ok_or_else(|| format!("{:?} is not a valid date", text))
+    }
+}
+
+// 2024-02-29T13:05:00Z, with milliseconds only when there are some
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let p = self.parts();
+        write!(f, "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", p.year, p.month, p.day, p.hour, p.minute, p.second)?;
+        if p.millisecond != 0 {
+            write!(f, ".{:03}", p.millisecond)?;
+        }
+        write!(f, "Z")
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.text[self.pos..]
+    }
+
+    fn error(&self, expected: &str) -> String {
+        match self.rest().chars().next() {
+            Some(c) => format!("expected {} at position {}, found {:?}", expected, self.pos, c),
+            None => format!("expected {} at position {}, found the end", expected, self.pos),
+        }
+    }
+
+    fn literal(&mut self, c: char) -> Result<(), String> {
+        if self.rest().starts_with(c) {
+            self.pos += c.len_utf8();
+            Ok(())
+        } else {
+            Err(self.error(&format!("{:?}", c)))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    // Up to `max` digits
+    fn number(&mut self, max: usize) -> Result<i64, String> {
+        let len = self.rest().bytes().take(max).take_while(u8::is_ascii_digit).count();
+        if len == 0 {
+            return Err(self.error("a number"));
+        }
+        let value = self.rest()[..len].parse().map_err(|_| self.error("a number"))?;
+        self.pos += len;
+        Ok(value)
+    }
+
+    // A number that may start with `-`, with at least `digits` digits
+    // allowed (years past 9999 need more)
+    fn signed(&mut self, digits: usize) -> Result<i64, String> {
+        let negative = self.rest().starts_with('-');
+        if negative {
+            self.pos += 1;
+        }
+        let n = self.number(digits.max(19))?;
+        Ok(if negative { -n } else { n })
+    }
+
+    // The index of a name, written in full or as its first three letters
+    fn name(&mut self, names: &[&str]) -> Result<i64, String> {
+        let rest = self.rest().to_ascii_lowercase();
+        for (i, name) in names.iter().enumerate() {
+            let name = name.to_ascii_lowercase();
+            for candidate in [&name[..], &name[..3]] {
+                if rest.starts_with(candidate) {
+                    self.pos += candidate.len();
+                    return Ok(i as i64);
+                }
+            }
+        }
+        Err(self.error("a name"))
+    }
+
+    fn word(&mut self, words: &[&str]) -> Result<(), String> {
+        let rest = self.rest().to_ascii_uppercase();
+        match words.iter().find(|w| rest.starts_with(*w)) {
+            Some(w) => {
+                self.pos += w.len();
+                Ok(())
+            },
+            None => Err(self.error(&words.join(" or "))),
+        }
+    }
+
+    // Minutes east of UTC
+    fn offset(&mut self) -> Result<i64, String> {
+        if self.rest().starts_with(['Z', 'z']) {
+            self.pos += 1;
+            return Ok(0);
+        }
+        let sign = match self.rest().chars().next() {
+            Some('+') => 1,
+            Some('-') => -1,
+            _ => return Err(self.error("an offset")),
+        };
+        self.pos += 1;
+        let start = self.pos;
+        let hours = self.number(2)?;
+        if self.pos - start != 2 {
+            return Err(self.error("two-digit offset hours"));
+        }
+        if self.rest().starts_with(':') {
+            self.pos += 1;
+        }
+        let minutes = self.number(2)?;
+        Ok(sign * (hours * 60 + minutes))
+    }
+}
+
+fn is_leap(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_year(year: i64) -> i64 {
+    if is_leap(year) { 366 } else { 365 }
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Days from 1970-01-01 to a date and back, after Howard Hinnant's
+// algorithms (counting in 400-year eras of 146097 days)
+fn days_from_civil(year: i64, month: i64, day: i64) -> Option<i64> {
+    let y = if month <= 2 { year.checked_sub(1)? } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era.checked_mul(146_097)?.checked_add(doe - 719_468)
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
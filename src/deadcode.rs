@@ -0,0 +1,168 @@
+// Dead code: statements after a `return` or `break` in the same block, and
+// the branch of an `if (true)` / `if (false)` that can never run.
+//
+//   let f = fn(x) {
+//       return x;
+//       print("never");        // warning: unreachable code
+//   };
+//   if (false) { slow() }      // warning: this branch never runs ...
+//
+// `eliminate` removes them and returns a warning for each, so the
+// evaluator has less to walk and the user hears about code that does
+// nothing. Only literal `true`/`false` conditions count; anything else
+// might depend on what runs.
+use crate::ast::{BlockStatement, Expression, Program, Statement};
+use crate::diagnostics::Diagnostic;
+use crate::span::Span;
+
+pub fn eliminate(program: &mut Program) -> Vec<Diagnostic> {
+    let mut warnings = vec![];
+    statements(&mut program.statements, &mut warnings);
+    warnings
+}
+
+fn statements(statements: &mut Vec<Statement>, warnings: &mut Vec<Diagnostic>) {
+    for s in statements.iter_mut() {
+        statement(s, warnings);
+    }
+    let end = statements.iter().position(|s| matches!(s, Statement::Return(_) | Statement::Break(_) | Statement::Continue(_)));
+    if let Some(end) = end
+        && let Some(span) = span_of(&statements[end + 1..])
+    {
+        warnings.push(Diagnostic::warning("unreachable-code", "unreachable code", span));
+        statements.truncate(end + 1);
+    }
+}
+
+fn span_of(statements: &[Statement]) -> Option<Span> {
+    Some(statements.first()?.span().to(statements.last()?.span()))
+}
+
+fn block(b: &mut BlockStatement, warnings: &mut Vec<Diagnostic>) {
+    statements(&mut b.statements, warnings);
+}
+
+fn statement(s: &mut Statement, warnings: &mut Vec<Diagnostic>) {
+    match s {
+        Statement::Let(s) => expression(&mut s.value, warnings),
+        Statement::LetTuple(s) => expression(&mut s.value, warnings),
+        Statement::Return(s) => expression(&mut s.return_value, warnings),
+        Statement::Yield(s) => expression(&mut s.value, warnings),
+        Statement::Defer(s) => expression(&mut s.value, warnings),
+        Statement::Break(s) => {
+            if let Some(value) = &mut s.value {
+                expression(value, warnings);
+            }
+        },
+        Statement::Continue(_) => {},
+        Statement::Expression(s) => expression(&mut s.expression, warnings),
+    }
+}
+
+fn expression(e: &mut Expression, warnings: &mut Vec<Diagnostic>) {
+    match e {
+        Expression::Identifier(_) | Expression::IntegerLiteral(_) | Expression::StringLiteral(_) |
+        Expression::CharLiteral(_) | Expression::DecimalLiteral(_) | Expression::Boolean(_) => {},
+        Expression::Prefix(e) => expression(&mut e.right, warnings),
+        Expression::Await(e) => expression(&mut e.value, warnings),
+        Expression::Infix(e) => {
+            expression(&mut e.left, warnings);
+            expression(&mut e.right, warnings);
+        },
+        Expression::Comparison(e) => {
+            for operand in &mut e.operands {
+                expression(operand, warnings);
+            }
+        },
+        Expression::If(e) => {
+            expression(&mut e.condition, warnings);
+            block(&mut e.consequence, warnings);
+            if let Some(alt) = &mut e.alternative {
+                block(alt, warnings);
+            }
+            match *e.condition {
+                Expression::Boolean(ref b) if b.value => {
+                    if let Some(span) = e.alternative.as_ref().and_then(|alt| span_of(&alt.statements)) {
+                        warnings.push(Diagnostic::warning("constant-condition", "this branch never runs: the condition is always true", span));
+                    }
+                    e.alternative = None;
+                },
+                Expression::Boolean(_) => {
+                    if let Some(span) = span_of(&e.consequence.statements) {
+                        warnings.push(Diagnostic::warning("constant-condition", "this branch never runs: the condition is always false", span));
+                    }
+                    e.consequence.statements.clear();
+                },
+                _ => {},
+            }
+        },
+        Expression::Function(e) => block(&mut e.body, warnings),
+        Expression::Call(e) if e.is_quote() => {},
+        Expression::Call(e) => {
+            expression(&mut e.function, warnings);
+            for a in &mut e.arguments {
+                expression(a, warnings);
+            }
+            for (_, value) in &mut e.keywords {
+                expression(value, warnings);
+            }
+        },
+        Expression::Array(e) => {
+            for el in &mut e.elements {
+                expression(el, warnings);
+            }
+        },
+        Expression::Tuple(e) => {
+            for el in &mut e.elements {
+                expression(el, warnings);
+            }
+        },
+        Expression::Hash(e) => {
+            for (key, value) in &mut e.pairs {
+                expression(key, warnings);
+                expression(value, warnings);
+            }
+        },
+        Expression::Index(e) => {
+            expression(&mut e.left, warnings);
+            expression(&mut e.index, warnings);
+        },
+        Expression::Slice(e) => {
+            expression(&mut e.left, warnings);
+            for bound in [&mut e.start, &mut e.end].into_iter().flatten() {
+                expression(bound, warnings);
+            }
+        },
+        Expression::Unsafe(e) => block(&mut e.body, warnings),
+        Expression::Zone(e) => {
+            for (_, value) in &mut e.limits {
+                expression(value, warnings);
+            }
+            block(&mut e.body, warnings);
+        },
+        Expression::Loop(e) => block(&mut e.body, warnings),
+        Expression::DoWhile(e) => {
+            block(&mut e.body, warnings);
+            expression(&mut e.condition, warnings);
+        },
+        Expression::For(e) => {
+            expression(&mut e.iterable, warnings);
+            block(&mut e.body, warnings);
+        },
+        Expression::Match(e) => {
+            expression(&mut e.subject, warnings);
+            for arm in &mut e.arms {
+                if let Some(guard) = &mut arm.guard {
+                    expression(guard, warnings);
+                }
+                block(&mut arm.body, warnings);
+            }
+        },
+        Expression::Switch(e) => {
+            expression(&mut e.subject, warnings);
+            for body in e.bodies_mut() {
+                block(body, warnings);
+            }
+        },
+    }
+}
@@ -0,0 +1,139 @@
+// Exact base-10 numbers, for money and anything else where 0.1 + 0.2
+// must be 0.3. A decimal is `units / 10^scale`: 1.50d is 150 at scale 2.
+//
+// The scale a value was written with is kept for display (1.50d prints as
+// 1.50) but two decimals are equal when their values are. Results of
+// `+` and `-` take the larger scale, `*` the sum of the scales (capped at
+// MAX_SCALE), and `/` the larger scale with the last digit rounded half
+// to even ("banker's rounding"), so 1.00d / 3 is 0.33 and 0.5d / 2 is 0.2.
+use std::cmp::Ordering;
+use std::fmt;
+use crate::numbers;
+
+pub const MAX_SCALE: u32 = 28;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    units: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    pub fn new(units: i128, scale: u32) -> Option<Decimal> {
+        (scale <= MAX_SCALE).then_some(Decimal { units, scale })
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn units(&self) -> i128 {
+        self.units
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.units == 0
+    }
+
+    // The whole part, toward zero: 2 for 2.75, -2 for -2.75
+    pub fn trunc(&self) -> i128 {
+        self.units / 10i128.pow(self.scale)
+    }
+
+    // The same value with more digits after the point
+    fn units_at(&self, scale: u32) -> Option<i128> {
+        self.units.checked_mul(10i128.checked_pow(scale - self.scale)?)
+    }
+
+    pub fn checked_add(self, other: Decimal) -> Option<Decimal> {
+        let scale = self.scale.max(other.scale);
+        Decimal::new(self.units_at(scale)?.checked_add(other.units_at(scale)?)?, scale)
+    }
+
+    pub fn checked_sub(self, other: Decimal) -> Option<Decimal> {
+        self.checked_add(other.checked_neg()?)
+    }
+
+    pub fn checked_mul(self, other: Decimal) -> Option<Decimal> {
+        let exact = Decimal { units: self.units.checked_mul(other.units)?, scale: self.scale + other.scale };
+        exact.round(exact.scale.min(MAX_SCALE))
+    }
+
+    // None on overflow or a zero divisor
+    pub fn checked_div(self, other: Decimal) -> Option<Decimal> {
+        if other.units == 0 {
+            return None;
+        }
+        // a/b = A·10^sb / (B·10^sa), wanted at scale s = max(sa, sb)
+        let scale = self.scale.max(other.scale);
+        let numerator = self.units_at(scale)?.checked_mul(10i128.checked_pow(other.scale)?)?;
+        Some(Decimal { units: div_half_even(numerator, other.units)?, scale })
+    }
+
+    pub fn checked_neg(self) -> Option<Decimal> {
+        Some(Decimal { units: self.units.checked_neg()?, scale: self.scale })
+    }
+
+    // To `scale` digits after the point, half to even when dropping digits
+    pub fn round(self, scale: u32) -> Option<Decimal> {
+        if scale >= self.scale {
+            return Decimal::new(self.units_at(scale)?, scale);
+        }
+        let divisor = 10i128.checked_pow(self.scale - scale)?;
+        Some(Decimal { units: div_half_even(self.units, divisor)?, scale })
+    }
+}
+
+// n / d rounded to the nearest integer, ties to the even one
+fn div_half_even(n: i128, d: i128) -> Option<i128> {
+    let quotient = n.checked_div(d)?;
+    let remainder = n % d;
+    let twice = remainder.unsigned_abs().checked_mul(2)?;
+    let away = match twice.cmp(&d.unsigned_abs()) {
+        Ordering::Greater => true,
+        Ordering::Equal => quotient % 2 != 0,
+        Ordering::Less => false,
+    };
+    if !away {
+        return Some(quotient);
+    }
+    if (n < 0) == (d < 0) { quotient.checked_add(1) } else { quotient.checked_sub(1) }
+}
+
+impl From<i64> for Decimal {
+    fn from(val: i64) -> Self {
+        Decimal { units: i128::from(val), scale: 0 }
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let scale = self.scale.max(other.scale);
+        match (self.units_at(scale), other.units_at(scale)) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            // Too big to rescale means bigger than the other side, which fit
+            (None, _) => if self.units < 0 { Ordering::Less } else { Ordering::Greater },
+            (_, None) => if other.units < 0 { Ordering::Greater } else { Ordering::Less },
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&numbers::format_decimal(self))
+    }
+}
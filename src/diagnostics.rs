@@ -0,0 +1,164 @@
+// Errors and warnings produced by the parser and the analysis passes.
+// Display only prints the message so REPL output stays terse; use
+// `render` when the source text is available, or `to_json` for tools.
+//
+// Warnings don't stop anything by default. Each one belongs to a rule
+// (see RULES), and a WarningPolicy can drop a rule's warnings or turn
+// them into errors; that's what `--allow`, `--deny` and `--deny-warnings`
+// set up on the command line.
+use std::collections::HashMap;
+use std::fmt;
+use crate::json::Json;
+use crate::span::{Span, LineIndex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    // The rule a warning comes from; errors have none unless a policy
+    // promoted them from a warning
+    pub rule: Option<&'static str>,
+    // More about it, a line each (for a runtime error, what the call it
+    // happened in had bound)
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic { severity: Severity::Error, message: message.into(), span, rule: None, notes: vec![] }
+    }
+
+    pub fn warning(rule: &'static str, message: impl Into<String>, span: Span) -> Self {
+        debug_assert!(RULES.contains(&rule), "unlisted rule {}", rule);
+        Diagnostic { severity: Severity::Warning, message: message.into(), span, rule: Some(rule), notes: vec![] }
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    // "3:7: error: Expected RParen, got Semicolon" (1-based like most
+    // tools), then "  note: ..." for each note
+    pub fn render(&self, source: &str) -> String {
+        let pos = LineIndex::new(source).position(self.span.start);
+        let mut out = format!("{}:{}: {}: {}", pos.line + 1, pos.column + 1, self.severity, self.message);
+        for note in &self.notes {
+            out.push_str(&format!("\n  note: {}", note));
+        }
+        out
+    }
+
+    // What `--output json` prints. `code` names the pass that found it
+    // ("parse", "type", "resolve"); offsets are in chars, line and column
+    // 1-based as in `render`. "rule" and "notes" are only there for ones
+    // that have them.
+    pub fn to_json(&self, code: &str, source: &str) -> Json {
+        let pos = LineIndex::new(source).position(self.span.start);
+        let mut fields = vec![
+            ("code", code.into()),
+            ("severity", self.severity.to_string().into()),
+            ("message", self.message.clone().into()),
+            ("span", Json::object(vec![
+                ("start", self.span.start.into()),
+                ("end", self.span.end.into()),
+                ("line", (pos.line + 1).into()),
+                ("column", (pos.column + 1).into()),
+            ])),
+        ];
+        if let Some(rule) = self.rule {
+            fields.push(("rule", rule.into()));
+        }
+        if !self.notes.is_empty() {
+            fields.push(("notes", Json::Array(self.notes.iter().map(|n| n.as_str().into()).collect())));
+        }
+        Json::object(fields)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+// Every rule a warning can come from
+pub const RULES: &[&str] = &[
+    "undefined-variable",   // a name that isn't bound anywhere in reach (resolver)
+    "unreachable-code",     // statements after a return, break or continue
+    "constant-condition",   // an `if (true)` or `if (false)` branch that never runs
+    "experimental-feature", // generators, async or macros without `#pragma feature(...)`
+    "deprecated",           // a call of a builtin that is on its way out
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+// What becomes of warnings. A level set for one rule wins over
+// `deny_warnings`, so `--deny-warnings --allow unreachable-code` still lets
+// unreachable code through.
+#[derive(Debug, Clone, Default)]
+pub struct WarningPolicy {
+    pub deny_warnings: bool,
+    rules: HashMap<&'static str, Level>,
+}
+
+impl WarningPolicy {
+    pub fn set(&mut self, rule: &str, level: Level) -> Result<(), String> {
+        match RULES.iter().find(|r| **r == rule) {
+            Some(rule) => {
+                self.rules.insert(rule, level);
+                Ok(())
+            },
+            None => Err(format!("unknown rule '{}' (the rules are {})", rule, RULES.join(", "))),
+        }
+    }
+
+    pub fn level(&self, rule: &str) -> Level {
+        match self.rules.get(rule) {
+            Some(level) => *level,
+            None if self.deny_warnings => Level::Deny,
+            None => Level::Warn,
+        }
+    }
+
+    // None for an allowed warning, an error for a denied one; errors pass
+    // through untouched
+    pub fn apply(&self, mut diagnostic: Diagnostic) -> Option<Diagnostic> {
+        let Some(rule) = diagnostic.rule.filter(|_| !diagnostic.is_error()) else {
+            return Some(diagnostic);
+        };
+        match self.level(rule) {
+            Level::Allow => None,
+            Level::Warn => Some(diagnostic),
+            Level::Deny => {
+                diagnostic.severity = Severity::Error;
+                Some(diagnostic)
+            },
+        }
+    }
+}
@@ -0,0 +1,52 @@
+// Reference docs built from `///` comments (`nova doc`). Every documented
+// top-level let is listed with its signature, as Markdown or HTML.
+use crate::ast::{Expression, Program, Statement};
+
+pub struct Entry {
+    pub name: String,
+    pub signature: String, // `add(a: int, b: int) -> int`, or `limit: int` for values
+    pub doc: String,
+}
+
+pub fn entries(program: &Program) -> Vec<Entry> {
+    program.statements.iter().filter_map(|stmt| {
+        let Statement::Let(s) = stmt else { return None };
+        let doc = s.doc.clone()?;
+        let name = s.name.value.clone();
+        let signature = match (&s.value, &s.annotation) {
+            (Expression::Function(fl), _) => {
+                let signature = fl.signature();
+                format!("{}{}", name, signature.strip_prefix("fn").unwrap_or(&signature))
+            },
+            (_, Some(annotation)) => format!("{}: {}", name, annotation),
+            (_, None) => name.clone(),
+        };
+        Some(Entry { name, signature, doc })
+    }).collect()
+}
+
+pub fn markdown(title: &str, program: &Program) -> String {
+    let mut out = format!("# {}\n", title);
+    for entry in entries(program) {
+        out.push_str(&format!("\n## `{}`\n\n{}\n", entry.signature, entry.doc));
+    }
+    out
+}
+
+pub fn html(title: &str, program: &Program) -> String {
+    let title = escape(title);
+    let mut out = format!("<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>\n<h1>{}</h1>\n", title, title);
+    for entry in entries(program) {
+        out.push_str(&format!("<h2 id=\"{}\"><code>{}</code></h2>\n", escape(&entry.name), escape(&entry.signature)));
+        // Blank lines separate paragraphs, like Markdown
+        for paragraph in entry.doc.split("\n\n").filter(|p| !p.trim().is_empty()) {
+            out.push_str(&format!("<p>{}</p>\n", escape(paragraph.trim())));
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
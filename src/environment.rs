@@ -1,25 +1,279 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+use crate::ast::Expression;
 use crate::object::Object;
+use crate::runtime::{Runtime, Capabilities};
 
-#[derive(Debug, PartialEq, Clone)]
+type Store = Rc<RefCell<HashMap<String, Object>>>;
+
+#[derive(Debug)]
 pub struct Environment {
-    store: HashMap<String, Object>,
+    store: Store,
+    // A function call's locals, by the slot numbers the resolver gave
+    // them (empty at top level); None until the `let` runs
+    locals: Arc<[String]>,
+    slots: Vec<Option<Object>>,
+    // A function call's view of the top-level bindings (None at top level)
+    globals: Option<Store>,
+    runtime: Rc<RefCell<Runtime>>,
+    // What the store adds to the runtime's memory count
+    bytes: usize,
+    // `defer`red expressions, for when this call (or the program) ends
+    deferred: Vec<Expression>,
 }
 
 impl Environment {
     pub fn new() -> Self {
+        Environment::with_capabilities(Capabilities::none())
+    }
+
+    pub fn with_capabilities(capabilities: Capabilities) -> Self {
+        Environment::with_runtime(Rc::new(RefCell::new(Runtime::new(capabilities))))
+    }
+
+    // Empty scope on an existing runtime (function calls)
+    pub fn with_runtime(runtime: Rc<RefCell<Runtime>>) -> Self {
         Environment {
-            store: HashMap::new(),
+            store: Store::default(),
+            locals: Arc::default(),
+            slots: vec![],
+            globals: None,
+            runtime,
+            bytes: 0,
+            deferred: vec![],
         }
     }
 
+    // Scope for a call to a function defined under `globals`
+    pub fn with_globals(runtime: Rc<RefCell<Runtime>>, globals: &Globals) -> Self {
+        let mut env = Environment::with_runtime(runtime);
+        env.globals = globals.0.upgrade();
+        env
+    }
+
+    // Scope for a call, with a slot for each of the function's locals
+    pub fn with_frame(runtime: Rc<RefCell<Runtime>>, globals: &Globals, locals: Arc<[String]>) -> Self {
+        let mut env = Environment::with_globals(runtime, globals);
+        env.slots = vec![None; locals.len()];
+        env.locals = locals;
+        env
+    }
+
+    // What a function defined here gets to see when it's called
+    pub fn globals(&self) -> Globals {
+        Globals(Rc::downgrade(self.globals.as_ref().unwrap_or(&self.store)))
+    }
+
+    // The value a function made here captures for a name it uses: this
+    // frame's binding, if it has one. Globals are left to be looked up
+    // when the function runs, and top-level code captures nothing.
+    pub fn capture(&self, slot: Option<usize>, name: &str) -> Option<Object> {
+        self.globals.as_ref()?;
+        match slot {
+            Some(slot) => self.slots.get(slot)?.clone(),
+            None => self.store.borrow().get(name).cloned(),
+        }
+    }
+
+    pub fn runtime(&self) -> &Rc<RefCell<Runtime>> {
+        &self.runtime
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        self.runtime.borrow().capabilities
+    }
+
     pub fn get(&self, name: &str) -> Option<Object> {
+        if let Some(slot) = self.locals.iter().position(|l| l == name)
+            && let Some(val) = &self.slots[slot]
+        {
+            return Some(val.clone());
+        }
         // We clone the object because our current Object enum owns its data
-        self.store.get(name).cloned()
+        let local = self.store.borrow().get(name).cloned();
+        local.or_else(|| self.globals.as_ref()?.borrow().get(name).cloned())
+    }
+
+    // A local by slot, without looking the name up. An empty slot falls
+    // back to the name, which finds the global of that name if any.
+    pub fn get_slot(&self, slot: usize, name: &str) -> Option<Object> {
+        match self.slots.get(slot) {
+            Some(Some(val)) => Some(val.clone()),
+            _ => self.get(name),
+        }
+    }
+
+    // Bindings sorted by name: this scope's, plus the top-level ones it
+    // doesn't shadow. Scopes go no deeper than that (a call starts with
+    // just the globals and what the function captured, a zone copies), so
+    // this is everything visible.
+    pub fn iter(&self) -> impl Iterator<Item = (String, Object)> {
+        let mut bindings = self.globals.as_ref().map(|g| g.borrow().clone()).unwrap_or_default();
+        bindings.extend(self.store.borrow().iter().map(|(k, v)| (k.clone(), v.clone())));
+        let slots = self.locals.iter().zip(&self.slots);
+        bindings.extend(slots.filter_map(|(name, val)| Some((name.clone(), val.clone()?))));
+        let mut bindings: Vec<(String, Object)> = bindings.into_iter().collect();
+        bindings.sort_by(|(a, _), (b, _)| a.cmp(b));
+        bindings.into_iter()
+    }
+
+    // This call's own bindings, without the globals: its slots in order,
+    // then anything bound by name
+    pub fn frame_bindings(&self) -> Vec<(String, Object)> {
+        let slots = self.locals.iter().zip(&self.slots);
+        let mut bindings: Vec<(String, Object)> = slots.filter_map(|(name, val)| Some((name.clone(), val.clone()?))).collect();
+        let mut named: Vec<(String, Object)> = self.store.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        named.sort_by(|(a, _), (b, _)| a.cmp(b));
+        bindings.extend(named);
+        bindings
+    }
+
+    // Whether this is the environment of a call with these slots
+    pub(crate) fn has_locals(&self, locals: &Arc<[String]>) -> bool {
+        Arc::ptr_eq(&self.locals, locals)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.iter().map(|(name, _)| name).collect()
+    }
+
+    // Approximate bytes bound, as counted against the memory limit (see
+    // Runtime::memory)
+    pub fn memory_used(&self) -> usize {
+        self.runtime.borrow().memory()
+    }
+
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.runtime.borrow_mut().memory_limit = limit;
     }
 
     pub fn set(&mut self, name: String, val: Object) -> Object {
-        self.store.insert(name, val.clone());
+        if let Some(slot) = self.locals.iter().position(|l| *l == name) {
+            return self.set_slot(slot, &name, val);
+        }
+        let added = binding_size(&name, &val);
+        let removed = self.store.borrow_mut().insert(name.clone(), val.clone()).map_or(0, |old| binding_size(&name, &old));
+        self.account(added, removed);
+        val
+    }
+
+    // Binds a local by slot; a slot this scope doesn't have goes by name.
+    // Takes the name borrowed, so rebinding a local in a loop allocates
+    // nothing for ints, bools and null.
+    pub fn set_slot(&mut self, slot: usize, name: &str, val: Object) -> Object {
+        let Some(current) = self.slots.get_mut(slot) else {
+            return self.set(name.to_string(), val);
+        };
+        let added = binding_size(name, &val);
+        let removed = current.replace(val.clone()).map_or(0, |old| binding_size(name, &old));
+        self.account(added, removed);
         val
     }
-}
\ No newline at end of file
+
+    // What binding the name here (by slot, or by name as `set` does) would
+    // replace in this scope's own bindings, for undoing it with `restore`
+    pub(crate) fn own(&self, slot: Option<usize>, name: &str) -> Option<Object> {
+        let slot = slot.filter(|&s| s < self.slots.len()).or_else(|| self.locals.iter().position(|l| l == name));
+        match slot {
+            Some(slot) => self.slots[slot].clone(),
+            None => self.store.borrow().get(name).cloned(),
+        }
+    }
+
+    // Puts back what `own` returned, unbinding the name if that was nothing
+    pub(crate) fn restore(&mut self, slot: Option<usize>, name: &str, old: Option<Object>) {
+        let slot = slot.filter(|&s| s < self.slots.len()).or_else(|| self.locals.iter().position(|l| l == name));
+        if let Some(old) = old {
+            match slot {
+                Some(slot) => self.set_slot(slot, name, old),
+                None => self.set(name.to_string(), old),
+            };
+            return;
+        }
+        let removed = match slot {
+            Some(slot) => self.slots[slot].take(),
+            None => self.store.borrow_mut().remove(name),
+        };
+        self.account(0, removed.map_or(0, |old| binding_size(name, &old)));
+    }
+
+    pub fn defer(&mut self, expression: Expression) {
+        self.deferred.push(expression);
+    }
+
+    pub fn deferred_len(&self) -> usize {
+        self.deferred.len()
+    }
+
+    // Last deferred first, down to the first `floor` (which belong to
+    // whoever is running the code that deferred the others)
+    pub fn pop_deferred(&mut self, floor: usize) -> Option<Expression> {
+        if self.deferred.len() > floor { self.deferred.pop() } else { None }
+    }
+
+    // `removed` can be more than was counted for the binding: a buffer
+    // grows in place after it's bound (push charges the growth itself)
+    fn account(&mut self, added: usize, removed: usize) {
+        self.bytes = (self.bytes + added).saturating_sub(removed);
+        self.runtime.borrow_mut().charge_memory(added, removed);
+    }
+}
+
+fn binding_size(name: &str, val: &Object) -> usize {
+    name.len() + val.approx_size()
+}
+
+// A copy holds its own bindings, so it counts them again. The globals
+// stay shared.
+impl Clone for Environment {
+    fn clone(&self) -> Self {
+        self.runtime.borrow_mut().charge_memory(self.bytes, 0);
+        Environment {
+            store: Rc::new(RefCell::new(self.store.borrow().clone())),
+            locals: self.locals.clone(),
+            slots: self.slots.clone(),
+            globals: self.globals.clone(),
+            runtime: self.runtime.clone(),
+            bytes: self.bytes,
+            // The copy's own defers run when it's done with
+            deferred: vec![],
+        }
+    }
+}
+
+impl Drop for Environment {
+    fn drop(&mut self) {
+        // Not while the runtime is borrowed (an Object dropped by a builtin)
+        if let Ok(mut runtime) = self.runtime.try_borrow_mut() {
+            runtime.charge_memory(0, self.bytes);
+        }
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// A function's link to the top-level bindings where it was defined, so its
+// body can call other top-level functions (or itself). Weak, since those
+// bindings hold the function; once they're gone the body sees only its
+// parameters and the builtins.
+#[derive(Clone, Default)]
+pub struct Globals(Weak<RefCell<HashMap<String, Object>>>);
+
+impl fmt::Debug for Globals {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Globals")
+    }
+}
+
+impl PartialEq for Globals {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ptr_eq(&other.0)
+    }
+}
@@ -1,105 +1,347 @@
-use crate::ast::{Statement, Expression};
-use crate::object::{Object, Function};
+use indexmap::IndexMap;
+use std::rc::Rc;
+use crate::ast::{CallExpression, Statement, Expression};
+use crate::object::{Object, Function, HashKey, Partial, Quote};
 use crate::environment::Environment; // <--- NEW IMPORT
-use crate::ast::BlockStatement;
+use crate::stdlib;
+use crate::macros;
+use crate::compare;
+use crate::datetime::DateTime;
+use crate::decimal::Decimal;
+use crate::future::{self, Future};
+use crate::generator::Generator;
+use crate::hooks::Hooks;
+use crate::runtime::{self, CallFrame, FrameDump, ZoneLimits};
 
 // Updated Signature: Now takes &mut Environment
 pub fn eval_program(program: &crate::ast::Program, env: &mut Environment) -> Object {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("eval", statements = program.statements.len()).entered();
     let mut result = Object::Null;
+    // Stays on for whatever runs in this environment afterwards
+    if program.pragmas.iter().any(|p| p == "strict") {
+        env.runtime().borrow_mut().strict = true;
+    }
+    // eval() runs a program inside a call that may have deferred things
+    // of its own
+    let floor = env.deferred_len();
     
     for statement in &program.statements {
         result = eval_statement(statement, env);
         
         // Unwrapping ReturnValue to stop execution
-        if let Object::ReturnValue(val) = result {
-            return *val;
+        match result {
+            Object::ReturnValue(val) => {
+                result = *val;
+                break;
+            },
+            Object::Error(_) => {
+                env.runtime().borrow_mut().futures.clear();
+                return run_deferred(result, env, floor);
+            },
+            _ => {},
+        }
+    }
+
+    // Async calls nobody awaited still get to finish
+    let result = future::run_pending(env).unwrap_or(result);
+    run_deferred(result, env, floor)
+}
+
+pub(crate) fn eval_statement(stmt: &Statement, env: &mut Environment) -> Object {
+    let result = eval_statement_unspanned(stmt, env);
+    // The innermost statement an error comes out of is where it happened;
+    // the ones it passes through on the way up keep that
+    if let Object::Error(msg) = &result {
+        let mut runtime = env.runtime().borrow_mut();
+        if runtime.error_span.is_none() {
+            runtime.error_span = Some(stmt.span());
+            // Only when the statement is in the call's own body, not in a
+            // generator or callback running under it
+            runtime.error_frame = runtime.frames.last()
+                .filter(|frame| env.has_locals(&frame.function.locals))
+                .map(|frame| FrameDump::new(frame, env.frame_bindings()));
+            runtime.hooks.each(|h| h.on_error(msg, stmt.span()));
         }
     }
-    
     result
 }
 
-fn eval_statement(stmt: &Statement, env: &mut Environment) -> Object {
+fn eval_statement_unspanned(stmt: &Statement, env: &mut Environment) -> Object {
+    if let Err(msg) = env.runtime().borrow_mut().step() {
+        return Object::Error(msg);
+    }
+    notify(env, |h| h.on_statement(stmt));
     match stmt {
         Statement::Expression(val) => eval_expression(&val.expression, env),
         Statement::Let(val) => {
             let value = eval_expression(&val.value, env);
-            env.set(val.name.value.clone(), value)
+            if value.is_error() {
+                return value;
+            }
+            let value = bind(env, &val.name, value);
+            if let Err(msg) = env.runtime().borrow().check_memory() {
+                return Object::Error(msg);
+            }
+            value
+        },
+        Statement::LetTuple(val) => {
+            let value = eval_expression(&val.value, env);
+            if value.is_error() {
+                return value;
+            }
+            let elements = match &value {
+                Object::Tuple(elements) if elements.len() == val.names.len() => elements.clone(),
+                Object::Tuple(elements) => {
+                    return Object::error(format!("let: can't unpack a tuple of {} into {} names", elements.len(), val.names.len()));
+                },
+                other => return Object::error(format!("let: can't unpack {}, expected a tuple", other.type_name())),
+            };
+            for (name, element) in val.names.iter().zip(elements) {
+                bind(env, name, element);
+            }
+            if let Err(msg) = env.runtime().borrow().check_memory() {
+                return Object::Error(msg);
+            }
+            value
+        },
+        // Generators handle the yields they can pause at themselves
+        Statement::Defer(d) => {
+            env.defer(d.value.clone());
+            Object::Null
         },
+        Statement::Yield(_) => Object::error("yield is only allowed in a function body or its if/else branches"),
         Statement::Return(val) => {
             let value = eval_expression(&val.return_value, env);
+            if value.is_error() {
+                return value;
+            }
             Object::ReturnValue(Box::new(value))
         },
+        // The parser only allows it inside a loop, which catches it
+        Statement::Break(val) => {
+            let value = match &val.value {
+                Some(exp) => eval_expression(exp, env),
+                None => Object::Null,
+            };
+            if value.is_error() {
+                return value;
+            }
+            Object::Break(Box::new(value), val.label.clone())
+        },
+        Statement::Continue(val) => Object::Continue(val.label.clone()),
     }
 }
 
-fn eval_expression(exp: &Expression, env: &mut Environment) -> Object {
+pub(crate) fn eval_expression(exp: &Expression, env: &mut Environment) -> Object {
+    runtime::with_stack(|| eval_expression_unguarded(exp, env))
+}
+
+fn eval_expression_unguarded(exp: &Expression, env: &mut Environment) -> Object {
     match exp {
         Expression::IntegerLiteral(i) => Object::Integer(i.value),
+        Expression::StringLiteral(s) => Object::String(s.value.clone()),
+        Expression::CharLiteral(c) => Object::Char(c.value),
+        Expression::DecimalLiteral(d) => Object::Decimal(d.value),
         Expression::Boolean(b) => Object::Boolean(b.value), // Ensure AST has Boolean if used, else skip
         Expression::Prefix(p) => {
             let right = eval_expression(&p.right, env);
-            eval_prefix_expression(&p.operator, right)
+            if right.is_error() {
+                return right;
+            }
+            if p.operator == "-" && let Some(f) = method(&right, "__neg") {
+                return call_function(f, vec![right], env);
+            }
+            if p.operator == "!" {
+                return Object::Boolean(!is_truthy(&right, env));
+            }
+            let operand = right.type_name();
+            match eval_prefix_expression(&p.operator, right) {
+                Object::Null if strict(env) => Object::error(format!("can't apply {} to {}", p.operator, operand)),
+                result => result,
+            }
         },
+        Expression::Infix(i) if i.operator == "&&" || i.operator == "||" => eval_logical_expression(i, env),
         Expression::Infix(i) => {
             let left = eval_expression(&i.left, env);
+            if left.is_error() {
+                return left;
+            }
             let right = eval_expression(&i.right, env);
-            eval_infix_expression(&i.operator, left, right)
+            if right.is_error() {
+                return right;
+            }
+            if let Some(result) = eval_overloaded_infix(&i.operator, &left, &right, env) {
+                return result;
+            }
+            let operands = (left.type_name(), right.type_name());
+            match eval_infix_expression(&i.operator, left, right) {
+                Object::Null if strict(env) => Object::error(format!("can't apply {} to {} and {}", i.operator, operands.0, operands.1)),
+                result => result,
+            }
+        },
+        Expression::Comparison(c) => eval_comparison_chain(c, env),
+        // Where an async function can't pause (and outside one), await
+        // blocks, running the other futures meanwhile
+        Expression::Await(a) => match eval_expression(&a.value, env) {
+            value if value.is_error() => value,
+            value => future::block_on(value, env),
         },
         Expression::If(ie) => eval_if_expression(ie, env),
         Expression::Identifier(ident) => {
-            match env.get(&ident.value) {
+            let value = match ident.slot {
+                Some(slot) => env.get_slot(slot, &ident.value),
+                None => env.get(&ident.value),
+            };
+            match value {
                 Some(val) => val,
-                None => Object::Null, 
+                None => match stdlib::lookup(&ident.value) {
+                    Some(builtin) => Object::Builtin(builtin),
+                    None if strict(env) => Object::error(format!("undefined variable '{}'", ident.value)),
+                    None => Object::Null,
+                },
             }
         },
         // NEW: Function Definition
         Expression::Function(fl) => {
-            Object::Function(Function {
+            Object::Function(Rc::new(Function {
                 parameters: fl.parameters.clone(),
                 body: fl.body.clone(),
-            })
+                locals: fl.locals.clone(),
+                globals: env.globals(),
+                captured: fl.captures.iter()
+                    .filter_map(|c| Some((c.value.clone(), env.capture(c.slot, &c.value)?)))
+                    .collect(),
+                is_async: fl.is_async,
+                compiled: None,
+            }))
         },
         // NEW: Function Call
         Expression::Call(c) => {
             let function = eval_expression(&c.function, env);
+            if function.is_error() {
+                return function;
+            }
+            if let Object::Builtin(b) = &function && b.name == "quote" {
+                return eval_quote(c, env);
+            }
             
             // 1. Evaluate arguments
-            let args = eval_expressions(&c.arguments, env);
+            let mut args = match eval_expressions(&c.arguments, env) {
+                Ok(args) => args,
+                Err(e) => return e,
+            };
+            if !c.keywords.is_empty() {
+                args = match bind_keywords(&function, args, &c.keywords, env) {
+                    Ok(args) => args,
+                    Err(e) => return e,
+                };
+            }
 
             // 2. Apply function
-            if let Object::Function(fn_obj) = function {
-                return apply_function(fn_obj, args, env);
-            } else {
-                return Object::Null; // Error: calling non-function
+            let name = match &*c.function {
+                Expression::Identifier(i) => Some(i.value.as_str()),
+                _ => None,
+            };
+            call_function_as(function, args, name, env)
+        },
+        Expression::Array(a) => match eval_expressions(&a.elements, env) {
+            Ok(elements) => Object::Array(elements),
+            Err(e) => e,
+        },
+        Expression::Tuple(t) => match eval_expressions(&t.elements, env) {
+            Ok(elements) => Object::Tuple(elements),
+            Err(e) => e,
+        },
+        Expression::Hash(h) => eval_hash_literal(h, env),
+        // Like a plain block (no new scope), but gated builtins are allowed
+        // until it finishes, including in functions called from it
+        Expression::Unsafe(u) => {
+            env.runtime().borrow_mut().unsafe_depth += 1;
+            let result = eval_block_statement(&u.body, env);
+            env.runtime().borrow_mut().unsafe_depth -= 1;
+            result
+        },
+        Expression::Zone(z) => eval_zone_expression(z, env),
+        Expression::Loop(l) => eval_loop_expression(l, env),
+        Expression::DoWhile(d) => eval_do_while_expression(d, env),
+        Expression::For(fe) => eval_for_expression(fe, env),
+        Expression::Match(me) => eval_match_expression(me, env),
+        Expression::Switch(se) => eval_switch_expression(se, env),
+        Expression::Index(ie) => {
+            let left = eval_expression(&ie.left, env);
+            if left.is_error() {
+                return left;
+            }
+            let index = eval_expression(&ie.index, env);
+            if index.is_error() {
+                return index;
+            }
+            // Like Lua, __index is only asked about keys the hash doesn't have
+            if let Object::Hash(pairs) = &left
+                && let Ok(key) = index.hash_key()
+                && !pairs.contains_key(&key)
+                && let Some(f) = method(&left, "__index")
+            {
+                return call_function(f, vec![left, index], env);
             }
+            eval_index_expression(left, index)
+        },
+        Expression::Slice(se) => {
+            let left = eval_expression(&se.left, env);
+            if left.is_error() {
+                return left;
+            }
+            let mut bounds = [None, None];
+            for (slot, bound) in bounds.iter_mut().zip([&se.start, &se.end]) {
+                if let Some(exp) = bound {
+                    match eval_expression(exp, env) {
+                        Object::Integer(i) => *slot = Some(i),
+                        Object::Error(msg) => return Object::Error(msg),
+                        other => return Object::error(format!("slice bounds must be ints, found {}", other.type_name())),
+                    }
+                }
+            }
+            eval_slice_expression(left, bounds[0], bounds[1])
         },
-        _ => Object::Null,
     }
 }
 
 // --- LOGIC HELPERS ---
 
+// `!` is done where it's evaluated, since it needs the runtime's truthiness
 fn eval_prefix_expression(operator: &str, right: Object) -> Object {
     match operator {
-        "!" => eval_bang_operator_expression(right),
         "-" => eval_minus_operator_expression(right),
         _ => Object::Null, // Unknown operator
     }
 }
 
-fn eval_bang_operator_expression(right: Object) -> Object {
-    match right {
-        Object::Boolean(true) => Object::Boolean(false),
-        Object::Boolean(false) => Object::Boolean(true),
-        Object::Null => Object::Boolean(true), // !null == true
-        _ => Object::Boolean(false), // !5 == false
+// `a && b`, `a || b`: b only runs when a doesn't decide the answer, and
+// the answer is whichever operand decided it (`name || "anonymous"`)
+fn eval_logical_expression(i: &crate::ast::InfixExpression, env: &mut Environment) -> Object {
+    let left = eval_expression(&i.left, env);
+    if left.is_error() || is_truthy(&left, env) == (i.operator == "||") {
+        return left;
     }
+    eval_expression(&i.right, env)
 }
 
 fn eval_minus_operator_expression(right: Object) -> Object {
     match right {
-        Object::Integer(val) => Object::Integer(-val),
+        Object::Integer(val) => match val.checked_neg() {
+            Some(v) => Object::Integer(v),
+            #[cfg(feature = "bigint")]
+            None => Object::from(-num_bigint::BigInt::from(val)),
+            #[cfg(not(feature = "bigint"))]
+            None => Object::error(format!("integer overflow: -({})", val)),
+        },
+        #[cfg(feature = "bigint")]
+        Object::BigInt(val) => Object::from(-val),
+        Object::Decimal(val) => match val.checked_neg() {
+            Some(v) => Object::Decimal(v),
+            None => Object::error(format!("decimal overflow: -({})", val)),
+        },
         _ => Object::Null,
     }
 }
@@ -107,16 +349,52 @@ fn eval_minus_operator_expression(right: Object) -> Object {
 fn eval_infix_expression(operator: &str, left: Object, right: Object) -> Object {
     match (left, right) {
         (Object::Integer(l), Object::Integer(r)) => eval_integer_infix_expression(operator, l, r),
-        _ => Object::Null, // Type mismatch or unknown types
+        (Object::Decimal(l), Object::Decimal(r)) => eval_decimal_infix_expression(operator, l, r),
+        (Object::Decimal(l), Object::Integer(r)) => eval_decimal_infix_expression(operator, l, r.into()),
+        (Object::Integer(l), Object::Decimal(r)) => eval_decimal_infix_expression(operator, l.into(), r),
+        #[cfg(feature = "bigint")]
+        (Object::BigInt(l), Object::BigInt(r)) => eval_bigint_infix_expression(operator, l, r),
+        #[cfg(feature = "bigint")]
+        (Object::BigInt(l), Object::Integer(r)) => eval_bigint_infix_expression(operator, l, r.into()),
+        #[cfg(feature = "bigint")]
+        (Object::Integer(l), Object::BigInt(r)) => eval_bigint_infix_expression(operator, l.into(), r),
+        (Object::String(l), Object::String(r)) if operator == "+" => eval_string_infix_expression(operator, l, r),
+        // A char joins a string like a one-character string would
+        (Object::String(l), Object::Char(r)) if operator == "+" => Object::String(l + &r.to_string()),
+        (Object::Char(l), Object::String(r)) if operator == "+" => Object::String(l.to_string() + &r),
+        (Object::Bytes(l), Object::Bytes(r)) if operator == "+" => Object::Bytes([l, r].concat()),
+        // Seconds either way; date_add() does the calendar units
+        (Object::DateTime(l), Object::Integer(r)) if operator == "+" || operator == "-" => eval_datetime_shift(operator, l, r),
+        (Object::Integer(l), Object::DateTime(r)) if operator == "+" => eval_datetime_shift(operator, r, l),
+        (Object::DateTime(l), Object::DateTime(r)) if operator == "-" => Object::Integer(l.timestamp() - r.timestamp()),
+        (l, r) => match operator {
+            "==" => Object::Boolean(compare::equals(&l, &r)),
+            "!=" => Object::Boolean(!compare::equals(&l, &r)),
+            "<" | ">" => match compare::compare(&l, &r) {
+                Ok(ord) => Object::Boolean(if operator == "<" { ord.is_lt() } else { ord.is_gt() }),
+                Err(msg) => Object::Error(msg),
+            },
+            _ => Object::Null, // Type mismatch or unknown types
+        },
     }
 }
 
+// Overflow is an error rather than wrapping (or panicking, in debug builds),
+// unless the `bigint` feature is on; then the result just gets bigger
 fn eval_integer_infix_expression(operator: &str, left: i64, right: i64) -> Object {
+    let checked = |result: Option<i64>| match result {
+        Some(v) => Object::Integer(v),
+        #[cfg(feature = "bigint")]
+        None => eval_bigint_infix_expression(operator, left.into(), right.into()),
+        #[cfg(not(feature = "bigint"))]
+        None => Object::error(format!("integer overflow: {} {} {}", left, operator, right)),
+    };
     match operator {
-        "+" => Object::Integer(left + right),
-        "-" => Object::Integer(left - right),
-        "*" => Object::Integer(left * right),
-        "/" => Object::Integer(left / right),
+        "+" => checked(left.checked_add(right)),
+        "-" => checked(left.checked_sub(right)),
+        "*" => checked(left.checked_mul(right)),
+        "/" if right == 0 => Object::error(format!("division by zero: {} / 0", left)),
+        "/" => checked(left.checked_div(right)),
         // Comparisons returning Booleans
         "<" => Object::Boolean(left < right),
         ">" => Object::Boolean(left > right),
@@ -126,18 +404,414 @@ fn eval_integer_infix_expression(operator: &str, left: i64, right: i64) -> Objec
     }
 }
 
+// Results that fit in an i64 go back to being plain ints
+#[cfg(feature = "bigint")]
+fn eval_bigint_infix_expression(operator: &str, left: num_bigint::BigInt, right: num_bigint::BigInt) -> Object {
+    match operator {
+        "+" => Object::from(left + right),
+        "-" => Object::from(left - right),
+        "*" => Object::from(left * right),
+        "/" if right == num_bigint::BigInt::ZERO => Object::error(format!("division by zero: {} / 0", left)),
+        "/" => Object::from(left / right), // Truncates toward zero, like i64
+        "<" => Object::Boolean(left < right),
+        ">" => Object::Boolean(left > right),
+        "==" => Object::Boolean(left == right),
+        "!=" => Object::Boolean(left != right),
+        _ => Object::Null,
+    }
+}
+
+fn eval_datetime_shift(operator: &str, date: DateTime, seconds: i64) -> Object {
+    let millis = seconds.checked_mul(if operator == "-" { -1000 } else { 1000 });
+    match millis.and_then(|ms| date.checked_add_millis(ms)) {
+        Some(d) => Object::DateTime(d),
+        None => Object::error(format!("datetime overflow: {} {} {}", date, operator, seconds)),
+    }
+}
+
+// An int on either side counts as a decimal with no digits after the point
+fn eval_decimal_infix_expression(operator: &str, left: Decimal, right: Decimal) -> Object {
+    let checked = |result: Option<Decimal>| match result {
+        Some(v) => Object::Decimal(v),
+        None => Object::error(format!("decimal overflow: {} {} {}", left, operator, right)),
+    };
+    match operator {
+        "+" => checked(left.checked_add(right)),
+        "-" => checked(left.checked_sub(right)),
+        "*" => checked(left.checked_mul(right)),
+        "/" if right == Decimal::from(0) => Object::error(format!("division by zero: {} / 0", left)),
+        "/" => checked(left.checked_div(right)),
+        "<" => Object::Boolean(left < right),
+        ">" => Object::Boolean(left > right),
+        "==" => Object::Boolean(left == right),
+        "!=" => Object::Boolean(left != right),
+        _ => Object::Null,
+    }
+}
+
+fn eval_string_infix_expression(operator: &str, left: String, right: String) -> Object {
+    match operator {
+        "+" => Object::String(left + &right),
+        _ => Object::Null,
+    }
+}
+
+// Missing elements and keys are null, like unbound names. Negative
+// indexes count from the end. Strings index by character, not byte;
+// bytes index to ints.
+fn eval_index_expression(left: Object, index: Object) -> Object {
+    match (left, index) {
+        (Object::Array(elements) | Object::Tuple(elements), Object::Integer(i)) => {
+            let i = if i < 0 { i + elements.len() as i64 } else { i };
+            usize::try_from(i).ok().and_then(|i| elements.get(i).cloned()).unwrap_or(Object::Null)
+        },
+        (Object::String(s), Object::Integer(i)) => {
+            let i = if i < 0 { i + s.chars().count() as i64 } else { i };
+            usize::try_from(i).ok().and_then(|i| s.chars().nth(i)).map_or(Object::Null, Object::Char)
+        },
+        (Object::Bytes(data), Object::Integer(i)) => {
+            let i = if i < 0 { i + data.len() as i64 } else { i };
+            usize::try_from(i).ok().and_then(|i| data.get(i)).map_or(Object::Null, |b| Object::Integer(i64::from(*b)))
+        },
+        (Object::Hash(pairs), index) => match index.hash_key() {
+            Ok(key) => pairs.get(&key).cloned().unwrap_or(Object::Null),
+            Err(e) => e,
+        },
+        (left, index) => Object::error(format!("can't index {} with {}", left.type_name(), index.type_name())),
+    }
+}
+
+// Bounds are clamped to the sequence like Python; only a start past the
+// end is an error. Strings slice by character, bytes by byte.
+fn eval_slice_expression(left: Object, start: Option<i64>, end: Option<i64>) -> Object {
+    let len = match &left {
+        Object::Array(elements) | Object::Tuple(elements) => elements.len(),
+        Object::String(s) => s.chars().count(),
+        Object::Bytes(data) => data.len(),
+        other => return Object::error(format!("can't slice {}", other.type_name())),
+    };
+    let clamp = |i: i64| {
+        let i = if i < 0 { i + len as i64 } else { i };
+        i.clamp(0, len as i64) as usize
+    };
+    let from = start.map(clamp).unwrap_or(0);
+    let to = end.map(clamp).unwrap_or(len);
+    if from > to {
+        return Object::error(format!("slice start {} is after end {}", start.unwrap_or(0), end.unwrap_or(len as i64)));
+    }
+    match left {
+        Object::Array(elements) => Object::Array(elements[from..to].to_vec()),
+        Object::Tuple(elements) => Object::Tuple(elements[from..to].to_vec()),
+        Object::String(s) => Object::String(s.chars().skip(from).take(to - from).collect()),
+        Object::Bytes(data) => Object::Bytes(data[from..to].to_vec()),
+        _ => unreachable!("checked above"),
+    }
+}
+
+// Pairs are evaluated left to right; a repeated key keeps the last value
+fn eval_hash_literal(h: &crate::ast::HashLiteral, env: &mut Environment) -> Object {
+    let mut pairs = IndexMap::new();
+    for (key_exp, value_exp) in &h.pairs {
+        let key = eval_expression(key_exp, env);
+        if key.is_error() {
+            return key;
+        }
+        let key = match key.hash_key() {
+            Ok(key) => key,
+            Err(e) => return e,
+        };
+        let value = eval_expression(value_exp, env);
+        if value.is_error() {
+            return value;
+        }
+        pairs.insert(key, value);
+    }
+    Object::Hash(pairs)
+}
+
+// Stops at the first comparison that isn't truthy and gives its result
+fn eval_comparison_chain(c: &crate::ast::ComparisonChain, env: &mut Environment) -> Object {
+    let mut left = eval_expression(&c.operands[0], env);
+    if left.is_error() {
+        return left;
+    }
+    let mut result = Object::Boolean(true);
+    for (operator, operand) in c.operators.iter().zip(&c.operands[1..]) {
+        let right = eval_expression(operand, env);
+        if right.is_error() {
+            return right;
+        }
+        result = match eval_overloaded_infix(operator, &left, &right, env) {
+            Some(result) => result,
+            None => eval_infix_expression(operator, left, right.clone()),
+        };
+        if result.is_error() || !is_truthy(&result, env) {
+            return result;
+        }
+        left = right;
+    }
+    result
+}
+
 fn eval_if_expression(ie: &crate::ast::IfExpression, env: &mut Environment) -> Object {
     let condition = eval_expression(&ie.condition, env);
+    if condition.is_error() {
+        return condition;
+    }
 
-    if is_truthy(&condition) {
-        return eval_block_statement(&ie.consequence, env);
+    if is_truthy(&condition, env) {
+        eval_block_statement(&ie.consequence, env)
     } else if let Some(alt) = &ie.alternative {
-        return eval_block_statement(alt, env);
+        eval_block_statement(alt, env)
     } else {
-        return Object::Null;
+        Object::Null
     }
 }
 
+// The body runs on a copy of the current bindings, so it can read outer
+// variables but nothing it binds survives the zone. What it defers runs
+// when it ends.
+fn eval_zone_expression(z: &crate::ast::ZoneExpression, env: &mut Environment) -> Object {
+    let mut limits = ZoneLimits::default();
+    for (name, exp) in &z.limits {
+        let n = match eval_expression(exp, env) {
+            Object::Integer(n) if n >= 0 => n,
+            Object::Error(msg) => return Object::Error(msg),
+            other => return Object::error(format!("zone: {} must be a non-negative int, found {}", name.value, other.type_name())),
+        };
+        match name.value.as_str() {
+            "steps" => limits.steps = Some(n as u64),
+            "depth" => limits.depth = Some(n as usize),
+            _ => {}, // Rejected by the parser
+        }
+    }
+
+    let mut zone_env = env.clone();
+    env.runtime().borrow_mut().enter_zone(limits);
+    let result = eval_block_statement(&z.body, &mut zone_env);
+    env.runtime().borrow_mut().exit_zone();
+    // Outside the zone's budget, so running out of it doesn't stop cleanup
+    run_deferred(result, &mut zone_env, 0)
+}
+
+// What a loop does with its body's result: go on (Ok) or stop with a value
+// (Err). A break or continue naming some other loop passes through to it,
+// like a return does.
+fn loop_signal(result: Object, label: &Option<String>) -> Result<(), Object> {
+    let ours = |target: &Option<String>| target.is_none() || target == label;
+    match result {
+        Object::Break(value, target) if ours(&target) => Err(*value),
+        Object::Continue(target) if ours(&target) => Ok(()),
+        result @ (Object::Break(..) | Object::Continue(_) | Object::ReturnValue(_) | Object::Error(_)) => Err(result),
+        _ => Ok(()),
+    }
+}
+
+// Every pass costs a step, so even `loop {}` runs out inside a zone
+fn eval_loop_expression(l: &crate::ast::LoopExpression, env: &mut Environment) -> Object {
+    loop {
+        if let Err(msg) = env.runtime().borrow_mut().step() {
+            return Object::Error(msg);
+        }
+        if let Err(result) = loop_signal(eval_block_statement(&l.body, env), &l.label) {
+            return result;
+        }
+    }
+}
+
+// Like a loop that checks after every pass; a false condition ends it with
+// null
+fn eval_do_while_expression(d: &crate::ast::DoWhileExpression, env: &mut Environment) -> Object {
+    loop {
+        if let Err(msg) = env.runtime().borrow_mut().step() {
+            return Object::Error(msg);
+        }
+        if let Err(result) = loop_signal(eval_block_statement(&d.body, env), &d.label) {
+            return result;
+        }
+        let condition = eval_expression(&d.condition, env);
+        if condition.is_error() {
+            return condition;
+        }
+        if !is_truthy(&condition, env) {
+            return Object::Null;
+        }
+    }
+}
+
+// What a for loop goes through: elements, chars, bytes, keys, or what a
+// generator yields (an Err item is the generator failing)
+pub(crate) fn iterate(value: Object) -> Result<Box<dyn Iterator<Item = Result<Object, Object>>>, Object> {
+    Ok(match value {
+        Object::Array(elements) | Object::Tuple(elements) => Box::new(elements.into_iter().map(Ok)),
+        Object::String(s) => Box::new(s.chars().collect::<Vec<_>>().into_iter().map(|c| Ok(Object::Char(c)))),
+        Object::Bytes(data) => Box::new(data.into_iter().map(|b| Ok(Object::Integer(i64::from(b))))),
+        Object::Hash(pairs) => Box::new(pairs.into_keys().map(|k| Ok(k.into()))),
+        Object::Set(keys) => Box::new(keys.into_iter().map(|k| Ok(k.into()))),
+        Object::Generator(g) => Box::new(std::iter::from_fn(move || g.resume().transpose())),
+        Object::Error(msg) => return Err(Object::Error(msg)),
+        other => return Err(Object::error(format!("for: can't iterate over {}", other.type_name()))),
+    })
+}
+
+// Strings give their characters, hashes and sets their keys in order,
+// generators whatever they yield. Like a loop, the value is the break's.
+fn eval_for_expression(fe: &crate::ast::ForExpression, env: &mut Environment) -> Object {
+    let items = match iterate(eval_expression(&fe.iterable, env)) {
+        Ok(items) => items,
+        Err(e) => return e,
+    };
+    for item in items {
+        let item = match item {
+            Ok(item) => item,
+            Err(e) => return e,
+        };
+        if let Err(msg) = env.runtime().borrow_mut().step() {
+            return Object::Error(msg);
+        }
+        bind(env, &fe.variable, item);
+        if let Err(msg) = env.runtime().borrow().check_memory() {
+            return Object::Error(msg);
+        }
+        if let Err(result) = loop_signal(eval_block_statement(&fe.body, env), &fe.label) {
+            return result;
+        }
+    }
+    Object::Null
+}
+
+// The first arm whose pattern matches and whose guard holds. A pattern's
+// bindings are made before its guard runs, like a `let`.
+fn eval_match_expression(me: &crate::ast::MatchExpression, env: &mut Environment) -> Object {
+    let subject = eval_expression(&me.subject, env);
+    if subject.is_error() {
+        return subject;
+    }
+    for arm in &me.arms {
+        let mut bindings = vec![];
+        match match_pattern(&arm.pattern, &subject, &mut bindings, env) {
+            Ok(true) => {},
+            Ok(false) => continue,
+            Err(e) => return e,
+        }
+        // Bound for the guard to see, and undone if it says no
+        let mut previous = vec![];
+        for (name, value) in bindings {
+            previous.push((name, env.own(name.slot, &name.value)));
+            let result = bind(env, name, value);
+            if result.is_error() {
+                return result;
+            }
+        }
+        if let Some(guard) = &arm.guard {
+            let condition = eval_expression(guard, env);
+            if condition.is_error() {
+                return condition;
+            }
+            if !is_truthy(&condition, env) {
+                for (name, old) in previous.into_iter().rev() {
+                    env.restore(name.slot, &name.value, old);
+                }
+                continue;
+            }
+        }
+        return eval_block_statement(&arm.body, env);
+    }
+    Object::error(format!("match: no arm matches {}", subject))
+}
+
+fn eval_switch_expression(se: &crate::ast::SwitchExpression, env: &mut Environment) -> Object {
+    let subject = eval_expression(&se.subject, env);
+    if subject.is_error() {
+        return subject;
+    }
+    for case in &se.cases {
+        for value in &case.values {
+            let value = eval_expression(value, env);
+            if value.is_error() {
+                return value;
+            }
+            if compare::equals(&value, &subject) {
+                return eval_block_statement(&case.body, env);
+            }
+        }
+    }
+    match &se.default {
+        Some(body) => eval_block_statement(body, env),
+        None => Object::error(format!("switch: no case for {}", subject)),
+    }
+}
+
+fn match_pattern<'a>(
+    pattern: &'a crate::ast::Pattern,
+    value: &Object,
+    bindings: &mut Vec<(&'a crate::ast::Identifier, Object)>,
+    env: &mut Environment,
+) -> Result<bool, Object> {
+    use crate::ast::Pattern;
+    match (pattern, value) {
+        (Pattern::Wildcard, _) => Ok(true),
+        (Pattern::Null, value) => Ok(matches!(value, Object::Null)),
+        (Pattern::Binding(name), value) => {
+            bindings.push((name, value.clone()));
+            Ok(true)
+        },
+        (Pattern::Literal(literal), value) => {
+            let literal = eval_expression(literal, env);
+            if literal.is_error() {
+                return Err(literal);
+            }
+            Ok(compare::equals(&literal, value))
+        },
+        (Pattern::Array(elements, rest), Object::Array(values)) => {
+            let fits = match rest {
+                Some(_) => values.len() >= elements.len(),
+                None => values.len() == elements.len(),
+            };
+            if !fits || !match_all(elements, values, bindings, env)? {
+                return Ok(false);
+            }
+            match rest {
+                Some(rest) => match_pattern(rest, &Object::Array(values[elements.len()..].to_vec()), bindings, env),
+                None => Ok(true),
+            }
+        },
+        (Pattern::Tuple(elements), Object::Tuple(values)) => {
+            Ok(values.len() == elements.len() && match_all(elements, values, bindings, env)?)
+        },
+        (Pattern::Hash(pairs), Object::Hash(map)) => {
+            for (key, pattern) in pairs {
+                let key = eval_expression(key, env);
+                if key.is_error() {
+                    return Err(key);
+                }
+                let Some(value) = key.hash_key().ok().and_then(|k| map.get(&k)) else {
+                    return Ok(false);
+                };
+                if !match_pattern(pattern, value, bindings, env)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        },
+        _ => Ok(false),
+    }
+}
+
+fn match_all<'a>(
+    patterns: &'a [crate::ast::Pattern],
+    values: &[Object],
+    bindings: &mut Vec<(&'a crate::ast::Identifier, Object)>,
+    env: &mut Environment,
+) -> Result<bool, Object> {
+    for (pattern, value) in patterns.iter().zip(values) {
+        if !match_pattern(pattern, value, bindings, env)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
 fn eval_block_statement(block: &crate::ast::BlockStatement, env: &mut Environment) -> Object {
     let mut result = Object::Null;
 
@@ -146,7 +820,8 @@ fn eval_block_statement(block: &crate::ast::BlockStatement, env: &mut Environmen
 
         // If we hit a return, we DON'T unwrap it yet. 
         // We pass the "Signal" up to eval_program or the function caller.
-        if let Object::ReturnValue(_) = result {
+        // Errors travel the same way.
+        if matches!(result, Object::ReturnValue(_) | Object::Break(..) | Object::Continue(_) | Object::Error(_)) {
             return result;
         }
     }
@@ -154,27 +829,208 @@ fn eval_block_statement(block: &crate::ast::BlockStatement, env: &mut Environmen
     result
 }
 
-fn is_truthy(obj: &Object) -> bool {
+// By the runtime's rules (Runtime::truthiness); only false and null are
+// false unless the host says otherwise
+pub(crate) fn is_truthy(obj: &Object, env: &Environment) -> bool {
+    env.runtime().borrow().truthiness.is_true(obj)
+}
+
+// --- OPERATOR OVERLOADING ---
+
+// A hash acts as a user-defined type by holding functions under these
+// names. The operands are passed in source order, whichever side
+// supplied the method (so `2 * v` calls v's __mul with (2, v)).
+fn operator_method(operator: &str) -> Option<&'static str> {
+    match operator {
+        "+" => Some("__add"),
+        "-" => Some("__sub"),
+        "*" => Some("__mul"),
+        "/" => Some("__div"),
+        "==" | "!=" => Some("__eq"),
+        "<" => Some("__lt"),
+        ">" => Some("__gt"),
+        _ => None,
+    }
+}
+
+fn method(obj: &Object, name: &str) -> Option<Object> {
     match obj {
-        Object::Null => false,
-        Object::Boolean(true) => true,
-        Object::Boolean(false) => false,
-        _ => true, // In Nova, integers/objects are "true" (like Ruby)
+        Object::Hash(pairs) => match pairs.get(&HashKey::from(name)) {
+            Some(f @ (Object::Function(_) | Object::Native(_) | Object::Builtin(_) | Object::Partial(_))) => Some(f.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn eval_overloaded_infix(operator: &str, left: &Object, right: &Object, env: &mut Environment) -> Option<Object> {
+    let name = operator_method(operator)?;
+    let f = method(left, name).or_else(|| method(right, name))?;
+    let result = call_function(f, vec![left.clone(), right.clone()], env);
+    if operator == "!=" && !result.is_error() {
+        return Some(Object::Boolean(!is_truthy(&result, env)));
+    }
+    Some(result)
+}
+
+// `quote(code)` or `quote { code }`: the code, not evaluated, with each
+// `unquote(value)` in it replaced by the value as code (see
+// macros::unquote)
+fn eval_quote(c: &CallExpression, env: &mut Environment) -> Object {
+    let Some(mut quote) = macros::quoted(c) else {
+        return Object::error("quote() takes the code to quote: quote(x + 1) or quote { ... }");
+    };
+    let mut unquote = |call: &CallExpression| {
+        if !matches!(eval_expression(&call.function, env), Object::Builtin(b) if b.name == "unquote") {
+            return Ok(None);
+        }
+        let [value] = call.arguments.as_slice() else {
+            return Err("unquote() takes one value".to_string());
+        };
+        match eval_expression(value, env) {
+            Object::Error(msg) => Err(msg),
+            value => macros::unquote(value).map(Some),
+        }
+    };
+    let rewritten = match &mut quote {
+        Quote::Expression(e) => macros::rewrite_expression(e, &mut unquote),
+        Quote::Block(b) => macros::rewrite_statements(&mut b.statements, &mut unquote),
+    };
+    if let Err((msg, _)) = rewritten {
+        return Object::Error(msg);
+    }
+    match &mut quote {
+        Quote::Expression(e) => e.visit_variables_mut(&mut macros::unslot),
+        Quote::Block(b) => b.visit_variables_mut(&mut macros::unslot),
     }
+    Object::Quote(Rc::new(quote))
+}
+
+pub(crate) fn call_function(function: Object, args: Vec<Object>, env: &mut Environment) -> Object {
+    call_function_as(function, args, None, env)
+}
+
+// `name` is what the call site called it by, for error reports
+fn call_function_as(function: Object, args: Vec<Object>, name: Option<&str>, env: &mut Environment) -> Object {
+    notify(env, |h| h.on_call(&function, &args));
+    match function {
+        Object::Function(fn_obj) => apply_function(fn_obj, args, name, env),
+        Object::Native(native) => (native.func)(args),
+        Object::Builtin(builtin) => (builtin.func)(env, args),
+        Object::Partial(partial) => {
+            let args: Vec<Object> = partial.args.into_iter().chain(args).collect();
+            match partial.arity {
+                Some(arity) if args.len() < arity => Object::Partial(Partial { args, ..partial }),
+                _ => call_function(*partial.function, args, env),
+            }
+        },
+        _ if strict(env) => Object::error(format!("can't call {}", function.type_name())),
+        _ => Object::Null, // Error: calling non-function
+    }
+}
+
+// Tells the host's hooks (see hooks.rs), if it registered any
+fn notify(env: &Environment, event: impl FnMut(&mut dyn Hooks)) {
+    let mut runtime = env.runtime().borrow_mut();
+    if !runtime.hooks.is_empty() {
+        runtime.hooks.each(event);
+    }
+}
+
+fn strict(env: &Environment) -> bool {
+    env.runtime().borrow().strict
+}
+
+// Slots keyword arguments into the positions of the parameters they name,
+// so the call itself only ever sees positional arguments
+fn bind_keywords(
+    function: &Object,
+    positional: Vec<Object>,
+    keywords: &[crate::ast::KeywordArgument],
+    env: &mut Environment,
+) -> Result<Vec<Object>, Object> {
+    let Object::Function(f) = function else {
+        return Err(Object::error(format!("{} doesn't take keyword arguments", function)));
+    };
+    let mut slots: Vec<Option<Object>> = vec![None; f.parameters.len().max(positional.len())];
+    for (slot, arg) in slots.iter_mut().zip(positional) {
+        *slot = Some(arg);
+    }
+    for (name, value) in keywords {
+        let Some(i) = f.parameters.iter().position(|p| p.value == name.value) else {
+            return Err(Object::error(format!("unknown keyword argument '{}'", name.value)));
+        };
+        if slots[i].is_some() {
+            return Err(Object::error(format!("argument '{}' given twice", name.value)));
+        }
+        let value = eval_expression(value, env);
+        if value.is_error() {
+            return Err(value);
+        }
+        slots[i] = Some(value);
+    }
+    slots.into_iter().enumerate().map(|(i, slot)| {
+        slot.ok_or_else(|| Object::error(format!("missing argument '{}'", f.parameters[i].value)))
+    }).collect()
 }
 
 // --- HELPER: Execute the function ---
-fn apply_function(fn_obj: Function, args: Vec<Object>, _old_env: &Environment) -> Object {
-    // 1. Create a NEW scope for the function execution
-    let mut extended_env = Environment::new(); 
-    
-    // 2. Bind arguments (x=5, y=10) in this new scope
-    for (i, param) in fn_obj.parameters.iter().enumerate() {
-        extended_env.set(param.value.clone(), args[i].clone());
+pub(crate) fn apply_function(fn_obj: Rc<Function>, args: Vec<Object>, name: Option<&str>, old_env: &Environment) -> Object {
+    // Machine code from the JIT, for the arguments it takes. It makes no
+    // calls of its own, so the call limits are all there is to check.
+    if let Some(compiled) = &fn_obj.compiled {
+        if let Err(msg) = old_env.runtime().borrow_mut().enter_call() {
+            return Object::Error(msg);
+        }
+        let value = (compiled.0)(&args);
+        old_env.runtime().borrow_mut().exit_call();
+        if let Some(value) = value {
+            return value;
+        }
     }
+    // 1. Create a NEW scope for the function execution (same runtime/capabilities),
+    //    seeing the top-level bindings from where the function was defined
+    let mut extended_env = Environment::with_frame(old_env.runtime().clone(), &fn_obj.globals, fn_obj.locals.clone());
+
+    // 2. Bind arguments (x=5, y=10) in this new scope, next to what the
+    //    function captured
+    if args.len() != fn_obj.parameters.len() {
+        return Object::error(format!("wrong number of arguments: expected {}, found {}", fn_obj.parameters.len(), args.len()));
+    }
+    for (name, value) in &fn_obj.captured {
+        extended_env.set(name.clone(), value.clone());
+    }
+    for (param, arg) in fn_obj.parameters.iter().zip(args) {
+        bind(&mut extended_env, param, arg);
+    }
+    if let Err(msg) = old_env.runtime().borrow().check_memory() {
+        return Object::Error(msg);
+    }
+
+    // A body that yields runs later, one piece per next()
+    if fn_obj.body.contains_yield() && !fn_obj.is_async {
+        return Object::Generator(Generator::new(fn_obj.body.clone(), extended_env));
+    }
+    // An async one starts now, up to its first await of something not
+    // ready yet
+    if fn_obj.is_async {
+        return Object::Future(Future::start(fn_obj.body.clone(), extended_env));
+    }
+
+    if let Err(msg) = old_env.runtime().borrow_mut().enter_call() {
+        return Object::Error(msg);
+    }
+    // Timed by subscribers that report how long spans were entered
+    #[cfg(feature = "tracing")]
+    let _span = tracing::trace_span!("call", function = name.unwrap_or("<anonymous>")).entered();
+    let frame = CallFrame { name: name.map(str::to_string), function: fn_obj.clone() };
+    old_env.runtime().borrow_mut().frames.push(frame);
 
     // 3. Execute the body
     let evaluated = eval_block_statement(&fn_obj.body, &mut extended_env);
+    let evaluated = run_deferred(evaluated, &mut extended_env, 0);
+    old_env.runtime().borrow_mut().frames.pop();
+    old_env.runtime().borrow_mut().exit_call();
 
     // 4. Unwrap return value if present
     if let Object::ReturnValue(val) = evaluated {
@@ -183,10 +1039,37 @@ fn apply_function(fn_obj: Function, args: Vec<Object>, _old_env: &Environment) -
     evaluated
 }
 
-fn eval_expressions(exps: &Vec<Expression>, env: &mut Environment) -> Vec<Object> {
+// What a body deferred, once it has finished with `result`. An error from
+// a deferred expression replaces the result, unless that is an error
+// already: the first error wins.
+pub(crate) fn run_deferred(mut result: Object, env: &mut Environment, floor: usize) -> Object {
+    while let Some(expression) = env.pop_deferred(floor) {
+        let value = eval_expression(&expression, env);
+        if value.is_error() && !result.is_error() {
+            result = value;
+        }
+    }
+    result
+}
+
+// Into the slot the resolver gave the name, if it has one
+pub(crate) fn bind(env: &mut Environment, name: &crate::ast::Identifier, value: Object) -> Object {
+    notify(env, |h| h.on_binding(&name.value, &value));
+    match name.slot {
+        Some(slot) => env.set_slot(slot, &name.value, value),
+        None => env.set(name.value.clone(), value),
+    }
+}
+
+// Stops at the first error
+fn eval_expressions(exps: &Vec<Expression>, env: &mut Environment) -> Result<Vec<Object>, Object> {
     let mut result = vec![];
     for e in exps {
-        result.push(eval_expression(e, env));
+        let value = eval_expression(e, env);
+        if value.is_error() {
+            return Err(value);
+        }
+        result.push(value);
     }
-    result
+    Ok(result)
 }
\ No newline at end of file
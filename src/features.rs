@@ -0,0 +1,174 @@
+// Feature gates and deprecations: warnings about how a file is written,
+// not what it does.
+//
+//   #pragma feature(generators, async)
+//
+// Generators (`yield`), async functions (`async fn`, `await`) and macros
+// are experimental, so their syntax may still change. A file that uses one
+// without turning it on with a pragma gets an `experimental-feature`
+// warning at its first use; existing scripts keep running, and
+// `--deny experimental-feature` makes the gates hard. Builtins on their way
+// out get a `deprecated` warning at every call that says what to use
+// instead, unless the name is the script's own.
+use crate::ast::{BlockStatement, Expression, Program, Statement};
+use crate::diagnostics::Diagnostic;
+use crate::resolver;
+use crate::span::Span;
+
+// What `#pragma feature(...)` may turn on
+pub const FEATURES: &[&str] = &["generators", "async", "macros"];
+
+// Builtins that still work but shouldn't be used, and what replaces them
+pub const DEPRECATED: &[(&str, &str)] = &[
+    ("has_key", "contains(hash, key)"),
+];
+
+// `bound` says whether a name is bound before the program runs (by an
+// earlier file in a project, or the host), so isn't the builtin
+pub fn check(program: &Program, bound: impl Fn(&str) -> bool) -> Vec<Diagnostic> {
+    let mut finder = Finder { found: vec![], at: Span::default() };
+    finder.statements(&program.statements);
+    finder.found.extend(program.macros.iter().map(|(_, span)| ("macros", *span)));
+
+    let mut warnings = vec![];
+    for feature in FEATURES {
+        if program.features.iter().any(|f| f == feature) {
+            continue;
+        }
+        if let Some((_, span)) = finder.found.iter().filter(|(f, _)| f == feature).min_by_key(|(_, span)| span.start) {
+            let what = if *feature == "async" { "async functions" } else { feature };
+            let message = format!("{} are experimental; turn them on with `#pragma feature({})` at the top of the file", what, feature);
+            warnings.push(Diagnostic::warning("experimental-feature", message, *span));
+        }
+    }
+
+    for reference in &resolver::resolve(program).references {
+        if reference.definition.is_none()
+            && let Some((name, instead)) = DEPRECATED.iter().find(|(name, _)| *name == reference.name)
+            && !bound(name)
+        {
+            warnings.push(Diagnostic::warning("deprecated", format!("{} is deprecated; use {} instead", name, instead), reference.span));
+        }
+    }
+    warnings
+}
+
+// Where each experimental feature is used: the innermost statement around
+// it, since expressions don't have spans of their own
+struct Finder {
+    found: Vec<(&'static str, Span)>,
+    at: Span,
+}
+
+impl Finder {
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn block(&mut self, block: &BlockStatement) {
+        self.statements(&block.statements);
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        let outer = std::mem::replace(&mut self.at, statement.span());
+        match statement {
+            Statement::Let(s) => self.expression(&s.value),
+            Statement::LetTuple(s) => self.expression(&s.value),
+            Statement::Return(s) => self.expression(&s.return_value),
+            Statement::Yield(s) => {
+                self.found.push(("generators", s.span));
+                self.expression(&s.value);
+            },
+            Statement::Defer(s) => self.expression(&s.value),
+            Statement::Break(s) => {
+                if let Some(value) = &s.value {
+                    self.expression(value);
+                }
+            },
+            Statement::Continue(_) => {},
+            Statement::Expression(s) => self.expression(&s.expression),
+        }
+        self.at = outer;
+    }
+
+    fn expression(&mut self, e: &Expression) {
+        match e {
+            Expression::Identifier(_) | Expression::IntegerLiteral(_) | Expression::StringLiteral(_) |
+            Expression::CharLiteral(_) | Expression::DecimalLiteral(_) | Expression::Boolean(_) => {},
+            Expression::Function(f) => {
+                if f.is_async {
+                    self.found.push(("async", self.at));
+                }
+                self.block(&f.body);
+            },
+            Expression::Await(e) => {
+                self.found.push(("async", self.at));
+                self.expression(&e.value);
+            },
+            Expression::Prefix(e) => self.expression(&e.right),
+            Expression::Infix(e) => {
+                self.expression(&e.left);
+                self.expression(&e.right);
+            },
+            Expression::Comparison(e) => e.operands.iter().for_each(|o| self.expression(o)),
+            Expression::If(e) => {
+                self.expression(&e.condition);
+                self.block(&e.consequence);
+                if let Some(alternative) = &e.alternative {
+                    self.block(alternative);
+                }
+            },
+            Expression::Call(e) => {
+                self.expression(&e.function);
+                e.all_arguments().for_each(|a| self.expression(a));
+                if let Some(block) = &e.block {
+                    self.block(block);
+                }
+            },
+            Expression::Array(e) => e.elements.iter().for_each(|el| self.expression(el)),
+            Expression::Tuple(e) => e.elements.iter().for_each(|el| self.expression(el)),
+            Expression::Hash(e) => e.pairs.iter().for_each(|(k, v)| {
+                self.expression(k);
+                self.expression(v);
+            }),
+            Expression::Index(e) => {
+                self.expression(&e.left);
+                self.expression(&e.index);
+            },
+            Expression::Slice(e) => {
+                self.expression(&e.left);
+                [&e.start, &e.end].into_iter().flatten().for_each(|b| self.expression(b));
+            },
+            Expression::Unsafe(e) => self.block(&e.body),
+            Expression::Zone(e) => {
+                e.limits.iter().for_each(|(_, v)| self.expression(v));
+                self.block(&e.body);
+            },
+            Expression::Loop(e) => self.block(&e.body),
+            Expression::DoWhile(e) => {
+                self.block(&e.body);
+                self.expression(&e.condition);
+            },
+            Expression::For(e) => {
+                self.expression(&e.iterable);
+                self.block(&e.body);
+            },
+            Expression::Match(e) => {
+                self.expression(&e.subject);
+                for arm in &e.arms {
+                    if let Some(guard) = &arm.guard {
+                        self.expression(guard);
+                    }
+                    self.block(&arm.body);
+                }
+            },
+            Expression::Switch(e) => {
+                self.expression(&e.subject);
+                e.cases.iter().flat_map(|c| &c.values).for_each(|v| self.expression(v));
+                e.bodies().for_each(|b| self.block(b));
+            },
+        }
+    }
+}
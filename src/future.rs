@@ -0,0 +1,215 @@
+// Async functions and the event loop that runs them. Calling an `async fn`
+// starts its body straight away and returns a future once the body gets
+// to an `await` of something that isn't ready; `await` on the future gives
+// the body's result (or its error):
+//
+//     let fetch = async fn(url) {
+//         let page = await spawn(fn(u) { unsafe { http_get(u) } }, url);
+//         return len(page);
+//     };
+//     let a = fetch("https://example.com/a");
+//     let b = fetch("https://example.com/b");   // both requests are out
+//     await a + await b
+//
+// There's one thread and no reactor: waiting happens on timers
+// (sleep_async) and on tasks, which do their blocking I/O on threads of
+// their own. A body pauses, on the same Stepper as generators, at an
+// `await` that is a statement of its own (`let x = await e;`,
+// `return await e;`, `await e;`). Any other await blocks in place, and so
+// does await outside an async function; while it waits, the loop keeps
+// the other futures of the interpreter going. Whatever is still pending
+// when the program ends is run to completion then.
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use crate::ast::{BlockStatement, Suspension};
+use crate::environment::Environment;
+use crate::generator::{Pause, Resume, Stepper};
+use crate::object::Object;
+
+// How long the loop sleeps at most before looking at tasks again
+const TICK: Duration = Duration::from_millis(1);
+
+#[derive(Clone)]
+pub struct Future {
+    state: Rc<RefCell<State>>,
+}
+
+enum State {
+    // An async function's body, and what it's waiting on
+    Running(Box<Stepper>, Option<(Object, Resume)>),
+    // sleep_async: ready (with null) at this instant
+    Sleeping(Instant),
+    // gather: ready once all of these are, with the results so far
+    All(Vec<(Object, Option<Object>)>),
+    Done(Object),
+}
+
+pub enum Poll {
+    Ready(Object),
+    // Not yet; nothing will change before the instant, if there is one
+    Pending(Option<Instant>),
+}
+
+impl Future {
+    // Runs the body of an async function up to its first pause, and keeps
+    // it with the interpreter's other futures
+    pub fn start(body: BlockStatement, env: Environment) -> Self {
+        let runtime = env.runtime().clone();
+        let future = Future::new(State::Running(Box::new(Stepper::new(body, env, Suspension::Await)), None));
+        if let Poll::Pending(_) = future.poll() {
+            runtime.borrow_mut().futures.push(future.clone());
+        }
+        future
+    }
+
+    pub fn sleep(duration: Duration) -> Self {
+        Future::new(State::Sleeping(Instant::now() + duration))
+    }
+
+    pub fn all(values: Vec<Object>) -> Self {
+        Future::new(State::All(values.into_iter().map(|value| (value, None)).collect()))
+    }
+
+    fn new(state: State) -> Self {
+        Future { state: Rc::new(RefCell::new(state)) }
+    }
+
+    // Takes the future as far as it can go without waiting
+    pub fn poll(&self) -> Poll {
+        let Ok(mut state) = self.state.try_borrow_mut() else {
+            return Poll::Ready(Object::error("await: a future can't wait for itself"));
+        };
+        let result = match &mut *state {
+            State::Done(value) => return Poll::Ready(value.clone()),
+            State::Sleeping(until) if Instant::now() < *until => return Poll::Pending(Some(*until)),
+            State::Sleeping(_) => Object::Null,
+            State::All(values) => match poll_all(values) {
+                Poll::Ready(value) => value,
+                pending => return pending,
+            },
+            State::Running(stepper, awaiting) => loop {
+                if let Some((value, _)) = awaiting {
+                    let value = match poll_value(value) {
+                        Poll::Ready(value) => value,
+                        pending => return pending,
+                    };
+                    let Some((_, resume)) = awaiting.take() else { unreachable!() };
+                    if value.is_error() {
+                        break value;
+                    }
+                    if let Some(returned) = stepper.deliver(resume, value) {
+                        break returned;
+                    }
+                }
+                match stepper.run() {
+                    Ok(Pause::Await(value, resume)) => *awaiting = Some((value, resume)),
+                    Ok(Pause::Return(value) | Pause::Yield(value)) => break value,
+                    Err(error) => break error,
+                }
+            },
+        };
+        *state = State::Done(result.clone());
+        Poll::Ready(result)
+    }
+
+    // Being polled further up the Rust stack
+    fn is_busy(&self) -> bool {
+        self.state.try_borrow_mut().is_err()
+    }
+
+    fn is_done(&self) -> bool {
+        self.state.try_borrow().is_ok_and(|state| matches!(*state, State::Done(_)))
+    }
+}
+
+// What awaiting a value waits for: a future, or a task to finish. Anything
+// else is ready already, as itself.
+pub fn poll_value(value: &Object) -> Poll {
+    match value {
+        Object::Future(future) => future.poll(),
+        Object::Task(task) if task.is_finished() => Poll::Ready(task.join()),
+        Object::Task(_) => Poll::Pending(None),
+        other => Poll::Ready(other.clone()),
+    }
+}
+
+// An array of the results, in order, or the first error. Each value is
+// only waited for until it has a result (a task can only be joined once).
+fn poll_all(values: &mut [(Object, Option<Object>)]) -> Poll {
+    let mut pending = None;
+    for (value, result) in values.iter_mut().filter(|(_, result)| result.is_none()) {
+        match poll_value(value) {
+            Poll::Ready(value) if value.is_error() => return Poll::Ready(value),
+            Poll::Ready(value) => *result = Some(value),
+            Poll::Pending(wake) => pending = Some(earliest(pending.flatten(), wake)),
+        }
+    }
+    match pending {
+        Some(wake) => Poll::Pending(wake),
+        None => Poll::Ready(Object::Array(values.iter_mut().filter_map(|(_, result)| result.take()).collect())),
+    }
+}
+
+fn earliest(a: Option<Instant>, b: Option<Instant>) -> Option<Instant> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+// Waits for a value the way await does, running the interpreter's other
+// futures in the meantime
+pub fn block_on(value: Object, env: &Environment) -> Object {
+    loop {
+        let mut wake = match poll_value(&value) {
+            Poll::Ready(value) => return value,
+            Poll::Pending(wake) => wake,
+        };
+        let others = env.runtime().borrow().futures.clone();
+        for future in others.iter().filter(|f| !f.is_busy()) {
+            if let Poll::Pending(until) = future.poll() {
+                wake = earliest(wake, until);
+            }
+        }
+        let mut runtime = env.runtime().borrow_mut();
+        runtime.futures.retain(|f| !f.is_done());
+        if runtime.cancel.is_cancelled() {
+            return Object::error("cancelled");
+        }
+        drop(runtime);
+        std::thread::sleep(wake.map_or(TICK, |w| w.saturating_duration_since(Instant::now()).min(TICK)));
+    }
+}
+
+// Runs every future nobody awaited to the end, once the program has. The
+// first of them to fail is the error.
+pub fn run_pending(env: &Environment) -> Option<Object> {
+    let mut failed = None;
+    loop {
+        let pending = std::mem::take(&mut env.runtime().borrow_mut().futures);
+        if pending.is_empty() {
+            return failed;
+        }
+        for future in pending {
+            let result = block_on(Object::Future(future), env);
+            if result.is_error() && failed.is_none() {
+                failed = Some(result);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Future {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Future")
+    }
+}
+
+// Like generators: only equal to itself
+impl PartialEq for Future {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.state, &other.state)
+    }
+}
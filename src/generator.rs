@@ -0,0 +1,228 @@
+// Generators: calling a function whose body contains `yield` doesn't run
+// it, it returns a generator that runs the body a piece at a time.
+//
+//     let count = fn(n) { yield n; yield n + 1; if (n > 0) { yield n + 2; } };
+//     let g = count(1);
+//     next(g)   // 1
+//     next(g)   // 2
+//
+// The tree-walker can't pause in the middle of a Rust call stack, so the
+// generator keeps its own: a stack of blocks with the index of the next
+// statement in each. `yield` may appear directly in the body or inside
+// if/else branches and loop bodies (the only blocks we step into); a
+// `yield` anywhere else is an error when it's reached. A loop's frame
+// starts over when it runs out (or on a `continue`), until a `break` pops
+// it.
+//
+// Async functions (future.rs) run on the same Stepper, pausing at their
+// `await`s instead: `let x = await e;`, `return await e;` and `await e;`,
+// directly in the body or in the same blocks.
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use crate::ast::{BlockStatement, Expression, Identifier, Statement, Suspension};
+use crate::environment::Environment;
+use crate::evaluator::{bind, eval_expression, eval_statement, is_truthy, run_deferred};
+use crate::object::Object;
+
+#[derive(Clone)]
+pub struct Generator {
+    state: Rc<RefCell<Stepper>>,
+}
+
+// A function body being run a piece at a time
+pub(crate) struct Stepper {
+    env: Environment,
+    frames: Vec<Frame>,
+    point: Suspension,
+    // What the last statement evaluated to: the result of a body that
+    // runs out without a `return`
+    last: Object,
+}
+
+struct Frame {
+    block: BlockStatement,
+    next: usize,
+    looping: bool,
+    label: Option<String>,
+}
+
+// Why the Stepper stopped
+pub(crate) enum Pause {
+    Yield(Object),
+    // Waiting on this value; the result goes where Resume says
+    Await(Object, Resume),
+    // The body finished, with this value
+    Return(Object),
+}
+
+pub(crate) enum Resume {
+    Bind(Identifier), // `let x = await e;`
+    Return,           // `return await e;`
+    Value,            // `await e;`
+}
+
+impl Generator {
+    pub fn new(body: BlockStatement, env: Environment) -> Self {
+        Generator { state: Rc::new(RefCell::new(Stepper::new(body, env, Suspension::Yield))) }
+    }
+
+    // Runs to the next `yield`. None once the body has finished; an error
+    // also finishes the generator.
+    pub fn resume(&self) -> Result<Option<Object>, Object> {
+        let Ok(mut state) = self.state.try_borrow_mut() else {
+            return Err(Object::error("generator is already running"));
+        };
+        match state.run() {
+            Ok(Pause::Yield(value)) => Ok(Some(value)),
+            Ok(_) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Stepper {
+    pub(crate) fn new(body: BlockStatement, env: Environment, point: Suspension) -> Self {
+        let frames = vec![Frame { block: body, next: 0, looping: false, label: None }];
+        Stepper { env, frames, point, last: Object::Null }
+    }
+
+    // Hands an awaited result to the body. Some(value) if that finished it
+    // (`return await e;`).
+    pub(crate) fn deliver(&mut self, resume: Resume, value: Object) -> Option<Object> {
+        match resume {
+            Resume::Bind(name) => {
+                self.last = bind(&mut self.env, &name, value);
+                None
+            },
+            Resume::Value => {
+                self.last = value;
+                None
+            },
+            Resume::Return => Some(self.finish(value)),
+        }
+    }
+
+    // Runs to the next pause, as one call for the depth limit. Anything but
+    // a Yield or Await finishes the body.
+    pub(crate) fn run(&mut self) -> Result<Pause, Object> {
+        let runtime = self.env.runtime().clone();
+        runtime.borrow_mut().enter_call().map_err(Object::Error)?;
+        let result = self.step_to_pause();
+        runtime.borrow_mut().exit_call();
+        match result {
+            Ok(Pause::Return(value)) => match self.finish(value) {
+                error @ Object::Error(_) => Err(error),
+                value => Ok(Pause::Return(value)),
+            },
+            Err(error) => Err(self.finish(error)),
+            paused => paused,
+        }
+    }
+
+    // Done with the body: what it deferred runs now
+    fn finish(&mut self, result: Object) -> Object {
+        self.frames.clear();
+        run_deferred(result, &mut self.env, 0)
+    }
+
+    fn step_to_pause(&mut self) -> Result<Pause, Object> {
+        loop {
+            let Some(frame) = self.frames.last_mut() else {
+                return Ok(Pause::Return(std::mem::replace(&mut self.last, Object::Null)));
+            };
+            let Some(stmt) = frame.block.statements.get(frame.next).cloned() else {
+                if frame.looping {
+                    frame.next = 0;
+                    self.env.runtime().borrow_mut().step().map_err(Object::Error)?;
+                } else {
+                    self.frames.pop();
+                }
+                continue;
+            };
+            frame.next += 1;
+
+            match (&stmt, self.point) {
+                (Statement::Yield(y), Suspension::Yield) => {
+                    let value = self.eval(&y.value)?;
+                    return Ok(Pause::Yield(value));
+                },
+                (Statement::Yield(_), Suspension::Await) => return Err(Object::error("yield is not allowed in an async function")),
+                (Statement::Let(l), Suspension::Await) if let Expression::Await(a) = &l.value => {
+                    let value = self.eval(&a.value)?;
+                    return Ok(Pause::Await(value, Resume::Bind(l.name.clone())));
+                },
+                (Statement::Return(r), Suspension::Await) if let Expression::Await(a) = &r.return_value => {
+                    let value = self.eval(&a.value)?;
+                    return Ok(Pause::Await(value, Resume::Return));
+                },
+                (Statement::Expression(s), Suspension::Await) if let Expression::Await(a) = &s.expression => {
+                    let value = self.eval(&a.value)?;
+                    return Ok(Pause::Await(value, Resume::Value));
+                },
+                (Statement::Expression(s), _) if s.expression.suspends_at(self.point) && let Expression::If(ie) = &s.expression => {
+                    let condition = self.eval(&ie.condition)?;
+                    let branch = if is_truthy(&condition, &self.env) { Some(&ie.consequence) } else { ie.alternative.as_ref() };
+                    if let Some(block) = branch {
+                        self.frames.push(Frame { block: block.clone(), next: 0, looping: false, label: None });
+                    }
+                    self.last = Object::Null;
+                },
+                (Statement::Expression(s), _) if s.expression.suspends_at(self.point) && let Expression::Loop(l) = &s.expression => {
+                    self.env.runtime().borrow_mut().step().map_err(Object::Error)?;
+                    self.frames.push(Frame { block: l.body.clone(), next: 0, looping: true, label: l.label.clone() });
+                    self.last = Object::Null;
+                },
+                _ => match eval_statement(&stmt, &mut self.env) {
+                    Object::Error(msg) => return Err(Object::Error(msg)),
+                    Object::ReturnValue(value) => return Ok(Pause::Return(*value)),
+                    // Nothing takes a stepped loop's value
+                    Object::Break(_, label) => {
+                        while let Some(frame) = self.frames.pop() && !frame.targeted_by(&label) {}
+                        self.last = Object::Null;
+                    },
+                    Object::Continue(label) => {
+                        while let Some(frame) = self.frames.last_mut() && !frame.targeted_by(&label) {
+                            self.frames.pop();
+                        }
+                        if let Some(frame) = self.frames.last_mut() {
+                            frame.next = frame.block.statements.len();
+                        }
+                    },
+                    value => self.last = value,
+                },
+            }
+        }
+    }
+
+    // One statement's worth of expression: counts as a step, and an error
+    // ends the body
+    fn eval(&mut self, expression: &Expression) -> Result<Object, Object> {
+        self.env.runtime().borrow_mut().step().map_err(Object::Error)?;
+        let value = eval_expression(expression, &mut self.env);
+        if value.is_error() {
+            return Err(value);
+        }
+        Ok(value)
+    }
+}
+
+impl Frame {
+    // Whether a break or continue with this label (or none) is for this frame
+    fn targeted_by(&self, label: &Option<String>) -> bool {
+        self.looping && (label.is_none() || *label == self.label)
+    }
+}
+
+impl fmt::Debug for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Generator")
+    }
+}
+
+// Each call makes a new generator; two are only equal if they're the same one
+impl PartialEq for Generator {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.state, &other.state)
+    }
+}
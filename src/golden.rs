@@ -0,0 +1,107 @@
+// Golden tests: scripts whose whole output is checked against a copy of
+// it someone read and agreed with, so a change to the parser or evaluator
+// that changes what any of them does shows up as a diff.
+//
+//   tests/fixtures/closures.nv         // the script
+//   tests/fixtures/closures.expected   // what running it gives
+//
+// What a run gives is what print() wrote, then the warnings and errors
+// the interpreter reported, rendered as on the command line (a runtime
+// error with its frame note), then `=> ` and the value the script ended
+// on if it didn't fail. Each script runs in an Interpreter of its own with
+// the default Config.
+//
+// With `update` set the .expected files are written from what the scripts
+// give now instead of compared, for when the change is meant to be there;
+// `NOVA_UPDATE_GOLDEN=1 cargo test` does that for the repo's fixtures.
+// The diff in the .expected files is then the thing to review.
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::interpreter::Interpreter;
+
+pub const SCRIPT_EXTENSION: &str = "nv";
+pub const EXPECTED_EXTENSION: &str = "expected";
+
+// A script whose output isn't what its .expected file says
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub script: PathBuf,
+    // None when there's no .expected file yet
+    pub expected: Option<String>,
+    pub actual: String,
+}
+
+impl fmt::Display for Mismatch {
+    // The first line that differs, which is usually enough to see what
+    // happened; the files themselves have the rest
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some(expected) = &self.expected else {
+            return write!(f, "{}: no .{} file", self.script.display(), EXPECTED_EXTENSION);
+        };
+        let mut expected_lines = expected.lines();
+        let mut actual_lines = self.actual.lines();
+        let mut line = 1;
+        loop {
+            match (expected_lines.next(), actual_lines.next()) {
+                (Some(e), Some(a)) if e == a => line += 1,
+                (None, None) => return write!(f, "{}: differs in line endings", self.script.display()),
+                (e, a) => {
+                    return write!(f, "{}:{}: expected {:?}, got {:?}", self.script.display(), line,
+                        e.unwrap_or("<end>"), a.unwrap_or("<end>"));
+                },
+            }
+        }
+    }
+}
+
+// What running `source` gives, as a .expected file has it
+pub fn run(source: &str) -> String {
+    let mut nova = Interpreter::new();
+    nova.env().runtime().borrow_mut().output = Some(String::new());
+    let result = nova.eval_source(source);
+
+    let mut out = nova.env().runtime().borrow_mut().output.take().unwrap_or_default();
+    for diagnostic in nova.diagnostics() {
+        out.push_str(&diagnostic.render(source));
+        out.push('\n');
+    }
+    if let Ok(value) = result {
+        out.push_str(&format!("=> {}\n", value));
+    }
+    out
+}
+
+// Runs every script in `dir` (not its subdirectories), in name order, and
+// returns the ones that don't match. Fails only if the directory or a
+// file can't be read or written.
+pub fn check_dir(dir: &Path, update: bool) -> io::Result<Vec<Mismatch>> {
+    let mut scripts = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == SCRIPT_EXTENSION) {
+            scripts.push(path);
+        }
+    }
+    scripts.sort();
+
+    let mut mismatches = vec![];
+    for script in scripts {
+        let actual = run(&fs::read_to_string(&script)?);
+        let expected_path = script.with_extension(EXPECTED_EXTENSION);
+        if update {
+            fs::write(&expected_path, &actual)?;
+            continue;
+        }
+        let expected = match fs::read_to_string(&expected_path) {
+            Ok(text) => Some(text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        if expected.as_deref() != Some(actual.as_str()) {
+            mismatches.push(Mismatch { script, expected, actual });
+        }
+    }
+    Ok(mismatches)
+}
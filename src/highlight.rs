@@ -0,0 +1,95 @@
+// Maps source text to classified spans for syntax highlighters and the
+// LSP semantic-tokens request. The lexer does the heavy lifting; the
+// resolver refines plain identifiers into functions and parameters.
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::resolver::{self, BindingKind};
+use crate::ast::Expression;
+use crate::span::Span;
+use crate::token::TokenType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenClass {
+    Keyword,
+    Identifier,
+    Function,
+    Parameter,
+    Number,
+    String,
+    Operator,
+    Comment,
+}
+
+impl TokenClass {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenClass::Keyword => "keyword",
+            TokenClass::Identifier => "identifier",
+            TokenClass::Function => "function",
+            TokenClass::Parameter => "parameter",
+            TokenClass::Number => "number",
+            TokenClass::String => "string",
+            TokenClass::Operator => "operator",
+            TokenClass::Comment => "comment",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClassifiedSpan {
+    pub span: Span,
+    pub class: TokenClass,
+}
+
+// Tokens that carry no meaning for highlighting (delimiters, EOF) are
+// left out. The result is sorted by position.
+pub fn classify(source: &str) -> Vec<ClassifiedSpan> {
+    let mut lexer = Lexer::new(source.to_string());
+    let mut tokens = vec![];
+    loop {
+        let (tok, span) = lexer.next_spanned();
+        if tok == TokenType::EOF {
+            break;
+        }
+        tokens.push((tok, span));
+    }
+
+    let mut parser = Parser::new(Lexer::new(source.to_string()));
+    let program = parser.parse_program();
+    let resolution = resolver::resolve(&program);
+
+    let mut out = vec![];
+    for (i, (tok, span)) in tokens.iter().enumerate() {
+        let class = match tok {
+            TokenType::Ident(_) => {
+                let next_is_call = matches!(tokens.get(i + 1), Some((TokenType::LParen, _)));
+                match resolution.definition_at(span.start) {
+                    Some(def) if def.kind == BindingKind::Parameter => TokenClass::Parameter,
+                    Some(def) if matches!(def.value, Some(Expression::Function(_))) => TokenClass::Function,
+                    _ if next_is_call => TokenClass::Function,
+                    _ => TokenClass::Identifier,
+                }
+            },
+            TokenType::Int(_) | TokenType::Decimal(_) => TokenClass::Number,
+            TokenType::Str(_) | TokenType::Char(_) => TokenClass::String,
+            TokenType::Function | TokenType::Let | TokenType::Mut | TokenType::True |
+            TokenType::False | TokenType::If | TokenType::Else | TokenType::Return |
+            TokenType::Unsafe | TokenType::Zone | TokenType::Yield | TokenType::Defer | TokenType::Loop |
+            TokenType::Break | TokenType::For | TokenType::In | TokenType::Match |
+            TokenType::Switch | TokenType::Case | TokenType::Default | TokenType::Do | TokenType::While |
+            TokenType::Continue | TokenType::Async | TokenType::Await | TokenType::Macro => TokenClass::Keyword,
+            TokenType::Assign | TokenType::Plus | TokenType::Minus | TokenType::Bang |
+            TokenType::Asterisk | TokenType::Slash | TokenType::LT | TokenType::GT |
+            TokenType::Eq | TokenType::NotEq | TokenType::Arrow | TokenType::Pipe | TokenType::And | TokenType::Or |
+            TokenType::DotDot | TokenType::Operator(_) => TokenClass::Operator,
+            _ => continue,
+        };
+        out.push(ClassifiedSpan { span: *span, class });
+    }
+
+    for comment in lexer.comments() {
+        out.push(ClassifiedSpan { span: *comment, class: TokenClass::Comment });
+    }
+    out.sort_by_key(|c| c.span.start);
+    out
+}
@@ -0,0 +1,65 @@
+// Callbacks a host registers to watch a script run: logging, auditing,
+// visualizing. Every method has an empty default, so a hook implements
+// only the events it cares about:
+//
+//   struct Calls(Rc<RefCell<Vec<String>>>);
+//   impl Hooks for Calls {
+//       fn on_call(&mut self, function: &Object, _args: &[Object]) {
+//           self.0.borrow_mut().push(function.type_name().to_string());
+//       }
+//   }
+//   nova.add_hooks(Calls(log.clone()));
+//
+// Hooks only observe; they can't change what the evaluator does. They
+// live in the Runtime, so they see every function call and generator of
+// the interpreter they were added to, but not tasks (those run on other
+// threads with runtimes of their own). Without any hooks registered the
+// evaluator only pays for an emptiness check.
+use std::fmt;
+use crate::ast::Statement;
+use crate::object::Object;
+use crate::span::Span;
+
+pub trait Hooks {
+    // Before each statement runs, blocks and function bodies included
+    fn on_statement(&mut self, _statement: &Statement) {}
+
+    // Before a function, builtin or host function is called
+    fn on_call(&mut self, _function: &Object, _args: &[Object]) {}
+
+    // When an error is raised, at the statement it came from (once, not
+    // again for each statement it unwinds through)
+    fn on_error(&mut self, _message: &str, _span: Span) {}
+
+    // When a name is bound: `let`, parameters, loop variables and
+    // destructured elements
+    fn on_binding(&mut self, _name: &str, _value: &Object) {}
+}
+
+// The hooks registered with a Runtime, called in the order they were added
+#[derive(Default)]
+pub struct HookList {
+    hooks: Vec<Box<dyn Hooks>>,
+}
+
+impl HookList {
+    pub fn add(&mut self, hooks: Box<dyn Hooks>) {
+        self.hooks.push(hooks);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    pub fn each(&mut self, mut event: impl FnMut(&mut dyn Hooks)) {
+        for hooks in &mut self.hooks {
+            event(hooks.as_mut());
+        }
+    }
+}
+
+impl fmt::Debug for HookList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HookList({})", self.hooks.len())
+    }
+}
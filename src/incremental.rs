@@ -0,0 +1,125 @@
+// Incremental reparsing for editors.
+//
+// Given the tree for the old text and a single edit, only the top-level
+// statements touching the edit are lexed and parsed again (plus one
+// neighbour on each side, since an edit can merge or split statements).
+// Everything else is kept as-is with its spans moved, so statement ids
+// outside the edit stay stable. When the reparsed region doesn't parse
+// cleanly, or the text has macros in it, we fall back to a full parse.
+use crate::ast::{Node, Program, Statement};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+// Replace the old chars `start..end` with `new_len` chars
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edit {
+    pub start: usize,
+    pub end: usize,
+    pub new_len: usize,
+}
+
+impl Edit {
+    pub fn delta(&self) -> isize {
+        self.new_len as isize - (self.end - self.start) as isize
+    }
+}
+
+pub struct Reparse {
+    pub program: Program,
+    pub errors: Vec<Diagnostic>,
+    // Top-level statements (indices into the new program) that were
+    // parsed again; None means a full parse happened
+    pub reparsed: Option<std::ops::Range<usize>>,
+}
+
+pub fn full_parse(text: &str) -> Reparse {
+    let mut p = Parser::new(Lexer::new(text.to_string()));
+    let program = p.parse_program();
+    Reparse { program, errors: p.errors, reparsed: None }
+}
+
+// `old` must come from an error-free parse of the text before the edit,
+// `new_text` is the full text after it.
+pub fn reparse(old: &Program, edit: &Edit, new_text: &str) -> Reparse {
+    let stmts = &old.statements;
+    // An edit anywhere can change what a macro call expands to
+    if stmts.is_empty() || !old.macros.is_empty() || new_text.contains("macro") {
+        return full_parse(new_text);
+    }
+
+    // Statements touching the edit, widened by one on each side
+    let first_hit = stmts.iter().position(|s| s.span().end >= edit.start).unwrap_or(stmts.len() - 1);
+    let last_hit = stmts.iter().rposition(|s| s.span().start <= edit.end).unwrap_or(0).max(first_hit);
+    let first = first_hit.saturating_sub(1);
+    let last = (last_hit + 1).min(stmts.len() - 1);
+
+    let delta = edit.delta();
+    let new_chars: Vec<char> = new_text.chars().collect();
+
+    let region_start = if first == 0 { 0 } else { stmts[first].span().start.min(edit.start) };
+    let region_end = if last == stmts.len() - 1 {
+        new_chars.len()
+    } else {
+        let old_end = stmts[last].span().end.max(edit.end);
+        old_end.saturating_add_signed(delta).min(new_chars.len())
+    };
+    if region_start > region_end {
+        return full_parse(new_text);
+    }
+
+    let region: String = new_chars[region_start..region_end].iter().collect();
+    let mut p = Parser::new(Lexer::new(region));
+    p.set_first_id(old.max_id() + 1);
+    let parsed = p.parse_program();
+    if !p.errors.is_empty() {
+        return full_parse(new_text);
+    }
+
+    // Old neighbours that sit entirely outside the edit
+    let untouched = |o: &Statement| o.span().end < edit.start || o.span().start > edit.end;
+    let moved = |o: &Statement| {
+        let mut o = o.clone();
+        if o.span().start > edit.end {
+            o.shift_spans(delta);
+        }
+        o
+    };
+
+    let mut statements: Vec<Statement> = stmts[..first].to_vec();
+    let old_region = &stmts[first..=last];
+    let mut fresh = vec![];
+    for mut stmt in parsed.statements {
+        stmt.shift_spans(region_start as isize);
+        // A neighbour that came back unchanged keeps its old node (and ids)
+        let same = old_region.iter()
+            .filter(|o| untouched(o))
+            .map(moved)
+            .find(|o| o.span() == stmt.span() && o.string() == stmt.string());
+        match same {
+            Some(o) => statements.push(o),
+            None => {
+                fresh.push(statements.len());
+                statements.push(stmt);
+            },
+        }
+    }
+
+    // One statement edited in place keeps its id
+    let touched: Vec<&Statement> = old_region.iter().filter(|o| !untouched(o)).collect();
+    if let ([index], [old_stmt]) = (fresh.as_slice(), touched.as_slice()) {
+        statements[*index].set_id(old_stmt.id());
+    }
+    let reparsed = first..statements.len();
+
+    for stmt in &stmts[last + 1..] {
+        let mut stmt = stmt.clone();
+        stmt.shift_spans(delta);
+        statements.push(stmt);
+    }
+
+    // The pragmas sit above the first statement, so only a region starting
+    // there can have changed them
+    let (pragmas, features) = if first == 0 { (parsed.pragmas, parsed.features) } else { (old.pragmas.clone(), old.features.clone()) };
+    Reparse { program: Program { statements, pragmas, features, macros: vec![] }, errors: vec![], reparsed: Some(reparsed) }
+}
@@ -0,0 +1,271 @@
+// The embedding entry point: one Interpreter owns the global environment
+// and the settings it was made with, and takes source text in.
+//
+//   let mut nova = Interpreter::new();
+//   nova.eval_source("let x = 20;")?;
+//   nova.eval_source("x * 2")?;          // Ok(Integer(40))
+//
+// Bindings persist between calls until reset(). Tools that need the AST
+// parse it themselves and hand it to eval_program(), which runs it under
+// the same guard.
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use crate::ast::Program;
+use crate::cost::{self, Cost};
+use crate::diagnostics::Diagnostic;
+use crate::environment::Environment;
+use crate::evaluator::eval_program;
+use crate::hooks::Hooks;
+use crate::lexer::Lexer;
+use crate::object::{NativeFunction, Object};
+use crate::operators::{Associativity, CustomOperator, Precedence};
+use crate::parser::Parser;
+use crate::runtime::{CancelHandle, Capabilities, Truthiness};
+use crate::typecheck;
+use crate::deadcode;
+use crate::features;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub capabilities: Capabilities,
+    pub memory_limit: Option<usize>,
+    // Reject sources whose type annotations don't check
+    pub type_check: bool,
+    // Unbound names, calls of non-functions and mismatched operands are
+    // errors rather than null (see Runtime::strict)
+    pub strict: bool,
+    // Which values conditions treat as false (see Runtime::truthiness)
+    pub truthiness: Truthiness,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config { capabilities: Capabilities::none(), memory_limit: None, type_check: true, strict: false, truthiness: Truthiness::RUBY }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Io(String),          // eval_file couldn't read the file
+    Parse(Vec<String>),  // Rendered diagnostics ("1:3: error: ...")
+    Type(Vec<String>),
+    Runtime(String),     // An error object reached the top level
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(msg) | Error::Runtime(msg) => write!(f, "{}", msg),
+            Error::Parse(lines) | Error::Type(lines) => write!(f, "{}", lines.join("\n")),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub struct Interpreter {
+    env: Environment,
+    config: Config,
+    // Host functions, bound again after reset()
+    natives: Vec<NativeFunction>,
+    // Parse and type diagnostics from the last eval, warnings included,
+    // and where its runtime error came from if it had one
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter::with_config(Config::default())
+    }
+
+    pub fn with_config(config: Config) -> Self {
+        Interpreter { env: fresh_env(&config), config, natives: vec![], diagnostics: vec![] }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn env(&self) -> &Environment {
+        &self.env
+    }
+
+    pub fn env_mut(&mut self) -> &mut Environment {
+        &mut self.env
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    // Makes a Rust function callable from scripts under its name
+    pub fn register(&mut self, native: NativeFunction) {
+        self.env.set(native.name.clone(), Object::Native(native.clone()));
+        self.natives.retain(|n| n.name != native.name);
+        self.natives.push(native);
+    }
+
+    // For stopping evaluations from another thread: cancel() makes the one
+    // running fail with "cancelled" at its next statement (or the next one
+    // to start, if none is running). The handle stays valid through reset().
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.env.runtime().borrow().cancel.clone()
+    }
+
+    // Registers observers for everything evaluated from now on (see
+    // hooks.rs); they stay through reset()
+    pub fn add_hooks(&mut self, hooks: impl Hooks + 'static) {
+        self.env.runtime().borrow_mut().hooks.add(Box::new(hooks));
+    }
+
+    // Makes `a <symbol> b` mean `function(a, b)` in later sources (see
+    // operators.rs), eval() and templates included. Fails on a symbol that
+    // can't be an operator.
+    pub fn define_operator(&mut self, symbol: &str, precedence: Precedence, associativity: Associativity, function: &str) -> Result<(), String> {
+        self.env.runtime().borrow_mut().operators.define(CustomOperator {
+            symbol: symbol.to_string(),
+            precedence,
+            associativity,
+            function: function.to_string(),
+        })
+    }
+
+    // Never panics: a bug that would is caught and reported as a runtime
+    // error instead of unwinding into the host
+    pub fn eval_source(&mut self, source: &str) -> Result<Object, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("eval_source", bytes = source.len()).entered();
+        self.guard(|nova| nova.eval_unguarded(source))
+    }
+
+    // Runs a program the host parsed and checked itself (the CLI does, to
+    // report diagnostics its own way): none of eval_source's passes, but
+    // the same guard, and a runtime error goes in diagnostics() likewise
+    pub fn eval_program(&mut self, program: &Program) -> Result<Object, Error> {
+        self.eval_with(|env| eval_program(program, env))
+    }
+
+    // Like eval_program, for something else that evaluates in the
+    // environment (the JIT)
+    pub fn eval_with(&mut self, f: impl FnOnce(&mut Environment) -> Object) -> Result<Object, Error> {
+        self.diagnostics.clear();
+        self.guard(|nova| {
+            let result = f(&mut nova.env);
+            nova.finish(result)
+        })
+    }
+
+    // Host code that runs scripts' code some other way (a template, a
+    // benchmark), under the same guard: a panic is an internal error
+    pub fn guarded<R>(&mut self, f: impl FnOnce(&mut Environment) -> R) -> Result<R, Error> {
+        self.guard(|nova| Ok(f(&mut nova.env)))
+    }
+
+    fn guard<R>(&mut self, f: impl FnOnce(&mut Self) -> Result<R, Error>) -> Result<R, Error> {
+        let checkpoint = self.env.runtime().borrow().checkpoint();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(self)));
+        // A cancel stops one evaluation, not every one after it
+        self.cancel_handle().clear();
+        result.unwrap_or_else(|payload| {
+            // Out of every unsafe block, zone and call it panicked in
+            self.env.runtime().borrow_mut().roll_back(checkpoint);
+            let msg = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            #[cfg(feature = "tracing")]
+            tracing::error!(panic = %msg, "internal error");
+            Err(Error::Runtime(format!("internal error: {}", msg)))
+        })
+    }
+
+    fn eval_unguarded(&mut self, source: &str) -> Result<Object, Error> {
+        let operators = self.env.runtime().borrow().operators.clone();
+        let mut p = Parser::with_operators(Lexer::new(source.to_string()), operators);
+        let mut program = p.parse_program();
+        self.diagnostics = p.errors;
+        if self.diagnostics.iter().any(Diagnostic::is_error) {
+            return Err(Error::Parse(render(&self.diagnostics, source)));
+        }
+
+        if self.config.type_check {
+            let type_errors = typecheck::check(&program);
+            if !type_errors.is_empty() {
+                let rendered = render(&type_errors, source);
+                self.diagnostics.extend(type_errors);
+                return Err(Error::Type(rendered));
+            }
+        }
+        self.diagnostics.extend(features::check(&program, |name| self.env.get(name).is_some()));
+        self.diagnostics.extend(deadcode::eliminate(&mut program));
+
+        let result = eval_program(&program, &mut self.env);
+        self.finish(result)
+    }
+
+    // An error that reached the top level, at the statement it came from
+    // and with what its call had bound
+    fn finish(&mut self, result: Object) -> Result<Object, Error> {
+        let Object::Error(msg) = result else { return Ok(result) };
+        let runtime = self.env.runtime().borrow();
+        let mut diagnostic = Diagnostic::error(msg.clone(), runtime.error_span.unwrap_or_default());
+        if let Some(frame) = &runtime.error_frame {
+            diagnostic = diagnostic.with_note(frame.to_string());
+        }
+        drop(runtime);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(error = %msg, notes = ?diagnostic.notes, "runtime error");
+        self.diagnostics.push(diagnostic);
+        Err(Error::Runtime(msg))
+    }
+
+    // What running the source would roughly cost (see cost.rs), without
+    // running it
+    pub fn estimate(&self, source: &str) -> Result<Cost, Error> {
+        let operators = self.env.runtime().borrow().operators.clone();
+        let mut p = Parser::with_operators(Lexer::new(source.to_string()), operators);
+        let program = p.parse_program();
+        if p.errors.iter().any(Diagnostic::is_error) {
+            return Err(Error::Parse(render(&p.errors, source)));
+        }
+        Ok(cost::estimate(&program))
+    }
+
+    pub fn eval_file(&mut self, path: &str) -> Result<Object, Error> {
+        let source = std::fs::read_to_string(path).map_err(|e| Error::Io(format!("{}: {}", path, e)))?;
+        self.eval_source(&source)
+    }
+
+    // Forgets every binding except registered host functions; hooks and
+    // operators stay too
+    pub fn reset(&mut self) {
+        let hooks = std::mem::take(&mut self.env.runtime().borrow_mut().hooks);
+        let operators = std::mem::take(&mut self.env.runtime().borrow_mut().operators);
+        let cancel = self.cancel_handle();
+        self.env = fresh_env(&self.config);
+        self.env.runtime().borrow_mut().hooks = hooks;
+        self.env.runtime().borrow_mut().operators = operators;
+        self.env.runtime().borrow_mut().cancel = cancel;
+        self.diagnostics.clear();
+        for native in &self.natives {
+            self.env.set(native.name.clone(), Object::Native(native.clone()));
+        }
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn fresh_env(config: &Config) -> Environment {
+    let mut env = Environment::with_capabilities(config.capabilities);
+    env.set_memory_limit(config.memory_limit);
+    env.runtime().borrow_mut().strict = config.strict;
+    env.runtime().borrow_mut().truthiness = config.truthiness;
+    env
+}
+
+fn render(diagnostics: &[Diagnostic], source: &str) -> Vec<String> {
+    diagnostics.iter().map(|d| d.render(source)).collect()
+}
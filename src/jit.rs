@@ -0,0 +1,582 @@
+// Cranelift JIT for integer/boolean functions (`nova run --jit`, needs the
+// `jit` cargo feature).
+//
+// Only top-level `let f = fn(...) { ... }` bindings are considered, and only
+// when the whole body stays inside a small typed subset: int parameters,
+// int/bool locals, arithmetic, comparisons, if/else and return. Anything
+// else (strings, calls, globals, if-without-else used as a value) leaves
+// the function to the interpreter. Calls stay interpreted for now: the
+// callee is looked up by name when the call runs, and a later `let` can
+// rebind it.
+//
+// A compiled function is still an ordinary Function value (keyword calls,
+// spawn, printing and source() see no difference); the machine code is a
+// fast path its calls try first. Compiled code reports trouble through a
+// status word instead of trapping: on overflow or division by zero it
+// bails out and the call goes on in the interpreter, in the caller's
+// environment, so results and errors always match the tree-walker.
+use std::collections::HashMap;
+use std::rc::Rc;
+use cranelift_codegen::ir::{self, AbiParam, InstBuilder, MemFlagsData, UserFuncName, condcodes::IntCC, types};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{FuncId, Linkage, Module, default_libcall_names};
+use crate::ast::{Program, Statement, Expression, BlockStatement, FunctionLiteral};
+use crate::environment::Environment;
+use crate::evaluator::eval_statement;
+use crate::object::{Compiled, Object, Function};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Ty {
+    Int,
+    Bool,
+    Never, // Control left through `return`
+}
+
+fn unify(a: Ty, b: Ty) -> Option<Ty> {
+    match (a, b) {
+        (Ty::Never, t) | (t, Ty::Never) => Some(t),
+        (a, b) if a == b => Some(a),
+        _ => None,
+    }
+}
+
+// Runs a program with the interpreter, giving the functions it could
+// compile their machine code as they're bound.
+pub fn run(program: &Program, env: &mut Environment) -> Object {
+    let compiled = compile(program);
+    let mut result = Object::Null;
+    for stmt in &program.statements {
+        result = eval_statement(stmt, env);
+        if let Statement::Let(s) = stmt
+            && let Some(code) = compiled.get(&s.name.value)
+            && let Object::Function(function) = &result
+        {
+            let function = Function { compiled: Some(code.clone()), ..(**function).clone() };
+            result = env.set(s.name.value.clone(), Object::Function(Rc::new(function)));
+        }
+        if let Object::ReturnValue(val) = result {
+            return *val;
+        }
+    }
+    result
+}
+
+// Compiles every eligible top-level function; the map is keyed by name
+pub fn compile(program: &Program) -> HashMap<String, Compiled> {
+    let candidates = find_candidates(program);
+    let signatures = infer_signatures(&candidates);
+    if signatures.is_empty() {
+        return HashMap::new();
+    }
+    // An unsupported host just means everything stays interpreted
+    Codegen::build(&candidates, &signatures).unwrap_or_default()
+}
+
+// --- CANDIDATES ---
+
+// Top-level functions bound exactly once, so the name always means this
+// body (async ones hand back futures, which native code can't)
+fn find_candidates(program: &Program) -> HashMap<String, FunctionLiteral> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    count_lets(&program.statements, &mut counts);
+
+    let mut out = HashMap::new();
+    for stmt in &program.statements {
+        if let Statement::Let(s) = stmt
+            && let Expression::Function(fl) = &s.value
+            && !fl.is_async
+            && counts.get(s.name.value.as_str()) == Some(&1)
+        {
+            out.insert(s.name.value.clone(), fl.clone());
+        }
+    }
+    out
+}
+
+fn count_lets<'a>(statements: &'a [Statement], counts: &mut HashMap<&'a str, usize>) {
+    for stmt in statements {
+        match stmt {
+            Statement::Let(s) => *counts.entry(s.name.value.as_str()).or_insert(0) += 1,
+            Statement::LetTuple(s) => {
+                for name in &s.names {
+                    *counts.entry(name.value.as_str()).or_insert(0) += 1;
+                }
+            },
+            Statement::Expression(s) => match &s.expression {
+                Expression::If(ie) => {
+                    count_lets(&ie.consequence.statements, counts);
+                    if let Some(alt) = &ie.alternative {
+                        count_lets(&alt.statements, counts);
+                    }
+                },
+                Expression::Unsafe(u) => count_lets(&u.body.statements, counts),
+                _ => {},
+            },
+            Statement::Return(_) | Statement::Yield(_) | Statement::Defer(_) | Statement::Break(_) | Statement::Continue(_) => {},
+        }
+    }
+}
+
+// --- TYPE INFERENCE ---
+
+// Return types of the functions we can compile
+fn infer_signatures(candidates: &HashMap<String, FunctionLiteral>) -> HashMap<String, Ty> {
+    let mut sigs = HashMap::new();
+    for (name, fl) in candidates {
+        let mut checker = TypeCheck { locals: HashMap::new(), ret: Ty::Never };
+        if let Some(ty) = checker.function(fl) {
+            sigs.insert(name.clone(), ty);
+        }
+    }
+    sigs
+}
+
+struct TypeCheck {
+    locals: HashMap<String, Ty>,
+    ret: Ty, // Joined type of the `return` statements seen so far
+}
+
+impl TypeCheck {
+    fn function(&mut self, fl: &FunctionLiteral) -> Option<Ty> {
+        for p in &fl.parameters {
+            self.locals.insert(p.value.clone(), Ty::Int);
+        }
+        let body = self.block(&fl.body, true)?;
+        match unify(body, self.ret)? {
+            Ty::Never => None,
+            ty => Some(ty),
+        }
+    }
+
+    fn block(&mut self, block: &BlockStatement, needs_value: bool) -> Option<Ty> {
+        if needs_value && block.statements.is_empty() {
+            return None; // Evaluates to null
+        }
+        let mut ty = Ty::Never;
+        for (i, stmt) in block.statements.iter().enumerate() {
+            let last = i + 1 == block.statements.len();
+            ty = self.statement(stmt, needs_value && last)?;
+            if ty == Ty::Never {
+                return Some(Ty::Never);
+            }
+        }
+        Some(ty)
+    }
+
+    fn statement(&mut self, stmt: &Statement, needs_value: bool) -> Option<Ty> {
+        match stmt {
+            Statement::Let(s) => {
+                let ty = self.expression(&s.value, true)?;
+                if ty == Ty::Never {
+                    return None;
+                }
+                match self.locals.get(&s.name.value) {
+                    Some(existing) if *existing != ty => None,
+                    _ => {
+                        self.locals.insert(s.name.value.clone(), ty);
+                        Some(ty)
+                    },
+                }
+            },
+            Statement::LetTuple(_) | Statement::Yield(_) | Statement::Defer(_) | Statement::Break(_) | Statement::Continue(_) => None,
+            Statement::Return(s) => {
+                let ty = self.expression(&s.return_value, true)?;
+                self.ret = unify(self.ret, ty)?;
+                Some(Ty::Never)
+            },
+            Statement::Expression(s) => self.expression(&s.expression, needs_value),
+        }
+    }
+
+    fn expression(&mut self, exp: &Expression, needs_value: bool) -> Option<Ty> {
+        match exp {
+            Expression::IntegerLiteral(_) => Some(Ty::Int),
+            Expression::Boolean(_) => Some(Ty::Bool),
+            Expression::Identifier(i) => self.locals.get(&i.value).copied(),
+            Expression::Prefix(p) => {
+                let right = self.expression(&p.right, true)?;
+                match (p.operator.as_str(), right) {
+                    ("-", Ty::Int) => Some(Ty::Int),
+                    ("!", Ty::Bool) => Some(Ty::Bool),
+                    _ => None,
+                }
+            },
+            Expression::Infix(i) => {
+                let left = self.expression(&i.left, true)?;
+                let right = self.expression(&i.right, true)?;
+                match (i.operator.as_str(), left, right) {
+                    ("+" | "-" | "*" | "/", Ty::Int, Ty::Int) => Some(Ty::Int),
+                    ("<" | ">", Ty::Int, Ty::Int) => Some(Ty::Bool),
+                    ("==" | "!=", l, r) if l == r && l != Ty::Never => Some(Ty::Bool),
+                    ("&&" | "||", Ty::Bool, Ty::Bool) => Some(Ty::Bool),
+                    _ => None,
+                }
+            },
+            Expression::If(ie) => {
+                if self.expression(&ie.condition, true)? != Ty::Bool {
+                    return None;
+                }
+                // A let inside a branch may never run, so it's not visible after
+                let outer = self.locals.clone();
+                let then = self.block(&ie.consequence, needs_value);
+                self.locals = outer.clone();
+                let ty = match &ie.alternative {
+                    Some(alt) => {
+                        let other = self.block(alt, needs_value);
+                        if needs_value { unify(then?, other?) } else { then.and(other).map(|_| Ty::Int) }
+                    },
+                    None if needs_value => None,
+                    None => then.map(|_| Ty::Int), // Value is discarded
+                };
+                self.locals = outer;
+                ty
+            },
+            Expression::StringLiteral(_) | Expression::CharLiteral(_) | Expression::DecimalLiteral(_) |
+            Expression::Function(_) | Expression::Call(_) |
+            Expression::Array(_) | Expression::Tuple(_) | Expression::Hash(_) | Expression::Index(_) |
+            Expression::Slice(_) | Expression::Unsafe(_) | Expression::Zone(_) | Expression::Loop(_) |
+            Expression::For(_) | Expression::Match(_) | Expression::Switch(_) | Expression::DoWhile(_) | Expression::Comparison(_) | Expression::Await(_) => None,
+        }
+    }
+}
+
+// --- CODE GENERATION ---
+
+// Each function `f` becomes a native-ABI function taking its int arguments
+// plus a status pointer, and an `f$entry` trampoline taking a pointer to
+// the argument array, which is what the host calls.
+struct Codegen<'a> {
+    module: JITModule,
+    ids: HashMap<String, FuncId>,
+    arities: HashMap<String, usize>,
+    candidates: &'a HashMap<String, FunctionLiteral>,
+}
+
+type EntryFn = unsafe extern "C" fn(*const i64, *mut i64) -> i64;
+
+impl<'a> Codegen<'a> {
+    fn build(
+        candidates: &'a HashMap<String, FunctionLiteral>,
+        sigs: &HashMap<String, Ty>,
+    ) -> Result<HashMap<String, Compiled>, String> {
+        let mut flags = settings::builder();
+        flags.set("use_colocated_libcalls", "false").map_err(|e| e.to_string())?;
+        flags.set("is_pic", "false").map_err(|e| e.to_string())?;
+        let isa = cranelift_native::builder()?
+            .finish(settings::Flags::new(flags))
+            .map_err(|e| e.to_string())?;
+        let module = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+
+        let mut cg = Codegen { module, ids: HashMap::new(), arities: HashMap::new(), candidates };
+        let mut entries = HashMap::new();
+
+        let mut names: Vec<&String> = sigs.keys().collect();
+        names.sort();
+        for name in &names {
+            let arity = candidates[*name].parameters.len();
+            let sig = cg.native_signature(arity);
+            let id = cg.module.declare_function(name, Linkage::Local, &sig).map_err(|e| e.to_string())?;
+            cg.ids.insert((*name).clone(), id);
+            cg.arities.insert((*name).clone(), arity);
+        }
+        for name in &names {
+            cg.define(name)?;
+            entries.insert((*name).clone(), cg.define_entry(name)?);
+        }
+        cg.module.finalize_definitions().map_err(|e| e.to_string())?;
+
+        let module = Rc::new(cg.module);
+        let mut compiled = HashMap::new();
+        for name in names {
+            let code = module.get_finalized_function(entries[name]);
+            // SAFETY: the trampoline was generated with exactly this signature
+            let entry: EntryFn = unsafe { std::mem::transmute::<*const u8, EntryFn>(code) };
+            let arity = candidates[name].parameters.len();
+            let ret = sigs[name];
+            let keep_alive = Rc::clone(&module);
+
+            let code = Compiled(Rc::new(move |args: &[Object]| {
+                let _ = &keep_alive; // Code lives as long as the module
+                let ints: Vec<i64> = args.iter()
+                    .map(|a| if let Object::Integer(i) = a { Some(*i) } else { None })
+                    .collect::<Option<_>>()
+                    .filter(|v: &Vec<i64>| v.len() == arity)?;
+                let mut status = 0i64;
+                // SAFETY: `ints` holds `arity` values, status is a valid i64
+                let value = unsafe { entry(ints.as_ptr(), &mut status) };
+                if status != 0 {
+                    return None;
+                }
+                Some(match ret {
+                    Ty::Bool => Object::Boolean(value != 0),
+                    _ => Object::Integer(value),
+                })
+            }));
+            compiled.insert(name.clone(), code);
+        }
+        Ok(compiled)
+    }
+
+    fn native_signature(&self, arity: usize) -> ir::Signature {
+        let ptr = self.module.target_config().pointer_type();
+        let mut sig = self.module.make_signature();
+        for _ in 0..arity {
+            sig.params.push(AbiParam::new(types::I64));
+        }
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+        sig
+    }
+
+    fn define(&mut self, name: &str) -> Result<(), String> {
+        let id = self.ids[name];
+        let fl = &self.candidates[name];
+        let mut ctx = self.module.make_context();
+        ctx.func.signature = self.native_signature(fl.parameters.len());
+        ctx.func.name = UserFuncName::user(0, id.as_u32());
+
+        let mut fbc = FunctionBuilderContext::new();
+        {
+            let mut b = FunctionBuilder::new(&mut ctx.func, &mut fbc);
+            let entry = b.create_block();
+            b.append_block_params_for_function_params(entry);
+            b.switch_to_block(entry);
+
+            let params = b.block_params(entry).to_vec();
+            let status = params[fl.parameters.len()];
+
+            // Shared exit for overflow / division by zero
+            let bail = b.create_block();
+
+            let mut lower = Lower { b, vars: HashMap::new(), bail };
+            for (p, value) in fl.parameters.iter().zip(params) {
+                let var = lower.var(&p.value);
+                lower.b.def_var(var, value);
+            }
+            if let Some(value) = lower.block(&fl.body) {
+                lower.b.ins().return_(&[value]);
+            }
+
+            lower.b.switch_to_block(bail);
+            let one = lower.b.ins().iconst(types::I64, 1);
+            lower.b.ins().store(MemFlagsData::new(), one, status, 0);
+            let zero = lower.b.ins().iconst(types::I64, 0);
+            lower.b.ins().return_(&[zero]);
+
+            lower.b.seal_all_blocks();
+            lower.b.finalize(self.module.target_config());
+        }
+        self.module.define_function(id, &mut ctx).map_err(|e| e.to_string())?;
+        self.module.clear_context(&mut ctx);
+        Ok(())
+    }
+
+    fn define_entry(&mut self, name: &str) -> Result<FuncId, String> {
+        let ptr = self.module.target_config().pointer_type();
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+
+        let id = self.module
+            .declare_function(&format!("{}$entry", name), Linkage::Local, &sig)
+            .map_err(|e| e.to_string())?;
+        let mut ctx = self.module.make_context();
+        ctx.func.signature = sig;
+        ctx.func.name = UserFuncName::user(0, id.as_u32());
+
+        let mut fbc = FunctionBuilderContext::new();
+        {
+            let mut b = FunctionBuilder::new(&mut ctx.func, &mut fbc);
+            let block = b.create_block();
+            b.append_block_params_for_function_params(block);
+            b.switch_to_block(block);
+            let (args, status) = (b.block_params(block)[0], b.block_params(block)[1]);
+
+            let mut call_args = vec![];
+            for i in 0..self.arities[name] {
+                call_args.push(b.ins().load(types::I64, MemFlagsData::trusted(), args, (i * 8) as i32));
+            }
+            call_args.push(status);
+            let callee = self.module.declare_func_in_func(self.ids[name], b.func);
+            let call = b.ins().call(callee, &call_args);
+            let result = b.inst_results(call)[0];
+            b.ins().return_(&[result]);
+            b.seal_all_blocks();
+            b.finalize(self.module.target_config());
+        }
+        self.module.define_function(id, &mut ctx).map_err(|e| e.to_string())?;
+        self.module.clear_context(&mut ctx);
+        Ok(id)
+    }
+}
+
+struct Lower<'a> {
+    b: FunctionBuilder<'a>,
+    vars: HashMap<String, Variable>,
+    bail: ir::Block,
+}
+
+impl Lower<'_> {
+    fn var(&mut self, name: &str) -> Variable {
+        if let Some(v) = self.vars.get(name) {
+            return *v;
+        }
+        let v = self.b.declare_var(types::I64);
+        self.vars.insert(name.to_string(), v);
+        v
+    }
+
+    // None means control already left the function (return)
+    fn block(&mut self, block: &BlockStatement) -> Option<ir::Value> {
+        let mut last = None;
+        for stmt in &block.statements {
+            last = Some(self.statement(stmt)?);
+        }
+        // Empty blocks only appear where the value is discarded
+        Some(last.unwrap_or_else(|| self.b.ins().iconst(types::I64, 0)))
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> Option<ir::Value> {
+        match stmt {
+            Statement::Let(s) => {
+                let value = self.expression(&s.value)?;
+                let var = self.var(&s.name.value);
+                self.b.def_var(var, value);
+                Some(value)
+            },
+            Statement::LetTuple(_) | Statement::Yield(_) | Statement::Defer(_) | Statement::Break(_) | Statement::Continue(_) => unreachable!("checked by TypeCheck"),
+            Statement::Return(s) => {
+                let value = self.expression(&s.return_value)?;
+                self.b.ins().return_(&[value]);
+                None
+            },
+            Statement::Expression(s) => self.expression(&s.expression),
+        }
+    }
+
+    fn bail_if(&mut self, cond: ir::Value) {
+        let cont = self.b.create_block();
+        self.b.ins().brif(cond, self.bail, &[], cont, &[]);
+        self.b.switch_to_block(cont);
+    }
+
+    fn bool_value(&mut self, cmp: ir::Value) -> ir::Value {
+        self.b.ins().uextend(types::I64, cmp)
+    }
+
+    fn expression(&mut self, exp: &Expression) -> Option<ir::Value> {
+        let ins_value = match exp {
+            Expression::IntegerLiteral(i) => self.b.ins().iconst(types::I64, i.value),
+            Expression::Boolean(bl) => self.b.ins().iconst(types::I64, bl.value as i64),
+            Expression::Identifier(i) => {
+                let var = self.var(&i.value);
+                self.b.use_var(var)
+            },
+            Expression::Prefix(p) => {
+                let right = self.expression(&p.right)?;
+                if p.operator == "-" {
+                    let zero = self.b.ins().iconst(types::I64, 0);
+                    let (value, overflow) = self.b.ins().ssub_overflow(zero, right);
+                    self.bail_if(overflow);
+                    value
+                } else {
+                    self.b.ins().bxor_imm_u(right, 1)
+                }
+            },
+            Expression::Infix(i) if i.operator == "&&" || i.operator == "||" => {
+                // The right side only runs when the left doesn't decide
+                let l = self.expression(&i.left)?;
+                let right_block = self.b.create_block();
+                let merge = self.b.create_block();
+                self.b.append_block_param(merge, types::I64);
+                if i.operator == "&&" {
+                    self.b.ins().brif(l, right_block, &[], merge, &[ir::BlockArg::Value(l)]);
+                } else {
+                    self.b.ins().brif(l, merge, &[ir::BlockArg::Value(l)], right_block, &[]);
+                }
+                self.b.switch_to_block(right_block);
+                let r = self.expression(&i.right)?;
+                self.b.ins().jump(merge, &[ir::BlockArg::Value(r)]);
+                self.b.switch_to_block(merge);
+                self.b.block_params(merge)[0]
+            },
+            Expression::Infix(i) => {
+                let l = self.expression(&i.left)?;
+                let r = self.expression(&i.right)?;
+                match i.operator.as_str() {
+                    "+" | "-" | "*" => {
+                        let (value, overflow) = match i.operator.as_str() {
+                            "+" => self.b.ins().sadd_overflow(l, r),
+                            "-" => self.b.ins().ssub_overflow(l, r),
+                            _ => self.b.ins().smul_overflow(l, r),
+                        };
+                        self.bail_if(overflow);
+                        value
+                    },
+                    "/" => {
+                        let zero = self.b.ins().icmp_imm_s(IntCC::Equal, r, 0);
+                        self.bail_if(zero);
+                        let min = self.b.ins().icmp_imm_s(IntCC::Equal, l, i64::MIN);
+                        let neg_one = self.b.ins().icmp_imm_s(IntCC::Equal, r, -1);
+                        let overflow = self.b.ins().band(min, neg_one);
+                        self.bail_if(overflow);
+                        self.b.ins().sdiv(l, r)
+                    },
+                    op => {
+                        let cc = match op {
+                            "<" => IntCC::SignedLessThan,
+                            ">" => IntCC::SignedGreaterThan,
+                            "==" => IntCC::Equal,
+                            _ => IntCC::NotEqual,
+                        };
+                        let cmp = self.b.ins().icmp(cc, l, r);
+                        self.bool_value(cmp)
+                    },
+                }
+            },
+            Expression::If(ie) => {
+                let cond = self.expression(&ie.condition)?;
+                let then_block = self.b.create_block();
+                let else_block = self.b.create_block();
+                let merge = self.b.create_block();
+                self.b.append_block_param(merge, types::I64);
+                self.b.ins().brif(cond, then_block, &[], else_block, &[]);
+
+                self.b.switch_to_block(then_block);
+                let then_value = self.block(&ie.consequence);
+                if let Some(v) = then_value {
+                    self.b.ins().jump(merge, &[ir::BlockArg::Value(v)]);
+                }
+
+                self.b.switch_to_block(else_block);
+                let else_value = match &ie.alternative {
+                    Some(alt) => self.block(alt),
+                    None => Some(self.b.ins().iconst(types::I64, 0)),
+                };
+                if let Some(v) = else_value {
+                    self.b.ins().jump(merge, &[ir::BlockArg::Value(v)]);
+                }
+
+                self.b.switch_to_block(merge);
+                if then_value.is_none() && else_value.is_none() {
+                    // Both branches returned; nothing can reach the merge block
+                    let zero = self.b.ins().iconst(types::I64, 0);
+                    self.b.ins().return_(&[zero]);
+                    return None;
+                }
+                self.b.block_params(merge)[0]
+            },
+            Expression::StringLiteral(_) | Expression::CharLiteral(_) | Expression::DecimalLiteral(_) |
+            Expression::Function(_) | Expression::Call(_) |
+            Expression::Array(_) | Expression::Tuple(_) | Expression::Hash(_) | Expression::Index(_) |
+            Expression::Slice(_) | Expression::Unsafe(_) | Expression::Zone(_) | Expression::Loop(_) |
+            Expression::For(_) | Expression::Match(_) | Expression::Switch(_) | Expression::DoWhile(_) | Expression::Comparison(_) | Expression::Await(_) => {
+                unreachable!("checked by TypeCheck")
+            },
+        };
+        Some(ins_value)
+    }
+}
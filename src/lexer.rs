@@ -1,10 +1,24 @@
 use crate::token::{TokenType, lookup_ident};
+use crate::span::Span;
+use crate::diagnostics::Diagnostic;
+use crate::decimal::Decimal;
+use crate::numbers;
 
 pub struct Lexer {
     input: Vec<char>,
     position: usize,
     read_position: usize,
     ch: char,
+    comments: Vec<Span>,
+    // `///` lines waiting for the token they document
+    pending_doc: Vec<String>,
+    docs: Vec<(usize, String)>,
+    // `#pragma name` lines, which the parser checks and puts on the Program
+    pragmas: Vec<(String, Span)>,
+    // Malformed tokens; the parser moves these into its own errors
+    pub(crate) errors: Vec<Diagnostic>,
+    // Custom operator symbols, longest first
+    operators: Vec<Vec<char>>,
 }
 
 impl Lexer {
@@ -14,11 +28,29 @@ impl Lexer {
             position: 0,
             read_position: 0,
             ch: '\0',
+            comments: vec![],
+            pending_doc: vec![],
+            docs: vec![],
+            pragmas: vec![],
+            errors: vec![],
+            operators: vec![],
         };
         l.read_char();
+        l.skip_shebang();
         l
     }
 
+    // `#!/usr/bin/env nova` on the first line is for the OS, not us. The
+    // newline stays so line numbers don't shift.
+    fn skip_shebang(&mut self) {
+        if self.ch == '#' && self.peek_char() == '!' {
+            while self.ch != '\n' && self.ch != '\0' {
+                self.read_char();
+            }
+            self.comments.push(Span::new(0, self.position.min(self.input.len())));
+        }
+    }
+
     fn read_char(&mut self) {
         if self.read_position >= self.input.len() {
             self.ch = '\0';
@@ -38,8 +70,36 @@ impl Lexer {
     }
 
     pub fn next_token(&mut self) -> TokenType {
+        self.next_spanned().0
+    }
+
+    // Same as next_token, plus where the token sits in the input
+    pub fn next_spanned(&mut self) -> (TokenType, Span) {
         self.skip_whitespace();
+        let start = self.position.min(self.input.len());
+        if !self.pending_doc.is_empty() {
+            self.docs.push((start, self.pending_doc.join("\n")));
+            self.pending_doc.clear();
+        }
+        let tok = self.read_token();
+        let end = self.position.min(self.input.len());
+        (tok, Span::new(start, end))
+    }
 
+    // Symbols to lex as TokenType::Operator, ahead of the built-in ones
+    pub fn set_operators(&mut self, symbols: &[String]) {
+        self.operators = symbols.iter().map(|s| s.chars().collect()).collect();
+    }
+
+    fn read_token(&mut self) -> TokenType {
+        let at = self.position.min(self.input.len());
+        if let Some(symbol) = self.operators.iter().find(|s| self.input[at..].starts_with(s)) {
+            let symbol: String = symbol.iter().collect();
+            for _ in 0..symbol.chars().count() {
+                self.read_char();
+            }
+            return TokenType::Operator(symbol);
+        }
         let tok = match self.ch {
             // NEW: Handle == and =
             '=' => {
@@ -73,21 +133,52 @@ impl Lexer {
                 }
             },
             '/' => TokenType::Slash,
+            '"' => TokenType::Str(self.read_string()),
+            '\'' => TokenType::Char(self.read_char_literal()),
             '*' => TokenType::Asterisk,
             ',' => TokenType::Comma,
+            ':' => TokenType::Colon,
             ';' => TokenType::Semicolon,
             '(' => TokenType::LParen,
             ')' => TokenType::RParen,
             '{' => TokenType::LBrace,
             '}' => TokenType::RBrace,
+            '[' => TokenType::LBracket,
+            ']' => TokenType::RBracket,
+            '|' if self.peek_char() == '|' => {
+                self.read_char();
+                TokenType::Or
+            },
+            '|' => TokenType::Pipe,
+            '&' if self.peek_char() == '&' => {
+                self.read_char();
+                TokenType::And
+            },
+            '.' if self.peek_char() == '.' => {
+                self.read_char();
+                TokenType::DotDot
+            },
             '\0' => TokenType::EOF,
+            'r' if self.peek_char() == '"' => {
+                self.read_char();
+                TokenType::Str(self.read_raw_string())
+            },
             _ => {
-                if is_letter(self.ch) {
+                if is_ident_start(self.ch) {
                     let literal = self.read_identifier();
                     return lookup_ident(&literal);
                 } else if is_digit(self.ch) {
+                    let start = self.position;
                     let literal = self.read_number();
-                    return TokenType::Int(literal.parse().unwrap());
+                    let suffix = self.ch == 'd' && !is_ident_continue(self.peek_char());
+                    if suffix || (self.ch == '.' && is_digit(self.peek_char())) {
+                        return self.read_decimal(start, literal);
+                    }
+                    return TokenType::Int(numbers::parse_int(&literal).unwrap_or_else(|| {
+                        let span = Span::new(start, self.position.min(self.input.len()));
+                        self.errors.push(Diagnostic::error(format!("Integer literal {} is too large", literal), span));
+                        0
+                    }));
                 } else {
                     TokenType::Illegal
                 }
@@ -98,9 +189,10 @@ impl Lexer {
         tok
     }
 
+    // Digits are fine after the first character (`_1`, `utf8`)
     fn read_identifier(&mut self) -> String {
         let position = self.position;
-        while is_letter(self.ch) {
+        while is_ident_continue(self.ch) {
             self.read_char();
         }
         self.input[position..self.position].iter().collect()
@@ -114,17 +206,202 @@ impl Lexer {
         self.input[position..self.position].iter().collect()
     }
 
-    fn skip_whitespace(&mut self) {
-        while self.ch == ' ' || self.ch == '\t' || self.ch == '\n' || self.ch == '\r' {
+    // The rest of `1.50d` after its whole part. Without the `d` it's an
+    // error, not a float: Nova doesn't have those.
+    fn read_decimal(&mut self, start: usize, whole: String) -> TokenType {
+        let mut literal = whole;
+        if self.ch == '.' {
+            self.read_char();
+            literal.push('.');
+            literal.push_str(&self.read_number());
+        }
+        let suffixed = self.ch == 'd';
+        if suffixed {
+            self.read_char();
+        }
+        let span = Span::new(start, self.position.min(self.input.len()));
+        match numbers::parse_decimal(&literal) {
+            Some(value) if suffixed => return TokenType::Decimal(value),
+            Some(_) => {
+                self.errors.push(Diagnostic::error(format!("Nova has no floats; write {}d for a decimal", literal), span));
+            },
+            None => self.errors.push(Diagnostic::error(format!("Decimal literal {}d is out of range", literal), span)),
+        }
+        TokenType::Decimal(Decimal::from(0))
+    }
+
+    // Leaves the lexer on the closing quote (or EOF if unterminated).
+    // Escapes are the ones Rust's `{:?}` writes, so strings print back in
+    // a form that reads back in: \n \t \r \0 \" \\ and \u{hex}.
+    fn read_string(&mut self) -> String {
+        let open = self.position;
+        let mut out = String::new();
+        loop {
+            self.read_char();
+            match self.ch {
+                '"' => break,
+                '\0' if self.position >= self.input.len() => {
+                    self.errors.push(Diagnostic::error("Unterminated string", Span::new(open, self.input.len())));
+                    break;
+                },
+                '\\' if self.read_position >= self.input.len() => continue,
+                '\\' => {
+                    let start = self.position;
+                    self.read_char();
+                    match self.read_escape() {
+                        Ok(ch) => out.push(ch),
+                        Err(msg) => {
+                            let end = (self.position + 1).min(self.input.len());
+                            self.errors.push(Diagnostic::error(msg, Span::new(start, end)));
+                        },
+                    }
+                },
+                ch => out.push(ch),
+            }
+        }
+        out
+    }
+
+    // The character after a backslash (the lexer is on it)
+    fn read_escape(&mut self) -> Result<char, String> {
+        match self.ch {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '"' => Ok('"'),
+            '\\' => Ok('\\'),
+            '\'' => Ok('\''),
+            'u' if self.peek_char() == '{' => {
+                self.read_char();
+                let mut hex = String::new();
+                while self.peek_char().is_ascii_hexdigit() && hex.len() < 6 {
+                    self.read_char();
+                    hex.push(self.ch);
+                }
+                if self.peek_char() != '}' {
+                    return Err("Invalid unicode escape: expected \\u{...} with 1 to 6 hex digits".to_string());
+                }
+                self.read_char();
+                u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
+                    .ok_or_else(|| format!("Invalid unicode escape: {:?} is not a character", hex))
+            },
+            ch => Err(format!("Unknown escape sequence \\{}", ch)),
+        }
+    }
+
+    // `'a'`, `'\n'`, `'\u{e9}'`. Leaves the lexer on the closing quote;
+    // a malformed literal is reported and lexes as '\0'.
+    fn read_char_literal(&mut self) -> char {
+        let open = self.position;
+        self.read_char();
+        let value = match self.ch {
+            '\'' => Err("Empty character literal".to_string()),
+            '\\' => {
+                self.read_char();
+                self.read_escape()
+            },
+            '\n' | '\0' => Err("Unterminated character literal".to_string()),
+            ch => Ok(ch),
+        };
+        if value.is_ok() {
+            if self.peek_char() == '\'' {
+                self.read_char();
+            } else {
+                let end = self.read_position.min(self.input.len());
+                self.errors.push(Diagnostic::error("Unterminated character literal", Span::new(open, end)));
+                return '\0';
+            }
+        }
+        value.unwrap_or_else(|msg| {
+            let end = self.read_position.min(self.input.len());
+            self.errors.push(Diagnostic::error(msg, Span::new(open, end)));
+            '\0'
+        })
+    }
+
+    // `r"C:\dir"`: everything up to the next quote, backslashes included
+    fn read_raw_string(&mut self) -> String {
+        let open = self.position - 1;
+        let position = self.position + 1;
+        loop {
             self.read_char();
+            if self.ch == '"' {
+                break;
+            }
+            if self.ch == '\0' && self.position >= self.input.len() {
+                self.errors.push(Diagnostic::error("Unterminated string", Span::new(open, self.input.len())));
+                break;
+            }
+        }
+        self.input[position..self.position.min(self.input.len())].iter().collect()
+    }
+
+    // Spans of the `//` comments skipped so far
+    pub fn comments(&self) -> &[Span] {
+        &self.comments
+    }
+
+    // The `#pragma` lines skipped so far: the name after `#pragma`, and
+    // where the line is
+    pub fn pragmas(&self) -> &[(String, Span)] {
+        &self.pragmas
+    }
+
+    // Text of the `///` comments directly above the token starting at offset
+    pub fn doc_at(&self, offset: usize) -> Option<&str> {
+        self.docs.iter().find(|(start, _)| *start == offset).map(|(_, doc)| doc.as_str())
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            while self.ch == ' ' || self.ch == '\t' || self.ch == '\n' || self.ch == '\r' {
+                self.read_char();
+            }
+            if self.ch == '/' && self.peek_char() == '/' {
+                let start = self.position;
+                while self.ch != '\n' && self.ch != '\0' {
+                    self.read_char();
+                }
+                let end = self.position.min(self.input.len());
+                self.comments.push(Span::new(start, end));
+                let text: String = self.input[start..end].iter().collect();
+                match text.strip_prefix("///") {
+                    Some(doc) if !doc.starts_with('/') => {
+                        self.pending_doc.push(doc.strip_prefix(' ').unwrap_or(doc).trim_end().to_string());
+                    },
+                    // A plain comment in between detaches the doc above it
+                    _ => self.pending_doc.clear(),
+                }
+                continue;
+            }
+            if self.ch == '#' && self.input[self.position..].starts_with(&['#', 'p', 'r', 'a', 'g', 'm', 'a']) {
+                let start = self.position;
+                while self.ch != '\n' && self.ch != '\0' {
+                    self.read_char();
+                }
+                let end = self.position.min(self.input.len());
+                self.comments.push(Span::new(start, end));
+                let name: String = self.input[start + "#pragma".len()..end].iter().collect();
+                self.pragmas.push((name.trim().to_string(), Span::new(start, end)));
+                continue;
+            }
+            break;
         }
     }
 }
 
-fn is_letter(ch: char) -> bool {
-    ch.is_alphabetic() || ch == '_'
+// Unicode's identifier rules (UAX #31), like Rust's: a letter or `_`
+// first, then letters, digits, `_` and combining marks (so a `café` typed
+// with a combining accent is still one name)
+pub(crate) fn is_ident_start(ch: char) -> bool {
+    ch == '_' || unicode_ident::is_xid_start(ch)
+}
+
+pub(crate) fn is_ident_continue(ch: char) -> bool {
+    unicode_ident::is_xid_continue(ch)
 }
 
 fn is_digit(ch: char) -> bool {
-    ch.is_numeric()
+    ch.is_ascii_digit()
 }
\ No newline at end of file
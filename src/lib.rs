@@ -0,0 +1,59 @@
+pub mod span;
+pub mod diagnostics;
+pub mod token;
+pub mod lexer;
+pub mod ast;
+pub mod parser;
+pub mod macros;
+pub mod operators;
+pub mod object;
+pub mod convert;
+pub mod compare;
+pub mod datetime;
+pub mod decimal;
+pub mod numbers;
+pub mod evaluator;
+pub mod generator;
+pub mod future;
+pub mod task;
+pub mod resource;
+pub mod environment;
+pub mod runtime;
+pub mod trace;
+pub mod hooks;
+pub mod interpreter;
+pub mod stdlib;
+pub mod resolver;
+pub mod refactor;
+pub mod typecheck;
+pub mod deadcode;
+pub mod features;
+pub mod golden;
+pub mod cost;
+pub mod ast_tree;
+pub mod incremental;
+pub mod cache;
+pub mod manifest;
+pub mod json;
+pub mod bench;
+pub mod codegen;
+pub mod doc;
+pub mod highlight;
+pub mod template;
+pub mod watch;
+pub mod lsp;
+
+#[cfg(feature = "repl")]
+pub mod repl;
+
+#[cfg(feature = "repl")]
+pub mod serve;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "capi")]
+pub mod capi;
+
+#[cfg(feature = "jit")]
+pub mod jit;
@@ -0,0 +1,499 @@
+// Language server (`nova lsp`), speaking JSON-RPC over stdin/stdout.
+//
+// Supported: incremental text sync, diagnostics (parser and type errors,
+// resolver and dead-code warnings), go-to-definition, hover, document symbols, semantic tokens,
+// rename.
+// Edits are applied with the incremental parser; analysis results are
+// recomputed from the cached tree on every request.
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use crate::ast::{Expression, Program, Statement, BlockStatement};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::highlight::{self, TokenClass};
+use crate::json::{self, Json};
+use crate::incremental::{self, Edit, Reparse};
+use crate::refactor;
+use crate::resolver::{self, BindingKind, Definition, Resolution};
+use crate::typecheck;
+use crate::deadcode;
+use crate::features;
+use crate::span::{LineIndex, Position, Span};
+
+// JSON-RPC error codes
+const METHOD_NOT_FOUND: i64 = -32601;
+const REQUEST_FAILED: i64 = -32803;
+
+pub fn run<R: BufRead, W: Write>(mut input: R, output: W) -> io::Result<()> {
+    let mut server = Server { out: output, documents: HashMap::new() };
+    while let Some(message) = read_message(&mut input)? {
+        let message = match json::parse(&message) {
+            Ok(m) => m,
+            Err(_) => continue, // Nothing sensible to reply to
+        };
+        if !server.handle(&message)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// --- TRANSPORT ---
+
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = match length {
+        Some(l) => l,
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length")),
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(String::from_utf8_lossy(&body).into_owned()))
+}
+
+fn write_message<W: Write>(out: &mut W, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    out.flush()
+}
+
+// --- DOCUMENTS ---
+
+struct Document {
+    text: String,
+    parsed: Reparse,
+}
+
+impl Document {
+    fn new(text: String) -> Self {
+        let parsed = incremental::full_parse(&text);
+        Document { text, parsed }
+    }
+
+    fn apply_change(&mut self, change: &Json) {
+        let new_text = match change.get("text").and_then(Json::as_str) {
+            Some(t) => t,
+            None => return,
+        };
+        let range = match change.get("range") {
+            Some(r) => r,
+            None => {
+                *self = Document::new(new_text.to_string());
+                return;
+            },
+        };
+
+        let chars: Vec<char> = self.text.chars().collect();
+        let index = LineIndex::new(&self.text);
+        let start = lsp_offset(&chars, &index, range.get("start").unwrap_or(&Json::Null));
+        let end = lsp_offset(&chars, &index, range.get("end").unwrap_or(&Json::Null)).max(start);
+
+        let mut text: String = chars[..start].iter().collect();
+        text.push_str(new_text);
+        text.extend(&chars[end..]);
+
+        let edit = Edit { start, end, new_len: new_text.chars().count() };
+        // The incremental parser needs a clean tree to splice into
+        self.parsed = if self.parsed.errors.is_empty() {
+            incremental::reparse(&self.parsed.program, &edit, &text)
+        } else {
+            incremental::full_parse(&text)
+        };
+        self.text = text;
+    }
+}
+
+// LSP columns are UTF-16 code units, ours are chars
+fn lsp_offset(chars: &[char], index: &LineIndex, position: &Json) -> usize {
+    let line = position.get("line").and_then(Json::as_i64).unwrap_or(0) as usize;
+    let target = position.get("character").and_then(Json::as_i64).unwrap_or(0) as usize;
+    let start = match index.line_start(line) {
+        Some(s) => s,
+        None => return chars.len(),
+    };
+    let mut units = 0;
+    let mut column = 0;
+    while let Some(ch) = chars.get(start + column) {
+        if units >= target || *ch == '\n' {
+            break;
+        }
+        units += ch.len_utf16();
+        column += 1;
+    }
+    index.offset(Position { line, column })
+}
+
+struct Analysis {
+    text: String,
+    chars: Vec<char>,
+    index: LineIndex,
+    errors: Vec<Diagnostic>,
+    resolution: Resolution,
+    types: Vec<Diagnostic>,
+    dead_code: Vec<Diagnostic>,
+    features: Vec<Diagnostic>,
+}
+
+impl Analysis {
+    fn new(doc: &Document) -> Self {
+        Analysis {
+            text: doc.text.clone(),
+            chars: doc.text.chars().collect(),
+            index: LineIndex::new(&doc.text),
+            errors: doc.parsed.errors.clone(),
+            resolution: resolver::resolve(&doc.parsed.program),
+            types: typecheck::check(&doc.parsed.program),
+            dead_code: deadcode::eliminate(&mut doc.parsed.program.clone()),
+            features: features::check(&doc.parsed.program, |_| false),
+        }
+    }
+
+    fn lsp_position(&self, offset: usize) -> Json {
+        let pos = self.index.position(offset);
+        let start = self.index.line_start(pos.line).unwrap_or(0);
+        let character: usize = self.chars[start..start + pos.column].iter().map(|c| c.len_utf16()).sum();
+        Json::object(vec![("line", pos.line.into()), ("character", character.into())])
+    }
+
+    fn offset_of(&self, position: &Json) -> usize {
+        lsp_offset(&self.chars, &self.index, position)
+    }
+
+    fn range(&self, span: Span) -> Json {
+        Json::object(vec![("start", self.lsp_position(span.start)), ("end", self.lsp_position(span.end))])
+    }
+}
+
+struct Server<W: Write> {
+    out: W,
+    documents: HashMap<String, Document>,
+}
+
+impl<W: Write> Server<W> {
+    // Returns false once the client asked us to exit
+    fn handle(&mut self, message: &Json) -> io::Result<bool> {
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params").cloned().unwrap_or(Json::Null);
+        let id = message.get("id").cloned();
+
+        match method {
+            "initialize" => self.reply(id, capabilities())?,
+            "shutdown" => self.reply(id, Json::Null)?,
+            "exit" => return Ok(false),
+            "textDocument/didOpen" => {
+                let doc = params.get("textDocument");
+                let uri = doc.and_then(|d| d.get("uri")).and_then(Json::as_str);
+                let text = doc.and_then(|d| d.get("text")).and_then(Json::as_str);
+                if let (Some(uri), Some(text)) = (uri, text) {
+                    self.documents.insert(uri.to_string(), Document::new(text.to_string()));
+                    self.publish_diagnostics(uri)?;
+                }
+            },
+            "textDocument/didChange" => {
+                // Changes are applied in order, each against the previous result
+                let changes = params.get("contentChanges").and_then(Json::as_array);
+                if let (Some(uri), Some(changes)) = (document_uri(&params), changes) {
+                    if let Some(doc) = self.documents.get_mut(&uri) {
+                        for change in changes {
+                            doc.apply_change(change);
+                        }
+                    }
+                    self.publish_diagnostics(&uri)?;
+                }
+            },
+            "textDocument/didClose" => {
+                if let Some(uri) = document_uri(&params) {
+                    self.documents.remove(&uri);
+                }
+            },
+            "textDocument/definition" => {
+                let result = self.with_document(&params, definition).unwrap_or(Json::Null);
+                self.reply(id, result)?;
+            },
+            "textDocument/hover" => {
+                let result = self.with_document(&params, hover).unwrap_or(Json::Null);
+                self.reply(id, result)?;
+            },
+            "textDocument/semanticTokens/full" => {
+                let result = self.with_document(&params, semantic_tokens).unwrap_or(Json::Null);
+                self.reply(id, result)?;
+            },
+            "textDocument/rename" => {
+                let new_name = params.get("newName").and_then(Json::as_str).unwrap_or_default();
+                let result = document_uri(&params).and_then(|uri| {
+                    let doc = self.documents.get(&uri)?;
+                    let position = params.get("position").unwrap_or(&Json::Null);
+                    Some(rename(&Analysis::new(doc), &doc.parsed.program, position, &uri, new_name))
+                });
+                match result {
+                    Some(Err(message)) => self.reply_error(id, REQUEST_FAILED, message)?,
+                    Some(Ok(edit)) => self.reply(id, edit)?,
+                    None => self.reply(id, Json::Null)?,
+                }
+            },
+            "textDocument/documentSymbol" => {
+                let result = self.with_document(&params, document_symbols).unwrap_or(Json::Array(vec![]));
+                self.reply(id, result)?;
+            },
+            _ => {
+                // Requests need an answer, notifications can be ignored
+                if id.is_some() {
+                    self.reply_error(id, METHOD_NOT_FOUND, format!("method not found: {}", method))?;
+                }
+            },
+        }
+        Ok(true)
+    }
+
+    fn with_document(&self, params: &Json, f: fn(&Analysis, &Json, &str) -> Json) -> Option<Json> {
+        let uri = document_uri(params)?;
+        let analysis = Analysis::new(self.documents.get(&uri)?);
+        let position = params.get("position").unwrap_or(&Json::Null);
+        Some(f(&analysis, position, &uri))
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> io::Result<()> {
+        let analysis = match self.documents.get(uri) {
+            Some(doc) => Analysis::new(doc),
+            None => return Ok(()),
+        };
+
+        let diagnostics: Vec<Json> = analysis.errors.iter()
+            .chain(analysis.resolution.diagnostics.iter())
+            .chain(analysis.types.iter())
+            .chain(analysis.dead_code.iter())
+            .chain(analysis.features.iter())
+            .map(|d| {
+                let mut fields = vec![
+                    ("range", analysis.range(d.span)),
+                    ("severity", Json::from(match d.severity { Severity::Error => 1i64, Severity::Warning => 2 })),
+                    ("source", "nova".into()),
+                    ("message", d.message.clone().into()),
+                ];
+                if let Some(rule) = d.rule {
+                    fields.push(("code", rule.into()));
+                }
+                Json::object(fields)
+            })
+            .collect();
+
+        self.send(Json::object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            ("params", Json::object(vec![("uri", uri.into()), ("diagnostics", Json::Array(diagnostics))])),
+        ]))
+    }
+
+    fn reply(&mut self, id: Option<Json>, result: Json) -> io::Result<()> {
+        let id = id.unwrap_or(Json::Null);
+        self.send(Json::object(vec![("jsonrpc", "2.0".into()), ("id", id), ("result", result)]))
+    }
+
+    fn reply_error(&mut self, id: Option<Json>, code: i64, message: String) -> io::Result<()> {
+        let id = id.unwrap_or(Json::Null);
+        let error = Json::object(vec![("code", code.into()), ("message", message.into())]);
+        self.send(Json::object(vec![("jsonrpc", "2.0".into()), ("id", id), ("error", error)]))
+    }
+
+    fn send(&mut self, message: Json) -> io::Result<()> {
+        write_message(&mut self.out, &message)
+    }
+}
+
+fn document_uri(params: &Json) -> Option<String> {
+    params.get("textDocument")?.get("uri")?.as_str().map(String::from)
+}
+
+fn capabilities() -> Json {
+    Json::object(vec![
+        ("capabilities", Json::object(vec![
+            ("textDocumentSync", Json::from(2i64)), // Incremental
+            ("definitionProvider", true.into()),
+            ("hoverProvider", true.into()),
+            ("documentSymbolProvider", true.into()),
+            ("renameProvider", true.into()),
+            ("semanticTokensProvider", Json::object(vec![
+                ("legend", Json::object(vec![
+                    ("tokenTypes", Json::Array(TOKEN_LEGEND.iter().map(|t| (*t).into()).collect())),
+                    ("tokenModifiers", Json::Array(vec![])),
+                ])),
+                ("full", true.into()),
+            ])),
+        ])),
+        ("serverInfo", Json::object(vec![("name", "nova-lsp".into())])),
+    ])
+}
+
+// --- FEATURES ---
+
+fn definition(a: &Analysis, position: &Json, uri: &str) -> Json {
+    match a.resolution.definition_at(a.offset_of(position)) {
+        Some(def) => Json::object(vec![("uri", uri.into()), ("range", a.range(def.span))]),
+        None => Json::Null,
+    }
+}
+
+fn hover(a: &Analysis, position: &Json, _uri: &str) -> Json {
+    let def = match a.resolution.definition_at(a.offset_of(position)) {
+        Some(d) => d,
+        None => return Json::Null,
+    };
+    let ty = definition_type(&a.resolution, def, 0);
+    let text = match def.kind {
+        BindingKind::Let => format!("let {}: {}", def.name, ty),
+        BindingKind::Parameter => format!("(parameter) {}: {}", def.name, ty),
+    };
+    Json::object(vec![(
+        "contents",
+        Json::object(vec![("kind", "markdown".into()), ("value", format!("```nova\n{}\n```", text).into())]),
+    )])
+}
+
+// A WorkspaceEdit with every use of the binding renamed, or why not
+fn rename(a: &Analysis, program: &Program, position: &Json, uri: &str, new_name: &str) -> Result<Json, String> {
+    let offset = a.offset_of(position);
+    let edits = refactor::rename(program, Span::new(offset, offset), new_name)?;
+    let edits = edits.into_iter()
+        .map(|e| Json::object(vec![("range", a.range(e.span)), ("newText", e.new_text.into())]))
+        .collect();
+    Ok(Json::object(vec![("changes", Json::Object(vec![(uri.to_string(), Json::Array(edits))]))]))
+}
+
+fn document_symbols(a: &Analysis, _position: &Json, _uri: &str) -> Json {
+    let symbols = a.resolution.top_level()
+        .filter(|d| d.kind == BindingKind::Let)
+        .map(|d| {
+            let is_fn = matches!(d.value, Some(Expression::Function(_)));
+            Json::object(vec![
+                ("name", d.name.clone().into()),
+                ("detail", definition_type(&a.resolution, d, 0).into()),
+                ("kind", Json::from(if is_fn { 12i64 } else { 13 })), // Function / Variable
+                ("range", a.range(d.statement)),
+                ("selectionRange", a.range(d.span)),
+            ])
+        })
+        .collect();
+    Json::Array(symbols)
+}
+
+// Indices into this legend are what the client receives
+const TOKEN_LEGEND: [&str; 8] = [
+    "keyword", "variable", "function", "parameter", "number", "string", "operator", "comment",
+];
+
+fn legend_index(class: TokenClass) -> i64 {
+    match class {
+        TokenClass::Keyword => 0,
+        TokenClass::Identifier => 1,
+        TokenClass::Function => 2,
+        TokenClass::Parameter => 3,
+        TokenClass::Number => 4,
+        TokenClass::String => 5,
+        TokenClass::Operator => 6,
+        TokenClass::Comment => 7,
+    }
+}
+
+// Encoded as the spec's relative quintuples:
+// deltaLine, deltaStart, length, tokenType, tokenModifiers
+fn semantic_tokens(a: &Analysis, _position: &Json, _uri: &str) -> Json {
+    let mut data = vec![];
+    let (mut prev_line, mut prev_start) = (0, 0);
+    for token in highlight::classify(&a.text) {
+        let start = a.lsp_position(token.span.start);
+        let line = start.get("line").and_then(Json::as_i64).unwrap_or(0);
+        let character = start.get("character").and_then(Json::as_i64).unwrap_or(0);
+        let length: usize = a.chars[token.span.start..token.span.end].iter().map(|c| c.len_utf16()).sum();
+
+        let delta_start = if line == prev_line { character - prev_start } else { character };
+        data.extend([
+            Json::from(line - prev_line),
+            Json::from(delta_start),
+            Json::from(length),
+            Json::from(legend_index(token.class)),
+            Json::from(0i64),
+        ]);
+        prev_line = line;
+        prev_start = character;
+    }
+    Json::object(vec![("data", Json::Array(data))])
+}
+
+// --- LIGHTWEIGHT TYPE INFERENCE (for hover) ---
+
+const MAX_DEPTH: usize = 16;
+
+fn definition_type(res: &Resolution, def: &Definition, depth: usize) -> String {
+    match &def.value {
+        Some(value) if depth < MAX_DEPTH => infer(res, value, depth + 1),
+        _ => "unknown".to_string(),
+    }
+}
+
+fn infer(res: &Resolution, exp: &Expression, depth: usize) -> String {
+    let unknown = || "unknown".to_string();
+    match exp {
+        Expression::IntegerLiteral(_) => "int".to_string(),
+        Expression::StringLiteral(_) => "string".to_string(),
+        Expression::CharLiteral(_) => "char".to_string(),
+        Expression::DecimalLiteral(_) => "decimal".to_string(),
+        Expression::Boolean(_) => "bool".to_string(),
+        Expression::Prefix(p) if p.operator == "!" => "bool".to_string(),
+        Expression::Prefix(p) => infer(res, &p.right, depth),
+        Expression::Await(_) => unknown(),
+        Expression::Comparison(_) => "bool".to_string(),
+        Expression::Infix(i) => match i.operator.as_str() {
+            "<" | ">" | "==" | "!=" => "bool".to_string(),
+            _ => {
+                let left = infer(res, &i.left, depth);
+                if left == infer(res, &i.right, depth) { left } else { unknown() }
+            },
+        },
+        Expression::Identifier(ident) => {
+            match res.reference_at(ident.span).and_then(|r| r.definition) {
+                Some(id) if depth < MAX_DEPTH => definition_type(res, &res.definitions[id], depth + 1),
+                _ => unknown(),
+            }
+        },
+        Expression::If(ie) => {
+            let then = block_type(res, &ie.consequence, depth);
+            match &ie.alternative {
+                Some(alt) if block_type(res, alt, depth) == then => then,
+                _ => unknown(),
+            }
+        },
+        Expression::Function(fl) => {
+            let params: Vec<String> = fl.parameters.iter().map(|p| p.value.clone()).collect();
+            format!("fn({})", params.join(", "))
+        },
+        Expression::Call(_) | Expression::Index(_) | Expression::Loop(_) | Expression::DoWhile(_) |
+        Expression::For(_) | Expression::Match(_) | Expression::Switch(_) => unknown(),
+        Expression::Array(_) => "array".to_string(),
+        Expression::Tuple(_) => "tuple".to_string(),
+        // Same kind of value as the thing being sliced
+        Expression::Slice(se) => infer(res, &se.left, depth),
+        Expression::Hash(_) => "hash".to_string(),
+        Expression::Unsafe(u) => block_type(res, &u.body, depth),
+        Expression::Zone(z) => block_type(res, &z.body, depth),
+    }
+}
+
+fn block_type(res: &Resolution, block: &BlockStatement, depth: usize) -> String {
+    match block.statements.last() {
+        Some(Statement::Expression(s)) => infer(res, &s.expression, depth),
+        _ => "unknown".to_string(),
+    }
+}
@@ -0,0 +1,375 @@
+// Macros: functions the parser runs on code, to add syntax without
+// touching the evaluator.
+//
+//   macro unless(cond, body) {
+//       quote(if (!unquote(cond)) { unquote(body) })
+//   }
+//   unless(x > 10) { print("small") }
+//
+// A macro's arguments arrive unevaluated, as quotes of the code written
+// there; a block after the call (only allowed for a macro) comes last, as
+// a quote of the block. It returns the code that replaces the call, built
+// with quote() and unquote(): a quoted expression goes where the call was,
+// a quoted block's statements (`quote { ... }`) are spliced in where the
+// call is a statement of its own.
+//
+// Expansion runs at the end of Parser::parse_program, so the resolver and
+// everything after it see only the expanded program. Macros are defined at
+// the top level, before their first use, and run in a fresh environment
+// with no capabilities and a step budget: all they can see is their
+// arguments and the builtins. A macro's output is expanded again, so
+// macros can use other macros.
+use std::rc::Rc;
+use crate::ast::{
+    ArrayLiteral, BooleanLiteral, CallExpression, CharLiteral, DecimalLiteral, Expression, FunctionLiteral, HashLiteral, Identifier,
+    IntegerLiteral, Statement, StringLiteral, TupleLiteral,
+};
+use crate::diagnostics::Diagnostic;
+use crate::environment::Environment;
+use crate::evaluator::{call_function, eval_expression};
+use crate::object::{Object, Quote};
+use crate::resolver;
+use crate::runtime::ZoneLimits;
+use crate::span::Span;
+use crate::token::TokenType;
+
+// Statements one expansion may run
+pub const MACRO_STEPS: u64 = 100_000;
+
+// Macros expanding to calls of macros, at most this deep
+pub const MAX_EXPANSION_DEPTH: usize = 64;
+
+// Macro calls one parse may expand, all together. A macro that uses its
+// argument twice, nested n deep, expands 2^n times; this stops it in
+// milliseconds, where the depth limit alone would let it run for hours
+// (in the LSP, on every edit).
+pub const MAX_EXPANSIONS: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct Macro {
+    pub name: String,
+    pub function: FunctionLiteral,
+    pub span: Span,
+}
+
+// Replaces every call to one of `macros` in `statements` with what it
+// expands to. Errors are at the statement the call was in.
+pub fn expand(statements: &mut Vec<Statement>, macros: &[Macro]) -> Vec<Diagnostic> {
+    let mut expander = Expander { macros, env: Environment::new(), depth: 0, expansions: 0 };
+    let mut errors = vec![];
+    let mut expanded = Vec::with_capacity(statements.len());
+    // Statement by statement, so one bad call doesn't hide the others
+    for statement in std::mem::take(statements) {
+        let mut one = vec![statement];
+        if let Err((msg, span)) = rewrite_statements(&mut one, &mut |call| expander.call(call)) {
+            errors.push(Diagnostic::error(msg, span));
+        }
+        expanded.append(&mut one);
+    }
+    *statements = expanded;
+    errors
+}
+
+struct Expander<'a> {
+    macros: &'a [Macro],
+    env: Environment,
+    depth: usize,
+    expansions: usize,
+}
+
+impl Expander<'_> {
+    fn call(&mut self, call: &CallExpression) -> Result<Option<Splice>, String> {
+        let Expression::Identifier(name) = &*call.function else { return Ok(None) };
+        let Some(m) = self.macros.iter().find(|m| m.name == name.value) else { return Ok(None) };
+        if !call.keywords.is_empty() {
+            return Err(format!("Macro '{}' can't take keyword arguments", m.name));
+        }
+        if self.depth >= MAX_EXPANSION_DEPTH {
+            return Err(format!("Macro '{}' expands more than {} levels deep", m.name, MAX_EXPANSION_DEPTH));
+        }
+        if self.expansions >= MAX_EXPANSIONS {
+            return Err(format!("Macros expand more than {} times in one file", MAX_EXPANSIONS));
+        }
+        self.expansions += 1;
+
+        let mut args: Vec<Object> = call.arguments.iter().map(|a| Object::Quote(Rc::new(Quote::Expression(Box::new(a.clone()))))).collect();
+        if let Some(block) = &call.block {
+            args.push(Object::Quote(Rc::new(Quote::Block(block.clone()))));
+        }
+        let function = eval_expression(&Expression::Function(m.function.clone()), &mut self.env);
+        self.env.runtime().borrow_mut().enter_zone(ZoneLimits { steps: Some(MACRO_STEPS), depth: None });
+        let result = call_function(function, args, &mut self.env);
+        self.env.runtime().borrow_mut().exit_zone();
+        let mut splice = match result {
+            Object::Quote(q) => match Rc::unwrap_or_clone(q) {
+                Quote::Expression(e) => Splice::Expression(e),
+                Quote::Block(b) => Splice::Statements(b.statements),
+            },
+            Object::Error(msg) => return Err(format!("In macro '{}': {}", m.name, msg)),
+            other => return Err(format!("Macro '{}' must return code built with quote(), not {}", m.name, other.type_name())),
+        };
+
+        // Its slots were numbered for wherever it was written; names are
+        // looked up again here, and the functions around it renumbered
+        self.depth += 1;
+        let expanded = match &mut splice {
+            Splice::Expression(e) => {
+                e.visit_variables_mut(&mut unslot);
+                rewrite_expression(e, &mut |c| self.call(c))
+            },
+            Splice::Statements(statements) => {
+                statements.iter_mut().for_each(|s| s.visit_variables_mut(&mut unslot));
+                rewrite_statements(statements, &mut |c| self.call(c))
+            },
+        };
+        self.depth -= 1;
+        expanded.map_err(|(msg, _)| msg)?;
+        Ok(Some(splice))
+    }
+}
+
+// What a call is replaced with
+pub(crate) enum Splice {
+    Expression(Box<Expression>),
+    // Only where the call is a statement of its own
+    Statements(Vec<Statement>),
+}
+
+// Given a call, what to put in its place, or None to leave it (and look
+// inside it)
+pub(crate) type Rewrite<'a> = dyn FnMut(&CallExpression) -> Result<Option<Splice>, String> + 'a;
+
+// Replaces calls throughout `statements`, outermost first; what a call is
+// replaced with isn't looked at again. Functions with a replacement inside
+// get their slots numbered again. Returns whether anything changed, or an
+// error with the span of the statement it's in.
+pub(crate) fn rewrite_statements(statements: &mut Vec<Statement>, f: &mut Rewrite) -> Result<bool, (String, Span)> {
+    let mut r = Rewriter { f, span: Span::default(), changed: false };
+    r.statements(statements)?;
+    Ok(r.changed)
+}
+
+pub(crate) fn rewrite_expression(e: &mut Expression, f: &mut Rewrite) -> Result<bool, (String, Span)> {
+    let mut r = Rewriter { f, span: Span::default(), changed: false };
+    r.expression(e)?;
+    Ok(r.changed)
+}
+
+// Code that's moved away from where it was written goes by names, not
+// by the slots of the function it was in
+pub(crate) fn unslot(ident: &mut Identifier, _binds: bool) {
+    ident.slot = None;
+}
+
+// What `quote(...)` or `quote { ... }` quotes
+pub(crate) fn quoted(call: &CallExpression) -> Option<Quote> {
+    match (call.arguments.as_slice(), call.keywords.as_slice(), &call.block) {
+        ([code], [], None) => Some(Quote::Expression(Box::new(code.clone()))),
+        ([], [], Some(block)) => Some(Quote::Block(block.clone())),
+        _ => None,
+    }
+}
+
+// The arguments of the unquote() calls in a quote: the only part of it
+// that's evaluated where it's written
+pub fn unquoted(call: &CallExpression) -> Vec<Expression> {
+    let mut found = vec![];
+    let mut collect = |c: &CallExpression| {
+        if !matches!(&*c.function, Expression::Identifier(i) if i.value == "unquote") {
+            return Ok(None);
+        }
+        found.extend(c.all_arguments().cloned());
+        Ok(Some(Splice::Expression(Box::new(Expression::Call(c.clone())))))
+    };
+    let _ = match quoted(call) {
+        Some(Quote::Expression(mut e)) => rewrite_expression(&mut e, &mut collect),
+        Some(Quote::Block(mut b)) => rewrite_statements(&mut b.statements, &mut collect),
+        None => Ok(false),
+    };
+    found
+}
+
+// A value as the code that makes it, for unquote()
+pub(crate) fn unquote(value: Object) -> Result<Splice, String> {
+    let all = |values: Vec<Object>| values.into_iter().map(expression).collect::<Result<Vec<_>, _>>();
+    Ok(Splice::Expression(Box::new(match value {
+        Object::Integer(value) => Expression::IntegerLiteral(IntegerLiteral { token: TokenType::Int(value), value }),
+        Object::Boolean(value) => Expression::Boolean(BooleanLiteral { token: if value { TokenType::True } else { TokenType::False }, value }),
+        Object::String(value) => Expression::StringLiteral(StringLiteral { token: TokenType::Str(value.clone()), value }),
+        Object::Char(value) => Expression::CharLiteral(CharLiteral { token: TokenType::Char(value), value }),
+        Object::Decimal(value) => Expression::DecimalLiteral(DecimalLiteral { token: TokenType::Decimal(value), value }),
+        Object::Array(values) => Expression::Array(ArrayLiteral { token: TokenType::LBracket, elements: all(values)? }),
+        Object::Tuple(values) => Expression::Tuple(TupleLiteral { token: TokenType::LParen, elements: all(values)? }),
+        Object::Hash(pairs) => {
+            let pairs = pairs.into_iter().map(|(k, v)| Ok((expression(k.into())?, expression(v)?)));
+            Expression::Hash(HashLiteral { token: TokenType::LBrace, pairs: pairs.collect::<Result<_, String>>()? })
+        },
+        Object::Quote(q) => match Rc::unwrap_or_clone(q) {
+            Quote::Expression(e) => *e,
+            Quote::Block(b) => return Ok(Splice::Statements(b.statements)),
+        },
+        other => return Err(format!("unquote() can't turn a {} into code", other.type_name())),
+    })))
+}
+
+// Inside a collection, where statements can't go
+fn expression(value: Object) -> Result<Expression, String> {
+    match unquote(value)? {
+        Splice::Expression(e) => Ok(*e),
+        Splice::Statements(_) => Err("unquote() can't put a block of statements inside a value".to_string()),
+    }
+}
+
+struct Rewriter<'f, 'a> {
+    f: &'f mut Rewrite<'a>,
+    // The statement being looked inside of, for errors
+    span: Span,
+    changed: bool,
+}
+
+type Failure = (String, Span);
+
+impl Rewriter<'_, '_> {
+    fn replacement(&mut self, e: &Expression) -> Result<Option<Splice>, Failure> {
+        let Expression::Call(call) = e else { return Ok(None) };
+        let splice = (self.f)(call).map_err(|msg| (msg, self.span))?;
+        self.changed |= splice.is_some();
+        Ok(splice)
+    }
+
+    fn statements(&mut self, statements: &mut Vec<Statement>) -> Result<(), Failure> {
+        let mut i = 0;
+        while i < statements.len() {
+            let outer = std::mem::replace(&mut self.span, statements[i].span());
+            if let Statement::Expression(s) = &mut statements[i]
+                && let Some(splice) = self.replacement(&s.expression)? {
+                match splice {
+                    Splice::Expression(e) => s.expression = *e,
+                    Splice::Statements(new) => {
+                        let n = new.len();
+                        statements.splice(i..=i, new);
+                        self.span = outer;
+                        i += n;
+                        continue;
+                    },
+                }
+            } else {
+                self.statement(&mut statements[i])?;
+            }
+            self.span = outer;
+            i += 1;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &mut Statement) -> Result<(), Failure> {
+        match statement {
+            Statement::Let(s) => self.expression(&mut s.value),
+            Statement::LetTuple(s) => self.expression(&mut s.value),
+            Statement::Return(s) => self.expression(&mut s.return_value),
+            Statement::Yield(s) => self.expression(&mut s.value),
+            Statement::Defer(s) => self.expression(&mut s.value),
+            Statement::Break(s) => match &mut s.value {
+                Some(value) => self.expression(value),
+                None => Ok(()),
+            },
+            Statement::Continue(_) => Ok(()),
+            Statement::Expression(s) => self.expression(&mut s.expression),
+        }
+    }
+
+    fn expression(&mut self, e: &mut Expression) -> Result<(), Failure> {
+        match self.replacement(e)? {
+            Some(Splice::Expression(new)) => {
+                *e = *new;
+                return Ok(());
+            },
+            Some(Splice::Statements(_)) => {
+                return Err(("A block of statements can't go where a value is expected".to_string(), self.span));
+            },
+            None => {},
+        }
+        match e {
+            Expression::Identifier(_) | Expression::IntegerLiteral(_) | Expression::StringLiteral(_) |
+            Expression::CharLiteral(_) | Expression::DecimalLiteral(_) | Expression::Boolean(_) => Ok(()),
+            Expression::Function(f) => {
+                let changed = std::mem::replace(&mut self.changed, false);
+                self.statements(&mut f.body.statements)?;
+                if self.changed {
+                    resolver::assign_slots(f);
+                }
+                self.changed |= changed;
+                Ok(())
+            },
+            Expression::Prefix(e) => self.expression(&mut e.right),
+            Expression::Await(e) => self.expression(&mut e.value),
+            Expression::Infix(e) => {
+                self.expression(&mut e.left)?;
+                self.expression(&mut e.right)
+            },
+            Expression::Comparison(e) => e.operands.iter_mut().try_for_each(|o| self.expression(o)),
+            Expression::If(e) => {
+                self.expression(&mut e.condition)?;
+                self.statements(&mut e.consequence.statements)?;
+                match &mut e.alternative {
+                    Some(alternative) => self.statements(&mut alternative.statements),
+                    None => Ok(()),
+                }
+            },
+            Expression::Call(e) => {
+                self.expression(&mut e.function)?;
+                e.arguments.iter_mut().try_for_each(|a| self.expression(a))?;
+                e.keywords.iter_mut().try_for_each(|(_, v)| self.expression(v))?;
+                match &mut e.block {
+                    Some(block) => self.statements(&mut block.statements),
+                    None => Ok(()),
+                }
+            },
+            Expression::Array(e) => e.elements.iter_mut().try_for_each(|el| self.expression(el)),
+            Expression::Tuple(e) => e.elements.iter_mut().try_for_each(|el| self.expression(el)),
+            Expression::Hash(e) => e.pairs.iter_mut().try_for_each(|(k, v)| {
+                self.expression(k)?;
+                self.expression(v)
+            }),
+            Expression::Index(e) => {
+                self.expression(&mut e.left)?;
+                self.expression(&mut e.index)
+            },
+            Expression::Slice(e) => {
+                self.expression(&mut e.left)?;
+                [&mut e.start, &mut e.end].into_iter().flatten().try_for_each(|b| self.expression(b))
+            },
+            Expression::Unsafe(e) => self.statements(&mut e.body.statements),
+            Expression::Zone(e) => {
+                e.limits.iter_mut().try_for_each(|(_, v)| self.expression(v))?;
+                self.statements(&mut e.body.statements)
+            },
+            Expression::Loop(e) => self.statements(&mut e.body.statements),
+            Expression::DoWhile(e) => {
+                self.statements(&mut e.body.statements)?;
+                self.expression(&mut e.condition)
+            },
+            Expression::For(e) => {
+                self.expression(&mut e.iterable)?;
+                self.statements(&mut e.body.statements)
+            },
+            Expression::Match(e) => {
+                self.expression(&mut e.subject)?;
+                for arm in &mut e.arms {
+                    if let Some(guard) = &mut arm.guard {
+                        self.expression(guard)?;
+                    }
+                    self.statements(&mut arm.body.statements)?;
+                }
+                Ok(())
+            },
+            Expression::Switch(e) => {
+                self.expression(&mut e.subject)?;
+                for case in &mut e.cases {
+                    case.values.iter_mut().try_for_each(|v| self.expression(v))?;
+                }
+                e.bodies_mut().try_for_each(|b| self.statements(&mut b.statements))
+            },
+        }
+    }
+}
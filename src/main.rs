@@ -1,46 +1,773 @@
-mod token;
-mod lexer;
-mod ast;
-mod parser;
-mod object;
-mod evaluator;
-mod environment; // <--- NEW MODULE
-
-use std::io::{self, Write};
-use lexer::Lexer;
-use parser::Parser;
-use evaluator::eval_program;
-use environment::Environment; // <--- NEW IMPORT
+use std::io;
+use nova_lang::lexer::Lexer;
+use nova_lang::parser::Parser;
+use nova_lang::interpreter::{self, Error, Interpreter};
+use nova_lang::object::Object;
+use nova_lang::operators::Operators;
+use nova_lang::ast::Program;
+use nova_lang::diagnostics::{Diagnostic, Level, WarningPolicy};
+use nova_lang::json::Json;
+use nova_lang::manifest::Manifest;
+use nova_lang::resolver;
+use nova_lang::deadcode;
+use nova_lang::features;
+use nova_lang::ast_tree;
+use nova_lang::repl::Repl;
+use nova_lang::runtime::Capabilities;
+use nova_lang::trace::Trace;
+use nova_lang::typecheck;
+
+const USAGE: &str = "Usage: nova [<file> | repl [--hide-null] | lsp | run [<file>] [--jit] [--strict] [--allow-ffi] [--allow-process] [--allow-fs] [--allow-net] [--max-memory <bytes>] [--output json] [--deny-warnings] [--allow <rule>] [--deny <rule>] [--record <trace> | --replay <trace>] [--watch [--keep-env]] | check [<file>...] [--no-types] [--output json] [--deny-warnings] [--allow <rule>] [--deny <rule>] [--ast-tree] | compile --target js <file> [-o <out>] | doc <file> [--html] [-o <out>] | render <template> [--data <script>] [--strict] [--allow-fs] [--allow-net] [-o <out>] | bench <file> [--time <ms>] [--warmup <ms>] | serve [--listen <addr>] [--json] [--max-memory <bytes>] [--max-steps <n>]]";
 
 fn main() {
-    println!("Welcome to Nova (v0.1)");
-    println!("Now supports VARIABLES! Try 'let x = 10;' then 'x * 2'");
-    println!("-----------------------------------------------------");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("lsp") => {
+            let stdin = io::stdin();
+            if let Err(e) = nova_lang::lsp::run(stdin.lock(), io::stdout()) {
+                eprintln!("nova lsp: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Some("run") => run(&args[1..]),
+        Some("check") => check(&args[1..]),
+        Some("compile") => compile(&args[1..]),
+        Some("doc") => doc(&args[1..]),
+        Some("render") => render(&args[1..]),
+        Some("bench") => bench(&args[1..]),
+        Some("repl") => repl(&args[1..]),
+        Some("serve") => serve(&args[1..]),
+        // `nova script.nv`, which is what a `#!/usr/bin/env nova` line runs
+        Some(path) if std::path::Path::new(path).is_file() => run(&args),
+        Some(other) => {
+            eprintln!("Unknown command '{}'. {}", other, USAGE);
+            std::process::exit(2);
+        },
+        None => repl(&[]),
+    }
+}
+
+// Scripts started from the command line may read the clock; anything that
+// touches the rest of the system needs an --allow flag
+fn cli_capabilities() -> Capabilities {
+    Capabilities { time: true, ..Capabilities::none() }
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("{}\n{}", msg, USAGE);
+    std::process::exit(2);
+}
+
+// How run and check report: text on stderr, or one JSON document on stdout
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Text,
+    Json,
+}
+
+fn output_arg(value: Option<&String>) -> Output {
+    match value.map(String::as_str) {
+        Some("text") => Output::Text,
+        Some("json") => Output::Json,
+        _ => usage_error("--output needs 'text' or 'json'"),
+    }
+}
+
+// --allow <rule> and --deny <rule>
+fn rule_arg(policy: &mut WarningPolicy, rule: Option<&String>, level: Level) {
+    let Some(rule) = rule else {
+        usage_error("--allow and --deny need a rule name");
+    };
+    if let Err(msg) = policy.set(rule, level) {
+        usage_error(&msg);
+    }
+}
+
+fn read_file(path: &str, output: Output) -> String {
+    match std::fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => {
+            let diagnostic = Json::object(vec![
+                ("code", "io".into()),
+                ("severity", "error".into()),
+                ("message", e.to_string().into()),
+                ("span", Json::Null),
+            ]);
+            exit_with_errors(path, output, vec![diagnostic])
+        },
+    }
+}
+
+// Parse errors, or type errors when it parsed, each tagged with its pass
+fn analyze(source: &str) -> (Program, Vec<(&'static str, Diagnostic)>) {
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    check_types(program, p.errors)
+}
+
+// The rest of analyze, for a program parsed elsewhere
+fn check_types(program: Program, parse_errors: Vec<Diagnostic>) -> (Program, Vec<(&'static str, Diagnostic)>) {
+    if !parse_errors.is_empty() {
+        return (program, parse_errors.into_iter().map(|e| ("parse", e)).collect());
+    }
+    let type_errors = typecheck::check(&program);
+    (program, type_errors.into_iter().map(|e| ("type", e)).collect())
+}
+
+// Resolver and dead-code warnings as the policy leaves them, each tagged
+// with its pass; the dead code itself is dropped from `program`. `globals`
+// are what the project's earlier files bound.
+fn lint(program: &mut Program, policy: &WarningPolicy, globals: &[String]) -> Vec<(&'static str, Diagnostic)> {
+    let mut warnings: Vec<_> = resolver::resolve_with(program, globals).diagnostics.into_iter().map(|w| ("resolve", w)).collect();
+    warnings.extend(features::check(program, |name| globals.iter().any(|g| g == name)).into_iter().map(|w| ("features", w)));
+    warnings.extend(deadcode::eliminate(program).into_iter().map(|w| ("deadcode", w)));
+    warnings.into_iter().filter_map(|(code, w)| Some((code, policy.apply(w)?))).collect()
+}
+
+// What a file binds at the top level, for the files after it
+fn top_level_names(program: &Program) -> Vec<String> {
+    resolver::resolve(program).top_level().map(|d| d.name.clone()).collect()
+}
+
+// The nova.toml in the current directory or above it, for run and check
+// without a file
+fn project() -> Manifest {
+    let dir = std::env::current_dir().unwrap_or_default();
+    let Some(path) = Manifest::find(&dir) else {
+        usage_error("Missing input file (and no nova.toml here or above)");
+    };
+    // Paths in messages stay short when it's right here
+    let path = path.strip_prefix(&dir).map(|p| p.to_path_buf()).unwrap_or(path);
+    Manifest::load(&path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
+
+// The project's files in the order they run, entry last
+fn project_files(manifest: &Manifest) -> Vec<String> {
+    match manifest.files() {
+        Ok(files) => files.iter().map(|f| f.display().to_string()).collect(),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        },
+    }
+}
+
+fn denied(warnings: &[(&'static str, Diagnostic)]) -> bool {
+    warnings.iter().any(|(_, w)| w.is_error())
+}
+
+// Reads, parses and type-checks a script, printing diagnostics and exiting on errors
+fn parse_file(path: &str) -> Program {
+    load(path, Output::Text).1
+}
+
+fn load(path: &str, output: Output) -> (String, Program) {
+    let source = read_file(path, output);
+    let (program, errors) = analyze(&source);
+    if !errors.is_empty() {
+        if output == Output::Text {
+            for (_, e) in &errors {
+                eprintln!("{}:{}", path, e.render(&source));
+            }
+            std::process::exit(1);
+        }
+        let diagnostics = errors.iter().map(|(code, e)| e.to_json(code, &source)).collect();
+        exit_with_errors(path, output, diagnostics);
+    }
+    (source, program)
+}
+
+// {"file": ..., "diagnostics": [...]}, plus "result" after a successful run
+fn json_report(path: &str, diagnostics: Vec<Json>, extra: Vec<(&str, Json)>) -> Json {
+    let mut fields = vec![("file", path.into()), ("diagnostics", Json::Array(diagnostics))];
+    fields.extend(extra);
+    Json::object(fields)
+}
+
+fn exit_with_errors(path: &str, output: Output, diagnostics: Vec<Json>) -> ! {
+    match output {
+        Output::Json => println!("{}", json_report(path, diagnostics, Vec::new())),
+        Output::Text => {
+            for d in &diagnostics {
+                eprintln!("{}: {}", path, d.get("message").and_then(Json::as_str).unwrap_or_default());
+            }
+        },
+    }
+    std::process::exit(1);
+}
+
+fn run(args: &[String]) {
+    let mut jit = false;
+    let mut strict = false;
+    let mut capabilities = cli_capabilities();
+    let mut max_memory = None;
+    let mut output = Output::Text;
+    let mut record = None;
+    let mut replay = None;
+    let mut watch = false;
+    let mut keep_env = false;
+    let mut policy = WarningPolicy::default();
+    let mut input = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--jit" => jit = true,
+            "--strict" => strict = true,
+            "--watch" => watch = true,
+            "--keep-env" => keep_env = true,
+            "--record" => record = Some(iter.next().cloned().unwrap_or_else(|| usage_error("--record needs a file"))),
+            "--replay" => replay = Some(iter.next().cloned().unwrap_or_else(|| usage_error("--replay needs a file"))),
+            "--output" => output = output_arg(iter.next()),
+            "--deny-warnings" => policy.deny_warnings = true,
+            "--allow" => rule_arg(&mut policy, iter.next(), Level::Allow),
+            "--deny" => rule_arg(&mut policy, iter.next(), Level::Deny),
+            "--max-memory" => match iter.next().and_then(|v| v.parse().ok()) {
+                Some(bytes) => max_memory = Some(bytes),
+                None => usage_error("--max-memory needs a number of bytes"),
+            },
+            "--allow-ffi" => capabilities.ffi = true,
+            "--allow-process" => capabilities.process = true,
+            "--allow-fs" => capabilities.fs = true,
+            "--allow-net" => capabilities.net = true,
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => usage_error(&format!("Unexpected argument '{}'", arg)),
+        }
+    }
+
+    // Without a file, the project's: settings from nova.toml add to the flags
+    let files = match input {
+        Some(input) => vec![input],
+        None => {
+            let manifest = project();
+            strict |= manifest.strict;
+            capabilities = capabilities.union(manifest.capabilities);
+            max_memory = max_memory.or(manifest.max_memory);
+            project_files(&manifest)
+        },
+    };
+    let (input, earlier) = files.split_last().expect("a project always has its entry");
+    if record.is_some() && replay.is_some() {
+        usage_error("--record and --replay can't be used together");
+    }
+    if keep_env && !watch {
+        usage_error("--keep-env only makes sense with --watch");
+    }
+    if watch {
+        if output == Output::Json || record.is_some() || replay.is_some() {
+            usage_error("--watch can't be combined with --output json, --record or --replay");
+        }
+        watch_script(&files, jit, strict, capabilities, max_memory, keep_env, &policy);
+    }
+
+    let mut nova = interpreter(capabilities, max_memory, strict);
+    if record.is_some() {
+        nova.env().runtime().borrow_mut().trace = Trace::record();
+    }
+    if let Some(path) = &replay {
+        let trace = std::fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|text| Trace::replay(&text));
+        match trace {
+            Ok(trace) => nova.env().runtime().borrow_mut().trace = trace,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            },
+        }
+    }
+    // Written even when the script failed; that's the run worth replaying
+    let write_trace = |nova: &Interpreter| {
+        if let Some(path) = &record {
+            write_output(Some(path.clone()), &nova.env().runtime().borrow().trace.to_text());
+        }
+    };
+
+    // A project's other files run first, in the same environment
+    let mut globals = vec![];
+    for path in earlier {
+        match run_earlier(path, &mut nova, output, jit, &policy, &globals) {
+            Some(names) => globals.extend(names),
+            None => {
+                write_trace(&nova);
+                std::process::exit(1);
+            },
+        }
+    }
+
+    // Warnings are printed and the script runs anyway, unless one was denied
+    let (source, mut program) = load(input, output);
+    let warnings = lint(&mut program, &policy, &globals);
+    if output == Output::Text {
+        for (_, w) in &warnings {
+            eprintln!("{}:{}", input, w.render(&source));
+        }
+    }
+    let mut diagnostics: Vec<Json> = warnings.iter().map(|(code, w)| w.to_json(code, &source)).collect();
+    if denied(&warnings) {
+        if output == Output::Json {
+            println!("{}", json_report(input, diagnostics, Vec::new()));
+        }
+        std::process::exit(1);
+    }
+    let result = evaluate(&mut nova, &program, jit);
+    write_trace(&nova);
+    match (output, result) {
+        (_, Err(e)) => {
+            let diagnostic = runtime_error(&nova, e);
+            match output {
+                Output::Text => eprintln!("{}:{}", input, diagnostic.render(&source)),
+                Output::Json => {
+                    diagnostics.push(diagnostic.to_json("runtime", &source));
+                    println!("{}", json_report(input, diagnostics, Vec::new()));
+                },
+            }
+            std::process::exit(1);
+        },
+        (Output::Text, Ok(Object::Null)) => {},
+        (Output::Text, Ok(result)) => println!("{}", result),
+        (Output::Json, Ok(result)) => {
+            let value = Json::object(vec![("value", result.to_string().into()), ("type", result.type_name().into())]);
+            println!("{}", json_report(input, diagnostics, vec![("result", value)]));
+        },
+    }
+}
+
+// Runs one of the files a project runs before its entry, reporting the way
+// the entry does (with a JSON document of its own); the names it bound, or
+// None if it failed
+fn run_earlier(path: &str, nova: &mut Interpreter, output: Output, jit: bool, policy: &WarningPolicy, globals: &[String]) -> Option<Vec<String>> {
+    let (source, mut program) = load(path, output);
+    let names = top_level_names(&program);
+    let warnings = lint(&mut program, policy, globals);
+    let mut diagnostics: Vec<Json> = warnings.iter().map(|(code, w)| w.to_json(code, &source)).collect();
+    if output == Output::Text {
+        for (_, w) in &warnings {
+            eprintln!("{}:{}", path, w.render(&source));
+        }
+    }
+    let mut failed = denied(&warnings);
+    if !failed && let Err(e) = evaluate(nova, &program, jit) {
+        let diagnostic = runtime_error(nova, e);
+        if output == Output::Text {
+            eprintln!("{}:{}", path, diagnostic.render(&source));
+        }
+        diagnostics.push(diagnostic.to_json("runtime", &source));
+        failed = true;
+    }
+    if output == Output::Json {
+        println!("{}", json_report(path, diagnostics, Vec::new()));
+    }
+    (!failed).then_some(names)
+}
+
+// The interpreter the CLI runs scripts in. It doesn't type-check: the
+// commands analyze a file themselves first, to report diagnostics per pass.
+fn interpreter(capabilities: Capabilities, max_memory: Option<usize>, strict: bool) -> Interpreter {
+    Interpreter::with_config(interpreter::Config {
+        capabilities,
+        memory_limit: max_memory,
+        type_check: false,
+        strict,
+        ..interpreter::Config::default()
+    })
+}
 
-    // Create memory ONCE, outside the loop
-    let mut env = Environment::new();
+fn evaluate(nova: &mut Interpreter, program: &Program, jit: bool) -> Result<Object, Error> {
+    if jit { run_jit(nova, program) } else { nova.eval_program(program) }
+}
+
+// At the statement the error came out of (see Runtime::error_span), with
+// what the call it's in had bound; an internal error has neither
+fn runtime_error(nova: &Interpreter, e: Error) -> Diagnostic {
+    match nova.diagnostics().last() {
+        Some(diagnostic) if diagnostic.is_error() => diagnostic.clone(),
+        _ => Diagnostic::error(e.to_string(), Default::default()),
+    }
+}
 
+// Runs the script, then again every time it's saved, until interrupted.
+// Errors are printed and waited out instead of ending the process. With
+// `keep_env` each run starts from the bindings the last one left, so
+// state built up at the top level survives edits. A project's files all
+// run, in order, and a save to any of them starts the run over.
+fn watch_script(files: &[String], jit: bool, strict: bool, capabilities: Capabilities, max_memory: Option<usize>, keep_env: bool, policy: &WarningPolicy) -> ! {
+    let fresh = || interpreter(capabilities, max_memory, strict);
+    let mut watcher = nova_lang::watch::Watcher::new(files);
+    // A save that leaves the text as it was isn't parsed again
+    let mut cache = nova_lang::cache::ParseCache::new(files.len());
+    let mut analyze = |source: &str| {
+        let (program, errors) = cache.parse(source, &Operators::new());
+        check_types(program, errors)
+    };
+    let entry = files.last().expect("a project always has its entry");
+    let watching = if files.len() == 1 { entry.clone() } else { format!("{} files", files.len()) };
+    let mut nova = fresh();
     loop {
-        print!(">> ");
-        io::stdout().flush().unwrap();
+        if !keep_env {
+            nova = fresh();
+        }
+        let mut globals = vec![];
+        for input in files {
+            // None stops the run at this file
+            let names = match std::fs::read_to_string(input) {
+                Ok(source) => match analyze(&source) {
+                    (mut program, errors) if errors.is_empty() => {
+                        let names = top_level_names(&program);
+                        let warnings = lint(&mut program, policy, &globals);
+                        for (_, w) in &warnings {
+                            eprintln!("{}:{}", input, w.render(&source));
+                        }
+                        // A denied warning waits for the next save like an error does
+                        if denied(&warnings) {
+                            None
+                        } else {
+                            match evaluate(&mut nova, &program, jit) {
+                                Err(e) => {
+                                    eprintln!("{}:{}", input, runtime_error(&nova, e).render(&source));
+                                    None
+                                },
+                                // The entry's result is the run's
+                                Ok(result) if input == entry && result != Object::Null => {
+                                    println!("{}", result);
+                                    Some(names)
+                                },
+                                _ => Some(names),
+                            }
+                        }
+                    },
+                    (_, errors) => {
+                        for (_, e) in &errors {
+                            eprintln!("{}:{}", input, e.render(&source));
+                        }
+                        None
+                    },
+                },
+                Err(e) => {
+                    eprintln!("{}: {}", input, e);
+                    None
+                },
+            };
+            match names {
+                Some(names) => globals.extend(names),
+                None => break,
+            }
+        }
+        eprintln!("nova: watching {} for changes (Ctrl-C to stop)", watching);
+        let changed = watcher.wait();
+        eprintln!("nova: {} changed, running again", changed[0].display());
+    }
+}
 
-        let mut line = String::new();
-        let bytes_read = io::stdin().read_line(&mut line).unwrap();
-        if bytes_read == 0 { break; }
+// Parses, type-checks and resolves without running; warnings (undefined
+// variables, dead code) are reported but don't fail the check unless
+// they're denied
+fn check(args: &[String]) {
+    let mut output = Output::Text;
+    let mut ast_tree = false;
+    let mut types = true;
+    let mut policy = WarningPolicy::default();
+    let mut inputs = vec![];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--output" => output = output_arg(iter.next()),
+            "--ast-tree" => ast_tree = true,
+            "--no-types" => types = false,
+            "--deny-warnings" => policy.deny_warnings = true,
+            "--allow" => rule_arg(&mut policy, iter.next(), Level::Allow),
+            "--deny" => rule_arg(&mut policy, iter.next(), Level::Deny),
+            _ if arg.starts_with("--") => usage_error(&format!("Unexpected argument '{}'", arg)),
+            _ => inputs.push(arg.clone()),
+        }
+    }
 
-        let l = Lexer::new(line);
-        let mut p = Parser::new(l);
-        let program = p.parse_program();
+    // Without files, the project's; each sees what the ones before it bind
+    let mut globals = inputs.is_empty().then(Vec::new);
+    if inputs.is_empty() {
+        inputs = project_files(&project());
+    }
+    // Every file is checked, whatever the ones before it had
+    let mut failed = false;
+    for input in &inputs {
+        failed |= check_file(input, output, ast_tree, types, &policy, globals.as_mut());
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
 
-        if p.errors.len() > 0 {
-            for msg in p.errors {
-                println!("\t{}", msg);
+// Reports everything the passes find in one file (a JSON document of its
+// own with --output json); true if any of it is an error
+fn check_file(path: &str, output: Output, ast_tree: bool, types: bool, policy: &WarningPolicy, globals: Option<&mut Vec<String>>) -> bool {
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            match output {
+                Output::Text => eprintln!("{}: {}", path, e),
+                Output::Json => {
+                    let diagnostic = Json::object(vec![
+                        ("code", "io".into()),
+                        ("severity", "error".into()),
+                        ("message", e.to_string().into()),
+                        ("span", Json::Null),
+                    ]);
+                    println!("{}", json_report(path, vec![diagnostic], Vec::new()));
+                },
             }
-            continue;
+            return true;
+        },
+    };
+    let mut p = Parser::new(Lexer::new(source.clone()));
+    let mut program = p.parse_program();
+    let mut found: Vec<_> = p.errors.into_iter().map(|e| ("parse", e)).collect();
+    // The passes after the parser need a whole tree
+    if found.is_empty() {
+        if types {
+            found.extend(typecheck::check(&program).into_iter().map(|e| ("type", e)));
+        }
+        // The tree as parsed, before dead code is dropped
+        if ast_tree && found.is_empty() {
+            print!("{}", ast_tree::render(&program, &source));
+            return false;
+        }
+        let names = top_level_names(&program);
+        match globals {
+            Some(globals) => {
+                found.extend(lint(&mut program, policy, globals));
+                globals.extend(names);
+            },
+            None => found.extend(lint(&mut program, policy, &[])),
+        }
+    }
+    match output {
+        Output::Text => {
+            for (_, d) in &found {
+                eprintln!("{}:{}", path, d.render(&source));
+            }
+        },
+        Output::Json => {
+            let diagnostics = found.iter().map(|(code, d)| d.to_json(code, &source)).collect();
+            println!("{}", json_report(path, diagnostics, Vec::new()));
+        },
+    }
+    found.iter().any(|(_, d)| d.is_error())
+}
+
+#[cfg(feature = "jit")]
+fn run_jit(nova: &mut Interpreter, program: &Program) -> Result<Object, Error> {
+    nova.eval_with(|env| nova_lang::jit::run(program, env))
+}
+
+#[cfg(not(feature = "jit"))]
+fn run_jit(_nova: &mut Interpreter, _program: &Program) -> Result<Object, Error> {
+    eprintln!("nova run: --jit needs a build with the `jit` feature (cargo build --features jit)");
+    std::process::exit(2);
+}
+
+fn compile(args: &[String]) {
+    let mut target = None;
+    let mut output = None;
+    let mut input = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--target" => target = iter.next().cloned(),
+            "-o" => output = iter.next().cloned(),
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => usage_error(&format!("Unexpected argument '{}'", arg)),
+        }
+    }
+
+    let input = input.unwrap_or_else(|| usage_error("Missing input file"));
+    let program = parse_file(&input);
+    let code = match target.as_deref() {
+        Some("js") => nova_lang::codegen::js::compile(&program),
+        Some(other) => usage_error(&format!("Unknown target '{}'", other)),
+        None => usage_error("Missing --target"),
+    };
+
+    write_output(output, &code);
+}
+
+fn doc(args: &[String]) {
+    let mut html = false;
+    let mut output = None;
+    let mut input = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--html" => html = true,
+            "-o" => output = iter.next().cloned(),
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => usage_error(&format!("Unexpected argument '{}'", arg)),
+        }
+    }
+
+    let input = input.unwrap_or_else(|| usage_error("Missing input file"));
+    let program = parse_file(&input);
+    let docs = if html {
+        nova_lang::doc::html(&input, &program)
+    } else {
+        nova_lang::doc::markdown(&input, &program)
+    };
+    write_output(output, &docs);
+}
+
+// Fills in a text template (see template.rs). `--data` names a script that
+// runs first, to bind what the template uses.
+fn render(args: &[String]) {
+    let mut capabilities = cli_capabilities();
+    let mut strict = false;
+    let mut data = None;
+    let mut output = None;
+    let mut input = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--data" => data = Some(iter.next().cloned().unwrap_or_else(|| usage_error("--data needs a script"))),
+            "-o" => output = iter.next().cloned(),
+            "--strict" => strict = true,
+            "--allow-ffi" => capabilities.ffi = true,
+            "--allow-process" => capabilities.process = true,
+            "--allow-fs" => capabilities.fs = true,
+            "--allow-net" => capabilities.net = true,
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => usage_error(&format!("Unexpected argument '{}'", arg)),
         }
+    }
+
+    let input = input.unwrap_or_else(|| usage_error("Missing template file"));
+    let text = read_file(&input, Output::Text);
+    let mut nova = interpreter(capabilities, None, strict);
+    if let Some(path) = data
+        && run_earlier(&path, &mut nova, Output::Text, false, &WarningPolicy::default(), &[]).is_none() {
+        std::process::exit(1);
+    }
+    match nova.guarded(|env| nova_lang::template::render(&text, env)) {
+        Ok(Ok(rendered)) => write_output(output, &rendered),
+        Ok(Err(e)) => {
+            eprintln!("{}:{}", input, e.render(&text));
+            std::process::exit(1);
+        },
+        Err(e) => {
+            eprintln!("{}: {}", input, e);
+            std::process::exit(1);
+        },
+    }
+}
+
+fn bench(args: &[String]) {
+    let mut config = nova_lang::bench::Config::default();
+    let mut input = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--time" => config.time = millis_arg(arg, iter.next()),
+            "--warmup" => config.warmup = millis_arg(arg, iter.next()),
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => usage_error(&format!("Unexpected argument '{}'", arg)),
+        }
+    }
+
+    let input = input.unwrap_or_else(|| usage_error("Missing input file"));
+    let program = parse_file(&input);
+    let mut nova = interpreter(cli_capabilities(), None, false);
+    if let Err(e) = nova.eval_program(&program) {
+        eprintln!("{}: {}", input, e);
+        std::process::exit(1);
+    }
+
+    let names = nova_lang::bench::benchmarks(nova.env());
+    if names.is_empty() {
+        eprintln!("{}: no bench_* functions without parameters", input);
+        std::process::exit(1);
+    }
+    for name in names {
+        match nova.guarded(|env| nova_lang::bench::run(&name, env, &config)).unwrap_or_else(|e| Err(e.to_string())) {
+            Ok(report) => println!("{}", report),
+            Err(msg) => {
+                eprintln!("{}", msg);
+                std::process::exit(1);
+            },
+        }
+    }
+}
+
+fn millis_arg(flag: &str, value: Option<&String>) -> std::time::Duration {
+    match value.and_then(|v| v.parse().ok()) {
+        Some(ms) => std::time::Duration::from_millis(ms),
+        None => usage_error(&format!("{} needs a number of milliseconds", flag)),
+    }
+}
+
+// Writes to the -o path, or stdout without one
+fn write_output(path: Option<String>, text: &str) {
+    match path {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, text) {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => print!("{}", text),
+    }
+}
+
+fn serve(args: &[String]) {
+    let mut config = nova_lang::serve::Config::default();
+    let mut address = nova_lang::serve::DEFAULT_ADDRESS.to_string();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--listen" => match iter.next() {
+                Some(addr) => address = addr.clone(),
+                None => usage_error("--listen needs an address"),
+            },
+            "--json" => config.json = true,
+            "--max-memory" => match iter.next().and_then(|v| v.parse().ok()) {
+                Some(bytes) => config.memory_limit = Some(bytes),
+                None => usage_error("--max-memory needs a number of bytes"),
+            },
+            "--max-steps" => match iter.next().and_then(|v| v.parse().ok()) {
+                Some(steps) => config.max_steps = Some(steps),
+                None => usage_error("--max-steps needs a number"),
+            },
+            _ => usage_error(&format!("Unexpected argument '{}'", arg)),
+        }
+    }
+
+    let listener = match std::net::TcpListener::bind(&address) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("nova serve: {}: {}", address, e);
+            std::process::exit(1);
+        },
+    };
+    eprintln!("nova serve: listening on {}", address);
+    if let Err(e) = nova_lang::serve::serve(listener, config) {
+        eprintln!("nova serve: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn repl(args: &[String]) {
+    let mut show_null = true;
+    for arg in args {
+        match arg.as_str() {
+            "--hide-null" => show_null = false,
+            _ => usage_error(&format!("Unexpected argument '{}'", arg)),
+        }
+    }
+
+    println!("Welcome to Nova (v0.1)");
+    println!("Now supports VARIABLES! Try 'let x = 10;' then 'x * 2'");
+    println!("-----------------------------------------------------");
 
-        // Pass the PERSISTENT env to the evaluator
-        let evaluated = eval_program(&program, &mut env);
-        println!("{}", evaluated);
+    let mut repl = Repl::new(interpreter(cli_capabilities(), None, false));
+    repl.show_null(show_null);
+    if let Err(e) = repl.run(io::stdin().lock(), io::stdout()) {
+        eprintln!("nova repl: {}", e);
+        std::process::exit(1);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,248 @@
+// nova.toml: what `nova run` and `nova check` do in a project directory
+// when no file is named.
+//
+//   [project]
+//   entry = "main.nv"            # what runs; its result is the run's
+//   sources = ["src"]            # .nv files run before it, in path order
+//   modules = ["vendor/csv.nv"]  # files or directories run before those
+//
+//   [settings]
+//   strict = true
+//   allow = ["fs", "net"]        # ffi, process, time, fs, net
+//   max_memory = 50000000
+//
+// Nova has no imports, so the files share one environment: each runs in
+// the bindings the ones before it left, modules first, then sources, then
+// the entry. Paths are relative to the manifest. Flags given on the
+// command line add to the settings.
+//
+// Only the part of TOML a manifest needs is read: tables, and keys with a
+// string, integer, boolean or one-line array value.
+use std::path::{Path, PathBuf};
+use crate::runtime::Capabilities;
+
+pub const FILE_NAME: &str = "nova.toml";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    // The directory nova.toml is in
+    pub root: PathBuf,
+    pub entry: PathBuf,
+    pub sources: Vec<PathBuf>,
+    pub modules: Vec<PathBuf>,
+    pub strict: bool,
+    pub capabilities: Capabilities,
+    pub max_memory: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Manifest {
+    // The nearest nova.toml in `dir` or a directory above it
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors().map(|d| d.join(FILE_NAME)).find(|path| path.is_file())
+    }
+
+    pub fn load(path: &Path) -> Result<Manifest, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let root = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        Manifest::parse(&text, &root).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // Errors say which line they're about, when it's one line
+    pub fn parse(text: &str, root: &Path) -> Result<Manifest, String> {
+        let mut manifest = Manifest {
+            root: root.to_path_buf(),
+            entry: PathBuf::new(),
+            sources: vec![],
+            modules: vec![],
+            strict: false,
+            capabilities: Capabilities { time: true, ..Capabilities::none() },
+            max_memory: None,
+        };
+        let mut table = String::new();
+        for (i, line) in text.lines().enumerate() {
+            let at = |msg: String| format!("line {}: {}", i + 1, msg);
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                table = name.trim().to_string();
+                if table != "project" && table != "settings" {
+                    return Err(at(format!("unknown table [{}] (expected [project] or [settings])", table)));
+                }
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(at(format!("expected `key = value`, found '{}'", line)));
+            };
+            let key = key.trim();
+            let value = parse_value(value.trim()).map_err(at)?;
+            manifest.set(&table, key, value).map_err(at)?;
+        }
+        if manifest.entry.as_os_str().is_empty() {
+            return Err("[project] needs an entry".to_string());
+        }
+        Ok(manifest)
+    }
+
+    fn set(&mut self, table: &str, key: &str, value: Value) -> Result<(), String> {
+        match (table, key) {
+            ("project", "entry") => self.entry = self.root.join(string(key, value)?),
+            ("project", "sources") => self.sources = paths(key, value)?.into_iter().map(|p| self.root.join(p)).collect(),
+            ("project", "modules") => self.modules = paths(key, value)?.into_iter().map(|p| self.root.join(p)).collect(),
+            ("settings", "strict") => match value {
+                Value::Boolean(b) => self.strict = b,
+                _ => return Err("strict must be true or false".to_string()),
+            },
+            ("settings", "max_memory") => match value {
+                Value::Integer(n) if n > 0 => self.max_memory = Some(n as usize),
+                _ => return Err("max_memory must be a positive number of bytes".to_string()),
+            },
+            ("settings", "allow") => {
+                for name in paths(key, value)? {
+                    match name.as_str() {
+                        "ffi" => self.capabilities.ffi = true,
+                        "process" => self.capabilities.process = true,
+                        "time" => self.capabilities.time = true,
+                        "fs" => self.capabilities.fs = true,
+                        "net" => self.capabilities.net = true,
+                        _ => return Err(format!("unknown capability '{}' (expected ffi, process, time, fs or net)", name)),
+                    }
+                }
+            },
+            ("", _) => return Err(format!("'{}' must be in [project] or [settings]", key)),
+            _ => return Err(format!("unknown key '{}' in [{}]", key, table)),
+        }
+        Ok(())
+    }
+
+    // Every file the project runs, in the order it runs them; the entry
+    // is last, and only there once
+    pub fn files(&self) -> Result<Vec<PathBuf>, String> {
+        let mut files = vec![];
+        for path in self.modules.iter().chain(&self.sources) {
+            if path.is_dir() {
+                collect_scripts(path, &mut files)?;
+            } else if path.is_file() {
+                files.push(path.clone());
+            } else {
+                return Err(format!("{}: no such file or directory", path.display()));
+            }
+        }
+        let mut seen = std::collections::HashSet::new();
+        files.retain(|f| *f != self.entry && seen.insert(f.clone()));
+        files.push(self.entry.clone());
+        Ok(files)
+    }
+}
+
+// The .nv files under `dir`, subdirectories included, sorted by path
+fn collect_scripts(dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            collect_scripts(&path, out)?;
+        } else if path.extension().is_some_and(|ext| ext == "nv") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn string(key: &str, value: Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s),
+        _ => Err(format!("{} must be a string", key)),
+    }
+}
+
+fn paths(key: &str, value: Value) -> Result<Vec<String>, String> {
+    match value {
+        Value::Array(items) => items.into_iter().map(|v| string(key, v)).collect(),
+        _ => Err(format!("{} must be an array of strings", key)),
+    }
+}
+
+// A # outside a string starts a comment
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {},
+        }
+    }
+    line
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    let mut chars = text.chars().peekable();
+    let value = value(&mut chars)?;
+    match chars.find(|c| !c.is_whitespace()) {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected '{}' after the value", c)),
+    }
+}
+
+fn value(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Value, String> {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    match chars.peek() {
+        Some('"') => {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => return Ok(Value::String(s)),
+                    Some('\\') => s.push(match chars.next() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some(c @ ('"' | '\\')) => c,
+                        other => return Err(format!("unknown escape \\{}", other.map(String::from).unwrap_or_default())),
+                    }),
+                    Some(c) => s.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+        },
+        Some('[') => {
+            chars.next();
+            let mut items = vec![];
+            loop {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                if chars.next_if_eq(&']').is_some() {
+                    return Ok(Value::Array(items));
+                }
+                items.push(value(chars)?);
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                match chars.next() {
+                    Some(',') => {},
+                    Some(']') => return Ok(Value::Array(items)),
+                    _ => return Err("expected ',' or ']' in array".to_string()),
+                }
+            }
+        },
+        Some(_) => {
+            let word: String = std::iter::from_fn(|| chars.next_if(|c| !matches!(c, ',' | ']') && !c.is_whitespace())).collect();
+            match word.as_str() {
+                "true" => Ok(Value::Boolean(true)),
+                "false" => Ok(Value::Boolean(false)),
+                _ => word.replace('_', "").parse().map(Value::Integer).map_err(|_| format!("can't read value '{}'", word)),
+            }
+        },
+        None => Err("missing value".to_string()),
+    }
+}
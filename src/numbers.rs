@@ -0,0 +1,80 @@
+// Numbers as text, both ways. Whatever reads a number from text or writes
+// one out goes through here (the lexer, Display, decimal(), int(),
+// toml_parse, JSON), so a value printed and read back is the value it
+// was, on every platform: the format is fixed ASCII, never the locale's,
+// with no exponents or digit grouping to disagree about.
+//
+//   int      -42          an optional `-`, then digits; no `+`, no `_`
+//   decimal  -0.50        the same, then `.` and the digits of its scale
+//   JSON     0.1          the shortest digits that read back as the same
+//                         f64, written out in full (no `1e300`)
+//
+// Ints are written with Rust's own formatting, which is already this.
+use crate::decimal::Decimal;
+
+pub fn parse_int(s: &str) -> Option<i64> {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+pub fn parse_decimal(s: &str) -> Option<Decimal> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if whole.is_empty() || !whole.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if digits.contains('.') && fraction.is_empty() {
+        return None;
+    }
+    let units: i128 = format!("{}{}", whole, fraction).parse().ok()?;
+    Decimal::new(if negative { -units } else { units }, u32::try_from(fraction.len()).ok()?)
+}
+
+pub fn format_decimal(d: &Decimal) -> String {
+    let digits = d.units().unsigned_abs().to_string();
+    let sign = if d.units() < 0 { "-" } else { "" };
+    let scale = d.scale() as usize;
+    if scale == 0 {
+        return format!("{}{}", sign, digits);
+    }
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - scale);
+    format!("{}{}.{}", sign, whole, fraction)
+}
+
+// The decimal an f64 is closest to, with as few digits as say which f64
+// it is (0.1, not 0.1000000000000000055511151231257827); None for NaN and
+// the infinities
+pub fn decimal_from_f64(f: f64) -> Option<Decimal> {
+    if !f.is_finite() {
+        return None;
+    }
+    parse_decimal(&format_f64(f))
+}
+
+// JSON's numbers. Finite results only: "1e999" is out of range.
+pub fn parse_f64(s: &str) -> Option<f64> {
+    let first = s.strip_prefix('-').unwrap_or(s).bytes().next()?;
+    if !first.is_ascii_digit() {
+        return None; // "inf", "NaN", "+1", ".5"
+    }
+    s.parse().ok().filter(|f: &f64| f.is_finite())
+}
+
+// Whole numbers without a fraction ("3", not "3.0"). JSON has no NaN or
+// infinity, so they're "null".
+pub fn format_f64(f: f64) -> String {
+    if !f.is_finite() {
+        return "null".to_string();
+    }
+    if f.fract() == 0.0 && f.abs() < 1e15 {
+        return (f as i64).to_string();
+    }
+    f.to_string()
+}
@@ -1,35 +1,439 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
 use std::fmt;
-use crate::ast::{Identifier, BlockStatement}; // Import AST nodes
+use std::rc::Rc;
+use std::sync::Arc;
+use indexmap::IndexMap;
+use crate::ast::{Identifier, BlockStatement, Expression, Node}; // Import AST nodes
+use crate::datetime::DateTime;
+use crate::decimal::Decimal;
+use crate::environment::Globals;
+use crate::future::Future;
+use crate::generator::Generator;
+use crate::resource::Resource;
+use crate::lexer::{is_ident_start, is_ident_continue};
+use crate::stdlib::Builtin;
+use crate::task::{Channel, Task};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Object {
     Integer(i64),
+    #[cfg(feature = "bigint")]
+    BigInt(num_bigint::BigInt), // An int past i64's range, made by overflow
+    Decimal(Decimal),         // Exact base-10 number (`1.50d`)
+    DateTime(DateTime),       // A UTC instant, to the millisecond (now(), date())
     Boolean(bool),
+    String(String),
+    Char(char),               // One Unicode scalar value (`'a'`, or s[i])
+    Bytes(Vec<u8>),           // Raw binary data (read_bytes, encode)
+    Buffer(Buffer),           // A string being built up (buffer(), push)
+    Array(Vec<Object>),
+    Tuple(Vec<Object>),
+    Hash(IndexMap<HashKey, Object>), // In insertion order, so output is stable
+    Set(BTreeSet<HashKey>),          // Same keys as hashes, sorted
     Null,
     ReturnValue(Box<Object>), // Wraps a value to signal "Stop!"
-    Function(Function),       // The executable function
+    Break(Box<Object>, Option<String>), // Same, but only out to the innermost (or labelled) loop
+    Continue(Option<String>), // On to that loop's next pass
+    Function(Rc<Function>),   // The executable function
+    Native(NativeFunction),   // A function implemented by the host
+    Builtin(Builtin),         // Standard library function (len, ffi_call, ...)
+    Partial(Partial),         // A function with some arguments already bound
+    Generator(Generator),     // A paused call to a function that yields
+    Future(Future),           // A call to an async function, or sleep_async/gather
+    Task(Task),               // A function running on another thread
+    Channel(Channel),         // Queue for passing values between tasks
+    Resource(Resource),       // An open file or socket
+    Quote(Rc<Quote>),         // Code as a value (quote(), and what macros take and give)
+    Error(String),            // Runtime error, unwinds like ReturnValue
+}
+
+// The values that can key a hash: only ones whose equality can't change
+// and that hash cheaply. Ordered int < bool < string < char, then by value.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+    Char(char),
+}
+
+impl From<HashKey> for Object {
+    fn from(key: HashKey) -> Object {
+        match key {
+            HashKey::Integer(i) => Object::Integer(i),
+            HashKey::Boolean(b) => Object::Boolean(b),
+            HashKey::String(s) => Object::String(s),
+            HashKey::Char(c) => Object::Char(c),
+        }
+    }
+}
+
+impl From<&str> for HashKey {
+    fn from(s: &str) -> HashKey {
+        HashKey::String(s.to_string())
+    }
+}
+
+impl fmt::Display for HashKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HashKey::Integer(i) => write!(f, "{}", i),
+            HashKey::Boolean(b) => write!(f, "{}", b),
+            HashKey::String(s) => write!(f, "{}", s),
+            HashKey::Char(c) => write!(f, "{}", c),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Function {
     pub parameters: Vec<Identifier>,
     pub body: BlockStatement,
-    // Note: In a production compiler, we would store the 'Environment' here 
-    // to support Closures (accessing outer variables). 
-    // We are skipping that for v0.1 to keep the Rust code simple.
+    // Slot names for a call's frame, from the literal
+    pub locals: Arc<[String]>,
+    // Top-level bindings the body can see
+    pub globals: Globals,
+    // Locals of the enclosing function the body uses, copied when the
+    // function was made: `fn(x) { fn(y) { x + y } }(1)` keeps x = 1
+    pub captured: Vec<(String, Object)>,
+    // `async fn`: calling it starts a future
+    pub is_async: bool,
+    // Machine code for the body, if the JIT compiled it
+    pub compiled: Option<Compiled>,
 }
 
-impl fmt::Display for Object {
+// A JIT-compiled function body (see jit.rs). It returns None for
+// arguments it doesn't take or when it bails out, and the call goes on in
+// the interpreter as if there were no compiled code.
+pub type CompiledFn = Rc<dyn Fn(&[Object]) -> Option<Object>>;
+
+#[derive(Clone)]
+pub struct Compiled(pub CompiledFn);
+
+impl fmt::Debug for Compiled {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Compiled")
+    }
+}
+
+// It computes the same as the body, so it doesn't make functions differ
+impl PartialEq for Compiled {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+// `partial(f, 1)` and `curry(f)`. Calling it passes the bound arguments
+// first, then the new ones. A curried one keeps returning partials until
+// it has `arity` arguments.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Partial {
+    pub function: Box<Object>,
+    pub args: Vec<Object>,
+    pub arity: Option<usize>,
+}
+
+// `buffer()`: a string to push() pieces onto. Unlike other values it's
+// shared rather than copied, so appending is amortized O(1) where
+// `s = s + piece` copies the whole string every time.
+#[derive(Debug, Clone, Default)]
+pub struct Buffer(pub Rc<RefCell<String>>);
+
+// Two buffers are only equal if they're the same one
+impl PartialEq for Buffer {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// Host code exposed to scripts as a callable value (embedder callbacks)
+pub type NativeFn = Rc<dyn Fn(Vec<Object>) -> Object>;
+
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: String,
+    pub func: NativeFn,
+}
+
+impl NativeFunction {
+    pub fn new(name: impl Into<String>, func: impl Fn(Vec<Object>) -> Object + 'static) -> Self {
+        NativeFunction { name: name.into(), func: Rc::new(func) }
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NativeFunction({})", self.name)
+    }
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.func, &other.func)
+    }
+}
+
+// A piece of code: a macro gets its arguments as these, the block after
+// the call as a Block, and gives back the code to put in the call's place
+#[derive(Debug, PartialEq, Clone)]
+pub enum Quote {
+    Expression(Box<Expression>),
+    Block(BlockStatement),
+}
+
+impl Object {
+    pub fn error(msg: impl Into<String>) -> Object {
+        Object::Error(msg.into())
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Object::Error(_))
+    }
+
+    pub fn hash_key(&self) -> Result<HashKey, Object> {
         match self {
-            Object::Integer(val) => write!(f, "{}", val),
-            Object::Boolean(val) => write!(f, "{}", val),
-            Object::Null => write!(f, "null"),
-            Object::ReturnValue(val) => write!(f, "{}", val),
-            Object::Function(fun) => {
-                let params: Vec<String> = fun.parameters.iter().map(|p| p.value.clone()).collect();
-                write!(f, "fn({}) {{ ... }}", params.join(", "))
-            },
+            Object::Integer(i) => Ok(HashKey::Integer(*i)),
+            Object::Boolean(b) => Ok(HashKey::Boolean(*b)),
+            Object::String(s) => Ok(HashKey::String(s.clone())),
+            Object::Char(c) => Ok(HashKey::Char(*c)),
+            other => Err(Object::error(format!("unusable as hash key: {}", other.type_name()))),
         }
     }
-}
\ No newline at end of file
+
+    // Short name used in error messages ("expected int, found string")
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => "int",
+            #[cfg(feature = "bigint")]
+            Object::BigInt(_) => "bigint",
+            Object::Decimal(_) => "decimal",
+            Object::DateTime(_) => "datetime",
+            Object::Boolean(_) => "bool",
+            Object::String(_) => "string",
+            Object::Char(_) => "char",
+            Object::Bytes(_) => "bytes",
+            Object::Buffer(_) => "buffer",
+            Object::Array(_) => "array",
+            Object::Tuple(_) => "tuple",
+            Object::Hash(_) => "hash",
+            Object::Set(_) => "set",
+            Object::Null => "null",
+            Object::ReturnValue(val) | Object::Break(val, _) => val.type_name(),
+            Object::Continue(_) => "null",
+            Object::Function(_) | Object::Native(_) | Object::Builtin(_) | Object::Partial(_) => "function",
+            Object::Generator(_) => "generator",
+            Object::Future(_) => "future",
+            Object::Task(_) => "task",
+            Object::Channel(_) => "channel",
+            Object::Resource(_) => "resource",
+            Object::Quote(_) => "quote",
+            Object::Error(_) => "error",
+        }
+    }
+
+    // Rough bytes this value holds, counting contents but not sharing
+    // (Rc'd state like generators and channels counts as one object)
+    pub fn approx_size(&self) -> usize {
+        let own = std::mem::size_of::<Object>();
+        let key = |k: &HashKey| std::mem::size_of::<HashKey>() + match k {
+            HashKey::String(s) => s.len(),
+            _ => 0,
+        };
+        own + match self {
+            Object::String(s) | Object::Error(s) => s.len(),
+            Object::Bytes(b) => b.len(),
+            Object::Buffer(b) => b.0.borrow().len(),
+            #[cfg(feature = "bigint")]
+            Object::BigInt(b) => b.bits().div_ceil(8) as usize,
+            Object::Array(items) | Object::Tuple(items) => items.iter().map(Object::approx_size).sum(),
+            Object::Hash(pairs) => pairs.iter().map(|(k, v)| key(k) + v.approx_size()).sum(),
+            Object::Set(keys) => keys.iter().map(key).sum(),
+            Object::ReturnValue(val) | Object::Break(val, _) => val.approx_size(),
+            Object::Function(f) => f.parameters.len() * std::mem::size_of::<Identifier>(),
+            Object::Partial(p) => p.function.approx_size() + p.args.iter().map(Object::approx_size).sum::<usize>(),
+            _ => 0,
+        }
+    }
+}
+
+// How much of a big value to print (REPL, print). Past `depth` levels a
+// collection shows as `[...]`; past `items` elements the rest are counted
+// instead of shown, and a function's body is cut after FUNCTION_BODY_CHARS.
+// None is no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Truncation {
+    pub depth: Option<usize>,
+    pub items: Option<usize>,
+}
+
+pub const FUNCTION_BODY_CHARS: usize = 80;
+
+impl Truncation {
+    pub const NONE: Truncation = Truncation { depth: None, items: None };
+}
+
+impl Default for Truncation {
+    fn default() -> Self {
+        Truncation { depth: Some(8), items: Some(100) }
+    }
+}
+
+impl Object {
+    // `[1, "a", {x: 2}]`: strings inside collections are quoted so they
+    // read back the way they'd be written; a string on its own isn't
+    pub fn display(&self, limits: Truncation) -> Pretty<'_> {
+        Pretty { value: self, limits }
+    }
+}
+
+pub struct Pretty<'a> {
+    value: &'a Object,
+    limits: Truncation,
+}
+
+impl fmt::Display for Pretty<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_value(f, self.value, self.limits, 0)
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_value(f, self, Truncation::NONE, 0)
+    }
+}
+
+fn write_value(f: &mut fmt::Formatter, value: &Object, limits: Truncation, depth: usize) -> fmt::Result {
+    let nested = depth + 1;
+    let too_deep = limits.depth.is_some_and(|max| depth >= max);
+    match value {
+        Object::Integer(val) => write!(f, "{}", val),
+        #[cfg(feature = "bigint")]
+        Object::BigInt(val) => write!(f, "{}", val),
+        Object::Decimal(val) => write!(f, "{}", val),
+        Object::DateTime(val) => write!(f, "{}", val),
+        Object::Boolean(val) => write!(f, "{}", val),
+        Object::String(val) if depth == 0 => write!(f, "{}", val),
+        Object::String(val) => write!(f, "{:?}", val),
+        Object::Char(val) if depth == 0 => write!(f, "{}", val),
+        Object::Char(val) => write!(f, "{:?}", val),
+        Object::Array(_) if too_deep => write!(f, "[...]"),
+        Object::Array(elements) => {
+            write!(f, "[")?;
+            write_items(f, elements, limits, |f, e| write_value(f, e, limits, nested))?;
+            write!(f, "]")
+        },
+        Object::Tuple(_) if too_deep => write!(f, "(...)"),
+        Object::Tuple(elements) => {
+            write!(f, "(")?;
+            write_items(f, elements, limits, |f, e| write_value(f, e, limits, nested))?;
+            if elements.len() == 1 {
+                write!(f, ",")?;
+            }
+            write!(f, ")")
+        },
+        Object::Hash(_) if too_deep => write!(f, "{{...}}"),
+        Object::Hash(pairs) => {
+            write!(f, "{{")?;
+            write_items(f, pairs, limits, |f, (k, v)| {
+                write_key(f, k, true)?;
+                write!(f, ": ")?;
+                write_value(f, v, limits, nested)
+            })?;
+            write!(f, "}}")
+        },
+        // Printed the way it's built
+        Object::Set(_) if too_deep => write!(f, "set([...])"),
+        Object::Set(keys) => {
+            write!(f, "set([")?;
+            write_items(f, keys, limits, |f, k| write_key(f, k, false))?;
+            write!(f, "])")
+        },
+        Object::Buffer(buffer) => write!(f, "buffer({:?})", buffer.0.borrow()),
+        Object::Bytes(_) if too_deep => write!(f, "bytes([...])"),
+        Object::Bytes(data) => {
+            write!(f, "bytes([")?;
+            write_items(f, data, limits, |f, b| write!(f, "{}", b))?;
+            write!(f, "])")
+        },
+        Object::Null => write!(f, "null"),
+        Object::ReturnValue(val) | Object::Break(val, _) => write_value(f, val, limits, depth),
+        Object::Continue(_) => write!(f, "null"),
+        Object::Function(fun) => {
+            if fun.is_async {
+                write!(f, "async ")?;
+            }
+            if too_deep {
+                return write!(f, "fn({}) {{ ... }}", param_list(fun));
+            }
+            let body = fun.body.string();
+            match limits.items.and_then(|_| body.char_indices().nth(FUNCTION_BODY_CHARS)) {
+                Some((cut, _)) => write!(f, "fn({}) {{ {} ... }}", param_list(fun), &body[..cut]),
+                None => write!(f, "fn({}) {{ {} }}", param_list(fun), body),
+            }
+        },
+        Object::Native(native) => write!(f, "native fn {}", native.name),
+        Object::Builtin(builtin) => write!(f, "builtin fn {}", builtin.name),
+        Object::Partial(partial) => {
+            write!(f, "partial(")?;
+            write_value(f, &partial.function, limits, nested)?;
+            for arg in &partial.args {
+                write!(f, ", ")?;
+                write_value(f, arg, limits, nested)?;
+            }
+            write!(f, ")")
+        },
+        Object::Generator(_) => write!(f, "generator"),
+        Object::Future(_) => write!(f, "future"),
+        Object::Task(_) => write!(f, "task"),
+        Object::Channel(_) => write!(f, "channel"),
+        Object::Resource(r) => write!(f, "{}", r),
+        Object::Quote(q) => match &**q {
+            Quote::Expression(e) => write!(f, "quote({})", e.string()),
+            Quote::Block(b) => write!(f, "quote {{ {} }}", b.string()),
+        },
+        Object::Error(msg) => write!(f, "ERROR: {}", msg),
+    }
+}
+
+fn param_list(fun: &Function) -> String {
+    fun.parameters.iter().map(|p| p.value.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+// Comma separated, with the elements past the item limit counted
+fn write_items<I: ExactSizeIterator>(
+    f: &mut fmt::Formatter,
+    items: impl IntoIterator<IntoIter = I>,
+    limits: Truncation,
+    mut write: impl FnMut(&mut fmt::Formatter, I::Item) -> fmt::Result,
+) -> fmt::Result {
+    let items = items.into_iter();
+    let total = items.len();
+    let shown = limits.items.map_or(total, |max| max.min(total));
+    for (i, item) in items.take(shown).enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write(f, item)?;
+    }
+    if shown < total {
+        write!(f, "{}... {} more", if shown > 0 { ", " } else { "" }, total - shown)?;
+    }
+    Ok(())
+}
+
+// Hash keys that look like names are printed bare: {x: 2, "a b": 3}
+fn write_key(f: &mut fmt::Formatter, key: &HashKey, bare_names: bool) -> fmt::Result {
+    match key {
+        HashKey::String(s) if bare_names && is_name(s) => write!(f, "{}", s),
+        HashKey::String(s) => write!(f, "{:?}", s),
+        HashKey::Char(c) => write!(f, "{:?}", c),
+        other => write!(f, "{}", other),
+    }
+}
+
+fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(is_ident_start) && chars.all(is_ident_continue)
+}
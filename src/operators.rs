@@ -0,0 +1,82 @@
+// Infix operators added by an embedder, for domain languages built on
+// Nova (formula engines and the like):
+//
+//   let mut nova = Interpreter::new();
+//   nova.register(NativeFunction::new("approx", |args| ...));
+//   nova.define_operator("~=", Precedence::Equals, Associativity::Left, "approx")?;
+//   nova.eval_source("1 ~= 2")?;   // approx(1, 2)
+//
+// An operator is sugar for a call: `a ~= b` parses as `approx(a, b)`, so
+// the function can be a host callback or a script function, whatever the
+// name is bound to when it runs. The lexer matches registered symbols
+// before the built-in ones, longest first.
+use crate::lexer::is_ident_continue;
+pub use crate::parser::Precedence;
+
+// Which way a chain of operators at the same level groups: Left makes
+// `a ~ b ~ c` mean `(a ~ b) ~ c`, Right makes it `a ~ (b ~ c)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomOperator {
+    pub symbol: String,
+    pub precedence: Precedence,
+    pub associativity: Associativity,
+    pub function: String,
+}
+
+// Symbols the lexer already gives a meaning to
+const RESERVED: &[&str] = &["=", "==", "!", "!=", "<", ">", "+", "-", "*", "/", "->", "|", "&&", "||"];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Operators {
+    operators: Vec<CustomOperator>,
+}
+
+impl Operators {
+    pub fn new() -> Self {
+        Operators::default()
+    }
+
+    // Redefining a symbol replaces it. Symbols are made of punctuation and
+    // other non-identifier characters (`~=`, `**`, `≈`), minus the ones
+    // that delimit things (brackets, quotes, `,` `;` `:`).
+    pub fn define(&mut self, operator: CustomOperator) -> Result<(), String> {
+        let symbol = &operator.symbol;
+        let usable = |c: char| !c.is_whitespace() && !is_ident_continue(c) && !"()[]{}\"',;:\\".contains(c);
+        if symbol.is_empty() || !symbol.chars().all(usable) {
+            return Err(format!("can't use {:?} as an operator", symbol));
+        }
+        if RESERVED.contains(&symbol.as_str()) {
+            return Err(format!("{:?} is a built-in operator", symbol));
+        }
+        if symbol.starts_with("//") {
+            return Err(format!("{:?} would start a comment", symbol));
+        }
+        if !matches!(operator.precedence, Precedence::Equals | Precedence::LessGreater | Precedence::Sum | Precedence::Product) {
+            return Err(format!("{:?}: an infix operator's precedence must be Equals, LessGreater, Sum or Product", symbol));
+        }
+        self.operators.retain(|o| o.symbol != operator.symbol);
+        self.operators.push(operator);
+        // Longest first, so `~==` wins over `~=`
+        self.operators.sort_by_key(|o| std::cmp::Reverse(o.symbol.chars().count()));
+        Ok(())
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&CustomOperator> {
+        self.operators.iter().find(|o| o.symbol == symbol)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operators.is_empty()
+    }
+
+    // Longest first
+    pub(crate) fn symbols(&self) -> Vec<String> {
+        self.operators.iter().map(|o| o.symbol.clone()).collect()
+    }
+}
@@ -1,31 +1,74 @@
 // src/parser.rs
+use std::sync::Arc;
 use crate::lexer::Lexer;
 use crate::token::TokenType;
+use crate::span::Span;
+use crate::diagnostics::Diagnostic;
+use crate::runtime;
+use crate::resolver;
+use crate::macros::{self, Macro};
+use crate::features::FEATURES;
+use crate::operators::{Associativity, Operators};
 use crate::ast::{
-    Program, Statement, LetStatement, ReturnStatement, ExpressionStatement,
-    Expression, Identifier, IntegerLiteral, PrefixExpression, InfixExpression
+    Program, Node, NodeId, Statement, LetStatement, LetTupleStatement, ReturnStatement, YieldStatement, DeferStatement,
+    ExpressionStatement, Expression, Identifier, IntegerLiteral, PrefixExpression, InfixExpression,
+    TypeExpr, KeywordArgument, MatchExpression, MatchArm, Pattern, SwitchExpression, SwitchCase,
 };
 
+// Resource limits a `zone(...)` header may set
+pub const ZONE_LIMITS: &[&str] = &["steps", "depth"];
+
+// What a `#pragma` line may name: `strict` turns on strict mode (see
+// Runtime::strict), `feature(a, b)` experimental features (see
+// features.rs)
+pub const PRAGMAS: &[&str] = &["strict", "feature"];
+
+// Deeper expressions are rejected; every later pass (and dropping the
+// tree) recurses once per level
+pub const MAX_NESTING: usize = 500;
+
 // PRECEDENCE LEVELS (Lowest to Highest)
-#[derive(PartialEq, PartialOrd)]
-enum Precedence {
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Precedence {
     Lowest,
+    Or,          // ||
+    And,         // &&
     Equals,      // ==
     LessGreater, // > or <
     Sum,         // +
     Product,     // *
     Prefix,      // -X or !X
     Call,        // myFunction(X)
+    Index,       // array[index]
+}
+
+impl Precedence {
+    // The next level down (custom right-associative operators)
+    pub(crate) fn below(self) -> Precedence {
+        match self {
+            Precedence::Lowest | Precedence::Or => Precedence::Lowest,
+            Precedence::And => Precedence::Or,
+            Precedence::Equals => Precedence::And,
+            Precedence::LessGreater => Precedence::Equals,
+            Precedence::Sum => Precedence::LessGreater,
+            Precedence::Product => Precedence::Sum,
+            Precedence::Prefix => Precedence::Product,
+            Precedence::Call => Precedence::Prefix,
+            Precedence::Index => Precedence::Call,
+        }
+    }
 }
 
 fn get_precedence(t: &TokenType) -> Precedence {
     match t {
+        TokenType::Or => Precedence::Or,
+        TokenType::And => Precedence::And,
         TokenType::Eq | TokenType::NotEq => Precedence::Equals,
         TokenType::LT | TokenType::GT => Precedence::LessGreater,
         TokenType::Plus | TokenType::Minus => Precedence::Sum,
         TokenType::Slash | TokenType::Asterisk => Precedence::Product,
-        TokenType::LParen => Precedence::Call,
         TokenType::LParen => Precedence::Call, // Ensure this maps to Call, not Lowest
+        TokenType::LBracket => Precedence::Index,
         _ => Precedence::Lowest,
     }
 }
@@ -34,52 +77,186 @@ pub struct Parser {
     l: Lexer,
     cur_token: TokenType,
     peek_token: TokenType,
-    pub errors: Vec<String>,
+    cur_span: Span,
+    peek_span: Span,
+    next_id: NodeId,
+    nesting: usize,
+    // Loops around the current position, within the current function, with
+    // their labels
+    loops: Vec<Option<String>>,
+    // A label just parsed, for the loop that follows it
+    label: Option<String>,
+    // Index of the too-deep error, if parsing gave up on the rest
+    gave_up: Option<usize>,
+    operators: Operators,
+    // Macros defined so far; a call to one can take a block after it
+    macros: Vec<Macro>,
+    pub errors: Vec<Diagnostic>,
 }
 
 impl Parser {
-    pub fn new(mut l: Lexer) -> Self {
-        let cur = l.next_token();
-        let peek = l.next_token();
-        Parser { l, cur_token: cur, peek_token: peek, errors: vec![] }
+    pub fn new(l: Lexer) -> Self {
+        Parser::with_operators(l, Operators::default())
+    }
+
+    // Also parses the embedder's custom infix operators
+    pub fn with_operators(mut l: Lexer, operators: Operators) -> Self {
+        l.set_operators(&operators.symbols());
+        let (cur, cur_span) = l.next_spanned();
+        let (peek, peek_span) = l.next_spanned();
+        Parser {
+            l, cur_token: cur, peek_token: peek, cur_span, peek_span, next_id: 1, nesting: 0, loops: vec![], label: None, gave_up: None,
+            operators, macros: vec![], errors: vec![],
+        }
+    }
+
+    // Ids handed to statements start here (used when splicing reparsed
+    // statements into an existing tree)
+    pub fn set_first_id(&mut self, id: NodeId) {
+        self.next_id = id;
+    }
+
+    fn new_id(&mut self) -> NodeId {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
     }
 
     fn next_token(&mut self) {
         self.cur_token = self.peek_token.clone();
-        self.peek_token = self.l.next_token();
+        self.cur_span = self.peek_span;
+        let (peek, peek_span) = self.l.next_spanned();
+        self.peek_token = peek;
+        self.peek_span = peek_span;
+        self.errors.append(&mut self.l.errors);
     }
 
     pub fn parse_program(&mut self) -> Program {
-        let mut program = Program { statements: vec![] };
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("parse").entered();
+        let mut program = Program { statements: vec![], pragmas: vec![], features: vec![], macros: vec![] };
         while self.cur_token != TokenType::EOF {
-            if let Some(stmt) = self.parse_statement() {
+            if self.cur_token == TokenType::Macro {
+                self.parse_macro_definition();
+            } else if let Some(stmt) = self.parse_statement() {
                 program.statements.push(stmt);
             }
             self.next_token();
         }
+        (program.pragmas, program.features) = self.pragmas(&program);
+        if !self.macros.is_empty() {
+            self.errors.extend(macros::expand(&mut program.statements, &self.macros));
+            program.macros = self.macros.iter().map(|m| (m.name.clone(), m.span)).collect();
+        }
         program
     }
 
+    // Pragmas go above all the code, since they change how all of it runs.
+    // The names, then the features turned on.
+    fn pragmas(&mut self, program: &Program) -> (Vec<String>, Vec<String>) {
+        let code = program.statements.first().map_or(usize::MAX, |s| s.span().start);
+        let mut names = vec![];
+        let mut features = vec![];
+        for (name, span) in self.l.pragmas() {
+            let list = name.strip_prefix("feature").map(str::trim_start);
+            if span.start > code {
+                self.errors.push(Diagnostic::error("#pragma must come before any code", *span));
+            } else if let Some(list) = list.filter(|l| l.starts_with('(')) {
+                let Some(list) = list.strip_prefix('(').and_then(|l| l.strip_suffix(')')) else {
+                    self.errors.push(Diagnostic::error("Expected #pragma feature(name, ...)", *span));
+                    continue;
+                };
+                for feature in list.split(',').map(str::trim) {
+                    if !FEATURES.contains(&feature) {
+                        self.errors.push(Diagnostic::error(format!("Unknown feature '{}' (expected one of: {})", feature, FEATURES.join(", ")), *span));
+                    } else if !features.iter().any(|f| f == feature) {
+                        features.push(feature.to_string());
+                    }
+                }
+            } else if name == "feature" {
+                self.errors.push(Diagnostic::error("Expected #pragma feature(name, ...)", *span));
+            } else if !PRAGMAS.contains(&name.as_str()) {
+                self.errors.push(Diagnostic::error(format!("Unknown pragma '{}' (expected one of: {})", name, PRAGMAS.join(", ")), *span));
+            } else if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        (names, features)
+    }
+
     fn parse_statement(&mut self) -> Option<Statement> {
         match self.cur_token {
             TokenType::Let => self.parse_let_statement(),
             TokenType::Return => self.parse_return_statement(),
+            TokenType::Yield => self.parse_yield_statement(),
+            TokenType::Defer => self.parse_defer_statement(),
+            TokenType::Break => self.parse_break_statement(),
+            TokenType::Continue => self.parse_continue_statement(),
+            TokenType::Ident(_) if self.peek_token == TokenType::Colon => self.parse_labelled_statement(),
+            TokenType::Macro => {
+                self.errors.push(Diagnostic::error("Macros can only be defined at the top level", self.cur_span));
+                self.parse_macro_definition();
+                None
+            },
             _ => self.parse_expression_statement(),
         }
     }
 
+    // `macro unless(cond, body) { ... }`: parsed like a function, kept out
+    // of the program's statements
+    fn parse_macro_definition(&mut self) {
+        let start = self.cur_span;
+        if !self.expect_peek_ident() { return; }
+        let TokenType::Ident(name) = self.cur_token.clone() else { return };
+        let Some(Expression::Function(mut function)) = self.parse_function_literal() else { return };
+        function.token = TokenType::Macro;
+        let span = start.to(self.cur_span);
+        if self.macros.iter().any(|m| m.name == name) {
+            self.errors.push(Diagnostic::error(format!("Macro '{}' is already defined", name), span));
+            return;
+        }
+        self.macros.push(Macro { name, function, span });
+    }
+
     // --- STATEMENT PARSING ---
 
     fn parse_let_statement(&mut self) -> Option<Statement> {
         let token = self.cur_token.clone();
-        
+        let start = self.cur_span;
+        let doc = self.l.doc_at(start.start).map(str::to_string);
+
+        // let (a, b) = ...
+        if self.peek_token == TokenType::LParen {
+            self.next_token();
+            let (names, types) = self.parse_function_parameters();
+            if types.iter().any(Option::is_some) {
+                self.errors.push(Diagnostic::error("Type annotations aren't allowed in tuple patterns", self.cur_span));
+            }
+            if !self.expect_peek(TokenType::Assign) { return None; }
+            self.next_token();
+            let value = self.parse_expression(Precedence::Lowest)?;
+            if self.peek_token == TokenType::Semicolon {
+                self.next_token();
+            }
+            let span = start.to(self.cur_span);
+            return Some(Statement::LetTuple(LetTupleStatement { id: self.new_id(), token, names, value, span }));
+        }
+
         if !self.expect_peek_ident() { return None; }
         
         let name_val = match &self.cur_token {
             TokenType::Ident(s) => s.clone(),
             _ => return None,
         };
-        let name = Identifier { token: self.cur_token.clone(), value: name_val };
+        let name = Identifier { token: self.cur_token.clone(), value: name_val, span: self.cur_span, slot: None };
+
+        let annotation = if self.peek_token == TokenType::Colon {
+            self.next_token();
+            self.next_token();
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
 
         if !self.expect_peek(TokenType::Assign) { return None; }
         
@@ -92,11 +269,13 @@ impl Parser {
             self.next_token();
         }
 
-        Some(Statement::Let(LetStatement { token, name, value }))
+        let span = start.to(self.cur_span);
+        Some(Statement::Let(LetStatement { id: self.new_id(), token, name, annotation, doc, value, span }))
     }
 
     fn parse_return_statement(&mut self) -> Option<Statement> {
         let token = self.cur_token.clone();
+        let start = self.cur_span;
         self.next_token();
 
         let return_value = self.parse_expression(Precedence::Lowest)?;
@@ -105,31 +284,188 @@ impl Parser {
             self.next_token();
         }
 
-        Some(Statement::Return(ReturnStatement { token, return_value }))
+        let span = start.to(self.cur_span);
+        Some(Statement::Return(ReturnStatement { id: self.new_id(), token, return_value, span }))
+    }
+
+    fn parse_yield_statement(&mut self) -> Option<Statement> {
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::Lowest)?;
+
+        if self.peek_token == TokenType::Semicolon {
+            self.next_token();
+        }
+
+        let span = start.to(self.cur_span);
+        Some(Statement::Yield(YieldStatement { id: self.new_id(), token, value, span }))
+    }
+
+    fn parse_defer_statement(&mut self) -> Option<Statement> {
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::Lowest)?;
+
+        if self.peek_token == TokenType::Semicolon {
+            self.next_token();
+        }
+
+        let span = start.to(self.cur_span);
+        Some(Statement::Defer(DeferStatement { id: self.new_id(), token, value, span }))
+    }
+
+    fn parse_break_statement(&mut self) -> Option<Statement> {
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        if self.loops.is_empty() {
+            self.errors.push(Diagnostic::error("'break' outside of a loop", start));
+        }
+        // A name is a label if some enclosing loop has it, else a value
+        let label = match &self.peek_token {
+            TokenType::Ident(name) if self.loops.contains(&Some(name.clone())) => {
+                let label = name.clone();
+                self.next_token();
+                Some(label)
+            },
+            _ => None,
+        };
+
+        let value = if matches!(self.peek_token, TokenType::Semicolon | TokenType::RBrace | TokenType::EOF) {
+            None
+        } else {
+            self.next_token();
+            Some(self.parse_expression(Precedence::Lowest)?)
+        };
+
+        if self.peek_token == TokenType::Semicolon {
+            self.next_token();
+        }
+
+        let span = start.to(self.cur_span);
+        Some(Statement::Break(crate::ast::BreakStatement { id: self.new_id(), token, label, value, span }))
+    }
+
+    fn parse_continue_statement(&mut self) -> Option<Statement> {
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        if self.loops.is_empty() {
+            self.errors.push(Diagnostic::error("'continue' outside of a loop", start));
+        }
+        let label = match &self.peek_token {
+            TokenType::Ident(name) => {
+                let label = name.clone();
+                self.next_token();
+                if !self.loops.is_empty() && !self.loops.contains(&Some(label.clone())) {
+                    self.errors.push(Diagnostic::error(format!("No loop labelled '{}' around this", label), self.cur_span));
+                }
+                Some(label)
+            },
+            _ => None,
+        };
+
+        if self.peek_token == TokenType::Semicolon {
+            self.next_token();
+        }
+
+        let span = start.to(self.cur_span);
+        Some(Statement::Continue(crate::ast::ContinueStatement { id: self.new_id(), token, label, span }))
+    }
+
+    // `name: loop { ... }`, or a for or do-while
+    fn parse_labelled_statement(&mut self) -> Option<Statement> {
+        let TokenType::Ident(name) = self.cur_token.clone() else {
+            return None;
+        };
+        let start = self.cur_span;
+        self.next_token();
+        if !matches!(self.peek_token, TokenType::Loop | TokenType::For | TokenType::Do) {
+            let msg = format!("Expected a loop after the label '{}', got {:?}", name, self.peek_token);
+            self.errors.push(Diagnostic::error(msg, self.peek_span));
+            return None;
+        }
+        if self.loops.contains(&Some(name.clone())) {
+            self.errors.push(Diagnostic::error(format!("The label '{}' is already used by an enclosing loop", name), start));
+        }
+        self.next_token();
+        self.label = Some(name);
+        let mut statement = self.parse_expression_statement()?;
+        if let Statement::Expression(s) = &mut statement {
+            s.span = start.to(s.span);
+        }
+        Some(statement)
     }
 
     fn parse_expression_statement(&mut self) -> Option<Statement> {
         let token = self.cur_token.clone();
+        let start = self.cur_span;
         let expression = self.parse_expression(Precedence::Lowest)?;
 
         if self.peek_token == TokenType::Semicolon {
             self.next_token();
         }
 
-        Some(Statement::Expression(ExpressionStatement { token, expression }))
+        let span = start.to(self.cur_span);
+        Some(Statement::Expression(ExpressionStatement { id: self.new_id(), token, expression, span }))
     }
 
     // --- PRATT PARSER CORE ---
 
     fn parse_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        self.nested(|p| p.parse_expression_unguarded(precedence))
+    }
+
+    // Runs a recursive parse one level deeper, giving up past MAX_NESTING
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        if self.nesting >= MAX_NESTING {
+            let msg = format!("Nested too deeply (more than {} levels)", MAX_NESTING);
+            self.errors.push(Diagnostic::error(msg, self.cur_span));
+            self.gave_up = Some(self.errors.len() - 1);
+            // Nothing after this would parse sensibly; skip to the end
+            while self.cur_token != TokenType::EOF {
+                self.next_token();
+            }
+            return None;
+        }
+        self.nesting += 1;
+        let result = runtime::with_stack(|| parse(self));
+        self.nesting -= 1;
+        // Every level unwinding from there reports a missing bracket
+        if self.nesting == 0 && let Some(i) = self.gave_up.take() {
+            self.errors.truncate(i + 1);
+        }
+        result
+    }
+
+    fn parse_expression_unguarded(&mut self, precedence: Precedence) -> Option<Expression> {
         let mut left_exp = match &self.cur_token {
             TokenType::Ident(_) => self.parse_identifier(),
             TokenType::Int(_) => self.parse_integer_literal(),
+            TokenType::Str(_) => self.parse_string_literal(),
+            TokenType::Char(value) => Some(Expression::CharLiteral(crate::ast::CharLiteral { token: self.cur_token.clone(), value: *value })),
+            TokenType::Decimal(value) => {
+                Some(Expression::DecimalLiteral(crate::ast::DecimalLiteral { token: self.cur_token.clone(), value: *value }))
+            },
             TokenType::Bang | TokenType::Minus => self.parse_prefix_expression(),
             TokenType::LParen => self.parse_grouped_expression(),
             TokenType::If => self.parse_if_expression(), // <--- NEW HOOK
             TokenType::Function => self.parse_function_literal(),
+            TokenType::Async => self.parse_async_function(),
+            TokenType::Await => self.parse_await_expression(),
+            TokenType::Pipe | TokenType::Or => self.parse_lambda(),
             TokenType::True | TokenType::False => self.parse_boolean(),
+            TokenType::LBracket => self.parse_array_literal(),
+            TokenType::LBrace => self.parse_hash_literal(),
+            TokenType::Unsafe => self.parse_unsafe_expression(),
+            TokenType::Zone => self.parse_zone_expression(),
+            TokenType::Loop => self.parse_loop_expression(),
+            TokenType::Do => self.parse_do_while_expression(),
+            TokenType::For => self.parse_for_expression(),
+            TokenType::Match => self.parse_match_expression(),
+            TokenType::Switch => self.parse_switch_expression(),
             _ => {
                 self.no_prefix_parse_fn_error(self.cur_token.clone());
                 return None;
@@ -137,17 +473,25 @@ impl Parser {
         };
 
         // 2. Infix Parsing (The loop handles operator precedence)
-        while self.peek_token != TokenType::Semicolon && precedence < get_precedence(&self.peek_token) {
+        while self.peek_token != TokenType::Semicolon && precedence < self.precedence(&self.peek_token) {
             match self.peek_token {
                 TokenType::Plus | TokenType::Minus | TokenType::Slash | TokenType::Asterisk | 
-TokenType::Eq | TokenType::NotEq | TokenType::LT | TokenType::GT => {
+TokenType::Eq | TokenType::NotEq | TokenType::LT | TokenType::GT | TokenType::And | TokenType::Or => {
                     self.next_token();
-                    left_exp = self.parse_infix_expression(left_exp.unwrap());
+                    left_exp = self.parse_infix_expression(left_exp?);
                 },
                 // In parse_expression loop:
                 TokenType::LParen => {
                     self.next_token();
-                    left_exp = self.parse_call_expression(left_exp.unwrap());
+                    left_exp = self.parse_call_expression(left_exp?);
+                },
+                TokenType::LBracket => {
+                    self.next_token();
+                    left_exp = self.parse_index_expression(left_exp?);
+                },
+                TokenType::Operator(_) => {
+                    self.next_token();
+                    left_exp = self.parse_custom_infix_expression(left_exp?);
                 },
                 _ => return left_exp
             }
@@ -158,20 +502,39 @@ TokenType::Eq | TokenType::NotEq | TokenType::LT | TokenType::GT => {
     // --- PREFIX HANDLERS ---
 
     fn parse_identifier(&mut self) -> Option<Expression> {
+        let TokenType::Ident(value) = &self.cur_token else { return None };
+        let ident = Identifier { token: self.cur_token.clone(), value: value.clone(), span: self.cur_span, slot: None };
+        // `quote { ... }`: quote() of a block, which a call can't take otherwise
+        if ident.value == "quote" && self.peek_token == TokenType::LBrace {
+            self.next_token();
+            let token = self.cur_token.clone();
+            let block = self.parse_block_statement();
+            return Some(Expression::Call(crate::ast::CallExpression {
+                token,
+                function: Box::new(Expression::Identifier(ident)),
+                arguments: vec![],
+                keywords: vec![],
+                block: Some(block),
+            }));
+        }
+        Some(Expression::Identifier(ident))
+    }
+
+    fn parse_integer_literal(&mut self) -> Option<Expression> {
         match &self.cur_token {
-            TokenType::Ident(value) => Some(Expression::Identifier(Identifier {
+            TokenType::Int(value) => Some(Expression::IntegerLiteral(IntegerLiteral {
                 token: self.cur_token.clone(),
-                value: value.clone(),
+                value: *value,
             })),
             _ => None,
         }
     }
 
-    fn parse_integer_literal(&mut self) -> Option<Expression> {
+    fn parse_string_literal(&mut self) -> Option<Expression> {
         match &self.cur_token {
-            TokenType::Int(value) => Some(Expression::IntegerLiteral(IntegerLiteral {
+            TokenType::Str(value) => Some(Expression::StringLiteral(crate::ast::StringLiteral {
                 token: self.cur_token.clone(),
-                value: *value,
+                value: value.clone(),
             })),
             _ => None,
         }
@@ -232,6 +595,10 @@ TokenType::Eq | TokenType::NotEq | TokenType::LT | TokenType::GT => {
             self.next_token();
         }
 
+        if self.cur_token == TokenType::EOF {
+            self.errors.push(Diagnostic::error("Expected RBrace, got EOF", self.cur_span));
+        }
+
         crate::ast::BlockStatement { token, statements }
     }
 
@@ -250,36 +617,177 @@ TokenType::Eq | TokenType::NotEq | TokenType::LT | TokenType::GT => {
         }))
     }
     
+    // `(x)` groups, `()`, `(x,)` and `(x, y)` are tuples
     fn parse_grouped_expression(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        if self.peek_token == TokenType::RParen {
+            self.next_token();
+            return Some(Expression::Tuple(crate::ast::TupleLiteral { token, elements: vec![] }));
+        }
+
         self.next_token();
-        let exp = self.parse_expression(Precedence::Lowest);
-        
+        let exp = self.parse_expression(Precedence::Lowest)?;
+        if self.peek_token != TokenType::Comma {
+            if !self.expect_peek(TokenType::RParen) {
+                return None;
+            }
+            return Some(exp);
+        }
+
+        let mut elements = vec![exp];
+        while self.peek_token == TokenType::Comma {
+            self.next_token();
+            if self.peek_token == TokenType::RParen {
+                break;
+            }
+            self.next_token();
+            elements.push(self.parse_expression(Precedence::Lowest)?);
+        }
         if !self.expect_peek(TokenType::RParen) {
             return None;
         }
-        exp
+        Some(Expression::Tuple(crate::ast::TupleLiteral { token, elements }))
+    }
+
+    // `async fn(...) { ... }`
+    fn parse_async_function(&mut self) -> Option<Expression> {
+        if !self.expect_peek(TokenType::Function) {
+            return None;
+        }
+        match self.parse_function_literal()? {
+            Expression::Function(mut function) => {
+                function.is_async = true;
+                Some(Expression::Function(function))
+            },
+            other => Some(other),
+        }
+    }
+
+    fn parse_await_expression(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        self.next_token();
+        let value = self.parse_expression(Precedence::Prefix)?;
+        Some(Expression::Await(crate::ast::AwaitExpression { token, value: Box::new(value) }))
     }
 
     fn parse_function_literal(&mut self) -> Option<Expression> {
         let token = self.cur_token.clone();
 
+        // Generic: fn<T, U>(...)
+        let mut type_params = vec![];
+        if self.peek_token == TokenType::LT {
+            self.next_token();
+            loop {
+                if !self.expect_peek_ident() {
+                    return None;
+                }
+                if let TokenType::Ident(name) = &self.cur_token {
+                    type_params.push(Identifier { token: self.cur_token.clone(), value: name.clone(), span: self.cur_span, slot: None });
+                }
+                if self.peek_token != TokenType::Comma {
+                    break;
+                }
+                self.next_token();
+            }
+            if !self.expect_peek(TokenType::GT) {
+                return None;
+            }
+        }
+
         if !self.expect_peek(TokenType::LParen) {
             return None;
         }
 
-        let parameters = self.parse_function_parameters();
+        let (parameters, param_types) = self.parse_function_parameters();
+
+        let return_type = if self.peek_token == TokenType::Arrow {
+            self.next_token();
+            self.next_token();
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
 
         if !self.expect_peek(TokenType::LBrace) {
             return None;
         }
 
+        // A break can't reach a loop outside the function
+        let loops = std::mem::take(&mut self.loops);
         let body = self.parse_block_statement();
+        self.loops = loops;
 
-        Some(Expression::Function(crate::ast::FunctionLiteral {
+        let mut function = crate::ast::FunctionLiteral {
             token,
+            type_params,
             parameters,
+            param_types,
+            return_type,
             body,
-        }))
+            locals: Arc::default(),
+            captures: vec![],
+            is_async: false,
+        };
+        resolver::assign_slots(&mut function);
+        Some(Expression::Function(function))
+    }
+
+    // --- TYPES ---
+
+    // `int`, `array<T>`, `hash<string, int>`, `fn(int, T) -> bool`.
+    // Starts on the type's first token and ends on its last.
+    fn parse_type(&mut self) -> Option<TypeExpr> {
+        self.nested(Self::parse_type_unguarded)
+    }
+
+    fn parse_type_unguarded(&mut self) -> Option<TypeExpr> {
+        match self.cur_token.clone() {
+            TokenType::Ident(name) => {
+                let mut args = vec![];
+                if self.peek_token == TokenType::LT {
+                    self.next_token();
+                    args = self.parse_type_list(TokenType::GT)?;
+                }
+                Some(TypeExpr::Named { name, args })
+            },
+            TokenType::Function => {
+                if !self.expect_peek(TokenType::LParen) {
+                    return None;
+                }
+                let params = self.parse_type_list(TokenType::RParen)?;
+                if !self.expect_peek(TokenType::Arrow) {
+                    return None;
+                }
+                self.next_token();
+                let ret = self.parse_type()?;
+                Some(TypeExpr::Function { params, ret: Box::new(ret) })
+            },
+            other => {
+                self.errors.push(Diagnostic::error(format!("Expected a type, got {:?}", other), self.cur_span));
+                None
+            },
+        }
+    }
+
+    // Comma separated types after an opening `<` or `(`, up to `end`
+    fn parse_type_list(&mut self, end: TokenType) -> Option<Vec<TypeExpr>> {
+        let mut types = vec![];
+        if self.peek_token == end {
+            self.next_token();
+            return Some(types);
+        }
+        loop {
+            self.next_token();
+            types.push(self.parse_type()?);
+            if self.peek_token != TokenType::Comma {
+                break;
+            }
+            self.next_token();
+        }
+        if !self.expect_peek(end) {
+            return None;
+        }
+        Some(types)
     }
 
     // --- INFIX HANDLERS ---
@@ -287,11 +795,23 @@ TokenType::Eq | TokenType::NotEq | TokenType::LT | TokenType::GT => {
     fn parse_infix_expression(&mut self, left: Expression) -> Option<Expression> {
         let token = self.cur_token.clone();
         let operator = token.to_string();
-        let precedence = get_precedence(&self.cur_token);
+        let precedence = self.precedence(&self.cur_token);
         
         self.next_token();
         let right = self.parse_expression(precedence)?;
-        
+
+        if matches!(token, TokenType::LT | TokenType::GT) && matches!(self.peek_token, TokenType::LT | TokenType::GT) {
+            let mut operands = vec![left, right];
+            let mut operators = vec![operator];
+            while matches!(self.peek_token, TokenType::LT | TokenType::GT) {
+                self.next_token();
+                operators.push(self.cur_token.to_string());
+                self.next_token();
+                operands.push(self.parse_expression(precedence)?);
+            }
+            return Some(Expression::Comparison(crate::ast::ComparisonChain { token, operands, operators }));
+        }
+
         Some(Expression::Infix(InfixExpression {
             token,
             left: Box::new(left),
@@ -300,8 +820,42 @@ TokenType::Eq | TokenType::NotEq | TokenType::LT | TokenType::GT => {
         }))
     }
 
+    // `a ~= b` becomes `f(a, b)`, where f is the function the embedder
+    // mapped the operator to
+    fn parse_custom_infix_expression(&mut self, left: Expression) -> Option<Expression> {
+        let TokenType::Operator(symbol) = &self.cur_token else { return None };
+        let operator = self.operators.get(symbol)?.clone();
+        let function = Identifier {
+            token: TokenType::Ident(operator.function.clone()),
+            value: operator.function,
+            span: self.cur_span,
+            slot: None,
+        };
+        // One level down lets an operator of the same level bind on the right
+        let precedence = match operator.associativity {
+            Associativity::Left => operator.precedence,
+            Associativity::Right => operator.precedence.below(),
+        };
+        self.next_token();
+        let right = self.parse_expression(precedence)?;
+        Some(Expression::Call(crate::ast::CallExpression {
+            token: TokenType::LParen,
+            function: Box::new(Expression::Identifier(function)),
+            arguments: vec![left, right],
+            keywords: vec![],
+            block: None,
+        }))
+    }
+
     // --- HELPERS ---
 
+    fn precedence(&self, t: &TokenType) -> Precedence {
+        match t {
+            TokenType::Operator(symbol) => self.operators.get(symbol).map_or(Precedence::Lowest, |o| o.precedence),
+            t => get_precedence(t),
+        }
+    }
+
     fn expect_peek(&mut self, t: TokenType) -> bool {
         if self.peek_token == t {
             self.next_token();
@@ -315,72 +869,609 @@ TokenType::Eq | TokenType::NotEq | TokenType::LT | TokenType::GT => {
     fn expect_peek_ident(&mut self) -> bool {
         match self.peek_token {
             TokenType::Ident(_) => { self.next_token(); true },
-            _ => { self.errors.push(format!("Expected Ident, got {:?}", self.peek_token)); false }
+            _ => {
+                let msg = format!("Expected Ident, got {:?}", self.peek_token);
+                self.errors.push(Diagnostic::error(msg, self.peek_span));
+                false
+            }
         }
     }
 
     fn peek_error(&mut self, t: &TokenType) {
-        self.errors.push(format!("Expected {:?}, got {:?}", t, self.peek_token));
+        let msg = format!("Expected {:?}, got {:?}", t, self.peek_token);
+        self.errors.push(Diagnostic::error(msg, self.peek_span));
     }
     
     fn no_prefix_parse_fn_error(&mut self, t: TokenType) {
-        self.errors.push(format!("No prefix parse function for {:?}", t));
+        let msg = format!("No prefix parse function for {:?}", t);
+        self.errors.push(Diagnostic::error(msg, self.cur_span));
     }
 
-    fn parse_function_parameters(&mut self) -> Vec<Identifier> {
-        let mut identifiers = vec![];
-
-        // Empty params: fn()
-        if self.peek_token == TokenType::RParen {
+    // `a, b: int, c)`: names with optional type annotations, one entry
+    // in the second list per name
+    // `|x, y| x + y` is shorthand for `fn(x, y) { x + y }`: the body is a
+    // single expression. Parameters may be annotated like fn's.
+    fn parse_lambda(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        let mut parameters = vec![];
+        let mut param_types = vec![];
+        // `|| 7` takes nothing; the lexer reads its two bars as one token
+        while token == TokenType::Pipe && self.peek_token != TokenType::Pipe {
+            if !self.expect_peek_ident() {
+                return None;
+            }
+            if let TokenType::Ident(value) = &self.cur_token {
+                parameters.push(Identifier { token: self.cur_token.clone(), value: value.clone(), span: self.cur_span, slot: None });
+            }
+            let ty = if self.peek_token == TokenType::Colon {
+                self.next_token();
+                self.next_token();
+                Some(self.parse_type()?)
+            } else {
+                None
+            };
+            param_types.push(ty);
+            if self.peek_token != TokenType::Comma {
+                break;
+            }
             self.next_token();
-            return identifiers;
+        }
+        if token == TokenType::Pipe && !self.expect_peek(TokenType::Pipe) {
+            return None;
         }
 
         self.next_token();
+        let start = self.cur_span;
+        let body_token = self.cur_token.clone();
+        let loops = std::mem::take(&mut self.loops);
+        let expression = self.parse_expression(Precedence::Lowest);
+        self.loops = loops;
+        let expression = expression?;
+        let statement = ExpressionStatement { id: self.new_id(), token: body_token, expression, span: start.to(self.cur_span) };
+
+        let mut function = crate::ast::FunctionLiteral {
+            token: token.clone(),
+            type_params: vec![],
+            parameters,
+            param_types,
+            return_type: None,
+            body: crate::ast::BlockStatement { token, statements: vec![Statement::Expression(statement)] },
+            locals: Arc::default(),
+            captures: vec![],
+            is_async: false,
+        };
+        resolver::assign_slots(&mut function);
+        Some(Expression::Function(function))
+    }
 
-        // First param
-        if let TokenType::Ident(value) = &self.cur_token {
-            identifiers.push(Identifier {
-                token: self.cur_token.clone(),
-                value: value.clone(),
-            });
-        }
+    fn parse_function_parameters(&mut self) -> (Vec<Identifier>, Vec<Option<TypeExpr>>) {
+        let mut identifiers = vec![];
+        let mut types = vec![];
 
-        // Subsequent params
-        while self.peek_token == TokenType::Comma {
-            self.next_token(); // skip ident
-            self.next_token(); // skip comma
+        // Empty params: fn()
+        if self.peek_token == TokenType::RParen {
+            self.next_token();
+            return (identifiers, types);
+        }
 
+        loop {
+            self.next_token();
             if let TokenType::Ident(value) = &self.cur_token {
                 identifiers.push(Identifier {
                     token: self.cur_token.clone(),
                     value: value.clone(),
+                    span: self.cur_span,
+                    slot: None,
                 });
+                let ty = if self.peek_token == TokenType::Colon {
+                    self.next_token();
+                    self.next_token();
+                    self.parse_type()
+                } else {
+                    None
+                };
+                types.push(ty);
+            }
+
+            if self.peek_token != TokenType::Comma {
+                break;
+            }
+            self.next_token(); // skip comma
+            // Trailing comma: fn(a, b,)
+            if self.peek_token == TokenType::RParen {
+                break;
             }
         }
 
         if !self.expect_peek(TokenType::RParen) {
-            return vec![];
+            return (vec![], vec![]);
         }
 
-        identifiers
+        (identifiers, types)
     }
 
     fn parse_call_expression(&mut self, function: Expression) -> Option<Expression> {
         let token = self.cur_token.clone();
-        let arguments = self.parse_call_arguments();
-        
+        let (arguments, keywords) = self.parse_call_arguments()?;
+        let block = match &function {
+            Expression::Identifier(name) if self.peek_token == TokenType::LBrace && self.macros.iter().any(|m| m.name == name.value) => {
+                self.next_token();
+                Some(self.parse_block_statement())
+            },
+            _ => None,
+        };
+
         Some(Expression::Call(crate::ast::CallExpression {
             token,
             function: Box::new(function),
             arguments,
+            keywords,
+            block,
+        }))
+    }
+
+    // `(1, 2, y: 3)`: positional arguments, then `name: value` pairs
+    fn parse_call_arguments(&mut self) -> Option<(Vec<Expression>, Vec<KeywordArgument>)> {
+        let mut arguments = vec![];
+        let mut keywords: Vec<KeywordArgument> = vec![];
+        if self.peek_token == TokenType::RParen {
+            self.next_token();
+            return Some((arguments, keywords));
+        }
+
+        loop {
+            self.next_token();
+            if let TokenType::Ident(name) = &self.cur_token && self.peek_token == TokenType::Colon {
+                let name = Identifier { token: self.cur_token.clone(), value: name.clone(), span: self.cur_span, slot: None };
+                if keywords.iter().any(|(k, _)| k.value == name.value) {
+                    let msg = format!("Keyword argument '{}' given twice", name.value);
+                    self.errors.push(Diagnostic::error(msg, self.cur_span));
+                }
+                self.next_token();
+                self.next_token();
+                let value = self.parse_expression(Precedence::Lowest)?;
+                keywords.push((name, value));
+            } else {
+                if !keywords.is_empty() {
+                    self.errors.push(Diagnostic::error("Positional argument after keyword arguments", self.cur_span));
+                }
+                arguments.push(self.parse_expression(Precedence::Lowest)?);
+            }
+            if self.peek_token != TokenType::Comma {
+                break;
+            }
+            self.next_token();
+            if self.peek_token == TokenType::RParen {
+                break;
+            }
+        }
+
+        if !self.expect_peek(TokenType::RParen) {
+            return None;
+        }
+        Some((arguments, keywords))
+    }
+
+    // `a[i]`, or a slice if there's a colon: `a[i:j]`, `a[:j]`, `a[i:]`
+    fn parse_index_expression(&mut self, left: Expression) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        let start = if self.peek_token == TokenType::Colon {
+            None
+        } else {
+            self.next_token();
+            Some(Box::new(self.parse_expression(Precedence::Lowest)?))
+        };
+
+        if self.peek_token == TokenType::Colon {
+            self.next_token();
+            let end = if self.peek_token == TokenType::RBracket {
+                None
+            } else {
+                self.next_token();
+                Some(Box::new(self.parse_expression(Precedence::Lowest)?))
+            };
+            if !self.expect_peek(TokenType::RBracket) {
+                return None;
+            }
+            return Some(Expression::Slice(crate::ast::SliceExpression { token, left: Box::new(left), start, end }));
+        }
+
+        let index = start?;
+        if !self.expect_peek(TokenType::RBracket) {
+            return None;
+        }
+        Some(Expression::Index(crate::ast::IndexExpression {
+            token,
+            left: Box::new(left),
+            index,
         }))
     }
 
-    fn parse_call_arguments(&mut self) -> Vec<Expression> {
+    fn parse_array_literal(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        let elements = self.parse_expression_list(TokenType::RBracket);
+        Some(Expression::Array(crate::ast::ArrayLiteral { token, elements }))
+    }
+
+    fn parse_hash_literal(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        let mut pairs = vec![];
+
+        while self.peek_token != TokenType::RBrace {
+            self.next_token();
+            let key = self.parse_expression(Precedence::Lowest)?;
+            if !self.expect_peek(TokenType::Colon) {
+                return None;
+            }
+            self.next_token();
+            let value = self.parse_expression(Precedence::Lowest)?;
+            pairs.push((key, value));
+
+            if self.peek_token != TokenType::RBrace && !self.expect_peek(TokenType::Comma) {
+                return None;
+            }
+        }
+        if !self.expect_peek(TokenType::RBrace) {
+            return None;
+        }
+        Some(Expression::Hash(crate::ast::HashLiteral { token, pairs }))
+    }
+
+    fn parse_unsafe_expression(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        if !self.expect_peek(TokenType::LBrace) {
+            return None;
+        }
+        let body = self.parse_block_statement();
+        Some(Expression::Unsafe(crate::ast::UnsafeExpression { token, body }))
+    }
+
+    fn parse_loop_expression(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        let label = self.label.take();
+        if !self.expect_peek(TokenType::LBrace) {
+            return None;
+        }
+        self.loops.push(label.clone());
+        let body = self.parse_block_statement();
+        self.loops.pop();
+        Some(Expression::Loop(crate::ast::LoopExpression { token, label, body }))
+    }
+
+    fn parse_do_while_expression(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        let label = self.label.take();
+        if !self.expect_peek(TokenType::LBrace) {
+            return None;
+        }
+        self.loops.push(label.clone());
+        let body = self.parse_block_statement();
+        self.loops.pop();
+        if !self.expect_peek(TokenType::While) || !self.expect_peek(TokenType::LParen) {
+            return None;
+        }
+        self.next_token();
+        let condition = self.parse_expression(Precedence::Lowest)?;
+        if !self.expect_peek(TokenType::RParen) {
+            return None;
+        }
+        Some(Expression::DoWhile(crate::ast::DoWhileExpression { token, label, body, condition: Box::new(condition) }))
+    }
+
+    // match (subject) { pattern [if guard] -> body, ... }. A body is an
+    // expression or a block; a hash literal body needs parentheses.
+    fn parse_match_expression(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        if !self.expect_peek(TokenType::LParen) {
+            return None;
+        }
+        self.next_token();
+        let subject = self.parse_expression(Precedence::Lowest)?;
+        if !self.expect_peek(TokenType::RParen) || !self.expect_peek(TokenType::LBrace) {
+            return None;
+        }
+        let mut arms = vec![];
+        while self.peek_token != TokenType::RBrace {
+            self.next_token();
+            let pattern = self.parse_pattern()?;
+            let guard = if self.peek_token == TokenType::If {
+                self.next_token();
+                self.next_token();
+                Some(self.parse_expression(Precedence::Lowest)?)
+            } else {
+                None
+            };
+            if !self.expect_peek(TokenType::Arrow) {
+                return None;
+            }
+            self.next_token();
+            let body = if self.cur_token == TokenType::LBrace {
+                self.parse_block_statement()
+            } else {
+                let start = self.cur_span;
+                let body_token = self.cur_token.clone();
+                let expression = self.parse_expression(Precedence::Lowest)?;
+                let statement = ExpressionStatement { id: self.new_id(), token: body_token.clone(), expression, span: start.to(self.cur_span) };
+                crate::ast::BlockStatement { token: body_token, statements: vec![Statement::Expression(statement)] }
+            };
+            arms.push(MatchArm { pattern, guard, body });
+
+            if self.peek_token != TokenType::RBrace && !self.expect_peek(TokenType::Comma) {
+                return None;
+            }
+        }
+        self.next_token();
+        Some(Expression::Match(MatchExpression { token, subject: Box::new(subject), arms }))
+    }
+
+    // switch (subject) { case 1, 2: statements... default: statements... }
+    fn parse_switch_expression(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        if !self.expect_peek(TokenType::LParen) {
+            return None;
+        }
+        self.next_token();
+        let subject = self.parse_expression(Precedence::Lowest)?;
+        if !self.expect_peek(TokenType::RParen) || !self.expect_peek(TokenType::LBrace) {
+            return None;
+        }
+        let mut cases: Vec<SwitchCase> = vec![];
+        let mut default = None;
+        let mut seen = vec![];
+        loop {
+            self.next_token();
+            match self.cur_token {
+                TokenType::RBrace => break,
+                TokenType::Case => {
+                    let mut values = vec![];
+                    loop {
+                        self.next_token();
+                        let span = self.cur_span;
+                        let literal = match self.cur_token {
+                            TokenType::Int(_) | TokenType::Str(_) => true,
+                            TokenType::Minus => matches!(self.peek_token, TokenType::Int(_)),
+                            _ => false,
+                        };
+                        if !literal {
+                            let msg = format!("Expected an int or string literal, got {:?}", self.cur_token);
+                            self.errors.push(Diagnostic::error(msg, span));
+                            return None;
+                        }
+                        let value = self.parse_literal_pattern()?;
+                        let key = value.string();
+                        if seen.contains(&key) {
+                            self.errors.push(Diagnostic::error(format!("Duplicate case {}", key), span.to(self.cur_span)));
+                        }
+                        seen.push(key);
+                        values.push(value);
+                        if self.peek_token != TokenType::Comma {
+                            break;
+                        }
+                        self.next_token();
+                    }
+                    if !self.expect_peek(TokenType::Colon) {
+                        return None;
+                    }
+                    let body = self.parse_case_body()?;
+                    cases.push(SwitchCase { values, body });
+                },
+                TokenType::Default => {
+                    if default.is_some() {
+                        self.errors.push(Diagnostic::error("A switch can only have one default", self.cur_span));
+                    }
+                    if !self.expect_peek(TokenType::Colon) {
+                        return None;
+                    }
+                    default = Some(self.parse_case_body()?);
+                },
+                _ => {
+                    let msg = format!("Expected case, default or RBrace, got {:?}", self.cur_token);
+                    self.errors.push(Diagnostic::error(msg, self.cur_span));
+                    return None;
+                },
+            }
+        }
+        Some(Expression::Switch(SwitchExpression { token, subject: Box::new(subject), cases, default }))
+    }
+
+    // The statements up to the next case, default or closing brace. An empty
+    // one would look like C's fallthrough while doing nothing, so it's an
+    // error; cases that share a body list their values together.
+    fn parse_case_body(&mut self) -> Option<crate::ast::BlockStatement> {
+        let token = self.cur_token.clone();
+        let colon = self.cur_span;
+        let mut statements = vec![];
+        while !matches!(self.peek_token, TokenType::Case | TokenType::Default | TokenType::RBrace | TokenType::EOF) {
+            self.next_token();
+            if let Some(stmt) = self.parse_statement() {
+                statements.push(stmt);
+            }
+        }
+        if self.peek_token == TokenType::EOF {
+            self.errors.push(Diagnostic::error("Expected RBrace, got EOF", self.peek_span));
+            return None;
+        }
+        if statements.is_empty() {
+            let msg = "Empty case: cases don't fall through, so list the values together (case 1, 2:)";
+            self.errors.push(Diagnostic::error(msg, colon));
+        }
+        Some(crate::ast::BlockStatement { token, statements })
+    }
+
+    fn parse_pattern(&mut self) -> Option<Pattern> {
+        let pattern = match &self.cur_token {
+            TokenType::Ident(name) if name == "_" => Pattern::Wildcard,
+            TokenType::Ident(name) if name == "null" => Pattern::Null,
+            TokenType::Ident(name) => Pattern::Binding(Identifier { token: self.cur_token.clone(), value: name.clone(), span: self.cur_span, slot: None }),
+            TokenType::Int(_) | TokenType::Str(_) | TokenType::Char(_) | TokenType::Decimal(_) | TokenType::True | TokenType::False => {
+                Pattern::Literal(Box::new(self.parse_literal_pattern()?))
+            },
+            TokenType::Minus if matches!(self.peek_token, TokenType::Int(_) | TokenType::Decimal(_)) => {
+                Pattern::Literal(Box::new(self.parse_literal_pattern()?))
+            },
+            TokenType::LBracket => {
+                let mut elements = vec![];
+                let mut rest = None;
+                while self.peek_token != TokenType::RBracket {
+                    self.next_token();
+                    if self.cur_token == TokenType::DotDot {
+                        rest = Some(Box::new(match &self.peek_token {
+                            TokenType::Ident(name) if name != "_" => {
+                                let name = name.clone();
+                                self.next_token();
+                                Pattern::Binding(Identifier { token: self.cur_token.clone(), value: name, span: self.cur_span, slot: None })
+                            },
+                            _ => Pattern::Wildcard,
+                        }));
+                        break;
+                    }
+                    elements.push(self.parse_pattern()?);
+                    if self.peek_token != TokenType::RBracket && !self.expect_peek(TokenType::Comma) {
+                        return None;
+                    }
+                }
+                if !self.expect_peek(TokenType::RBracket) {
+                    return None;
+                }
+                Pattern::Array(elements, rest)
+            },
+            // (p) is just p; (p,) and (p, q) are tuples
+            TokenType::LParen => {
+                let mut elements = vec![];
+                let mut trailing_comma = false;
+                while self.peek_token != TokenType::RParen {
+                    self.next_token();
+                    elements.push(self.parse_pattern()?);
+                    trailing_comma = false;
+                    if self.peek_token != TokenType::RParen {
+                        if !self.expect_peek(TokenType::Comma) {
+                            return None;
+                        }
+                        trailing_comma = true;
+                    }
+                }
+                self.next_token();
+                if elements.len() == 1 && !trailing_comma {
+                    elements.pop()?
+                } else {
+                    Pattern::Tuple(elements)
+                }
+            },
+            TokenType::LBrace => {
+                let mut pairs = vec![];
+                while self.peek_token != TokenType::RBrace {
+                    self.next_token();
+                    let key = self.parse_literal_pattern()?;
+                    if !self.expect_peek(TokenType::Colon) {
+                        return None;
+                    }
+                    self.next_token();
+                    pairs.push((key, self.parse_pattern()?));
+                    if self.peek_token != TokenType::RBrace && !self.expect_peek(TokenType::Comma) {
+                        return None;
+                    }
+                }
+                self.next_token();
+                Pattern::Hash(pairs)
+            },
+            other => {
+                let msg = format!("Expected a pattern, got {:?}", other);
+                self.errors.push(Diagnostic::error(msg, self.cur_span));
+                return None;
+            },
+        };
+        Some(pattern)
+    }
+
+    // A constant: an int or decimal (maybe negative), string, char or bool
+    fn parse_literal_pattern(&mut self) -> Option<Expression> {
+        match &self.cur_token {
+            TokenType::Int(_) | TokenType::Str(_) | TokenType::Char(_) | TokenType::Decimal(_) | TokenType::True | TokenType::False => {
+                self.parse_expression(Precedence::Prefix)
+            },
+            TokenType::Minus if matches!(self.peek_token, TokenType::Int(_) | TokenType::Decimal(_)) => {
+                self.parse_expression(Precedence::Prefix)
+            },
+            other => {
+                let msg = format!("Expected a literal, got {:?}", other);
+                self.errors.push(Diagnostic::error(msg, self.cur_span));
+                None
+            },
+        }
+    }
+
+    fn parse_for_expression(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        let label = self.label.take();
+        if !self.expect_peek_ident() {
+            return None;
+        }
+        let TokenType::Ident(name) = &self.cur_token else { return None };
+        let variable = Identifier { token: self.cur_token.clone(), value: name.clone(), span: self.cur_span, slot: None };
+        if !self.expect_peek(TokenType::In) {
+            return None;
+        }
+        self.next_token();
+        let iterable = self.parse_expression(Precedence::Lowest)?;
+        if !self.expect_peek(TokenType::LBrace) {
+            return None;
+        }
+        self.loops.push(label.clone());
+        let body = self.parse_block_statement();
+        self.loops.pop();
+        Some(Expression::For(crate::ast::ForExpression { token, label, variable, iterable: Box::new(iterable), body }))
+    }
+
+    fn parse_zone_expression(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        let mut limits = vec![];
+
+        if self.peek_token == TokenType::LParen {
+            self.next_token();
+            while self.peek_token != TokenType::RParen {
+                if !self.expect_peek_ident() {
+                    return None;
+                }
+                let name = match &self.cur_token {
+                    TokenType::Ident(s) => s.clone(),
+                    _ => return None,
+                };
+                if !ZONE_LIMITS.contains(&name.as_str()) {
+                    let msg = format!("Unknown zone limit '{}' (expected one of: {})", name, ZONE_LIMITS.join(", "));
+                    self.errors.push(Diagnostic::error(msg, self.cur_span));
+                }
+                let name = Identifier { token: self.cur_token.clone(), value: name, span: self.cur_span, slot: None };
+
+                if !self.expect_peek(TokenType::Colon) {
+                    return None;
+                }
+                self.next_token();
+                let value = self.parse_expression(Precedence::Lowest)?;
+                limits.push((name, value));
+
+                if self.peek_token == TokenType::Comma {
+                    self.next_token();
+                } else {
+                    break;
+                }
+            }
+            if !self.expect_peek(TokenType::RParen) {
+                return None;
+            }
+        }
+
+        if !self.expect_peek(TokenType::LBrace) {
+            return None;
+        }
+        let body = self.parse_block_statement();
+        Some(Expression::Zone(crate::ast::ZoneExpression { token, limits, body }))
+    }
+
+    // Comma separated expressions up to `end` (call arguments, array elements)
+    fn parse_expression_list(&mut self, end: TokenType) -> Vec<Expression> {
         let mut args = vec![];
 
-        if self.peek_token == TokenType::RParen {
+        if self.peek_token == end {
             self.next_token();
             return args;
         }
@@ -392,13 +1483,16 @@ TokenType::Eq | TokenType::NotEq | TokenType::LT | TokenType::GT => {
 
         while self.peek_token == TokenType::Comma {
             self.next_token();
+            if self.peek_token == end {
+                break;
+            }
             self.next_token();
             if let Some(arg) = self.parse_expression(Precedence::Lowest) {
                 args.push(arg);
             }
         }
 
-        if !self.expect_peek(TokenType::RParen) {
+        if !self.expect_peek(end) {
             return vec![];
         }
 
@@ -0,0 +1,80 @@
+// Source-to-source refactors, built on the resolver. They return text edits
+// for the host (the LSP, an editor plugin) to apply, rather than a tree,
+// so comments and formatting survive.
+//
+//   let edits = refactor::rename(&program, span_of_x, "count")?;
+//   // every `x` that means this binding, and no other, becomes `count`
+//
+// A `let` of a name that's already bound in the same scope binds the same
+// variable again, so rename treats all of a scope's bindings of the name
+// as one: renaming `x` in `let x = 1; let x = x + 1;` renames all three.
+use crate::ast::Program;
+use crate::lexer::Lexer;
+use crate::resolver::{self, Resolution};
+use crate::span::Span;
+use crate::token::TokenType;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub span: Span,
+    pub new_text: String,
+}
+
+// Renames the binding at `span` (its declaration or any use of it) and
+// every reference to it. Fails if there's no binding there, if `new_name`
+// isn't an identifier, or if the new name would change what some name
+// refers to. The edits are sorted by position and don't overlap.
+pub fn rename(program: &Program, span: Span, new_name: &str) -> Result<Vec<TextEdit>, String> {
+    let mut lexer = Lexer::new(new_name.to_string());
+    if !matches!((lexer.next_token(), lexer.next_token()), (TokenType::Ident(name), TokenType::EOF) if name == new_name) {
+        return Err(format!("'{}' isn't a valid name", new_name));
+    }
+    let res = resolver::resolve(program);
+    let Some(target) = res.definition_at(span.start) else {
+        return Err("Nothing to rename here".to_string());
+    };
+    let (old_name, scope) = (target.name.clone(), target.scope);
+    if old_name == new_name {
+        return Ok(vec![]);
+    }
+
+    let group: Vec<usize> = (0..res.definitions.len())
+        .filter(|&d| res.definitions[d].scope == scope && res.definitions[d].name == old_name)
+        .collect();
+    let references: Vec<_> = res.references.iter()
+        .filter(|r| r.definition.is_some_and(|d| group.contains(&d)))
+        .collect();
+
+    if res.definitions.iter().any(|d| d.scope == scope && d.name == new_name) {
+        return Err(format!("'{}' is already bound in the same scope", new_name));
+    }
+    // A use inside a scope that binds the new name would find that instead
+    let shadowed = references.iter().any(|r| {
+        let mut inner = std::iter::successors(Some(r.scope), |&s| res.scopes[s].parent).take_while(|&s| s != scope);
+        inner.any(|s| res.definitions.iter().any(|d| d.scope == s && d.name == new_name))
+    });
+    if shadowed {
+        return Err(format!("'{}' is bound where '{}' is used, so that use would refer to it instead", new_name, old_name));
+    }
+    // A use of the new name in the binding's scope (or one inside it) that
+    // means something from outside would find the renamed binding
+    let captured = res.references.iter()
+        .filter(|r| r.name == new_name && within(&res, r.scope, scope))
+        .any(|r| r.definition.is_none_or(|d| !within(&res, res.definitions[d].scope, scope)));
+    if captured {
+        return Err(format!("'{}' already means something else where '{}' is visible", new_name, old_name));
+    }
+
+    let mut edits: Vec<TextEdit> = group.iter().map(|&d| res.definitions[d].span)
+        .chain(references.iter().map(|r| r.span))
+        .map(|span| TextEdit { span, new_text: new_name.to_string() })
+        .collect();
+    edits.sort_by_key(|e| e.span.start);
+    edits.dedup();
+    Ok(edits)
+}
+
+// `inner` is `outer` or inside it
+fn within(res: &Resolution, inner: usize, outer: usize) -> bool {
+    std::iter::successors(Some(inner), |&s| res.scopes[s].parent).any(|s| s == outer)
+}
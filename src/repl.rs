@@ -0,0 +1,134 @@
+// The interactive loop, over any input and output: `nova repl` hands it
+// stdin and stdout, but a GUI, a test or a socket server can drive it the
+// same way.
+//
+//   let mut repl = Repl::new(Interpreter::new());
+//   repl.run("let x = 2;\nx * 21\n".as_bytes(), &mut out)?;   // out: ">> 2\n>> 42\n>> "
+//
+// One interpreter lives across lines, so bindings do. Besides Nova source, a line can be
+// `:env` (list the bindings), `:null show` / `:null hide`, `:ast <code>`
+// (print how the code parses, without running it) or `:help [name]` (the
+// builtins, or one of them).
+use std::io::{self, BufRead, Write};
+use crate::ast_tree;
+use crate::diagnostics::Diagnostic;
+use crate::environment::Environment;
+use crate::interpreter::{Error, Interpreter};
+use crate::lexer::Lexer;
+use crate::object::Object;
+use crate::parser::Parser;
+use crate::stdlib;
+
+pub const PROMPT: &str = ">> ";
+
+pub struct Repl {
+    nova: Interpreter,
+    // Statements like `let` and calls for their effect evaluate to null;
+    // hiding stops echoing those
+    show_null: bool,
+    // Results so far, for `_1`, `_2`, ...
+    results: usize,
+}
+
+impl Repl {
+    pub fn new(nova: Interpreter) -> Self {
+        Repl { nova, show_null: true, results: 0 }
+    }
+
+    pub fn show_null(&mut self, show: bool) {
+        self.show_null = show;
+    }
+
+    pub fn env(&self) -> &Environment {
+        self.nova.env()
+    }
+
+    // Prompts, reads and answers lines until the input ends
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        write!(output, "{}", PROMPT)?;
+        output.flush()?;
+        for line in input.lines() {
+            self.eval_line(&line?, &mut output)?;
+            write!(output, "{}", PROMPT)?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    // One line of input, with whatever it prints written to `output`
+    pub fn eval_line(&mut self, line: &str, output: &mut impl Write) -> io::Result<()> {
+        match line.trim() {
+            ":env" => {
+                for (name, value) in self.nova.env().iter() {
+                    writeln!(output, "{} = {}", name, value)?;
+                }
+                return Ok(());
+            },
+            ":null show" => {
+                self.show_null = true;
+                return Ok(());
+            },
+            ":null hide" => {
+                self.show_null = false;
+                return Ok(());
+            },
+            ":help" => {
+                for builtin in stdlib::all() {
+                    writeln!(output, "{}", builtin.help())?;
+                }
+                return Ok(());
+            },
+            _ => {},
+        }
+        if let Some(name) = line.trim().strip_prefix(":help ") {
+            match stdlib::lookup(name.trim()) {
+                Some(builtin) => writeln!(output, "{}", builtin.help())?,
+                None => writeln!(output, "no builtin named '{}'", name.trim())?,
+            }
+            return Ok(());
+        }
+
+        if let Some(code) = line.trim_start().strip_prefix(":ast") {
+            let code = code.trim_start();
+            let operators = self.nova.env().runtime().borrow().operators.clone();
+            let mut p = Parser::with_operators(Lexer::new(code.to_string()), operators);
+            let program = p.parse_program();
+            if !p.errors.is_empty() {
+                return write_parse_errors(&p.errors, output);
+            }
+            return write!(output, "{}", ast_tree::render(&program, code));
+        }
+
+        // A runtime error shows as the error value it was
+        let evaluated = match self.nova.eval_source(line) {
+            Ok(value) => value,
+            Err(Error::Runtime(msg)) => Object::Error(msg),
+            Err(Error::Parse(_)) => {
+                let errors: Vec<_> = self.nova.diagnostics().iter().filter(|d| d.is_error()).cloned().collect();
+                return write_parse_errors(&errors, output);
+            },
+            Err(e) => return writeln!(output, "{}", e),
+        };
+        if evaluated == Object::Null && !self.show_null {
+            return Ok(());
+        }
+        let limits = self.nova.env().runtime().borrow().truncation;
+        writeln!(output, "{}", evaluated.display(limits))?;
+
+        // `_` is the last value, `_N` the Nth one this session
+        if !matches!(evaluated, Object::Null | Object::Error(_)) {
+            self.results += 1;
+            self.nova.env_mut().set(format!("_{}", self.results), evaluated.clone());
+            self.nova.env_mut().set("_".to_string(), evaluated);
+        }
+        Ok(())
+    }
+}
+
+// Just the messages, the line being right above them
+fn write_parse_errors(errors: &[Diagnostic], output: &mut impl Write) -> io::Result<()> {
+    for msg in errors {
+        writeln!(output, "\t{}", msg)?;
+    }
+    Ok(())
+}
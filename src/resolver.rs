@@ -0,0 +1,410 @@
+// Name resolution: links every identifier use to the binding it refers to.
+//
+// Scopes are the top level, function bodies and zone blocks. Other blocks
+// (if/else, unsafe) don't open a scope, a `let` inside them binds in the
+// enclosing one, same as the evaluator does.
+//
+// Inside its own scope a use sees the latest binding *before* it. Uses of
+// outer bindings from a function body are looked up when the function runs,
+// so there we also accept a binding made later (this is what makes
+// `let f = fn(n) { f(n - 1) }` resolve). Zones run immediately, so they
+// don't get that leeway.
+use std::collections::HashMap;
+use crate::ast::{Program, Statement, Expression, BlockStatement, FunctionLiteral, Identifier};
+use crate::diagnostics::Diagnostic;
+use crate::macros;
+use crate::runtime;
+use crate::span::Span;
+use crate::stdlib;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingKind {
+    Let,
+    Parameter,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    pub kind: BindingKind,
+    pub span: Span,      // The identifier itself
+    pub statement: Span, // Whole `let` statement (same as span for parameters)
+    pub value: Option<Expression>,
+    pub scope: usize,
+    visible_from: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub name: String,
+    pub span: Span,
+    pub scope: usize,
+    pub definition: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    TopLevel,
+    Function,
+    Zone,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scope {
+    pub parent: Option<usize>,
+    pub kind: ScopeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Resolution {
+    pub scopes: Vec<Scope>,
+    pub definitions: Vec<Definition>,
+    pub references: Vec<Reference>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolution {
+    // The binding under the cursor: either a use of it or its declaration
+    pub fn definition_at(&self, offset: usize) -> Option<&Definition> {
+        if let Some(r) = self.references.iter().find(|r| r.span.contains(offset)) {
+            return r.definition.map(|id| &self.definitions[id]);
+        }
+        self.definitions.iter().find(|d| d.span.contains(offset))
+    }
+
+    pub fn reference_at(&self, span: Span) -> Option<&Reference> {
+        self.references.iter().find(|r| r.span == span)
+    }
+
+    pub fn references_to(&self, definition: usize) -> impl Iterator<Item = &Reference> {
+        self.references.iter().filter(move |r| r.definition == Some(definition))
+    }
+
+    pub fn top_level(&self) -> impl Iterator<Item = &Definition> {
+        self.definitions.iter().filter(|d| d.scope == 0)
+    }
+}
+
+// Gives every variable a function binds a numbered slot, so calls can keep
+// locals in a Vec instead of a map. Runs on each function literal as it's
+// parsed; nested functions have their own frames and were numbered first.
+// Uses of a slotted name before its `let` are numbered too: they read the
+// slot and fall back to the globals while it's empty, which is what a
+// lookup by name did. Whatever is left unslotted is a capture: a local of
+// the function around this one, or a global.
+pub fn assign_slots(function: &mut FunctionLiteral) {
+    let mut slots: HashMap<String, usize> = HashMap::new();
+    let mut locals = vec![];
+    let mut add = |name: &str| {
+        if !slots.contains_key(name) {
+            slots.insert(name.to_string(), locals.len());
+            locals.push(name.to_string());
+        }
+    };
+    for p in &function.parameters {
+        add(&p.value);
+    }
+    function.body.visit_variables_mut(&mut |ident, binds| if binds { add(&ident.value) });
+
+    for p in &mut function.parameters {
+        p.slot = slots.get(&p.value).copied();
+    }
+    let mut captures: Vec<Identifier> = vec![];
+    function.body.visit_variables_mut(&mut |ident, _| {
+        ident.slot = slots.get(&ident.value).copied();
+        if ident.slot.is_none() && !captures.iter().any(|c| c.value == ident.value) {
+            captures.push(ident.clone());
+        }
+    });
+    function.locals = locals.into();
+    function.captures = captures;
+}
+
+pub fn resolve(program: &Program) -> Resolution {
+    resolve_with(program, &[])
+}
+
+// For a program that runs where `globals` are bound already (by the files
+// of a project that run before it); using one isn't a warning
+pub fn resolve_with(program: &Program, globals: &[String]) -> Resolution {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("resolve").entered();
+    let mut r = Resolver {
+        res: Resolution::default(),
+        current: 0,
+        globals,
+    };
+    r.res.scopes.push(Scope { parent: None, kind: ScopeKind::TopLevel });
+    for stmt in &program.statements {
+        r.resolve_statement(stmt);
+    }
+    r.link();
+    r.res
+}
+
+struct Resolver<'a> {
+    res: Resolution,
+    current: usize,
+    globals: &'a [String],
+}
+
+impl Resolver<'_> {
+    fn define(&mut self, def: Definition) {
+        self.res.definitions.push(def);
+    }
+
+    // Returns the scope to restore afterwards
+    fn enter_scope(&mut self, kind: ScopeKind) -> usize {
+        let outer = self.current;
+        self.res.scopes.push(Scope { parent: Some(outer), kind });
+        self.current = self.res.scopes.len() - 1;
+        outer
+    }
+
+    fn resolve_statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Let(s) => {
+                self.resolve_expression(&s.value);
+                self.define(Definition {
+                    name: s.name.value.clone(),
+                    kind: BindingKind::Let,
+                    span: s.name.span,
+                    statement: s.span,
+                    value: Some(s.value.clone()),
+                    scope: self.current,
+                    visible_from: s.span.end,
+                });
+            },
+            Statement::LetTuple(s) => {
+                self.resolve_expression(&s.value);
+                // With a tuple literal on the right each name gets its element
+                let elements = match &s.value {
+                    Expression::Tuple(t) if t.elements.len() == s.names.len() => Some(&t.elements),
+                    _ => None,
+                };
+                for (i, name) in s.names.iter().enumerate() {
+                    self.define(Definition {
+                        name: name.value.clone(),
+                        kind: BindingKind::Let,
+                        span: name.span,
+                        statement: s.span,
+                        value: elements.map(|e| e[i].clone()),
+                        scope: self.current,
+                        visible_from: s.span.end,
+                    });
+                }
+            },
+            Statement::Return(s) => self.resolve_expression(&s.return_value),
+            Statement::Yield(s) => self.resolve_expression(&s.value),
+            Statement::Defer(s) => self.resolve_expression(&s.value),
+            Statement::Break(s) => {
+                if let Some(value) = &s.value {
+                    self.resolve_expression(value);
+                }
+            },
+            Statement::Continue(_) => {},
+            Statement::Expression(s) => self.resolve_expression(&s.expression),
+        }
+    }
+
+    fn resolve_block(&mut self, block: &BlockStatement) {
+        for stmt in &block.statements {
+            self.resolve_statement(stmt);
+        }
+    }
+
+    fn resolve_expression(&mut self, exp: &Expression) {
+        runtime::with_stack(|| self.resolve_expression_unguarded(exp))
+    }
+
+    fn resolve_expression_unguarded(&mut self, exp: &Expression) {
+        match exp {
+            Expression::Identifier(ident) => {
+                self.res.references.push(Reference {
+                    name: ident.value.clone(),
+                    span: ident.span,
+                    scope: self.current,
+                    definition: None,
+                });
+            },
+            Expression::IntegerLiteral(_) | Expression::StringLiteral(_) | Expression::CharLiteral(_) | Expression::DecimalLiteral(_) |
+            Expression::Boolean(_) => {},
+            Expression::Prefix(p) => self.resolve_expression(&p.right),
+            Expression::Await(a) => self.resolve_expression(&a.value),
+            Expression::Infix(i) => {
+                self.resolve_expression(&i.left);
+                self.resolve_expression(&i.right);
+            },
+            Expression::Comparison(c) => {
+                for operand in &c.operands {
+                    self.resolve_expression(operand);
+                }
+            },
+            Expression::If(ie) => {
+                self.resolve_expression(&ie.condition);
+                self.resolve_block(&ie.consequence);
+                if let Some(alt) = &ie.alternative {
+                    self.resolve_block(alt);
+                }
+            },
+            Expression::Function(fl) => {
+                let outer = self.enter_scope(ScopeKind::Function);
+
+                for param in &fl.parameters {
+                    self.define(Definition {
+                        name: param.value.clone(),
+                        kind: BindingKind::Parameter,
+                        span: param.span,
+                        statement: param.span,
+                        value: None,
+                        scope: self.current,
+                        visible_from: 0,
+                    });
+                }
+                self.resolve_block(&fl.body);
+
+                self.current = outer;
+            },
+            Expression::Call(c) if c.is_quote() => {
+                self.resolve_expression(&c.function);
+                for e in macros::unquoted(c) {
+                    self.resolve_expression(&e);
+                }
+            },
+            Expression::Call(c) => {
+                self.resolve_expression(&c.function);
+                for arg in c.all_arguments() {
+                    self.resolve_expression(arg);
+                }
+            },
+            Expression::Array(a) => {
+                for el in &a.elements {
+                    self.resolve_expression(el);
+                }
+            },
+            Expression::Tuple(t) => {
+                for el in &t.elements {
+                    self.resolve_expression(el);
+                }
+            },
+            Expression::Hash(h) => {
+                for (key, value) in &h.pairs {
+                    self.resolve_expression(key);
+                    self.resolve_expression(value);
+                }
+            },
+            Expression::Index(ie) => {
+                self.resolve_expression(&ie.left);
+                self.resolve_expression(&ie.index);
+            },
+            Expression::Slice(se) => {
+                self.resolve_expression(&se.left);
+                for bound in [&se.start, &se.end].into_iter().flatten() {
+                    self.resolve_expression(bound);
+                }
+            },
+            Expression::Unsafe(u) => self.resolve_block(&u.body),
+            Expression::Loop(l) => self.resolve_block(&l.body),
+            Expression::DoWhile(d) => {
+                self.resolve_block(&d.body);
+                self.resolve_expression(&d.condition);
+            },
+            Expression::For(fe) => {
+                self.resolve_expression(&fe.iterable);
+                self.define(Definition {
+                    name: fe.variable.value.clone(),
+                    kind: BindingKind::Let,
+                    span: fe.variable.span,
+                    statement: fe.variable.span,
+                    value: None,
+                    scope: self.current,
+                    visible_from: fe.variable.span.end,
+                });
+                self.resolve_block(&fe.body);
+            },
+            // Patterns only hold literals; their names bind like a for's
+            Expression::Match(me) => {
+                self.resolve_expression(&me.subject);
+                for arm in &me.arms {
+                    for name in arm.pattern.bindings() {
+                        self.define(Definition {
+                            name: name.value.clone(),
+                            kind: BindingKind::Let,
+                            span: name.span,
+                            statement: name.span,
+                            value: None,
+                            scope: self.current,
+                            visible_from: name.span.end,
+                        });
+                    }
+                    if let Some(guard) = &arm.guard {
+                        self.resolve_expression(guard);
+                    }
+                    self.resolve_block(&arm.body);
+                }
+            },
+            Expression::Switch(se) => {
+                self.resolve_expression(&se.subject);
+                for body in se.bodies() {
+                    self.resolve_block(body);
+                }
+            },
+            Expression::Zone(z) => {
+                for (_, value) in &z.limits {
+                    self.resolve_expression(value);
+                }
+                let outer = self.enter_scope(ScopeKind::Zone);
+                self.resolve_block(&z.body);
+                self.current = outer;
+            },
+        }
+    }
+
+    // Second pass: every binding is known now, so uses inside function
+    // bodies can see bindings made after the function literal.
+    fn link(&mut self) {
+        for i in 0..self.res.references.len() {
+            let (name, offset, scope) = {
+                let r = &self.res.references[i];
+                (r.name.clone(), r.span.start, r.scope)
+            };
+            let found = self.lookup(&name, offset, scope);
+            self.res.references[i].definition = found;
+            if found.is_none() && stdlib::lookup(&name).is_none() && !self.globals.contains(&name) {
+                let span = self.res.references[i].span;
+                self.res.diagnostics.push(Diagnostic::warning("undefined-variable", format!("undefined variable '{}'", name), span));
+            }
+        }
+    }
+
+    fn lookup(&self, name: &str, offset: usize, scope: usize) -> Option<usize> {
+        let mut current = Some(scope);
+        // Set once the lookup leaves a function body
+        let mut deferred = false;
+        while let Some(s) = current {
+            let candidates = self.res.definitions.iter().enumerate()
+                .filter(|(_, d)| d.scope == s && d.name == name);
+
+            let mut before: Option<usize> = None;
+            let mut after: Option<usize> = None;
+            for (id, d) in candidates {
+                if d.visible_from <= offset {
+                    before = Some(id); // Definitions are stored in source order
+                } else if after.is_none() {
+                    after = Some(id);
+                }
+            }
+
+            if before.is_some() {
+                return before;
+            }
+            if deferred && after.is_some() {
+                return after;
+            }
+
+            deferred |= self.res.scopes[s].kind == ScopeKind::Function;
+            current = self.res.scopes[s].parent;
+        }
+        None
+    }
+}
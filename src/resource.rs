@@ -0,0 +1,89 @@
+// Host handles a script holds on to: open files and sockets. close() lets
+// one go early; otherwise it closes when the last copy of it is dropped,
+// so a handle the script forgets about doesn't stay open for the rest of
+// the process. `defer close(f);` closes it when the function finishes.
+//
+// Copies share the handle, across threads too: `spawn(serve, conn)` hands
+// the task the same connection, and closing it there closes it here.
+use std::fmt;
+use std::fs::File;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub enum Handle {
+    File(File),
+    Listener(TcpListener),
+    Stream(TcpStream),
+}
+
+#[derive(Clone)]
+pub struct Resource {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    id: u64,
+    kind: &'static str,
+    // None once closed
+    handle: Mutex<Option<Handle>>,
+}
+
+impl Resource {
+    pub fn new(handle: Handle) -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        let kind = match handle {
+            Handle::File(_) => "file",
+            Handle::Listener(_) => "listener",
+            Handle::Stream(_) => "socket",
+        };
+        let inner = Inner { id: NEXT.fetch_add(1, Ordering::Relaxed), kind, handle: Mutex::new(Some(handle)) };
+        Resource { inner: Arc::new(inner) }
+    }
+
+    // "file", "listener" or "socket", closed or not
+    pub fn kind(&self) -> &'static str {
+        self.inner.kind
+    }
+
+    // Calls f with the open handle. The handle stays locked meanwhile, so
+    // anything that blocks should work on a try_clone() of it.
+    pub fn with<T>(&self, name: &str, f: impl FnOnce(&mut Handle) -> T) -> Result<T, String> {
+        match self.inner.handle.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            Some(handle) => Ok(f(handle)),
+            None => Err(format!("{}: {} is closed", name, self)),
+        }
+    }
+
+    // False if it was already closed. A connection is shut down too, so a
+    // task blocked reading it (or the other side) sees the end.
+    pub fn close(&self) -> bool {
+        match self.inner.handle.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            Some(Handle::Stream(stream)) => {
+                let _ = stream.shutdown(Shutdown::Both);
+                true
+            },
+            Some(_) => true,
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} #{}", self.inner.kind, self.inner.id)
+    }
+}
+
+impl fmt::Debug for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Resource({})", self)
+    }
+}
+
+// Copies are the same resource; separately opened ones never are
+impl PartialEq for Resource {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
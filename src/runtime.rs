@@ -0,0 +1,503 @@
+use std::fmt;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use crate::cache::ParseCache;
+use crate::future::Future;
+use crate::hooks::HookList;
+use crate::object::{Function, Object, Truncation};
+use crate::operators::Operators;
+use crate::span::Span;
+use crate::stdlib::random::Rng;
+use crate::trace::Trace;
+
+// Interpreter-wide state that isn't a variable binding: what the script is
+// allowed to do, resources builtins hand out and execution budgets. One
+// Runtime is shared (through Environment) by the top level and every
+// function call.
+
+// Sandbox switches for builtins that reach outside the interpreter.
+// Everything is off by default; the host (CLI flags, embedder) opts in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Capabilities {
+    pub ffi: bool,     // ffi_load / ffi_call
+    pub process: bool, // spawn
+    pub time: bool,    // time / clock_ms / sleep
+    pub fs: bool,      // read_bytes / write_bytes
+    pub net: bool,     // http_get / http_post
+}
+
+impl Capabilities {
+    pub fn none() -> Self {
+        Capabilities::default()
+    }
+
+    pub fn all() -> Self {
+        Capabilities { ffi: true, process: true, time: true, fs: true, net: true }
+    }
+
+    // Everything either allows
+    pub fn union(self, other: Capabilities) -> Self {
+        Capabilities {
+            ffi: self.ffi || other.ffi,
+            process: self.process || other.process,
+            time: self.time || other.time,
+            fs: self.fs || other.fs,
+            net: self.net || other.net,
+        }
+    }
+}
+
+// Which values count as false in conditions and to `!`, `&&` and `||`.
+// By default only false and null do, like Ruby; hosts whose users expect
+// Python's rules can make zero and empty values false too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Truthiness {
+    pub zero: bool,  // 0 and 0d
+    pub empty: bool, // "", and an empty array, tuple, hash, set, bytes or buffer
+}
+
+impl Truthiness {
+    pub const RUBY: Truthiness = Truthiness { zero: false, empty: false };
+    pub const PYTHON: Truthiness = Truthiness { zero: true, empty: true };
+
+    pub fn is_true(&self, value: &Object) -> bool {
+        match value {
+            Object::Null | Object::Boolean(false) => false,
+            Object::Integer(0) => !self.zero,
+            Object::Decimal(d) => !(self.zero && d.is_zero()),
+            Object::String(s) => !(self.empty && s.is_empty()),
+            Object::Array(items) | Object::Tuple(items) => !(self.empty && items.is_empty()),
+            Object::Hash(pairs) => !(self.empty && pairs.is_empty()),
+            Object::Set(keys) => !(self.empty && keys.is_empty()),
+            Object::Bytes(b) => !(self.empty && b.is_empty()),
+            Object::Buffer(b) => !(self.empty && b.0.borrow().is_empty()),
+            _ => true,
+        }
+    }
+}
+
+// Limits requested by a `zone(...)` header, relative to zone entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ZoneLimits {
+    pub steps: Option<u64>,    // Statements executed
+    pub depth: Option<usize>,  // Nested function calls
+}
+
+// What a zone allows. Its steps are counted in a meter that tasks started
+// inside it charge too; the depth limit is absolute for this thread and
+// already narrowed by the zones around it.
+#[derive(Debug, Clone)]
+struct Budget {
+    steps: Option<Arc<StepMeter>>,
+    depth: Option<usize>,
+}
+
+#[derive(Debug)]
+struct StepMeter {
+    spent: AtomicU64,
+    limit: u64,
+}
+
+pub const DEFAULT_MAX_DEPTH: usize = 10_000;
+
+// Tasks (and pmap workers) a runtime and everything started from it may
+// have running at once
+pub const MAX_TASKS: usize = 64;
+
+// What a runtime and the tasks started from it, and theirs, draw on
+// together
+#[derive(Debug, Default)]
+struct Pool {
+    // Bytes bound in all of them, against the one memory limit
+    memory: AtomicUsize,
+    tasks: AtomicUsize,
+}
+
+// What a task's runtime takes over from the one that starts it (see
+// task.rs): the same sandbox, settings and cancel flag, the zones it was
+// started in and the memory limit. Its steps count against those zones
+// and its bindings against the limit along with everyone else's, so a
+// script can't get more out of either by doing the work on other threads.
+#[derive(Debug, Clone)]
+pub struct Inherited {
+    capabilities: Capabilities,
+    zones: Vec<Budget>,
+    memory_limit: Option<usize>,
+    pool: Arc<Pool>,
+    operators: Operators,
+    strict: bool,
+    truthiness: Truthiness,
+    truncation: Truncation,
+    cancel: CancelHandle,
+}
+
+impl Inherited {
+    // Room for up to `wanted` more tasks, at least one; Err when MAX_TASKS
+    // are already running
+    pub(crate) fn reserve_tasks(&self, wanted: usize) -> Result<TaskSlots, String> {
+        let tasks = &self.pool.tasks;
+        let mut running = tasks.load(Ordering::Relaxed);
+        loop {
+            let count = wanted.min(MAX_TASKS.saturating_sub(running));
+            if count == 0 {
+                return Err(format!("too many tasks running (at most {})", MAX_TASKS));
+            }
+            match tasks.compare_exchange_weak(running, running + count, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return Ok(TaskSlots { pool: self.pool.clone(), count }),
+                Err(now) => running = now,
+            }
+        }
+    }
+}
+
+// Running tasks counted by Inherited::reserve_tasks, until it's dropped
+pub(crate) struct TaskSlots {
+    pool: Arc<Pool>,
+    pub count: usize,
+}
+
+impl Drop for TaskSlots {
+    fn drop(&mut self) {
+        self.pool.tasks.fetch_sub(self.count, Ordering::Relaxed);
+    }
+}
+
+// How far into unsafe blocks, zones and calls evaluation is. A panic
+// unwinds past the code that would have left them, so the interpreter
+// takes one of these first and rolls back to it (see Interpreter::guard).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Checkpoint {
+    unsafe_depth: usize,
+    depth: usize,
+    frames: usize,
+    zones: usize,
+}
+
+// Stops a running evaluation from another thread. The evaluator looks at
+// it before every statement (and loop iteration) and fails with
+// "cancelled" once it's set. recv gives up waiting too; other builtins that
+// block (sleep, join) finish first.
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle {
+    flag: Arc<AtomicBool>,
+}
+
+impl CancelHandle {
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    // Ready to cancel the next evaluation
+    pub fn clear(&self) {
+        self.flag.store(false, Ordering::Relaxed);
+    }
+}
+
+// A call of a Nova function that hasn't returned yet
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    // What it was called by (`add` in `add(1, 2)`); None when it wasn't a
+    // name (`fs[0](1)`, a callback a builtin calls)
+    pub name: Option<String>,
+    pub function: Rc<Function>,
+}
+
+// The bindings of the call a runtime error happened in, as they were at
+// the time, each value shortened to SHOWN_CHARS. Displays as
+// `in add(a = 1, b = "x"), with total = 3`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameDump {
+    pub name: Option<String>,
+    pub parameters: Vec<(String, String)>,
+    // Bound by the body so far, in the order they were declared
+    pub locals: Vec<(String, String)>,
+}
+
+pub const SHOWN_CHARS: usize = 40;
+
+impl FrameDump {
+    pub(crate) fn new(frame: &CallFrame, bindings: Vec<(String, Object)>) -> Self {
+        let limits = Truncation { depth: Some(2), items: Some(5) };
+        let shown = |value: &Object| {
+            let text = match value {
+                Object::String(_) | Object::Char(_) => format!("{:?}", value.to_string()),
+                _ => value.display(limits).to_string(),
+            };
+            match text.char_indices().nth(SHOWN_CHARS) {
+                Some((end, _)) => format!("{}...", &text[..end]),
+                None => text,
+            }
+        };
+        let is_parameter = |name: &str| frame.function.parameters.iter().any(|p| p.value == name);
+        let (parameters, locals) = bindings.into_iter()
+            .map(|(name, value)| (name, shown(&value)))
+            .partition(|(name, _)| is_parameter(name));
+        FrameDump { name: frame.name.clone(), parameters, locals }
+    }
+}
+
+impl fmt::Display for FrameDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |bindings: &[(String, String)]| {
+            bindings.iter().map(|(name, value)| format!("{} = {}", name, value)).collect::<Vec<_>>().join(", ")
+        };
+        write!(f, "in {}({})", self.name.as_deref().unwrap_or("fn"), list(&self.parameters))?;
+        if !self.locals.is_empty() {
+            write!(f, ", with {}", list(&self.locals))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Runtime {
+    pub capabilities: Capabilities,
+    // How many `unsafe` blocks are currently executing
+    pub unsafe_depth: usize,
+    // Statements executed so far and current call depth
+    pub steps: u64,
+    pub depth: usize,
+    // The function calls being evaluated, innermost last (a generator or
+    // async body resuming isn't one)
+    pub frames: Vec<CallFrame>,
+    zones: Vec<Budget>,
+    // Approximate bytes held by variable bindings in every live scope
+    // (see charge_memory), and the most the host allows (None: unlimited)
+    memory: usize,
+    pub memory_limit: Option<usize>,
+    pool: Arc<Pool>,
+    // Calls nested deeper than this fail even outside zones
+    pub max_depth: usize,
+    // How much of big values print() and the REPL show
+    pub truncation: Truncation,
+    // Where print() writes when the host captures it (see golden.rs);
+    // None is stdout
+    pub output: Option<String>,
+    // Generator behind rand() and friends, seeded on first use
+    rng: Option<Rng>,
+    // Source of the statement the error now unwinding came from; cleared
+    // when the next statement starts, so a handled error doesn't linger
+    pub error_span: Option<Span>,
+    // What was bound in the call that statement is in, if it's in one
+    pub error_frame: Option<FrameDump>,
+    // Strict mode (`--strict`, `#pragma strict`): an unbound name, calling
+    // something that isn't a function and an operator applied to types it
+    // doesn't take are errors instead of null
+    pub strict: bool,
+    // What conditions treat as false
+    pub truthiness: Truthiness,
+    // The host's observers (see hooks.rs)
+    pub hooks: HookList,
+    pub cancel: CancelHandle,
+    // Async function calls that haven't finished (see future.rs)
+    pub futures: Vec<Future>,
+    // Infix operators the host defined (see operators.rs), for the code
+    // parsed while running too: eval(), templates
+    pub operators: Operators,
+    // Code eval() has parsed, in case it's evaluated again
+    pub parse_cache: ParseCache,
+    // Non-deterministic inputs being recorded or replayed (see trace.rs)
+    pub trace: Trace,
+    // Libraries opened by ffi_load; scripts refer to them by index
+    #[cfg(feature = "ffi")]
+    pub libraries: Vec<libloading::Library>,
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Runtime::new(Capabilities::none())
+    }
+}
+
+impl Runtime {
+    pub fn new(capabilities: Capabilities) -> Self {
+        Runtime {
+            capabilities,
+            unsafe_depth: 0,
+            steps: 0,
+            depth: 0,
+            frames: vec![],
+            zones: vec![],
+            memory: 0,
+            memory_limit: None,
+            pool: Arc::default(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            truncation: Truncation::default(),
+            output: None,
+            rng: None,
+            error_span: None,
+            error_frame: None,
+            strict: false,
+            truthiness: Truthiness::default(),
+            hooks: HookList::default(),
+            cancel: CancelHandle::default(),
+            futures: vec![],
+            operators: Operators::new(),
+            parse_cache: ParseCache::default(),
+            trace: Trace::Off,
+            #[cfg(feature = "ffi")]
+            libraries: vec![],
+        }
+    }
+
+    pub fn inherited(&self) -> Inherited {
+        Inherited {
+            capabilities: self.capabilities,
+            // The task's calls start again from depth 0
+            zones: self.zones.iter().map(|b| Budget {
+                steps: b.steps.clone(),
+                depth: b.depth.map(|limit| limit.saturating_sub(self.depth)),
+            }).collect(),
+            memory_limit: self.memory_limit,
+            pool: self.pool.clone(),
+            operators: self.operators.clone(),
+            strict: self.strict,
+            truthiness: self.truthiness,
+            truncation: self.truncation,
+            cancel: self.cancel.clone(),
+        }
+    }
+
+    pub fn inheriting(inherited: Inherited) -> Self {
+        let mut runtime = Runtime::new(inherited.capabilities);
+        runtime.zones = inherited.zones;
+        runtime.memory_limit = inherited.memory_limit;
+        runtime.pool = inherited.pool;
+        runtime.operators = inherited.operators;
+        runtime.strict = inherited.strict;
+        runtime.truthiness = inherited.truthiness;
+        runtime.truncation = inherited.truncation;
+        runtime.cancel = inherited.cancel;
+        runtime
+    }
+
+    // Fails only when replaying a trace that has no seed next
+    pub(crate) fn rng(&mut self) -> Result<&mut Rng, String> {
+        if self.rng.is_none() {
+            let seed = self.trace.input("seed", || Ok(Rng::entropy_seed() as i64))?;
+            self.rng = Some(Rng::from_seed(seed as u64));
+        }
+        Ok(self.rng.get_or_insert_with(Rng::default))
+    }
+
+    pub(crate) fn seed_rng(&mut self, seed: u64) {
+        self.rng = Some(Rng::from_seed(seed));
+    }
+
+    pub(crate) fn checkpoint(&self) -> Checkpoint {
+        Checkpoint { unsafe_depth: self.unsafe_depth, depth: self.depth, frames: self.frames.len(), zones: self.zones.len() }
+    }
+
+    pub(crate) fn roll_back(&mut self, checkpoint: Checkpoint) {
+        self.unsafe_depth = checkpoint.unsafe_depth;
+        self.depth = checkpoint.depth;
+        self.frames.truncate(checkpoint.frames);
+        self.zones.truncate(checkpoint.zones);
+    }
+
+    pub fn enter_zone(&mut self, limits: ZoneLimits) {
+        let depth = limits.depth.map(|n| self.depth.saturating_add(n));
+        let outer = self.zones.last().and_then(|b| b.depth);
+        self.zones.push(Budget {
+            steps: limits.steps.map(|limit| Arc::new(StepMeter { spent: AtomicU64::new(0), limit })),
+            depth: min_limit(depth, outer),
+        });
+    }
+
+    pub fn exit_zone(&mut self) {
+        self.zones.pop();
+    }
+
+    // Called before every statement
+    pub fn step(&mut self) -> Result<(), String> {
+        if self.cancel.is_cancelled() {
+            return Err("cancelled".to_string());
+        }
+        self.steps += 1;
+        self.error_span = None;
+        self.error_frame = None;
+        // Every zone it's in, which tasks may be charging at the same time
+        for meter in self.zones.iter().filter_map(|b| b.steps.as_deref()) {
+            if meter.spent.fetch_add(1, Ordering::Relaxed) >= meter.limit {
+                return Err("zone: step limit exceeded".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    // Pair every successful enter_call with exit_call
+    pub fn enter_call(&mut self) -> Result<(), String> {
+        match self.zones.last().and_then(|b| b.depth) {
+            Some(limit) if self.depth >= limit => Err("zone: call depth limit exceeded".to_string()),
+            _ if self.depth >= self.max_depth => {
+                Err(format!("stack overflow: more than {} nested calls", self.max_depth))
+            },
+            _ => {
+                self.depth += 1;
+                Ok(())
+            },
+        }
+    }
+
+    pub fn exit_call(&mut self) {
+        self.depth -= 1;
+    }
+
+    // Bytes in use against the memory limit: this runtime's bindings and
+    // those of the tasks started from it, and of the runtime that started
+    // it if it's a task
+    pub fn memory(&self) -> usize {
+        self.pool.memory.load(Ordering::Relaxed)
+    }
+
+    // Counts bindings made and dropped; `removed` can be more than is held
+    pub fn charge_memory(&mut self, added: usize, removed: usize) {
+        let before = self.memory;
+        self.memory = (self.memory + added).saturating_sub(removed);
+        if self.memory > before {
+            self.pool.memory.fetch_add(self.memory - before, Ordering::Relaxed);
+        } else {
+            self.pool.memory.fetch_sub(before - self.memory, Ordering::Relaxed);
+        }
+    }
+
+    // Called after binding values; the bindings stay, but evaluation stops
+    pub fn check_memory(&self) -> Result<(), String> {
+        match self.memory_limit {
+            Some(limit) if self.memory() > limit => {
+                Err(format!("out of memory: {} bytes in use, limit is {}", self.memory(), limit))
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+// A task's bindings that outlive its scopes are let go with it
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        self.pool.memory.fetch_sub(self.memory, Ordering::Relaxed);
+    }
+}
+
+fn min_limit<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+// Runs f with some stack to spare, continuing on a freshly allocated
+// segment when the current one runs low. The recursive passes (parser,
+// checker, evaluator) go through this so deeply nested scripts can't
+// overflow the host's stack. wasm32 can't switch stacks.
+pub fn with_stack<R>(f: impl FnOnce() -> R) -> R {
+    #[cfg(not(target_arch = "wasm32"))]
+    return stacker::maybe_grow(128 * 1024, 2 * 1024 * 1024, f);
+    #[cfg(target_arch = "wasm32")]
+    return f();
+}
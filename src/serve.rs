@@ -0,0 +1,112 @@
+// `nova serve`: evaluates programs sent over TCP, for editor integrations
+// and poking at a running setup from elsewhere.
+//
+// Each connection gets its own interpreter, so bindings persist for the
+// connection and nothing is shared between clients. Requests are one per
+// line:
+//
+//   plain:  let x = 20;            ->  20
+//           x * 2                  ->  40
+//           nope(                  ->  1:6: error: ...
+//   --json: {"id": 1, "code": "x * 2"}
+//                                  ->  {"id":1,"value":"40","type":"int"}
+//                                      {"id":2,"error":"..."}
+//
+// Scripts run sandboxed: no capabilities at all, a memory limit, and a
+// step budget per request so `loop {}` can't tie up a thread. Tasks a
+// request spawns run under the same budget and limit (what's left of them
+// when they start), so they can't either. Output from print() goes to the
+// server's stdout, not to the client.
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use crate::interpreter::{self, Error, Interpreter};
+use crate::json::{self, Json};
+use crate::object::Object;
+use crate::runtime::{Capabilities, ZoneLimits};
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7070";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub json: bool,
+    pub memory_limit: Option<usize>,
+    pub max_steps: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config { json: false, memory_limit: Some(64 * 1024 * 1024), max_steps: Some(10_000_000) }
+    }
+}
+
+// Runs until the listener fails; one thread per connection
+pub fn serve(listener: TcpListener, config: Config) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        std::thread::spawn(move || {
+            let reader = match stream.try_clone() {
+                Ok(s) => BufReader::new(s),
+                Err(_) => return,
+            };
+            // A client hanging up mid-reply is its own business
+            let _ = handle(reader, stream, config);
+        });
+    }
+    Ok(())
+}
+
+// One connection's worth of requests, until the input ends
+pub fn handle(input: impl BufRead, mut output: impl Write, config: Config) -> io::Result<()> {
+    let mut nova = Interpreter::with_config(interpreter::Config {
+        capabilities: Capabilities::none(),
+        memory_limit: config.memory_limit,
+        type_check: true,
+        strict: false,
+        truthiness: Default::default(),
+    });
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = if config.json { json_reply(&mut nova, &line, config).to_string() } else { plain_reply(&mut nova, &line, config) };
+        writeln!(output, "{}", reply)?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+fn plain_reply(nova: &mut Interpreter, code: &str, config: Config) -> String {
+    match eval(nova, code, config) {
+        Ok(value) => value.display(nova.env().runtime().borrow().truncation).to_string(),
+        Err(Error::Runtime(msg)) => format!("ERROR: {}", msg),
+        Err(e) => e.to_string(),
+    }
+}
+
+fn json_reply(nova: &mut Interpreter, line: &str, config: Config) -> Json {
+    let request = match json::parse(line) {
+        Ok(request) => request,
+        Err(e) => return Json::object(vec![("id", Json::Null), ("error", format!("invalid request: {}", e).into())]),
+    };
+    let id = request.get("id").cloned().unwrap_or(Json::Null);
+    let Some(code) = request.get("code").and_then(Json::as_str) else {
+        return Json::object(vec![("id", id), ("error", "invalid request: missing \"code\"".into())]);
+    };
+    match eval(nova, code, config) {
+        Ok(value) => {
+            let shown = value.display(nova.env().runtime().borrow().truncation).to_string();
+            Json::object(vec![("id", id), ("value", shown.into()), ("type", value.type_name().into())])
+        },
+        Err(e) => Json::object(vec![("id", id), ("error", e.to_string().into())]),
+    }
+}
+
+// Inside a zone, so the step budget is per request
+fn eval(nova: &mut Interpreter, code: &str, config: Config) -> Result<Object, Error> {
+    let runtime = nova.env().runtime().clone();
+    runtime.borrow_mut().enter_zone(ZoneLimits { steps: config.max_steps, depth: None });
+    let result = nova.eval_source(code);
+    runtime.borrow_mut().exit_zone();
+    result
+}
@@ -0,0 +1,77 @@
+// Source locations. Offsets are in chars (the lexer works on Vec<char>),
+// positions are 0-based line/column pairs for humans and editors.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    // Smallest span covering both
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+// Maps char offsets to line/column and back
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+    len: usize,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let mut line_starts = vec![0];
+        let mut len = 0;
+        for (i, ch) in source.chars().enumerate() {
+            if ch == '\n' {
+                line_starts.push(i + 1);
+            }
+            len = i + 1;
+        }
+        LineIndex { line_starts, len }
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.len);
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        Position { line, column: offset - self.line_starts[line] }
+    }
+
+    pub fn offset(&self, pos: Position) -> usize {
+        match self.line_starts.get(pos.line) {
+            Some(start) => (start + pos.column).min(self.len),
+            None => self.len,
+        }
+    }
+
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        self.line_starts.get(line).copied()
+    }
+}
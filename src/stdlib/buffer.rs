@@ -0,0 +1,66 @@
+// Building strings a piece at a time:
+//
+//   let out = buffer();
+//   for n in [1, 2, 3] { push(out, n, ", "); }
+//   to_string(out)   // "1, 2, 3, "
+//
+// `s = s + piece` in a loop copies everything built so far on every pass;
+// a buffer is shared by every name bound to it, so push() appends in
+// place, in amortized constant time. Strings go in as they are and any
+// other value the way print() shows it.
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::environment::Environment;
+use crate::object::{Buffer, Object};
+use super::args;
+
+// buffer() or buffer(initial)
+pub fn buffer(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let initial = if argv.is_empty() { Ok((String::new(),)) } else { args::<(String,)>("buffer", &argv) };
+    match initial {
+        Ok((text,)) => Object::Buffer(Buffer(Rc::new(RefCell::new(text)))),
+        Err(e) => e,
+    }
+}
+
+pub fn push(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let Some((Object::Buffer(buffer), values)) = argv.split_first() else {
+        return match argv.first() {
+            Some(other) => Object::error(format!("push: argument 1: expected buffer, found {}", other.type_name())),
+            None => Object::error("push: wrong number of arguments: expected at least 1, found 0"),
+        };
+    };
+    let limits = env.runtime().borrow().truncation;
+    let mut text = buffer.0.borrow_mut();
+    let before = text.len();
+    for value in values {
+        match value {
+            Object::String(s) => text.push_str(s),
+            // Pushing a buffer onto itself would borrow it twice
+            Object::Buffer(other) if Rc::ptr_eq(&buffer.0, &other.0) => {
+                let copy = text.clone();
+                text.push_str(&copy);
+            },
+            other => text.push_str(&other.display(limits).to_string()),
+        }
+    }
+    // The bindings' sizes were counted when they were made, so the growth
+    // is charged here, once, however many names the buffer is bound to
+    let mut runtime = env.runtime().borrow_mut();
+    runtime.charge_memory(text.len() - before, 0);
+    if let Err(msg) = runtime.check_memory() {
+        return Object::Error(msg);
+    }
+    drop(text);
+    Object::Buffer(buffer.clone())
+}
+
+pub fn to_string(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let limits = env.runtime().borrow().truncation;
+    match args::<(Object,)>("to_string", &argv) {
+        Ok((Object::Buffer(buffer),)) => Object::String(buffer.0.borrow().clone()),
+        Ok((Object::String(s),)) => Object::String(s),
+        Ok((other,)) => Object::String(other.display(limits).to_string()),
+        Err(e) => e,
+    }
+}
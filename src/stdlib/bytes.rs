@@ -0,0 +1,158 @@
+// Binary data.
+//
+//     unsafe { read_bytes("logo.png")[0:4] }   // bytes([137, 80, 78, 71])
+//     encode("hé")                             // bytes([104, 195, 169])
+//     decode(bytes([104, 233]), "latin-1")     // "hé"
+//
+// A bytes value indexes to ints (0-255), slices to bytes and joins with
+// `+`. `encode` and `decode` default to UTF-8 and also know utf-16le,
+// utf-16be, latin-1 and ascii. Reading and writing files needs the
+// filesystem capability (`--allow-fs`) and an unsafe block.
+//
+// open_file(path, mode) keeps a file open, for reading it a piece at a
+// time or appending as a script goes: mode "r" reads, "w" replaces the
+// file and "a" appends to it. The result is a resource for read(), write()
+// and close() (see tcp.rs).
+use std::fs::OpenOptions;
+use crate::environment::Environment;
+use crate::object::Object;
+use crate::resource::{Handle, Resource};
+use super::{args, require};
+
+// bytes(array of ints)
+pub fn bytes(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (values,) = match args::<(Vec<i64>,)>("bytes", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let mut out = Vec::with_capacity(values.len());
+    for value in values {
+        match u8::try_from(value) {
+            Ok(b) => out.push(b),
+            Err(_) => return Object::error(format!("bytes: {} is not a byte (0-255)", value)),
+        }
+    }
+    Object::Bytes(out)
+}
+
+pub fn read_bytes(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (path,) = match args::<(String,)>("read_bytes", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    if let Err(e) = require(env, "read_bytes", |c| c.fs, "--allow-fs") {
+        return e;
+    }
+    match std::fs::read(&path) {
+        Ok(data) => Object::Bytes(data),
+        Err(e) => Object::error(format!("read_bytes: {}: {}", path, e)),
+    }
+}
+
+pub fn open_file(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (path, mode) = match args::<(String, String)>("open_file", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let mut options = OpenOptions::new();
+    match mode.as_str() {
+        "r" => options.read(true),
+        "w" => options.write(true).create(true).truncate(true),
+        "a" => options.append(true).create(true),
+        _ => return Object::error(format!("open_file: unknown mode {:?} (expected \"r\", \"w\" or \"a\")", mode)),
+    };
+    if let Err(e) = require(env, "open_file", |c| c.fs, "--allow-fs") {
+        return e;
+    }
+    match options.open(&path) {
+        Ok(file) => Object::Resource(Resource::new(Handle::File(file))),
+        Err(e) => Object::error(format!("open_file: {}: {}", path, e)),
+    }
+}
+
+// Replaces the file if it exists
+pub fn write_bytes(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (path, data) = match args::<(String, Object)>("write_bytes", &argv) {
+        Ok((path, Object::Bytes(data))) => (path, data),
+        Ok((_, other)) => return Object::error(format!("write_bytes: expected bytes, found {}", other.type_name())),
+        Err(e) => return e,
+    };
+    if let Err(e) = require(env, "write_bytes", |c| c.fs, "--allow-fs") {
+        return e;
+    }
+    match std::fs::write(&path, data) {
+        Ok(()) => Object::Null,
+        Err(e) => Object::error(format!("write_bytes: {}: {}", path, e)),
+    }
+}
+
+// encode(string) or encode(string, encoding)
+pub fn encode(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let parsed = if argv.len() == 1 {
+        args::<(String,)>("encode", &argv).map(|(s,)| (s, "utf-8".to_string()))
+    } else {
+        args::<(String, String)>("encode", &argv)
+    };
+    let (text, encoding) = match parsed {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    // Chars past what a one-byte encoding can hold
+    let narrow = |max: u32| {
+        text.chars()
+            .map(|c| u8::try_from(c as u32).ok().filter(|b| u32::from(*b) <= max).ok_or(c))
+            .collect::<Result<Vec<u8>, char>>()
+            .map_err(|c| Object::error(format!("encode: {:?} can't be encoded as {}", c, encoding)))
+    };
+    let encoded = match encoding.as_str() {
+        "utf-8" => Ok(text.as_bytes().to_vec()),
+        "utf-16le" => Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+        "utf-16be" => Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+        "latin-1" => narrow(0xff),
+        "ascii" => narrow(0x7f),
+        _ => Err(unknown("encode", &encoding)),
+    };
+    encoded.map_or_else(|e| e, Object::Bytes)
+}
+
+// decode(bytes) or decode(bytes, encoding). Invalid input is an error,
+// not replacement characters.
+pub fn decode(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let parsed = if argv.len() == 1 {
+        args::<(Object,)>("decode", &argv).map(|(b,)| (b, "utf-8".to_string()))
+    } else {
+        args::<(Object, String)>("decode", &argv)
+    };
+    let (data, encoding) = match parsed {
+        Ok((Object::Bytes(data), encoding)) => (data, encoding),
+        Ok((other, _)) => return Object::error(format!("decode: expected bytes, found {}", other.type_name())),
+        Err(e) => return e,
+    };
+    let utf16 = |unit: fn([u8; 2]) -> u16| {
+        if data.len() % 2 != 0 {
+            return Err(Object::error(format!("decode: {} input has an odd number of bytes", encoding)));
+        }
+        let units = data.chunks_exact(2).map(|pair| unit([pair[0], pair[1]]));
+        char::decode_utf16(units)
+            .collect::<Result<String, _>>()
+            .map_err(|e| Object::error(format!("decode: invalid {}: unpaired surrogate {:#x}", encoding, e.unpaired_surrogate())))
+    };
+    let decoded = match encoding.as_str() {
+        "utf-8" => std::str::from_utf8(&data)
+            .map(str::to_string)
+            .map_err(|e| Object::error(format!("decode: invalid utf-8 at byte {}", e.valid_up_to()))),
+        "utf-16le" => utf16(u16::from_le_bytes),
+        "utf-16be" => utf16(u16::from_be_bytes),
+        "latin-1" => Ok(data.iter().map(|&b| char::from(b)).collect()),
+        "ascii" => match data.iter().position(|b| !b.is_ascii()) {
+            Some(i) => Err(Object::error(format!("decode: byte {} at {} is not ascii", data[i], i))),
+            None => Ok(data.iter().map(|&b| char::from(b)).collect()),
+        },
+        _ => Err(unknown("decode", &encoding)),
+    };
+    decoded.map_or_else(|e| e, Object::String)
+}
+
+fn unknown(name: &str, encoding: &str) -> Object {
+    Object::error(format!("{}: unknown encoding {:?} (expected utf-8, utf-16le, utf-16be, latin-1 or ascii)", name, encoding))
+}
@@ -0,0 +1,168 @@
+// Sets, hashes, membership and sizes. A set holds the same kinds of values
+// a hash can be keyed by (ints, bools, strings) and is built from an array:
+//
+//     let seen = set([1, 2, 2, 3]);   // set([1, 2, 3])
+//     contains(seen, 2)               // true
+//
+//     let config = merge({"port": 80, "tls": false}, {"port": 8080});
+//     keys(config)                    // ["port", "tls"]
+//     delete(config, "tls")           // {port: 8080}
+//
+// The set and hash operations return new values and leave their arguments
+// alone; `let config = merge(config, extra);` rebinds the name to the
+// result. A hash keeps its keys in the order they were first added (a
+// merged-in key that was already there keeps its place), and sort_keys()
+// puts them in order: ints, then bools, strings and chars.
+use std::collections::BTreeSet;
+use indexmap::IndexMap;
+use crate::compare;
+use crate::environment::Environment;
+use crate::object::{HashKey, Object};
+use super::args;
+
+type Keys = BTreeSet<HashKey>;
+type Pairs = IndexMap<HashKey, Object>;
+
+// set(), set(array) or set(set)
+pub fn set(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match argv.as_slice() {
+        [] => Object::Set(Keys::new()),
+        [Object::Set(keys)] => Object::Set(keys.clone()),
+        [Object::Array(elements)] => {
+            let mut keys = Keys::new();
+            for element in elements {
+                match element.hash_key() {
+                    Ok(key) => keys.insert(key),
+                    Err(_) => return Object::error(format!("set: unusable as set element: {}", element.type_name())),
+                };
+            }
+            Object::Set(keys)
+        },
+        [other] => Object::error(format!("set: expected array, found {}", other.type_name())),
+        _ => Object::error(format!("set: wrong number of arguments: expected 0 or 1, found {}", argv.len())),
+    }
+}
+
+pub fn union(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(Keys, Keys)>("union", &argv) {
+        Ok((a, b)) => Object::Set(a.union(&b).cloned().collect()),
+        Err(e) => e,
+    }
+}
+
+pub fn intersection(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(Keys, Keys)>("intersection", &argv) {
+        Ok((a, b)) => Object::Set(a.intersection(&b).cloned().collect()),
+        Err(e) => e,
+    }
+}
+
+// Elements of the first set that aren't in the second
+pub fn difference(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(Keys, Keys)>("difference", &argv) {
+        Ok((a, b)) => Object::Set(a.difference(&b).cloned().collect()),
+        Err(e) => e,
+    }
+}
+
+// contains(collection, value): set member, array element (deep equality),
+// hash key, substring or character
+pub fn contains(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (collection, value): (Object, Object) = match args("contains", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let found = match (&collection, &value) {
+        (Object::Array(elements), _) => elements.iter().any(|e| compare::equals(e, &value)),
+        (Object::String(s), Object::String(sub)) => s.contains(sub.as_str()),
+        (Object::String(s), Object::Char(c)) => s.contains(*c),
+        // Values that can't be keys can't be members either
+        (Object::Set(keys), _) => value.hash_key().is_ok_and(|k| keys.contains(&k)),
+        (Object::Hash(pairs), _) => value.hash_key().is_ok_and(|k| pairs.contains_key(&k)),
+        (Object::String(_), _) => {
+            return Object::error(format!("contains: can't search a string for {}", value.type_name()));
+        },
+        _ => return Object::error(format!("contains: expected set, array, hash or string, found {}", collection.type_name())),
+    };
+    Object::Boolean(found)
+}
+
+// Elements, keys, characters of a string (not its UTF-8 bytes) or bytes
+pub fn len(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (value,): (Object,) = match args("len", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let n = match &value {
+        Object::Array(elements) | Object::Tuple(elements) => elements.len(),
+        Object::Hash(pairs) => pairs.len(),
+        Object::Set(keys) => keys.len(),
+        Object::String(s) => s.chars().count(),
+        Object::Bytes(data) => data.len(),
+        Object::Buffer(buffer) => buffer.0.borrow().chars().count(),
+        other => return Object::error(format!("len: expected a string, bytes or collection, found {}", other.type_name())),
+    };
+    Object::Integer(n as i64)
+}
+
+pub fn keys(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(Pairs,)>("keys", &argv) {
+        Ok((pairs,)) => pairs.into_keys().map(Object::from).collect::<Vec<_>>().into(),
+        Err(e) => e,
+    }
+}
+
+pub fn values(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(Pairs,)>("values", &argv) {
+        Ok((pairs,)) => Object::Array(pairs.into_values().collect()),
+        Err(e) => e,
+    }
+}
+
+// A value that can't be a key is never one, like with contains()
+pub fn has_key(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(Pairs, Object)>("has_key", &argv) {
+        Ok((pairs, key)) => Object::Boolean(key.hash_key().is_ok_and(|k| pairs.contains_key(&k))),
+        Err(e) => e,
+    }
+}
+
+// The hash without the key; deleting a key that isn't there is fine
+pub fn delete(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (mut pairs, key) = match args::<(Pairs, Object)>("delete", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    match key.hash_key() {
+        Ok(k) => {
+            pairs.shift_remove(&k);
+            Object::Hash(pairs)
+        },
+        Err(_) => Object::error(format!("delete: unusable as hash key: {}", key.type_name())),
+    }
+}
+
+pub fn sort_keys(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(Pairs,)>("sort_keys", &argv) {
+        Ok((mut pairs,)) => {
+            pairs.sort_keys();
+            Object::Hash(pairs)
+        },
+        Err(e) => e,
+    }
+}
+
+// merge(a, b, ...): all the pairs, the later hash winning on a shared key
+pub fn merge(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    if argv.is_empty() {
+        return Object::error("merge: wrong number of arguments: expected at least 1, found 0");
+    }
+    let mut merged = Pairs::new();
+    for (i, arg) in argv.into_iter().enumerate() {
+        match arg {
+            Object::Hash(pairs) => merged.extend(pairs),
+            other => return Object::error(format!("merge: argument {}: expected hash, found {}", i + 1, other.type_name())),
+        }
+    }
+    Object::Hash(merged)
+}
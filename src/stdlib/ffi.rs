@@ -0,0 +1,164 @@
+// Calling C functions in shared libraries:
+//
+//   unsafe {
+//       let libc = ffi_load("libc.so.6");
+//       ffi_call(libc, "abs", [-5])                  // 5
+//       ffi_call(libc, "getenv", ["HOME"], "string") // "/home/me"
+//   }
+//
+// Needs the `ffi` cargo feature, the ffi capability (`--allow-ffi`) and an
+// unsafe block around the calls.
+//
+// Nova has no floats or structs, so the calling convention is kept
+// simple: up to six arguments, each passed as one machine word (ints as
+// int64_t, bools as 0/1, strings as NUL-terminated char*, null as NULL).
+// The result is read back as an int (default), a C string or nothing:
+// the optional fourth argument is "int", "string" or "void". Getting the
+// signature wrong is undefined behaviour, same as in C.
+use crate::environment::Environment;
+use crate::object::Object;
+use super::{args, require};
+
+pub fn ffi_load(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (path,): (String,) = match args("ffi_load", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    if let Err(e) = require(env, "ffi_load", |c| c.ffi, "--allow-ffi") {
+        return e;
+    }
+    imp::load(env, &path)
+}
+
+pub fn ffi_call(env: &mut Environment, mut argv: Vec<Object>) -> Object {
+    // The return type is optional
+    let ret = if argv.len() == 4 {
+        match argv.pop() {
+            Some(Object::String(s)) => s,
+            Some(other) => return Object::error(format!("ffi_call: argument 4: expected string, found {}", other.type_name())),
+            None => unreachable!(),
+        }
+    } else {
+        "int".to_string()
+    };
+    let (handle, symbol, call_args): (i64, String, Vec<Object>) = match args("ffi_call", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    if let Err(e) = require(env, "ffi_call", |c| c.ffi, "--allow-ffi") {
+        return e;
+    }
+    imp::call(env, handle, &symbol, &call_args, &ret)
+}
+
+#[cfg(feature = "ffi")]
+mod imp {
+    use std::ffi::{CStr, CString, c_char};
+    use libloading::{Library, Symbol};
+    use crate::environment::Environment;
+    use crate::object::Object;
+
+    const MAX_ARGS: usize = 6;
+
+    pub fn load(env: &mut Environment, path: &str) -> Object {
+        // SAFETY: loading runs the library's initialisers; the script was
+        // granted the ffi capability, which is exactly this trust
+        match unsafe { Library::new(path) } {
+            Ok(lib) => {
+                let mut runtime = env.runtime().borrow_mut();
+                runtime.libraries.push(lib);
+                Object::Integer(runtime.libraries.len() as i64 - 1)
+            },
+            Err(e) => Object::error(format!("ffi_load: {}", e)),
+        }
+    }
+
+    pub fn call(env: &mut Environment, handle: i64, symbol: &str, args: &[Object], ret: &str) -> Object {
+        if !matches!(ret, "int" | "string" | "void") {
+            return Object::error(format!("ffi_call: unknown return type '{}' (int, string or void)", ret));
+        }
+        if args.len() > MAX_ARGS {
+            return Object::error(format!("ffi_call: at most {} arguments are supported", MAX_ARGS));
+        }
+
+        // Strings must outlive the call, keep them here
+        let mut strings = vec![];
+        let mut words = vec![];
+        for (i, arg) in args.iter().enumerate() {
+            let word = match arg {
+                Object::Integer(v) => *v,
+                Object::Boolean(b) => *b as i64,
+                Object::Null => 0,
+                Object::String(s) => match CString::new(s.as_str()) {
+                    Ok(c) => {
+                        let ptr = c.as_ptr() as i64;
+                        strings.push(c);
+                        ptr
+                    },
+                    Err(_) => return Object::error(format!("ffi_call: argument {} contains a NUL byte", i + 1)),
+                },
+                other => return Object::error(format!("ffi_call: can't pass {} to C", other.type_name())),
+            };
+            words.push(word);
+        }
+
+        let runtime = env.runtime().borrow();
+        let Some(lib) = usize::try_from(handle).ok().and_then(|h| runtime.libraries.get(h)) else {
+            return Object::error(format!("ffi_call: invalid library handle {}", handle));
+        };
+        // SAFETY: the symbol is only used as an address below
+        let func: Symbol<unsafe extern "C" fn()> = match unsafe { lib.get(symbol.as_bytes()) } {
+            Ok(f) => f,
+            Err(e) => return Object::error(format!("ffi_call: {}", e)),
+        };
+        let addr = *func as *const ();
+
+        // SAFETY: none really; the script promises the C signature matches
+        // what it passes, as with any FFI
+        let result = unsafe { call_words(addr, &words) };
+        drop(strings);
+
+        match ret {
+            "void" => Object::Null,
+            "string" if result == 0 => Object::Null,
+            // SAFETY: the script declared a char* return
+            "string" => Object::String(unsafe { CStr::from_ptr(result as *const c_char) }.to_string_lossy().into_owned()),
+            _ => Object::Integer(result),
+        }
+    }
+
+    // Calls `addr` as `int64_t f(int64_t, ...)` with the given words. On
+    // the 64-bit targets we support, pointers and ints up to 64 bits are
+    // passed the same way, so this covers any all-integer/pointer signature.
+    unsafe fn call_words(addr: *const (), w: &[i64]) -> i64 {
+        use std::mem::transmute;
+        type W = i64;
+        unsafe {
+            match w.len() {
+                0 => transmute::<*const (), extern "C" fn() -> W>(addr)(),
+                1 => transmute::<*const (), extern "C" fn(W) -> W>(addr)(w[0]),
+                2 => transmute::<*const (), extern "C" fn(W, W) -> W>(addr)(w[0], w[1]),
+                3 => transmute::<*const (), extern "C" fn(W, W, W) -> W>(addr)(w[0], w[1], w[2]),
+                4 => transmute::<*const (), extern "C" fn(W, W, W, W) -> W>(addr)(w[0], w[1], w[2], w[3]),
+                5 => transmute::<*const (), extern "C" fn(W, W, W, W, W) -> W>(addr)(w[0], w[1], w[2], w[3], w[4]),
+                _ => transmute::<*const (), extern "C" fn(W, W, W, W, W, W) -> W>(addr)(w[0], w[1], w[2], w[3], w[4], w[5]),
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "ffi"))]
+mod imp {
+    use crate::environment::Environment;
+    use crate::object::Object;
+
+    const DISABLED: &str = "FFI support is not compiled in (build with the `ffi` feature)";
+
+    pub fn load(_env: &mut Environment, _path: &str) -> Object {
+        Object::error(format!("ffi_load: {}", DISABLED))
+    }
+
+    pub fn call(_env: &mut Environment, _handle: i64, _symbol: &str, _args: &[Object], _ret: &str) -> Object {
+        Object::error(format!("ffi_call: {}", DISABLED))
+    }
+}
@@ -0,0 +1,161 @@
+// Reading data formats into Nova values:
+//
+//   csv_parse("a,b\n1,\"x, y\"\n")          // [["a", "b"], ["1", "x, y"]]
+//   csv_parse("a,b\n1,2\n", true)           // [{a: "1", b: "2"}]
+//   toml_parse("[server]\nport = 8080\n")   // {server: {port: 8080}}
+//
+// CSV follows RFC 4180: fields may be quoted, a quote inside a quoted
+// field is doubled, and quoted fields can hold commas and newlines. Every
+// field is a string; with `true` the first row names the fields and each
+// other row becomes a hash. Both \n and \r\n end a row.
+//
+// TOML needs the `toml` cargo feature. Tables become hashes, keeping the
+// document's key order; floats become decimals (Nova has no floats) and
+// dates and times become strings.
+use indexmap::IndexMap;
+use crate::environment::Environment;
+use crate::object::{HashKey, Object};
+use super::args;
+
+// csv_parse(text) or csv_parse(text, header)
+pub fn csv_parse(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let parsed = if argv.len() == 1 {
+        args::<(String,)>("csv_parse", &argv).map(|(text,)| (text, false))
+    } else {
+        args::<(String, bool)>("csv_parse", &argv)
+    };
+    let (text, header) = match parsed {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let rows = match csv_rows(&text) {
+        Ok(rows) => rows,
+        Err(msg) => return Object::error(format!("csv_parse: {}", msg)),
+    };
+    if !header {
+        return rows.into_iter().map(|row| row.into_iter().map(Object::String).collect::<Vec<_>>()).collect::<Vec<_>>().into();
+    }
+    let mut rows = rows.into_iter();
+    let names = rows.next().unwrap_or_default();
+    let mut records = vec![];
+    for (i, row) in rows.enumerate() {
+        if row.len() != names.len() {
+            // Row 1 is the header
+            return Object::error(format!("csv_parse: row {} has {} fields, the header has {}", i + 2, row.len(), names.len()));
+        }
+        let record: IndexMap<HashKey, Object> = names.iter().cloned().map(HashKey::String).zip(row.into_iter().map(Object::String)).collect();
+        records.push(Object::Hash(record));
+    }
+    Object::Array(records)
+}
+
+fn csv_rows(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut chars = text.chars().peekable();
+    // Whether the current row has anything in it yet, so a trailing
+    // newline doesn't make an empty last row
+    let mut started = false;
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => {
+                let start = line;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        },
+                        Some('"') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            field.push(c);
+                        },
+                        None => return Err(format!("line {}: unclosed quote", start)),
+                    }
+                }
+                if !matches!(chars.peek(), None | Some(',' | '\r' | '\n')) {
+                    return Err(format!("line {}: text after a closing quote", line));
+                }
+                started = true;
+            },
+            ',' => {
+                row.push(std::mem::take(&mut field));
+                started = true;
+            },
+            '\r' if chars.peek() == Some(&'\n') => {},
+            '\n' => {
+                if started || !field.is_empty() {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                started = false;
+                line += 1;
+            },
+            c => {
+                field.push(c);
+                started = true;
+            },
+        }
+    }
+    if started || !field.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+pub fn toml_parse(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(String,)>("toml_parse", &argv) {
+        Ok((text,)) => imp::parse(&text),
+        Err(e) => e,
+    }
+}
+
+#[cfg(feature = "toml")]
+mod imp {
+    use toml::{Table, Value};
+    use crate::numbers;
+    use crate::object::{HashKey, Object};
+
+    pub fn parse(text: &str) -> Object {
+        match text.parse::<Table>() {
+            Ok(table) => table_object(table).unwrap_or_else(|msg| Object::error(format!("toml_parse: {}", msg))),
+            // The parser's message spans several lines with a caret
+            Err(e) => Object::error(format!("toml_parse: {}", e.to_string().trim_end())),
+        }
+    }
+
+    fn table_object(table: Table) -> Result<Object, String> {
+        let pairs = table.into_iter().map(|(k, v)| Ok((HashKey::String(k), value(v)?)));
+        Ok(Object::Hash(pairs.collect::<Result<_, String>>()?))
+    }
+
+    fn value(v: Value) -> Result<Object, String> {
+        Ok(match v {
+            Value::String(s) => Object::String(s),
+            Value::Integer(i) => Object::Integer(i),
+            Value::Float(f) => match numbers::decimal_from_f64(f) {
+                Some(d) => Object::Decimal(d),
+                None => return Err(format!("{} has no decimal equivalent", f)),
+            },
+            Value::Boolean(b) => Object::Boolean(b),
+            Value::Datetime(d) => Object::String(d.to_string()),
+            Value::Array(elements) => Object::Array(elements.into_iter().map(value).collect::<Result<_, _>>()?),
+            Value::Table(table) => table_object(table)?,
+        })
+    }
+}
+
+#[cfg(not(feature = "toml"))]
+mod imp {
+    use crate::object::Object;
+
+    pub fn parse(_text: &str) -> Object {
+        Object::error("toml_parse: TOML support is not compiled in (build with the `toml` feature)")
+    }
+}
@@ -0,0 +1,50 @@
+// Building functions out of other functions. partial() binds leading
+// arguments now and takes the rest later; curry() takes them one call
+// at a time (or several at once) until the function has all it needs.
+use crate::environment::Environment;
+use crate::object::{Object, Partial};
+
+pub fn partial(_env: &mut Environment, mut argv: Vec<Object>) -> Object {
+    if argv.is_empty() {
+        return Object::error("partial: wrong number of arguments: expected at least 1, found 0");
+    }
+    let function = argv.remove(0);
+    if let Err(e) = callable("partial", &function) {
+        return e;
+    }
+    Object::Partial(Partial { function: Box::new(function), args: argv, arity: None })
+}
+
+pub fn curry(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (function,): (Object,) = match super::args("curry", &argv) {
+        Ok(args) => args,
+        Err(e) => return e,
+    };
+    if let Err(e) = callable("curry", &function) {
+        return e;
+    }
+    let arity = match arity(&function) {
+        Some(n) => n,
+        None => return Object::error(format!("curry: can't tell how many arguments {} takes", function)),
+    };
+    Object::Partial(Partial { function: Box::new(function), args: vec![], arity: Some(arity) })
+}
+
+fn callable(name: &str, function: &Object) -> Result<(), Object> {
+    match function {
+        Object::Function(_) | Object::Native(_) | Object::Builtin(_) | Object::Partial(_) => Ok(()),
+        other => Err(Object::error(format!("{}: expected function, found {}", name, other.type_name()))),
+    }
+}
+
+// Arguments still needed; unknown for host functions
+fn arity(function: &Object) -> Option<usize> {
+    match function {
+        Object::Function(f) => Some(f.parameters.len()),
+        Object::Partial(p) => match p.arity {
+            Some(n) => Some(n.saturating_sub(p.args.len())),
+            None => arity(&p.function).map(|n| n.saturating_sub(p.args.len())),
+        },
+        _ => None,
+    }
+}
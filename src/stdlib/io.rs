@@ -0,0 +1,23 @@
+// Console output.
+//
+//   print("total:", [1, "a"])   // total: [1, "a"]
+//
+// Values print the way the REPL shows them, cut down to the runtime's
+// truncation limits so a huge array doesn't flood the terminal. A host
+// that set Runtime::output gets the lines there instead of on stdout.
+use std::io::Write;
+use crate::environment::Environment;
+use crate::object::Object;
+
+pub fn print(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let limits = env.runtime().borrow().truncation;
+    let line: Vec<String> = argv.iter().map(|a| a.display(limits).to_string()).collect();
+    if let Some(output) = &mut env.runtime().borrow_mut().output {
+        output.push_str(&line.join(" "));
+        output.push('\n');
+        return Object::Null;
+    }
+    // A closed stdout (`nova run x.nv | head`) isn't the script's problem
+    let _ = writeln!(std::io::stdout(), "{}", line.join(" "));
+    Object::Null
+}
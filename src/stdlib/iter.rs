@@ -0,0 +1,41 @@
+// The iterator protocol: next(it) hands out one value at a time and
+// returns null once the iterator is used up. Generators are the only
+// iterators so far; collect() drains one into an array.
+use crate::environment::Environment;
+use crate::generator::Generator;
+use crate::object::Object;
+use super::args;
+
+pub fn next(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let generator = match generator("next", argv) {
+        Ok(g) => g,
+        Err(e) => return e,
+    };
+    match generator.resume() {
+        Ok(value) => value.unwrap_or(Object::Null),
+        Err(e) => e,
+    }
+}
+
+pub fn collect(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let generator = match generator("collect", argv) {
+        Ok(g) => g,
+        Err(e) => return e,
+    };
+    let mut values = vec![];
+    loop {
+        match generator.resume() {
+            Ok(Some(value)) => values.push(value),
+            Ok(None) => return Object::Array(values),
+            Err(e) => return e,
+        }
+    }
+}
+
+fn generator(name: &str, argv: Vec<Object>) -> Result<Generator, Object> {
+    let (it,): (Object,) = args(name, &argv)?;
+    match it {
+        Object::Generator(g) => Ok(g),
+        other => Err(Object::error(format!("{}: expected generator, found {}", name, other.type_name()))),
+    }
+}
@@ -0,0 +1,185 @@
+// Integer math. Nova only has ints for now, so sqrt is the integer square
+// root and floor/ceil return their argument; they are here so scripts
+// written today keep working once floats exist. Results that don't fit in
+// an int are errors rather than silently wrapping.
+//
+// Exact fractions are decimals (see decimal.rs): `decimal("19.99")` makes
+// one and `round(d, 2)` rounds one, half to even. int() and decimal() read
+// what to_string() writes (see numbers.rs).
+use crate::decimal::MAX_SCALE;
+use crate::numbers::{parse_decimal, parse_int};
+use crate::environment::Environment;
+use crate::object::Object;
+use super::args;
+
+// The most bits a big int pow() makes may have (2 MiB of digits), so a
+// typo'd exponent fails instead of allocating until the host runs out
+#[cfg(feature = "bigint")]
+const MAX_POW_BITS: u64 = 1 << 24;
+
+pub fn abs(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (n,): (i64,) = match args("abs", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    match n.checked_abs() {
+        Some(v) => Object::Integer(v),
+        None => overflow("abs"),
+    }
+}
+
+// min(a, b, ...) or min(array)
+pub fn min(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match numbers("min", argv) {
+        Ok(ns) => Object::Integer(ns.into_iter().min().unwrap_or_default()),
+        Err(e) => e,
+    }
+}
+
+pub fn max(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match numbers("max", argv) {
+        Ok(ns) => Object::Integer(ns.into_iter().max().unwrap_or_default()),
+        Err(e) => e,
+    }
+}
+
+#[cfg_attr(not(feature = "bigint"), allow(unused_variables))]
+pub fn pow(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (base, exp): (i64, i64) = match args("pow", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    if exp < 0 {
+        return Object::error(format!("pow: exponent must be non-negative, found {}", exp));
+    }
+    let Ok(exp) = u32::try_from(exp) else {
+        return Object::error(format!("pow: exponent too large, found {}", exp));
+    };
+    match base.checked_pow(exp) {
+        Some(v) => Object::Integer(v),
+        #[cfg(feature = "bigint")]
+        None => {
+            // Only |base| >= 2 overflows, so this is within a bit of the size
+            let bits = u64::from(base.unsigned_abs().ilog2() + 1) * u64::from(exp);
+            let runtime = env.runtime().borrow();
+            let over_limit = runtime.memory_limit.is_some_and(|limit| runtime.memory().saturating_add((bits / 8) as usize) > limit);
+            if bits > MAX_POW_BITS || over_limit {
+                return Object::error(format!("pow: result too large (about {} bits)", bits));
+            }
+            Object::from(num_bigint::BigInt::from(base).pow(exp))
+        },
+        #[cfg(not(feature = "bigint"))]
+        None => overflow("pow"),
+    }
+}
+
+// Largest int whose square is <= n
+pub fn sqrt(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (n,): (i64,) = match args("sqrt", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    if n < 0 {
+        return Object::error(format!("sqrt: negative argument {}", n));
+    }
+    Object::Integer(n.isqrt())
+}
+
+pub fn floor(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(i64,)>("floor", &argv) {
+        Ok((n,)) => Object::Integer(n),
+        Err(e) => e,
+    }
+}
+
+pub fn ceil(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(i64,)>("ceil", &argv) {
+        Ok((n,)) => Object::Integer(n),
+        Err(e) => e,
+    }
+}
+
+// --- DECIMALS ---
+
+// decimal(int), decimal("1.50") or decimal(decimal)
+pub fn decimal(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (value,): (Object,) = match args("decimal", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    match value {
+        Object::Integer(n) => Object::Decimal(n.into()),
+        Object::Decimal(d) => Object::Decimal(d),
+        Object::String(s) => match parse_decimal(s.trim()) {
+            Some(d) => Object::Decimal(d),
+            None => Object::error(format!("decimal: not a decimal number: {:?}", s)),
+        },
+        other => Object::error(format!("decimal: expected int, string or decimal, found {}", other.type_name())),
+    }
+}
+
+// int("42"), or the whole part of a decimal, toward zero (round() first
+// to round it)
+pub fn int(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (value,): (Object,) = match args("int", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    match value {
+        Object::Integer(n) => Object::Integer(n),
+        Object::Decimal(d) => match i64::try_from(d.trunc()) {
+            Ok(n) => Object::Integer(n),
+            Err(_) => overflow("int"),
+        },
+        Object::String(s) => match parse_int(s.trim()) {
+            Some(n) => Object::Integer(n),
+            None => Object::error(format!("int: not an int: {:?}", s)),
+        },
+        other => Object::error(format!("int: expected string, decimal or int, found {}", other.type_name())),
+    }
+}
+
+// round(d) or round(d, places); ints are already round
+pub fn round(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let parsed = if argv.len() == 1 {
+        args::<(Object,)>("round", &argv).map(|(value,)| (value, 0))
+    } else {
+        args::<(Object, i64)>("round", &argv)
+    };
+    let (value, places) = match parsed {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let Some(places) = u32::try_from(places).ok().filter(|p| *p <= MAX_SCALE) else {
+        return Object::error(format!("round: places must be between 0 and {}, found {}", MAX_SCALE, places));
+    };
+    match value {
+        Object::Integer(n) => Object::Integer(n),
+        Object::Decimal(d) => match d.round(places) {
+            Some(rounded) => Object::Decimal(rounded),
+            None => Object::error("round: decimal overflow"),
+        },
+        other => Object::error(format!("round: expected decimal or int, found {}", other.type_name())),
+    }
+}
+
+// --- HELPERS ---
+
+// At least one int, given either as separate arguments or as one array
+fn numbers(name: &str, argv: Vec<Object>) -> Result<Vec<i64>, Object> {
+    let ns: Vec<i64> = match argv.as_slice() {
+        [Object::Array(_)] => args::<(Vec<i64>,)>(name, &argv)?.0,
+        _ => argv.iter().enumerate().map(|(i, arg)| match arg {
+            Object::Integer(n) => Ok(*n),
+            other => Err(Object::error(format!("{}: argument {}: expected int, found {}", name, i + 1, other.type_name()))),
+        }).collect::<Result<_, _>>()?,
+    };
+    if ns.is_empty() {
+        return Err(Object::error(format!("{}: needs at least one number", name)));
+    }
+    Ok(ns)
+}
+
+fn overflow(name: &str) -> Object {
+    Object::error(format!("{}: integer overflow", name))
+}
@@ -0,0 +1,192 @@
+// The standard library: functions every script can call without defining
+// them. Builtins are looked up by name when an identifier isn't bound in
+// the environment, so a script can still shadow them with its own `let`.
+//
+// Each area lives in its own file and lists its functions in `BUILTINS`
+// below. Builtins get the caller's environment (for the runtime state and
+// capability checks) and the evaluated arguments.
+use std::fmt;
+use crate::environment::Environment;
+use crate::object::Object;
+use crate::convert::{ArgError, FromNovaArgs};
+use crate::runtime::Capabilities;
+
+mod buffer;
+mod bytes;
+mod collections;
+mod ffi;
+mod formats;
+mod functions;
+mod io;
+mod iter;
+mod math;
+mod net;
+mod paths;
+mod pattern;
+mod process;
+mod reflect;
+mod tasks;
+mod tcp;
+pub(crate) mod random;
+mod time;
+
+pub type BuiltinFn = fn(&mut Environment, Vec<Object>) -> Object;
+
+#[derive(Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub func: BuiltinFn,
+    // For help(): how it's called (`?` marks an optional argument) and
+    // what it does, in a line
+    pub signature: &'static str,
+    pub doc: &'static str,
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Builtin({})", self.name)
+    }
+}
+
+impl Builtin {
+    // `len(value): Elements, keys, ...`, for help() and the REPL's :help
+    pub fn help(&self) -> String {
+        format!("{}: {}", self.signature, self.doc)
+    }
+}
+
+// Names are unique, comparing function pointers isn't reliable
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+const BUILTINS: &[Builtin] = &[
+    Builtin { name: "print", func: io::print, signature: "print(values...)", doc: "Prints the values on one line, separated by spaces" },
+    Builtin { name: "ffi_load", func: ffi::ffi_load, signature: "ffi_load(path)", doc: "Opens a shared library for ffi_call (needs --allow-ffi and unsafe)" },
+    Builtin { name: "ffi_call", func: ffi::ffi_call, signature: "ffi_call(lib, symbol, args, ret?)", doc: "Calls a C function; ret is \"int\" (default), \"string\" or \"void\"" },
+    Builtin { name: "spawn", func: tasks::spawn, signature: "spawn(f, args...)", doc: "Runs f(args...) on its own thread and returns a task; spawn(cmd, args) runs a program" },
+    Builtin { name: "join", func: tasks::join, signature: "join(task)", doc: "Waits for a task and returns its result" },
+    Builtin { name: "pmap", func: tasks::pmap, signature: "pmap(array, f)", doc: "f applied to every element on worker threads, in order (f sees what it captured, not top-level bindings)" },
+    Builtin { name: "gather", func: tasks::gather, signature: "gather(array)", doc: "A future of the results of all the futures (or tasks) in the array, in order" },
+    Builtin { name: "channel", func: tasks::channel, signature: "channel()", doc: "A new channel for send and recv between tasks" },
+    Builtin { name: "send", func: tasks::send, signature: "send(channel, value)", doc: "Sends a copy of the value down the channel" },
+    Builtin { name: "recv", func: tasks::recv, signature: "recv(channel)", doc: "The next value sent down the channel; blocks until there is one" },
+    Builtin { name: "time", func: time::time, signature: "time()", doc: "Seconds since the Unix epoch" },
+    Builtin { name: "clock_ms", func: time::clock_ms, signature: "clock_ms()", doc: "Monotonic milliseconds, for measuring the time between two calls" },
+    Builtin { name: "sleep", func: time::sleep, signature: "sleep(ms)", doc: "Pauses for the given number of milliseconds" },
+    Builtin { name: "sleep_async", func: time::sleep_async, signature: "sleep_async(ms)", doc: "A future that is ready after the given number of milliseconds" },
+    Builtin { name: "now", func: time::now, signature: "now()", doc: "The current time as a UTC datetime" },
+    Builtin { name: "date", func: time::date, signature: "date(seconds) or date(year, month, day, hour?, minute?, second?)", doc: "A datetime from Unix seconds or from calendar fields (UTC)" },
+    Builtin { name: "date_format", func: time::date_format, signature: "date_format(date, format)", doc: "Formats a datetime with strftime directives (%Y-%m-%d %H:%M:%S, %b, %a, ...)" },
+    Builtin { name: "date_parse", func: time::date_parse, signature: "date_parse(text, format?)", doc: "Reads a datetime with the given directives, or as ISO 8601 without a format" },
+    Builtin { name: "date_parts", func: time::date_parts, signature: "date_parts(date)", doc: "A hash of year, month, day, hour, minute, second, millisecond, weekday and yearday" },
+    Builtin { name: "date_add", func: time::date_add, signature: "date_add(date, n, unit)", doc: "Moves a datetime by n ms, seconds, minutes, hours, days, weeks, months or years" },
+    Builtin { name: "timestamp", func: time::timestamp, signature: "timestamp(date)", doc: "Unix seconds of a datetime" },
+    Builtin { name: "rand", func: random::rand, signature: "rand()", doc: "A random non-negative int" },
+    Builtin { name: "rand_int", func: random::rand_int, signature: "rand_int(lo, hi)", doc: "A random int from lo to hi, both included" },
+    Builtin { name: "rand_seed", func: random::rand_seed, signature: "rand_seed(seed)", doc: "Seeds rand and rand_int, so the same seed gives the same numbers" },
+    Builtin { name: "abs", func: math::abs, signature: "abs(n)", doc: "The absolute value of n" },
+    Builtin { name: "min", func: math::min, signature: "min(a, b, ...) or min(array)", doc: "The smallest of the ints" },
+    Builtin { name: "max", func: math::max, signature: "max(a, b, ...) or max(array)", doc: "The largest of the ints" },
+    Builtin { name: "pow", func: math::pow, signature: "pow(base, exponent)", doc: "base raised to a non-negative power; ** calls it" },
+    Builtin { name: "sqrt", func: math::sqrt, signature: "sqrt(n)", doc: "The largest int whose square is at most n" },
+    Builtin { name: "floor", func: math::floor, signature: "floor(n)", doc: "Rounds down; ints are returned as they are" },
+    Builtin { name: "ceil", func: math::ceil, signature: "ceil(n)", doc: "Rounds up; ints are returned as they are" },
+    Builtin { name: "decimal", func: math::decimal, signature: "decimal(value)", doc: "An exact decimal from an int, a string like \"1.50\" or a decimal" },
+    Builtin { name: "int", func: math::int, signature: "int(value)", doc: "An int from a string like \"-42\", or a decimal's whole part (toward zero)" },
+    Builtin { name: "round", func: math::round, signature: "round(d, places?)", doc: "Rounds a decimal to the given places (default 0), half to even" },
+    Builtin { name: "http_get", func: net::http_get, signature: "http_get(url, headers?)", doc: "Fetches a URL; returns {status, headers, body} (needs --allow-net and unsafe)" },
+    Builtin { name: "http_post", func: net::http_post, signature: "http_post(url, body, headers?)", doc: "Posts a string or bytes to a URL; returns {status, headers, body} (needs --allow-net and unsafe)" },
+    Builtin { name: "csv_parse", func: formats::csv_parse, signature: "csv_parse(text, header?)", doc: "Rows of string fields; with header true, a hash per row keyed by the first row" },
+    Builtin { name: "toml_parse", func: formats::toml_parse, signature: "toml_parse(text)", doc: "A TOML document as nested hashes (floats become decimals)" },
+    Builtin { name: "tcp_listen", func: tcp::tcp_listen, signature: "tcp_listen(port, host?)", doc: "Listens for TCP connections on 127.0.0.1 or host; returns a socket (needs --allow-net and unsafe)" },
+    Builtin { name: "tcp_connect", func: tcp::tcp_connect, signature: "tcp_connect(host, port)", doc: "Opens a TCP connection; returns a socket (needs --allow-net and unsafe)" },
+    Builtin { name: "tcp_port", func: tcp::tcp_port, signature: "tcp_port(listener)", doc: "The port a listener is bound to, for tcp_listen(0)" },
+    Builtin { name: "accept", func: tcp::accept, signature: "accept(listener)", doc: "Waits for the next connection; returns a socket" },
+    Builtin { name: "read", func: tcp::read, signature: "read(conn, max_bytes?)", doc: "What has arrived on a connection, or the next part of a file (string, or bytes if not UTF-8); null at the end" },
+    Builtin { name: "write", func: tcp::write, signature: "write(conn, data)", doc: "Sends a string or bytes down a connection or into a file; returns the byte count" },
+    Builtin { name: "close", func: tcp::close, signature: "close(resource)", doc: "Closes a file, connection or listener (closing twice is fine)" },
+    Builtin { name: "regex_match", func: pattern::regex_match, signature: "regex_match(pattern, text)", doc: "Whether the pattern matches somewhere in the text" },
+    Builtin { name: "regex_find_all", func: pattern::regex_find_all, signature: "regex_find_all(pattern, text)", doc: "Every match of the pattern, as an array of strings" },
+    Builtin { name: "regex_captures", func: pattern::regex_captures, signature: "regex_captures(pattern, text)", doc: "The first match's groups, by number and name, or null" },
+    Builtin { name: "regex_replace", func: pattern::regex_replace, signature: "regex_replace(pattern, text, replacement)", doc: "Replaces every match; $1 and $name refer to groups" },
+    Builtin { name: "set", func: collections::set, signature: "set(array?)", doc: "A set of the array's elements, without duplicates" },
+    Builtin { name: "union", func: collections::union, signature: "union(a, b)", doc: "The elements in either set" },
+    Builtin { name: "intersection", func: collections::intersection, signature: "intersection(a, b)", doc: "The elements in both sets" },
+    Builtin { name: "difference", func: collections::difference, signature: "difference(a, b)", doc: "The elements of a that aren't in b" },
+    Builtin { name: "contains", func: collections::contains, signature: "contains(collection, value)", doc: "Set member, array element, hash key, substring or character" },
+    Builtin { name: "keys", func: collections::keys, signature: "keys(hash)", doc: "A hash's keys, in the order they were added" },
+    Builtin { name: "values", func: collections::values, signature: "values(hash)", doc: "A hash's values, in the order of its keys" },
+    Builtin { name: "has_key", func: collections::has_key, signature: "has_key(hash, key)", doc: "Whether the hash has the key (deprecated: use contains)" },
+    Builtin { name: "delete", func: collections::delete, signature: "delete(hash, key)", doc: "A copy of the hash without the key" },
+    Builtin { name: "sort_keys", func: collections::sort_keys, signature: "sort_keys(hash)", doc: "A copy of the hash with its keys in sorted order" },
+    Builtin { name: "merge", func: collections::merge, signature: "merge(hashes...)", doc: "A new hash with every pair; later hashes win on shared keys" },
+    Builtin { name: "len", func: collections::len, signature: "len(value)", doc: "Elements, keys, characters of a string or bytes" },
+    Builtin { name: "buffer", func: buffer::buffer, signature: "buffer(initial?)", doc: "A string builder: push() onto it, to_string() at the end" },
+    Builtin { name: "push", func: buffer::push, signature: "push(buffer, values...)", doc: "Appends to a buffer (strings as they are, other values as print shows them); returns the buffer" },
+    Builtin { name: "to_string", func: buffer::to_string, signature: "to_string(value)", doc: "A buffer's contents, or any value as print shows it" },
+    Builtin { name: "bytes", func: bytes::bytes, signature: "bytes(array)", doc: "Bytes from an array of ints from 0 to 255" },
+    Builtin { name: "read_bytes", func: bytes::read_bytes, signature: "read_bytes(path)", doc: "A file's contents as bytes (needs --allow-fs and unsafe)" },
+    Builtin { name: "open_file", func: bytes::open_file, signature: "open_file(path, mode)", doc: "Opens a file to read (\"r\"), replace (\"w\") or append to (\"a\"); returns a resource for read, write and close (needs --allow-fs and unsafe)" },
+    Builtin { name: "write_bytes", func: bytes::write_bytes, signature: "write_bytes(path, data)", doc: "Writes bytes to a file, replacing it (needs --allow-fs and unsafe)" },
+    Builtin { name: "path_join", func: paths::path_join, signature: "path_join(parts...)", doc: "Joins path parts with the platform's separator" },
+    Builtin { name: "path_basename", func: paths::path_basename, signature: "path_basename(path)", doc: "The last component of a path" },
+    Builtin { name: "path_dirname", func: paths::path_dirname, signature: "path_dirname(path)", doc: "A path without its last component" },
+    Builtin { name: "path_ext", func: paths::path_ext, signature: "path_ext(path)", doc: "A path's extension without the dot, or \"\"" },
+    Builtin { name: "glob", func: paths::glob, signature: "glob(pattern)", doc: "The paths matching a pattern with *, ?, [...] and ** (needs --allow-fs and unsafe)" },
+    Builtin { name: "encode", func: bytes::encode, signature: "encode(string, encoding?)", doc: "A string's bytes in an encoding (default utf-8)" },
+    Builtin { name: "decode", func: bytes::decode, signature: "decode(bytes, encoding?)", doc: "Bytes read as a string in an encoding (default utf-8)" },
+    Builtin { name: "next", func: iter::next, signature: "next(iterator)", doc: "The iterator's next value, or null when it's used up" },
+    Builtin { name: "collect", func: iter::collect, signature: "collect(iterator)", doc: "Every value left in the iterator, as an array" },
+    Builtin { name: "partial", func: functions::partial, signature: "partial(f, args...)", doc: "f with its first arguments bound; the call passes the rest" },
+    Builtin { name: "curry", func: functions::curry, signature: "curry(f)", doc: "f taking its arguments one call at a time (or several at once)" },
+    Builtin { name: "eval", func: reflect::eval, signature: "eval(code, isolated?)", doc: "Runs Nova code (a string or a quote) in the caller's scope, or a throwaway one" },
+    Builtin { name: "quote", func: reflect::quote, signature: "quote(code)", doc: "The code itself (an expression, or a { block }), unevaluated, with each unquote(value) in it replaced by the value" },
+    Builtin { name: "unquote", func: reflect::unquote, signature: "unquote(value)", doc: "Inside quote(): a value (an int, string, quote, ...) put into the code" },
+    Builtin { name: "names", func: reflect::names, signature: "names()", doc: "The names bound in the current scope" },
+    Builtin { name: "params", func: reflect::params, signature: "params(f)", doc: "A function's parameter names" },
+    Builtin { name: "arity", func: reflect::arity, signature: "arity(f)", doc: "How many arguments a function takes" },
+    Builtin { name: "source", func: reflect::source, signature: "source(f)", doc: "A function's definition, one statement per line" },
+    Builtin { name: "help", func: reflect::help, signature: "help(name?)", doc: "A builtin's signature and description, or all of them" },
+    Builtin { name: "memory_used", func: reflect::memory_used, signature: "memory_used()", doc: "Approximate bytes held by bindings, as counted against the memory limit" },
+];
+
+pub fn lookup(name: &str) -> Option<Builtin> {
+    BUILTINS.iter().find(|b| b.name == name).copied()
+}
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+    BUILTINS.iter().map(|b| b.name)
+}
+
+pub fn all() -> &'static [Builtin] {
+    BUILTINS
+}
+
+// --- HELPERS ---
+
+// Unpacks arguments, turning a mismatch into "name: <problem>"
+fn args<T: FromNovaArgs>(name: &str, args: &[Object]) -> Result<T, Object> {
+    T::from_args(args).map_err(|e: ArgError| Object::error(format!("{}: {}", name, e)))
+}
+
+// Fails unless the host granted the capability
+fn allowed(env: &Environment, name: &str, granted: fn(&Capabilities) -> bool, flag: &str) -> Result<(), Object> {
+    if granted(&env.capabilities()) {
+        Ok(())
+    } else {
+        Err(Object::error(format!("{}: not permitted (needs {})", name, flag)))
+    }
+}
+
+// Gate for builtins that reach outside the sandbox: the host must have
+// granted the capability and the call must happen inside `unsafe { }`
+fn require(env: &Environment, name: &str, granted: fn(&Capabilities) -> bool, flag: &str) -> Result<(), Object> {
+    allowed(env, name, granted, flag)?;
+    if env.runtime().borrow().unsafe_depth == 0 {
+        return Err(Object::error(format!("{}: only allowed inside an unsafe block", name)));
+    }
+    Ok(())
+}
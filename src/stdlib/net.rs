@@ -0,0 +1,155 @@
+// HTTP requests:
+//
+//   unsafe {
+//       let r = http_get("https://api.example.com/items", {"Accept": "application/json"});
+//       r["status"]    // 200
+//       r["headers"]   // {"content-type": "application/json", ...}
+//       r["body"]      // "[{\"id\": 1}]"
+//       http_post("https://api.example.com/items", "{\"id\": 2}", {"Content-Type": "application/json"})
+//   }
+//
+// Both return {status, headers, body} whatever the status is; only not
+// getting a response at all (bad URL, no connection, timeout) is an error.
+// Header names come back lowercase. A body that isn't UTF-8 comes back as
+// bytes, and bytes can be posted too. Needs the `net` cargo feature, the
+// net capability (`--allow-net`) and an unsafe block.
+use crate::environment::Environment;
+use crate::object::{HashKey, Object};
+use super::{args, require};
+
+// http_get(url) or http_get(url, headers)
+pub fn http_get(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let parsed = if argv.len() == 1 {
+        args::<(String,)>("http_get", &argv).map(|(url,)| (url, Object::Null))
+    } else {
+        args::<(String, Object)>("http_get", &argv)
+    };
+    let (url, headers) = match parsed {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let headers = match headers_arg("http_get", &headers, 2) {
+        Ok(h) => h,
+        Err(e) => return e,
+    };
+    if let Err(e) = require(env, "http_get", |c| c.net, "--allow-net") {
+        return e;
+    }
+    imp::request("http_get", &url, None, &headers)
+}
+
+// http_post(url, body) or http_post(url, body, headers); body is a string
+// or bytes
+pub fn http_post(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let parsed = if argv.len() == 2 {
+        args::<(String, Object)>("http_post", &argv).map(|(url, body)| (url, body, Object::Null))
+    } else {
+        args::<(String, Object, Object)>("http_post", &argv)
+    };
+    let (url, body, headers) = match parsed {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let body = match body {
+        Object::String(s) => s.into_bytes(),
+        Object::Bytes(b) => b,
+        other => return Object::error(format!("http_post: argument 2: expected string or bytes, found {}", other.type_name())),
+    };
+    let headers = match headers_arg("http_post", &headers, 3) {
+        Ok(h) => h,
+        Err(e) => return e,
+    };
+    if let Err(e) = require(env, "http_post", |c| c.net, "--allow-net") {
+        return e;
+    }
+    imp::request("http_post", &url, Some(body), &headers)
+}
+
+// A hash of header names to values; null for none
+fn headers_arg(name: &str, headers: &Object, position: usize) -> Result<Vec<(String, String)>, Object> {
+    let pairs = match headers {
+        Object::Null => return Ok(vec![]),
+        Object::Hash(pairs) => pairs,
+        other => return Err(Object::error(format!("{}: argument {}: expected hash, found {}", name, position, other.type_name()))),
+    };
+    pairs.iter().map(|(key, value)| match (key, value) {
+        (HashKey::String(k), Object::String(v)) => Ok((k.clone(), v.clone())),
+        _ => Err(Object::error(format!("{}: headers must map strings to strings, found {}: {}", name, key, value.type_name()))),
+    }).collect()
+}
+
+#[cfg(feature = "net")]
+mod imp {
+    use std::sync::OnceLock;
+    use std::time::Duration;
+    use indexmap::IndexMap;
+    use ureq::Agent;
+    use crate::object::Object;
+
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    // One agent for the process, so connections get reused. Statuses
+    // aren't errors here: the script gets to look at them.
+    fn agent() -> &'static Agent {
+        static AGENT: OnceLock<Agent> = OnceLock::new();
+        AGENT.get_or_init(|| {
+            let config = Agent::config_builder()
+                .http_status_as_error(false)
+                .timeout_global(Some(TIMEOUT))
+                .build();
+            Agent::new_with_config(config)
+        })
+    }
+
+    pub fn request(name: &str, url: &str, body: Option<Vec<u8>>, headers: &[(String, String)]) -> Object {
+        let result = match body {
+            None => {
+                let mut request = agent().get(url);
+                for (k, v) in headers {
+                    request = request.header(k, v);
+                }
+                request.call()
+            },
+            Some(body) => {
+                let mut request = agent().post(url);
+                for (k, v) in headers {
+                    request = request.header(k, v);
+                }
+                request.send(body)
+            },
+        };
+        let mut response = match result {
+            Ok(r) => r,
+            Err(e) => return Object::error(format!("{}: {}: {}", name, url, e)),
+        };
+
+        let mut header_map = IndexMap::new();
+        for (k, v) in response.headers() {
+            let value = String::from_utf8_lossy(v.as_bytes()).into_owned();
+            header_map.insert(k.as_str().into(), value.into());
+        }
+        let status = i64::from(response.status().as_u16());
+        let body = match response.body_mut().read_to_vec() {
+            Ok(data) => match String::from_utf8(data) {
+                Ok(text) => Object::String(text),
+                Err(e) => Object::Bytes(e.into_bytes()),
+            },
+            Err(e) => return Object::error(format!("{}: {}: {}", name, url, e)),
+        };
+
+        let mut result = IndexMap::new();
+        result.insert("status".into(), status.into());
+        result.insert("headers".into(), Object::Hash(header_map));
+        result.insert("body".into(), body);
+        Object::Hash(result)
+    }
+}
+
+#[cfg(not(feature = "net"))]
+mod imp {
+    use crate::object::Object;
+
+    pub fn request(name: &str, _url: &str, _body: Option<Vec<u8>>, _headers: &[(String, String)]) -> Object {
+        Object::error(format!("{}: HTTP support is not compiled in (build with the `net` feature)", name))
+    }
+}
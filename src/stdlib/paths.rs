@@ -0,0 +1,212 @@
+// File paths, for build scripts:
+//
+//   path_join("src", "stdlib", "mod.rs")    // "src/stdlib/mod.rs" ("src\stdlib\mod.rs" on Windows)
+//   path_basename("src/main.rs")            // "main.rs"
+//   path_dirname("src/main.rs")             // "src"
+//   path_ext("archive.tar.gz")              // "gz"
+//   unsafe { glob("src/**/*.rs") }          // ["src/ast.rs", "src/stdlib/mod.rs", ...]
+//
+// The path_ functions only look at the string, with the platform's rules
+// for separators; a part of path_join that is absolute replaces what came
+// before it. A missing dirname or extension is "".
+//
+// glob() reads the filesystem, so it needs the filesystem capability
+// (`--allow-fs`) and an unsafe block. `*` and `?` match within one name,
+// `[abc]`, `[a-z]` and `[!a]` match one character, and `**` matches any
+// number of directories. Wildcards don't match a leading dot, like in a
+// shell. Matches come back sorted, relative if the pattern was.
+use std::path::{Component, Path, PathBuf};
+use crate::environment::Environment;
+use crate::object::Object;
+use super::{args, require};
+
+// path_join(parts...)
+pub fn path_join(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    if argv.is_empty() {
+        return Object::error("path_join: wrong number of arguments: expected at least 1, found 0");
+    }
+    let mut path = PathBuf::new();
+    for (i, part) in argv.iter().enumerate() {
+        match part {
+            Object::String(s) => path.push(s),
+            other => return Object::error(format!("path_join: argument {}: expected string, found {}", i + 1, other.type_name())),
+        }
+    }
+    Object::String(path.to_string_lossy().into_owned())
+}
+
+// The last component, "" for a path ending in `..` or a root
+pub fn path_basename(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(String,)>("path_basename", &argv) {
+        Ok((path,)) => Path::new(&path).file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned()).into(),
+        Err(e) => e,
+    }
+}
+
+// Everything but the last component; a root is its own dirname
+pub fn path_dirname(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(String,)>("path_dirname", &argv) {
+        Ok((path,)) => match Path::new(&path).parent() {
+            Some(parent) => parent.to_string_lossy().into_owned().into(),
+            None if Path::new(&path).has_root() => path.into(),
+            None => Object::String(String::new()),
+        },
+        Err(e) => e,
+    }
+}
+
+// Without the dot; a leading dot (".bashrc") doesn't start an extension
+pub fn path_ext(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(String,)>("path_ext", &argv) {
+        Ok((path,)) => Path::new(&path).extension().map_or(String::new(), |e| e.to_string_lossy().into_owned()).into(),
+        Err(e) => e,
+    }
+}
+
+pub fn glob(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (pattern,) = match args::<(String,)>("glob", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    if let Err(e) = require(env, "glob", |c| c.fs, "--allow-fs") {
+        return e;
+    }
+    match glob_paths(&pattern) {
+        Ok(paths) => paths.into_iter().map(|p| p.to_string_lossy().into_owned()).collect::<Vec<_>>().into(),
+        Err(msg) => Object::error(format!("glob: {}", msg)),
+    }
+}
+
+fn glob_paths(pattern: &str) -> Result<Vec<PathBuf>, String> {
+    // The root (and drive, on Windows) is where the walk starts; the rest
+    // is matched one name at a time
+    let mut base = PathBuf::new();
+    let mut parts = vec![];
+    for component in Path::new(pattern).components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => base.push(component),
+            Component::CurDir => parts.push(".".to_string()),
+            Component::ParentDir => parts.push("..".to_string()),
+            Component::Normal(name) => {
+                let name = name.to_str().ok_or_else(|| format!("{:?} is not valid UTF-8", name))?;
+                check_classes(name)?;
+                parts.push(name.to_string());
+            },
+        }
+    }
+    let mut out = vec![];
+    walk(&base, &parts, &mut out);
+    out.sort();
+    out.dedup();
+    Ok(out)
+}
+
+fn walk(dir: &Path, parts: &[String], out: &mut Vec<PathBuf>) {
+    let Some((part, rest)) = parts.split_first() else {
+        out.push(dir.to_path_buf());
+        return;
+    };
+    if part == "**" {
+        walk(dir, rest, out);
+        // Not into hidden directories, or symlinked ones, which could lead
+        // back up
+        for (name, path, is_dir) in entries(dir) {
+            if is_dir && !name.starts_with('.') {
+                walk(&path, parts, out);
+            }
+        }
+    } else if !has_wildcard(part) {
+        let path = dir.join(part);
+        if (rest.is_empty() && path.exists()) || path.is_dir() {
+            walk(&path, rest, out);
+        }
+    } else {
+        let pattern: Vec<char> = part.chars().collect();
+        for (name, path, _) in entries(dir) {
+            let name: Vec<char> = name.chars().collect();
+            let hidden = name.first() == Some(&'.') && pattern.first() != Some(&'.');
+            if !hidden && wildcard(&pattern, &name) && (rest.is_empty() || path.is_dir()) {
+                walk(&path, rest, out);
+            }
+        }
+    }
+}
+
+// A directory's entries with UTF-8 names, and whether each is a real
+// directory (not a link to one). One that can't be read (or doesn't
+// exist) just has none, like in a shell.
+fn entries(dir: &Path) -> Vec<(String, PathBuf, bool)> {
+    let reading = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let Ok(read) = std::fs::read_dir(reading) else {
+        return vec![];
+    };
+    read.filter_map(Result::ok)
+        .filter_map(|entry| {
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            let name = entry.file_name().into_string().ok()?;
+            let path = dir.join(&name);
+            Some((name, path, is_dir))
+        })
+        .collect()
+}
+
+fn has_wildcard(name: &str) -> bool {
+    name.contains(['*', '?', '['])
+}
+
+// Every `[` needs a `]`, and one straight after the `[` (or `[!`) is
+// one of the characters rather than the end
+fn check_classes(name: &str) -> Result<(), String> {
+    let mut rest = name;
+    while let Some(open) = rest.find('[') {
+        let class = rest[open + 1..].strip_prefix('!').unwrap_or(&rest[open + 1..]);
+        let first = class.chars().next().map_or(0, char::len_utf8);
+        match class[first..].find(']') {
+            Some(close) => rest = &class[first + close + 1..],
+            None => return Err(format!("unclosed [ in {:?}", name)),
+        }
+    }
+    Ok(())
+}
+
+// Whether a name matches one component of a pattern
+fn wildcard(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| wildcard(rest, &name[skip..])),
+        Some(('?', rest)) => match name.split_first() {
+            Some((_, name)) => wildcard(rest, name),
+            None => false,
+        },
+        Some(('[', rest)) => {
+            let (negated, rest) = match rest.split_first() {
+                Some(('!', rest)) => (true, rest),
+                _ => (false, rest),
+            };
+            // A `]` straight after the `[` is one of the characters
+            let Some(close) = rest.iter().skip(1).position(|&c| c == ']').map(|i| i + 1) else {
+                return false;
+            };
+            let (class, rest) = (&rest[..close], &rest[close + 1..]);
+            let Some((&c, name)) = name.split_first() else {
+                return false;
+            };
+            let mut matched = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    matched |= (class[i]..=class[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    matched |= class[i] == c;
+                    i += 1;
+                }
+            }
+            matched != negated && wildcard(rest, name)
+        },
+        Some((&p, rest)) => match name.split_first() {
+            Some((&c, name)) if c == p => wildcard(rest, name),
+            _ => false,
+        },
+    }
+}
@@ -0,0 +1,144 @@
+// Regular expressions (the `regex` feature, on by default), with the
+// syntax of Rust's regex crate:
+//
+//   regex_match(r"^\d+$", "123")                  // true
+//   regex_find_all(r"\d+", "a1b22")               // ["1", "22"]
+//   regex_captures(r"(?<k>\w+)=(\d+)", "x=1")     // {0: "x=1", 1: "x", 2: "1", k: "x"}
+//   regex_replace(r"(\w+)@", "me@host", "$1 at ") // "me at host"
+//
+// Captures come back as a hash keyed by group number, plus the name of
+// each named group; a group that didn't take part in the match is null.
+// Patterns are compiled once and cached.
+use crate::environment::Environment;
+use crate::object::Object;
+use super::args;
+
+pub fn regex_match(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(String, String)>("regex_match", &argv) {
+        Ok((pattern, text)) => imp::is_match("regex_match", &pattern, &text),
+        Err(e) => e,
+    }
+}
+
+pub fn regex_find_all(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(String, String)>("regex_find_all", &argv) {
+        Ok((pattern, text)) => imp::find_all("regex_find_all", &pattern, &text),
+        Err(e) => e,
+    }
+}
+
+pub fn regex_captures(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(String, String)>("regex_captures", &argv) {
+        Ok((pattern, text)) => imp::captures("regex_captures", &pattern, &text),
+        Err(e) => e,
+    }
+}
+
+pub fn regex_replace(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(String, String, String)>("regex_replace", &argv) {
+        Ok((pattern, text, replacement)) => imp::replace("regex_replace", &pattern, &text, &replacement),
+        Err(e) => e,
+    }
+}
+
+#[cfg(feature = "regex")]
+mod imp {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use indexmap::IndexMap;
+    use regex::Regex;
+    use crate::object::{HashKey, Object};
+
+    // Enough for the patterns of a typical script; past that we start over
+    const CACHE_SIZE: usize = 64;
+
+    thread_local! {
+        static CACHE: RefCell<HashMap<String, Regex>> = RefCell::new(HashMap::new());
+    }
+
+    fn compile(name: &str, pattern: &str) -> Result<Regex, Object> {
+        CACHE.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            if let Some(re) = cache.get(pattern) {
+                return Ok(re.clone());
+            }
+            // The crate's message points at the problem over several lines
+            let re = Regex::new(pattern).map_err(|e| {
+                let msg = e.to_string();
+                let reason = msg.lines().last().unwrap_or_default().trim_start_matches("error: ").to_string();
+                Object::error(format!("{}: invalid regex: {}", name, reason))
+            })?;
+            if cache.len() >= CACHE_SIZE {
+                cache.clear();
+            }
+            cache.insert(pattern.to_string(), re.clone());
+            Ok(re)
+        })
+    }
+
+    pub fn is_match(name: &str, pattern: &str, text: &str) -> Object {
+        match compile(name, pattern) {
+            Ok(re) => Object::Boolean(re.is_match(text)),
+            Err(e) => e,
+        }
+    }
+
+    pub fn find_all(name: &str, pattern: &str, text: &str) -> Object {
+        match compile(name, pattern) {
+            Ok(re) => re.find_iter(text).map(|m| m.as_str()).collect::<Vec<_>>().into(),
+            Err(e) => e,
+        }
+    }
+
+    pub fn captures(name: &str, pattern: &str, text: &str) -> Object {
+        let re = match compile(name, pattern) {
+            Ok(re) => re,
+            Err(e) => return e,
+        };
+        let Some(caps) = re.captures(text) else {
+            return Object::Null;
+        };
+        let group = |m: Option<regex::Match>| m.map_or(Object::Null, |m| Object::from(m.as_str()));
+        // The numbered groups, then the named ones again by name
+        let mut pairs = IndexMap::new();
+        for i in 0..caps.len() {
+            pairs.insert(HashKey::Integer(i as i64), group(caps.get(i)));
+        }
+        for (i, name) in re.capture_names().enumerate() {
+            if let Some(name) = name {
+                pairs.insert(HashKey::from(name), group(caps.get(i)));
+            }
+        }
+        Object::Hash(pairs)
+    }
+
+    pub fn replace(name: &str, pattern: &str, text: &str, replacement: &str) -> Object {
+        match compile(name, pattern) {
+            Ok(re) => Object::String(re.replace_all(text, replacement).into_owned()),
+            Err(e) => e,
+        }
+    }
+}
+
+#[cfg(not(feature = "regex"))]
+mod imp {
+    use crate::object::Object;
+
+    const DISABLED: &str = "regex support is not compiled in (build with the `regex` feature)";
+
+    pub fn is_match(name: &str, _pattern: &str, _text: &str) -> Object {
+        Object::error(format!("{}: {}", name, DISABLED))
+    }
+
+    pub fn find_all(name: &str, _pattern: &str, _text: &str) -> Object {
+        Object::error(format!("{}: {}", name, DISABLED))
+    }
+
+    pub fn captures(name: &str, _pattern: &str, _text: &str) -> Object {
+        Object::error(format!("{}: {}", name, DISABLED))
+    }
+
+    pub fn replace(name: &str, _pattern: &str, _text: &str, _replacement: &str) -> Object {
+        Object::error(format!("{}: {}", name, DISABLED))
+    }
+}
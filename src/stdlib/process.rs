@@ -0,0 +1,42 @@
+// Running other programs:
+//
+//   unsafe {
+//       let r = spawn("ls", ["-l"]);
+//       r["stdout"]
+//   }
+//
+// Waits for the program and returns {status, stdout, stderr}. `status` is
+// the exit code, or null when the process was killed by a signal. The
+// program is started directly, not through a shell. Needs the process
+// capability (`--allow-process`) and an unsafe block.
+use indexmap::IndexMap;
+use std::process::Command;
+use crate::environment::Environment;
+use crate::object::Object;
+use super::{args, require};
+
+pub fn spawn(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let parsed = if argv.len() == 1 {
+        args::<(String,)>("spawn", &argv).map(|(cmd,)| (cmd, vec![]))
+    } else {
+        args::<(String, Vec<String>)>("spawn", &argv)
+    };
+    let (cmd, cmd_args) = match parsed {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    if let Err(e) = require(env, "spawn", |c| c.process, "--allow-process") {
+        return e;
+    }
+
+    let output = match Command::new(&cmd).args(&cmd_args).output() {
+        Ok(output) => output,
+        Err(e) => return Object::error(format!("spawn: {}: {}", cmd, e)),
+    };
+
+    let mut result = IndexMap::new();
+    result.insert("status".into(), output.status.code().map(i64::from).into());
+    result.insert("stdout".into(), String::from_utf8_lossy(&output.stdout).into_owned().into());
+    result.insert("stderr".into(), String::from_utf8_lossy(&output.stderr).into_owned().into());
+    Object::Hash(result)
+}
@@ -0,0 +1,114 @@
+// Random numbers from a seedable generator kept in the runtime, so
+//
+//   rand_seed(42);
+//   rand_int(1, 6)
+//
+// gives the same rolls on every run and every platform. Without a seed the
+// generator starts from a per-run random state.
+//
+// Nova has no floats yet, so rand() returns a non-negative int spanning
+// the full 63-bit range rather than a fraction.
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use crate::environment::Environment;
+use crate::object::Object;
+use super::args;
+
+// xoshiro256** (Blackman & Vigna): small, fast and good enough for
+// scripting; not for cryptography
+#[derive(Debug, Clone)]
+pub struct Rng {
+    s: [u64; 4],
+}
+
+impl Rng {
+    pub fn from_seed(seed: u64) -> Self {
+        // Expand the seed with splitmix64 so nearby seeds diverge
+        let mut x = seed;
+        let mut next = || {
+            x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        };
+        Rng { s: [next(), next(), next(), next()] }
+    }
+
+    pub fn from_entropy() -> Self {
+        Rng::from_seed(Rng::entropy_seed())
+    }
+
+    // RandomState is seeded by the OS where there is one
+    pub(crate) fn entropy_seed() -> u64 {
+        RandomState::new().build_hasher().finish()
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.s[1] << 17;
+        self.s[2] ^= self.s[0];
+        self.s[3] ^= self.s[1];
+        self.s[1] ^= self.s[2];
+        self.s[0] ^= self.s[3];
+        self.s[2] ^= t;
+        self.s[3] = self.s[3].rotate_left(45);
+        result
+    }
+
+    // Uniform in 0..=span, without modulo bias
+    fn below_inclusive(&mut self, span: u64) -> u64 {
+        if span == u64::MAX {
+            return self.next_u64();
+        }
+        let n = span + 1;
+        let zone = u64::MAX - (u64::MAX % n) - 1;
+        loop {
+            let v = self.next_u64();
+            if v <= zone {
+                return v % n;
+            }
+        }
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::from_entropy()
+    }
+}
+
+pub fn rand(env: &mut Environment, argv: Vec<Object>) -> Object {
+    if let Err(e) = args::<()>("rand", &argv) {
+        return e;
+    }
+    match env.runtime().borrow_mut().rng() {
+        Ok(rng) => Object::Integer((rng.next_u64() >> 1) as i64),
+        Err(msg) => Object::error(format!("rand: {}", msg)),
+    }
+}
+
+// Both ends are included
+pub fn rand_int(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (lo, hi): (i64, i64) = match args("rand_int", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    if lo > hi {
+        return Object::error(format!("rand_int: empty range {}..{}", lo, hi));
+    }
+    let span = hi.abs_diff(lo);
+    match env.runtime().borrow_mut().rng() {
+        Ok(rng) => Object::Integer(lo.wrapping_add(rng.below_inclusive(span) as i64)),
+        Err(msg) => Object::error(format!("rand_int: {}", msg)),
+    }
+}
+
+pub fn rand_seed(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (seed,): (i64,) = match args("rand_seed", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    env.runtime().borrow_mut().seed_rng(seed as u64);
+    Object::Null
+}
@@ -0,0 +1,159 @@
+// The interpreter, from inside a script.
+//
+//   eval("1 + 2")                  // 3
+//   eval("let x = 5;")             // binds x in the caller's scope
+//   eval("let y = 5; y", true)     // isolated: y is gone afterwards
+//
+// Isolated code can read the caller's bindings but anything it binds is
+// thrown away, like a zone. Either way it shares the runtime, so step and
+// depth limits and capabilities still apply.
+//
+//   names()        // ["f", "x"]: what's bound in the current scope
+//   params(f)      // ["a", "b"]
+//   arity(f)       // 2
+//   source(f)      // "fn(a, b) {\n    (a + b)\n}"
+//   memory_used()  // bytes held by bindings, see Runtime::memory
+//   help("len")    // "len(value): Elements, keys, ...", help() lists them all
+//
+//   quote(x + 1)              // the code itself, as a value
+//   quote(unquote(n) * 2)     // with n's value put in: quote(5 * 2) for n = 5
+//   quote { let y = 2; y }    // a block of it
+//   eval(q)                   // runs quoted code, like a string of it
+//
+// Builtins and host functions have no parameter list or source; those
+// calls return null for them.
+use std::rc::Rc;
+use crate::ast::{Node, Program};
+use crate::environment::Environment;
+use crate::evaluator::{eval_expression, eval_program};
+use crate::object::{Function, Object, Quote};
+use super::{args, Builtin};
+
+pub fn eval(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let parsed = if argv.len() == 1 {
+        args::<(Object,)>("eval", &argv).map(|(code,)| (code, false))
+    } else {
+        args::<(Object, bool)>("eval", &argv)
+    };
+    let (code, isolated) = match parsed {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let mut copy;
+    let env = if isolated {
+        copy = env.clone();
+        &mut copy
+    } else {
+        env
+    };
+
+    let source = match code {
+        Object::String(source) => source,
+        // Already code; nothing to parse
+        Object::Quote(quote) => return match &*quote {
+            Quote::Expression(e) => eval_expression(e, env),
+            Quote::Block(b) => eval_program(&Program { statements: b.statements.clone(), pragmas: vec![], features: vec![], macros: vec![] }, env),
+        },
+        other => return Object::error(format!("eval: argument 1: expected string or quote, found {}", other.type_name())),
+    };
+    let (program, errors) = {
+        let runtime = &mut *env.runtime().borrow_mut();
+        let before = runtime.parse_cache.bytes();
+        let parsed = runtime.parse_cache.parse(&source, &runtime.operators);
+        runtime.charge_memory(runtime.parse_cache.bytes(), before);
+        // Caching is only worth it while there's room
+        if runtime.check_memory().is_err() {
+            runtime.charge_memory(0, runtime.parse_cache.bytes());
+            runtime.parse_cache.clear();
+        }
+        parsed
+    };
+    if !errors.is_empty() {
+        let errors: Vec<String> = errors.iter().map(|e| e.render(&source)).collect();
+        return Object::error(format!("eval: {}", errors.join("; ")));
+    }
+    eval_program(&program, env)
+}
+
+pub fn names(env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<()>("names", &argv) {
+        Ok(()) => env.names().into(),
+        Err(e) => e,
+    }
+}
+
+// Approximate bytes held by bindings, as counted against the memory limit
+pub fn memory_used(env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<()>("memory_used", &argv) {
+        Ok(()) => Object::Integer(i64::try_from(env.memory_used()).unwrap_or(i64::MAX)),
+        Err(e) => e,
+    }
+}
+
+pub fn params(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match function("params", &argv) {
+        Ok(Some(f)) => f.parameters.iter().map(|p| p.value.clone()).collect::<Vec<_>>().into(),
+        Ok(None) => Object::Null,
+        Err(e) => e,
+    }
+}
+
+pub fn arity(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match function("arity", &argv) {
+        Ok(Some(f)) => Object::Integer(f.parameters.len() as i64),
+        Ok(None) => Object::Null,
+        Err(e) => e,
+    }
+}
+
+// One statement per line; nested blocks stay on one line
+pub fn source(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let f = match function("source", &argv) {
+        Ok(Some(f)) => f,
+        Ok(None) => return Object::Null,
+        Err(e) => return e,
+    };
+    let params: Vec<&str> = f.parameters.iter().map(|p| p.value.as_str()).collect();
+    let mut out = format!("fn({}) {{\n", params.join(", "));
+    for stmt in &f.body.statements {
+        out.push_str(&format!("    {}\n", stmt.string()));
+    }
+    out.push('}');
+    Object::String(out)
+}
+
+// help() is every builtin's line; help(name) or help(builtin) is one
+pub fn help(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let builtin = match argv.as_slice() {
+        [] => return Object::String(super::all().iter().map(Builtin::help).collect::<Vec<_>>().join("\n")),
+        [Object::Builtin(b)] => *b,
+        [Object::String(name)] => match super::lookup(name) {
+            Some(b) => b,
+            None => return Object::error(format!("help: no builtin named '{}'", name)),
+        },
+        [other] => return Object::error(format!("help: expected string or builtin, found {}", other.type_name())),
+        _ => return Object::error(format!("help: wrong number of arguments: expected 0 or 1, found {}", argv.len())),
+    };
+    Object::String(builtin.help())
+}
+
+// None for functions without a Nova definition (builtins, natives, partials)
+fn function(name: &str, argv: &[Object]) -> Result<Option<Rc<Function>>, Object> {
+    let (f,): (Object,) = args(name, argv)?;
+    match f {
+        Object::Function(f) => Ok(Some(f)),
+        Object::Builtin(_) | Object::Native(_) | Object::Partial(_) => Ok(None),
+        other => Err(Object::error(format!("{}: expected function, found {}", name, other.type_name()))),
+    }
+}
+
+// quote() and unquote() are done by the evaluator where they're written as
+// calls, since quote's argument isn't evaluated (see eval_quote); these only
+// run when one is called some other way, like through partial()
+pub fn quote(_env: &mut Environment, _argv: Vec<Object>) -> Object {
+    Object::error("quote() has to be called by name, with the code to quote: quote(x + 1)")
+}
+
+pub fn unquote(_env: &mut Environment, _argv: Vec<Object>) -> Object {
+    Object::error("unquote() only works inside quote()")
+}
@@ -0,0 +1,108 @@
+// Concurrency builtins:
+//
+//   let worker = fn(ch, n) { send(ch, n * 2); n };
+//   let ch = channel();
+//   let t = spawn(worker, ch, 21);
+//   recv(ch)    // 42
+//   join(t)     // 21
+//
+// spawn(f, args...) runs f(args...) on its own thread and returns a task;
+// spawn(cmd, ...) with a string is the process builtin. Values are copied
+// between threads (see task.rs), so a task never sees the caller's later
+// changes and vice versa.
+//
+// pmap(xs, f) is [f(x) for each x] with the calls spread over worker
+// threads. Like a spawned function, f takes along what it captured but
+// not the top-level bindings around it.
+//
+// gather(futures) is a future of all their results, for awaiting several
+// async calls (or tasks) at once: `await gather([fetch(a), fetch(b)])`.
+use crate::environment::Environment;
+use crate::future::Future;
+use crate::object::Object;
+use crate::task::{self, Channel, Message, Task};
+use super::{args, process};
+
+pub fn spawn(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let Some(function @ (Object::Function(_) | Object::Builtin(_) | Object::Partial(_))) = argv.first() else {
+        return process::spawn(env, argv);
+    };
+    let function = match Message::from_object(function) {
+        Ok(msg) => msg,
+        Err(msg) => return Object::error(format!("spawn: {}", msg)),
+    };
+    let mut messages = vec![];
+    for arg in &argv[1..] {
+        match Message::from_object(arg) {
+            Ok(msg) => messages.push(msg),
+            Err(msg) => return Object::error(format!("spawn: {}", msg)),
+        }
+    }
+    match Task::spawn(function, messages, env.runtime().borrow().inherited()) {
+        Ok(task) => Object::Task(task),
+        Err(msg) => Object::error(format!("spawn: {}", msg)),
+    }
+}
+
+pub fn gather(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(Vec<Object>,)>("gather", &argv) {
+        Ok((values,)) => Object::Future(Future::all(values)),
+        Err(e) => e,
+    }
+}
+
+pub fn join(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(Object,)>("join", &argv) {
+        Ok((Object::Task(task),)) => task.join(),
+        Ok((other,)) => Object::error(format!("join: expected task, found {}", other.type_name())),
+        Err(e) => e,
+    }
+}
+
+pub fn channel(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<()>("channel", &argv) {
+        Ok(()) => Object::Channel(Channel::new()),
+        Err(e) => e,
+    }
+}
+
+pub fn send(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (ch, value) = match args::<(Object, Object)>("send", &argv) {
+        Ok((Object::Channel(ch), value)) => (ch, value),
+        Ok((other, _)) => return Object::error(format!("send: expected channel, found {}", other.type_name())),
+        Err(e) => return e,
+    };
+    match Message::from_object(&value) {
+        Ok(msg) => {
+            ch.send(msg);
+            Object::Null
+        },
+        Err(msg) => Object::error(format!("send: {}", msg)),
+    }
+}
+
+// Blocks until something is sent. The wait counts as a step every tick, so
+// a zone's step budget runs out and cancelling stops it.
+pub fn recv(env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(Object,)>("recv", &argv) {
+        Ok((Object::Channel(ch),)) => match ch.recv(|| env.runtime().borrow_mut().step()) {
+            Ok(msg) => msg.into_object(),
+            Err(msg) => Object::error(format!("recv: {}", msg)),
+        },
+        Ok((other,)) => Object::error(format!("recv: expected channel, found {}", other.type_name())),
+        Err(e) => e,
+    }
+}
+
+pub fn pmap(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (elements, function) = match args::<(Object, Object)>("pmap", &argv) {
+        Ok((Object::Array(elements), function @ (Object::Function(_) | Object::Builtin(_) | Object::Partial(_)))) => (elements, function),
+        Ok((Object::Array(_), other)) => return Object::error(format!("pmap: argument 2: expected function, found {}", other.type_name())),
+        Ok((other, _)) => return Object::error(format!("pmap: argument 1: expected array, found {}", other.type_name())),
+        Err(e) => return e,
+    };
+    match task::map_parallel(&function, &elements, env.runtime().borrow().inherited()) {
+        Ok(result) => result,
+        Err(msg) => Object::error(format!("pmap: {}", msg)),
+    }
+}
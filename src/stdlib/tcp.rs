@@ -0,0 +1,217 @@
+// TCP sockets, for tiny services and clients:
+//
+//   unsafe {
+//       let server = tcp_listen(8080);
+//       loop {
+//           let conn = accept(server);
+//           let request = read(conn);
+//           write(conn, "HTTP/1.0 200 OK\r\n\r\nhello\n");
+//           close(conn);
+//       }
+//   }
+//
+//   unsafe {
+//       let conn = tcp_connect("example.com", 80);
+//       write(conn, "HEAD / HTTP/1.0\r\n\r\n");
+//       read(conn)
+//   }
+//
+// Sockets are resources (see resource.rs) handed out by tcp_listen,
+// tcp_connect and accept; they can be passed to `spawn(handle_client,
+// conn)` to serve a connection on its own task. read() returns what has
+// arrived (a string, or bytes if it isn't UTF-8) and null once the other
+// side has closed. tcp_listen binds 127.0.0.1 unless given a host. Needs
+// the net capability (`--allow-net`) and an unsafe block.
+//
+// read(), write() and close() work on files from open_file too (with the
+// filesystem capability instead). close() needs neither a capability nor
+// unsafe, so `defer close(conn);` can run wherever the function ends.
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use crate::environment::Environment;
+use crate::object::Object;
+use crate::resource::{Handle, Resource};
+use super::{args, require};
+
+// read() without a size reads at most this much
+const READ_SIZE: usize = 64 * 1024;
+
+fn resource(name: &str, value: &Object) -> Result<Resource, Object> {
+    match value {
+        Object::Resource(r) => Ok(r.clone()),
+        other => Err(Object::error(format!("{}: argument 1: expected resource, found {}", name, other.type_name()))),
+    }
+}
+
+// The socket's own handle to block on, so others can use the resource
+// while this waits for the network
+fn listener(name: &str, resource: &Resource) -> Result<TcpListener, Object> {
+    let cloned = resource.with(name, |handle| match handle {
+        Handle::Listener(l) => l.try_clone().map_err(|e| format!("{}: {}", name, e)),
+        _ => Err(format!("{}: {} is not a listener", name, resource)),
+    });
+    cloned.and_then(|r| r).map_err(Object::Error)
+}
+
+// What read and write work on: a connection or a file
+enum Stream {
+    Socket(TcpStream),
+    File(std::fs::File),
+}
+
+fn stream(name: &str, resource: &Resource, env: &Environment) -> Result<Stream, Object> {
+    let cloned = resource.with(name, |handle| match handle {
+        Handle::Stream(s) => s.try_clone().map(Stream::Socket).map_err(|e| format!("{}: {}", name, e)),
+        Handle::File(f) => f.try_clone().map(Stream::File).map_err(|e| format!("{}: {}", name, e)),
+        Handle::Listener(_) => Err(format!("{}: {} is a listener, not a connection", name, resource)),
+    });
+    let stream = cloned.and_then(|r| r).map_err(Object::Error)?;
+    match stream {
+        Stream::Socket(_) => require(env, name, |c| c.net, "--allow-net")?,
+        Stream::File(_) => require(env, name, |c| c.fs, "--allow-fs")?,
+    }
+    Ok(stream)
+}
+
+// tcp_listen(port) or tcp_listen(port, host)
+pub fn tcp_listen(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let parsed = if argv.len() == 1 {
+        args::<(i64,)>("tcp_listen", &argv).map(|(port,)| (port, "127.0.0.1".to_string()))
+    } else {
+        args::<(i64, String)>("tcp_listen", &argv)
+    };
+    let (port, host) = match parsed {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let Ok(port) = u16::try_from(port) else {
+        return Object::error(format!("tcp_listen: {} is not a port (0-65535)", port));
+    };
+    if let Err(e) = require(env, "tcp_listen", |c| c.net, "--allow-net") {
+        return e;
+    }
+    match TcpListener::bind((host.as_str(), port)) {
+        Ok(listener) => Object::Resource(Resource::new(Handle::Listener(listener))),
+        Err(e) => Object::error(format!("tcp_listen: {}:{}: {}", host, port, e)),
+    }
+}
+
+pub fn tcp_connect(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (host, port): (String, i64) = match args("tcp_connect", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let Ok(port) = u16::try_from(port) else {
+        return Object::error(format!("tcp_connect: {} is not a port (0-65535)", port));
+    };
+    if let Err(e) = require(env, "tcp_connect", |c| c.net, "--allow-net") {
+        return e;
+    }
+    match TcpStream::connect((host.as_str(), port)) {
+        Ok(stream) => Object::Resource(Resource::new(Handle::Stream(stream))),
+        Err(e) => Object::error(format!("tcp_connect: {}:{}: {}", host, port, e)),
+    }
+}
+
+// The port a listener got, for tcp_listen(0)
+pub fn tcp_port(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let handle = match args::<(Object,)>("tcp_port", &argv).and_then(|(r,)| resource("tcp_port", &r)) {
+        Ok(r) => r,
+        Err(e) => return e,
+    };
+    if let Err(e) = require(env, "tcp_port", |c| c.net, "--allow-net") {
+        return e;
+    }
+    match listener("tcp_port", &handle).and_then(|l| l.local_addr().map_err(|e| Object::error(format!("tcp_port: {}", e)))) {
+        Ok(addr) => Object::Integer(i64::from(addr.port())),
+        Err(e) => e,
+    }
+}
+
+// Blocks until a client connects
+pub fn accept(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let handle = match args::<(Object,)>("accept", &argv).and_then(|(r,)| resource("accept", &r)) {
+        Ok(r) => r,
+        Err(e) => return e,
+    };
+    if let Err(e) = require(env, "accept", |c| c.net, "--allow-net") {
+        return e;
+    }
+    let listener = match listener("accept", &handle) {
+        Ok(l) => l,
+        Err(e) => return e,
+    };
+    match listener.accept() {
+        Ok((stream, _)) => Object::Resource(Resource::new(Handle::Stream(stream))),
+        Err(e) => Object::error(format!("accept: {}", e)),
+    }
+}
+
+// read(conn) or read(conn, max_bytes); blocks until something arrives.
+// On a file, the next part of it.
+pub fn read(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let parsed = if argv.len() == 1 {
+        args::<(Object,)>("read", &argv).map(|(handle,)| (handle, READ_SIZE as i64))
+    } else {
+        args::<(Object, i64)>("read", &argv)
+    };
+    let (handle, size) = match parsed.and_then(|(r, size)| Ok((resource("read", &r)?, size))) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let size = match usize::try_from(size) {
+        Ok(n) if n > 0 => n,
+        _ => return Object::error(format!("read: size must be positive, found {}", size)),
+    };
+    let mut buf = vec![0; size];
+    let read = match stream("read", &handle, env) {
+        Ok(Stream::Socket(mut s)) => s.read(&mut buf),
+        Ok(Stream::File(mut f)) => f.read(&mut buf),
+        Err(e) => return e,
+    };
+    match read {
+        Ok(0) => Object::Null,
+        Ok(n) => {
+            buf.truncate(n);
+            match String::from_utf8(buf) {
+                Ok(text) => Object::String(text),
+                Err(e) => Object::Bytes(e.into_bytes()),
+            }
+        },
+        Err(e) => Object::error(format!("read: {}", e)),
+    }
+}
+
+// write(conn, string or bytes); all of it, returns the byte count
+pub fn write(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (handle, data) = match args::<(Object, Object)>("write", &argv).and_then(|(r, data)| Ok((resource("write", &r)?, data))) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let data = match data {
+        Object::String(s) => s.into_bytes(),
+        Object::Bytes(b) => b,
+        other => return Object::error(format!("write: argument 2: expected string or bytes, found {}", other.type_name())),
+    };
+    let written = match stream("write", &handle, env) {
+        Ok(Stream::Socket(mut s)) => s.write_all(&data),
+        Ok(Stream::File(mut f)) => f.write_all(&data),
+        Err(e) => return e,
+    };
+    match written {
+        Ok(()) => Object::Integer(data.len() as i64),
+        Err(e) => Object::error(format!("write: {}", e)),
+    }
+}
+
+// Closing something already closed is fine, so an explicit close() and a
+// deferred one don't clash
+pub fn close(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(Object,)>("close", &argv).and_then(|(r,)| resource("close", &r)) {
+        Ok(handle) => {
+            handle.close();
+            Object::Null
+        },
+        Err(e) => e,
+    }
+}
@@ -0,0 +1,203 @@
+// Clocks, sleeping and dates. Reading the time makes a script's output
+// depend on when it runs, so time(), clock_ms(), now(), sleep() and
+// sleep_async() need the time capability (granted by the CLI, off for
+// embedders unless they ask for it). Working with dates someone else made doesn't:
+//
+//   let start = date_parse("2024-03-01 09:30", "%Y-%m-%d %H:%M");
+//   let end = date_add(start, 1, "months");   // 2024-04-01T09:30:00Z
+//   end - start                               // 2678400 (seconds)
+//   date_format(end + 90, "%a %d %b, %H:%M")  // "Mon 01 Apr, 09:31"
+//
+// Datetimes are UTC. `+` and `-` with an int move by seconds, and one
+// datetime minus another is the seconds between them. Anywhere a datetime
+// is expected an int counts as Unix seconds, so date_format(time(), ...)
+// works too. See datetime.rs for the format directives.
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use indexmap::IndexMap;
+use crate::convert::FromObject;
+use crate::datetime::DateTime;
+use crate::environment::Environment;
+use crate::future::Future;
+use crate::object::Object;
+use super::{args, allowed};
+
+// Seconds since the Unix epoch
+pub fn time(env: &mut Environment, argv: Vec<Object>) -> Object {
+    if let Err(e) = args::<()>("time", &argv).and(allowed(env, "time", |c| c.time, "the time capability")) {
+        return e;
+    }
+    let live = || match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => Ok(d.as_secs() as i64),
+        Err(_) => Err("system clock is before 1970".to_string()),
+    };
+    match env.runtime().borrow_mut().trace.input("time", live) {
+        Ok(secs) => Object::Integer(secs),
+        Err(msg) => Object::error(format!("time: {}", msg)),
+    }
+}
+
+// Monotonic milliseconds, only meaningful as a difference between two calls
+pub fn clock_ms(env: &mut Environment, argv: Vec<Object>) -> Object {
+    if let Err(e) = args::<()>("clock_ms", &argv).and(allowed(env, "clock_ms", |c| c.time, "the time capability")) {
+        return e;
+    }
+    static ORIGIN: OnceLock<Instant> = OnceLock::new();
+    let origin = ORIGIN.get_or_init(Instant::now);
+    match env.runtime().borrow_mut().trace.input("clock_ms", || Ok(origin.elapsed().as_millis() as i64)) {
+        Ok(ms) => Object::Integer(ms),
+        Err(msg) => Object::error(format!("clock_ms: {}", msg)),
+    }
+}
+
+pub fn sleep(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (ms,): (i64,) = match args("sleep", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    if let Err(e) = allowed(env, "sleep", |c| c.time, "the time capability") {
+        return e;
+    }
+    if ms < 0 {
+        return Object::error(format!("sleep: negative duration {}", ms));
+    }
+    std::thread::sleep(Duration::from_millis(ms as u64));
+    Object::Null
+}
+
+// A future that is ready (with null) after ms, for async functions to wait
+// on without holding up the others
+pub fn sleep_async(env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (ms,): (i64,) = match args("sleep_async", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    if let Err(e) = allowed(env, "sleep_async", |c| c.time, "the time capability") {
+        return e;
+    }
+    if ms < 0 {
+        return Object::error(format!("sleep_async: negative duration {}", ms));
+    }
+    Object::Future(Future::sleep(Duration::from_millis(ms as u64)))
+}
+
+// The current time as a datetime, to the millisecond
+pub fn now(env: &mut Environment, argv: Vec<Object>) -> Object {
+    if let Err(e) = args::<()>("now", &argv).and(allowed(env, "now", |c| c.time, "the time capability")) {
+        return e;
+    }
+    let live = || match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => Ok(d.as_millis() as i64),
+        Err(_) => Err("system clock is before 1970".to_string()),
+    };
+    match env.runtime().borrow_mut().trace.input("now", live) {
+        Ok(ms) => Object::DateTime(DateTime::from_millis(ms)),
+        Err(msg) => Object::error(format!("now: {}", msg)),
+    }
+}
+
+// date(unix_seconds) or date(year, month, day, hour?, minute?, second?)
+pub fn date(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    if argv.len() == 1 {
+        return match args::<(DateTime,)>("date", &argv) {
+            Ok((d,)) => Object::DateTime(d),
+            Err(e) => e,
+        };
+    }
+    if !(3..=6).contains(&argv.len()) {
+        return Object::error(format!("date: wrong number of arguments: expected 1 or 3 to 6, found {}", argv.len()));
+    }
+    let mut fields = [0; 6];
+    for (i, arg) in argv.iter().enumerate() {
+        match i64::from_object(arg) {
+            Ok(n) => fields[i] = n,
+            Err(e) => return Object::error(format!("date: argument {}: {}", i + 1, e)),
+        }
+    }
+    let [year, month, day, hour, minute, second] = fields;
+    match DateTime::from_parts(year, month, day, hour, minute, second, 0) {
+        Some(d) => Object::DateTime(d),
+        None => Object::error(format!("date: no such time: {}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, hour, minute, second)),
+    }
+}
+
+pub fn date_format(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (date, format): (DateTime, String) = match args("date_format", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    match date.format(&format) {
+        Ok(text) => Object::String(text),
+        Err(msg) => Object::error(format!("date_format: {}", msg)),
+    }
+}
+
+// date_parse(text) for ISO 8601, or date_parse(text, format)
+pub fn date_parse(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let parsed = if argv.len() == 1 {
+        args::<(String,)>("date_parse", &argv).map(|(text,)| (text, None))
+    } else {
+        args::<(String, String)>("date_parse", &argv).map(|(text, format)| (text, Some(format)))
+    };
+    let (text, format) = match parsed {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let result = match format {
+        Some(format) => DateTime::parse(&text, &format),
+        None => DateTime::parse_iso(&text),
+    };
+    match result {
+        Ok(d) => Object::DateTime(d),
+        Err(msg) => Object::error(format!("date_parse: {}", msg)),
+    }
+}
+
+// {year, month, day, hour, minute, second, millisecond, weekday, yearday};
+// weekday 1 is Monday
+pub fn date_parts(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (date,): (DateTime,) = match args("date_parts", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let p = date.parts();
+    let fields = [
+        ("year", p.year), ("month", p.month), ("day", p.day),
+        ("hour", p.hour), ("minute", p.minute), ("second", p.second), ("millisecond", p.millisecond),
+        ("weekday", p.weekday), ("yearday", p.yearday),
+    ];
+    Object::Hash(fields.into_iter().map(|(k, v)| (k.into(), v.into())).collect::<IndexMap<_, _>>())
+}
+
+// date_add(date, n, unit); months and years keep the day where they can
+// and otherwise use the month's last day
+pub fn date_add(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    let (date, n, unit): (DateTime, i64, String) = match args("date_add", &argv) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let millis = |per: i64| n.checked_mul(per).and_then(|ms| date.checked_add_millis(ms));
+    let result = match unit.as_str() {
+        "ms" | "milliseconds" => millis(1),
+        "seconds" => millis(1000),
+        "minutes" => millis(60_000),
+        "hours" => millis(3_600_000),
+        "days" => millis(86_400_000),
+        "weeks" => millis(7 * 86_400_000),
+        "months" => date.checked_add_months(n),
+        "years" => n.checked_mul(12).and_then(|months| date.checked_add_months(months)),
+        _ => return Object::error(format!("date_add: unknown unit {:?} (ms, seconds, minutes, hours, days, weeks, months, years)", unit)),
+    };
+    match result {
+        Some(d) => Object::DateTime(d),
+        None => Object::error(format!("date_add: {} plus {} {} is out of range", date, n, unit)),
+    }
+}
+
+// Unix seconds, for storing or for APIs that want a number
+pub fn timestamp(_env: &mut Environment, argv: Vec<Object>) -> Object {
+    match args::<(DateTime,)>("timestamp", &argv) {
+        Ok((date,)) => Object::Integer(date.timestamp()),
+        Err(e) => e,
+    }
+}
@@ -0,0 +1,295 @@
+// Threads for scripts. Each task runs a function on its own OS thread
+// with a fresh interpreter (its own bindings, the starting runtime's
+// capabilities and settings, and a share in its limits; see
+// runtime::Inherited), and tasks talk through channels. At most
+// runtime::MAX_TASKS run at once.
+//
+// Interpreter objects are single-threaded (Rc everywhere), so nothing is
+// shared: arguments, messages and results are deep-copied into a
+// `Message` on the way across and rebuilt on the other side. Functions
+// travel as their AST. Channels, tasks and resources are the exception;
+// their handles are Arc'd and can be passed around freely.
+use std::cell::RefCell;
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt;
+use std::rc::Rc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use indexmap::IndexMap;
+use crate::ast::{BlockStatement, Identifier};
+use crate::datetime::DateTime;
+use crate::decimal::Decimal;
+use crate::environment::{Environment, Globals};
+use crate::evaluator::call_function;
+use crate::object::{Buffer, Function, HashKey, Object, Partial, Quote};
+use crate::resource::Resource;
+use crate::runtime::{Inherited, Runtime};
+use crate::stdlib::Builtin;
+
+// An Object that can cross threads
+pub enum Message {
+    Integer(i64),
+    #[cfg(feature = "bigint")]
+    BigInt(num_bigint::BigInt),
+    Decimal(Decimal),
+    DateTime(DateTime),
+    Boolean(bool),
+    String(String),
+    Char(char),
+    Bytes(Vec<u8>),
+    // The other side gets a buffer of its own
+    Buffer(String),
+    Array(Vec<Message>),
+    Tuple(Vec<Message>),
+    Hash(IndexMap<HashKey, Message>),
+    Set(BTreeSet<HashKey>),
+    Null,
+    // Its globals stay behind; what it captured goes along
+    Function(Vec<Identifier>, BlockStatement, Arc<[String]>, Vec<(String, Message)>, bool),
+    Builtin(Builtin),
+    Partial(Box<Message>, Vec<Message>, Option<usize>),
+    Channel(Channel),
+    Task(Task),
+    Resource(Resource),
+    Quote(Quote),
+    Error(String),
+}
+
+impl Message {
+    pub fn from_object(obj: &Object) -> Result<Message, String> {
+        let all = |elements: &[Object]| elements.iter().map(Message::from_object).collect::<Result<Vec<_>, _>>();
+        Ok(match obj {
+            Object::Integer(i) => Message::Integer(*i),
+            #[cfg(feature = "bigint")]
+            Object::BigInt(b) => Message::BigInt(b.clone()),
+            Object::Decimal(d) => Message::Decimal(*d),
+            Object::DateTime(d) => Message::DateTime(*d),
+            Object::Boolean(b) => Message::Boolean(*b),
+            Object::String(s) => Message::String(s.clone()),
+            Object::Char(c) => Message::Char(*c),
+            Object::Bytes(b) => Message::Bytes(b.clone()),
+            Object::Buffer(b) => Message::Buffer(b.0.borrow().clone()),
+            Object::Array(elements) => Message::Array(all(elements)?),
+            Object::Tuple(elements) => Message::Tuple(all(elements)?),
+            Object::Hash(pairs) => {
+                let mut out = IndexMap::new();
+                for (k, v) in pairs {
+                    out.insert(k.clone(), Message::from_object(v)?);
+                }
+                Message::Hash(out)
+            },
+            Object::Set(keys) => Message::Set(keys.clone()),
+            Object::Null => Message::Null,
+            Object::ReturnValue(val) | Object::Break(val, _) => Message::from_object(val)?,
+            Object::Continue(_) => Message::Null,
+            Object::Function(f) => {
+                let captured = f.captured.iter().map(|(name, value)| Ok((name.clone(), Message::from_object(value)?)));
+                Message::Function(f.parameters.clone(), f.body.clone(), f.locals.clone(), captured.collect::<Result<_, String>>()?, f.is_async)
+            },
+            Object::Builtin(b) => Message::Builtin(*b),
+            Object::Partial(p) => Message::Partial(Box::new(Message::from_object(&p.function)?), all(&p.args)?, p.arity),
+            Object::Channel(c) => Message::Channel(c.clone()),
+            Object::Task(t) => Message::Task(t.clone()),
+            Object::Resource(r) => Message::Resource(r.clone()),
+            Object::Quote(q) => Message::Quote((**q).clone()),
+            Object::Error(msg) => Message::Error(msg.clone()),
+            Object::Native(_) | Object::Generator(_) | Object::Future(_) => {
+                return Err(format!("a {} can't be sent to another thread", obj.type_name()));
+            },
+        })
+    }
+
+    pub fn into_object(self) -> Object {
+        let all = |elements: Vec<Message>| elements.into_iter().map(Message::into_object).collect();
+        match self {
+            Message::Integer(i) => Object::Integer(i),
+            #[cfg(feature = "bigint")]
+            Message::BigInt(b) => Object::BigInt(b),
+            Message::Decimal(d) => Object::Decimal(d),
+            Message::DateTime(d) => Object::DateTime(d),
+            Message::Boolean(b) => Object::Boolean(b),
+            Message::String(s) => Object::String(s),
+            Message::Char(c) => Object::Char(c),
+            Message::Bytes(b) => Object::Bytes(b),
+            Message::Buffer(s) => Object::Buffer(Buffer(Rc::new(RefCell::new(s)))),
+            Message::Array(elements) => Object::Array(all(elements)),
+            Message::Tuple(elements) => Object::Tuple(all(elements)),
+            Message::Hash(pairs) => Object::Hash(pairs.into_iter().map(|(k, v)| (k, v.into_object())).collect()),
+            Message::Set(keys) => Object::Set(keys),
+            Message::Null => Object::Null,
+            Message::Function(parameters, body, locals, captured, is_async) => {
+                let captured = captured.into_iter().map(|(name, value)| (name, value.into_object())).collect();
+                Object::Function(Rc::new(Function { parameters, body, locals, globals: Globals::default(), captured, is_async, compiled: None }))
+            },
+            Message::Builtin(b) => Object::Builtin(b),
+            Message::Partial(function, args, arity) => {
+                Object::Partial(Partial { function: Box::new(function.into_object()), args: all(args), arity })
+            },
+            Message::Channel(c) => Object::Channel(c),
+            Message::Task(t) => Object::Task(t),
+            Message::Resource(r) => Object::Resource(r),
+            Message::Quote(q) => Object::Quote(Rc::new(q)),
+            Message::Error(msg) => Object::Error(msg),
+        }
+    }
+}
+
+// --- CHANNELS ---
+
+// Unbounded FIFO; any number of tasks may send and receive
+#[derive(Clone, Default)]
+pub struct Channel {
+    inner: Arc<(Mutex<VecDeque<Message>>, Condvar)>,
+}
+
+impl Channel {
+    pub fn new() -> Self {
+        Channel::default()
+    }
+
+    pub fn send(&self, msg: Message) {
+        let (queue, ready) = &*self.inner;
+        queue.lock().unwrap_or_else(|e| e.into_inner()).push_back(msg);
+        ready.notify_one();
+    }
+
+    // Blocks until a message arrives. Every TICK spent waiting it calls
+    // `waiting`, and gives up with its error if there is one.
+    pub fn recv(&self, mut waiting: impl FnMut() -> Result<(), String>) -> Result<Message, String> {
+        let (queue, ready) = &*self.inner;
+        let mut queue = queue.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if let Some(msg) = queue.pop_front() {
+                return Ok(msg);
+            }
+            let (next, timeout) = ready.wait_timeout(queue, TICK).unwrap_or_else(|e| e.into_inner());
+            queue = next;
+            if timeout.timed_out() {
+                waiting()?;
+            }
+        }
+    }
+}
+
+// How often a blocked recv looks up from waiting
+const TICK: Duration = Duration::from_millis(10);
+
+impl fmt::Debug for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Channel")
+    }
+}
+
+impl PartialEq for Channel {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+// --- TASKS ---
+
+#[derive(Clone)]
+pub struct Task {
+    handle: Arc<Mutex<Option<JoinHandle<Message>>>>,
+}
+
+impl Task {
+    // Calls `function(args...)` on a new thread. Fails when too many tasks
+    // are running, where threads aren't available (wasm) or the OS refuses.
+    pub fn spawn(function: Message, args: Vec<Message>, inherited: Inherited) -> Result<Self, String> {
+        let slot = inherited.reserve_tasks(1)?;
+        let handle = thread::Builder::new().spawn(move || {
+            let _slot = slot;
+            let mut env = Environment::with_runtime(Rc::new(RefCell::new(Runtime::inheriting(inherited))));
+            let args: Vec<Object> = args.into_iter().map(Message::into_object).collect();
+            let result = match function {
+                Message::Function(..) | Message::Builtin(_) | Message::Partial(..) => call_function(function.into_object(), args, &mut env),
+                _ => Object::error("spawn: expected function"),
+            };
+            Message::from_object(&result).unwrap_or_else(|msg| Message::Error(format!("task result: {}", msg)))
+        }).map_err(|e| e.to_string())?;
+        Ok(Task { handle: Arc::new(Mutex::new(Some(handle))) })
+    }
+
+    // Whether join() would return straight away
+    pub fn is_finished(&self) -> bool {
+        self.handle.lock().unwrap_or_else(|e| e.into_inner()).as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    // Waits for the task and returns its result; only the first join gets it
+    pub fn join(&self) -> Object {
+        let handle = self.handle.lock().unwrap_or_else(|e| e.into_inner()).take();
+        match handle.map(JoinHandle::join) {
+            Some(Ok(msg)) => msg.into_object(),
+            Some(Err(_)) => Object::error("join: task panicked"),
+            None => Object::error("join: task was already joined"),
+        }
+    }
+}
+
+// --- PARALLEL MAP ---
+
+// function(x) for every element, the elements split into one run of
+// neighbours per worker thread (at most one per core, and only as many as
+// MAX_TASKS leaves room for). Each worker has its
+// own interpreter, like a task. The results come back in order as an
+// array, unless a call fails; then the first error, by position, is the
+// result. Err is for what went wrong around the calls (a value that can't
+// be sent, no threads).
+pub fn map_parallel(function: &Object, elements: &[Object], inherited: Inherited) -> Result<Object, String> {
+    let wanted = thread::available_parallelism().map_or(1, |n| n.get()).min(elements.len()).max(1);
+    let slots = inherited.reserve_tasks(wanted)?;
+    let workers = slots.count;
+    let size = elements.len().div_ceil(workers).max(1);
+    let mut jobs = vec![];
+    for chunk in elements.chunks(size) {
+        let chunk = chunk.iter().map(Message::from_object).collect::<Result<Vec<_>, _>>()?;
+        jobs.push((Message::from_object(function)?, chunk));
+    }
+    let results = thread::scope(|scope| {
+        let handles = jobs.into_iter().map(|(function, chunk)| {
+            let inherited = inherited.clone();
+            thread::Builder::new().spawn_scoped(scope, move || map_chunk(function, chunk, inherited))
+        });
+        let handles = handles.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+        handles.into_iter().map(|h| h.join().map_err(|_| "a worker panicked".to_string())).collect::<Result<Vec<_>, _>>()
+    })?;
+    let mut out = Vec::with_capacity(elements.len());
+    for msg in results.into_iter().flatten() {
+        match msg.into_object() {
+            Object::Error(msg) => return Ok(Object::Error(msg)),
+            value => out.push(value),
+        }
+    }
+    Ok(Object::Array(out))
+}
+
+// Stops at the first error, which ends the chunk
+fn map_chunk(function: Message, chunk: Vec<Message>, inherited: Inherited) -> Vec<Message> {
+    let mut env = Environment::with_runtime(Rc::new(RefCell::new(Runtime::inheriting(inherited))));
+    let function = function.into_object();
+    let mut out = vec![];
+    for element in chunk {
+        let result = call_function(function.clone(), vec![element.into_object()], &mut env);
+        let msg = Message::from_object(&result).unwrap_or_else(|msg| Message::Error(format!("pmap result: {}", msg)));
+        let failed = matches!(msg, Message::Error(_));
+        out.push(msg);
+        if failed {
+            break;
+        }
+    }
+    out
+}
+
+impl fmt::Debug for Task {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Task")
+    }
+}
+
+impl PartialEq for Task {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.handle, &other.handle)
+    }
+}
@@ -0,0 +1,298 @@
+// Text templates with Nova in them (`nova render`):
+//
+//   Hello {{ user.name }}!
+//   {% if len(items) > 0 %}
+//   {% for item in items %}
+//   - {{ item }}
+//   {% end %}
+//   {% else %}
+//   Nothing yet.
+//   {% end %}
+//   {# a comment #}
+//
+// `{{ code }}` is replaced by what the code evaluates to (null by nothing,
+// strings without quotes). `{% if %}` takes `{% else if %}` and
+// `{% else %}` branches; `{% for x in e %}` goes through what a for loop
+// would. Both end at `{% end %}`. Everything runs in the environment
+// render() is given, and like a block a for doesn't open a scope: `x`
+// stays bound afterwards.
+//
+// A tag or comment alone on its line takes the whole line with it, so
+// blocks can be laid out like the text around them. Tags end at the first
+// `}}`, `%}` or `#}`, even inside a string.
+//
+// Errors are diagnostics with spans in the template's text.
+use crate::ast::{Expression, ForExpression, Program, Statement};
+use crate::diagnostics::Diagnostic;
+use crate::environment::Environment;
+use crate::evaluator::{bind, eval_expression, eval_program, is_truthy, iterate};
+use crate::lexer::Lexer;
+use crate::object::Object;
+use crate::operators::Operators;
+use crate::parser::Parser;
+use crate::span::Span;
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Output(Program, Span),
+    // Each condition with its branch, then the else branch
+    If(Vec<(Program, Span, Vec<Node>)>, Vec<Node>),
+    For(Box<ForExpression>, Span, Vec<Node>),
+}
+
+// What the scanner cuts a template into
+enum Piece {
+    Text(String),
+    Output(String, usize, Span),
+    // A {% %} tag: its trimmed contents, where they start, the whole tag
+    Tag(String, usize, Span),
+}
+
+impl Template {
+    pub fn parse(text: &str) -> Result<Template, Diagnostic> {
+        Template::with_operators(text, &Operators::new())
+    }
+
+    // Parses the code in the tags with the host's custom operators too
+    pub fn with_operators(text: &str, operators: &Operators) -> Result<Template, Diagnostic> {
+        let pieces = scan(text)?;
+        let mut pieces = pieces.into_iter();
+        let (nodes, end) = block(&mut pieces, operators)?;
+        match end {
+            None => Ok(Template { nodes }),
+            Some((tag, _, span)) => Err(Diagnostic::error(format!("{{% {} %}} without a block to close", tag), span)),
+        }
+    }
+
+    // The text, with every tag replaced by what it evaluates to
+    pub fn render(&self, env: &mut Environment) -> Result<String, Diagnostic> {
+        let mut out = String::new();
+        render_nodes(&self.nodes, env, &mut out)?;
+        Ok(out)
+    }
+}
+
+// Template parsing and render in one go, with the runtime's operators
+pub fn render(text: &str, env: &mut Environment) -> Result<String, Diagnostic> {
+    let operators = env.runtime().borrow().operators.clone();
+    Template::with_operators(text, &operators)?.render(env)
+}
+
+fn scan(text: &str) -> Result<Vec<Piece>, Diagnostic> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut pieces = vec![];
+    let mut pending = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let close = match (chars[i], chars.get(i + 1)) {
+            ('{', Some('{')) => "}}",
+            ('{', Some('%')) => "%}",
+            ('{', Some('#')) => "#}",
+            (c, _) => {
+                pending.push(c);
+                i += 1;
+                continue;
+            },
+        };
+        let start = i + 2;
+        let Some(end) = find(&chars, start, close) else {
+            let opening: String = chars[i..start].iter().collect();
+            return Err(Diagnostic::error(format!("{} is never closed with {}", opening, close), Span::new(i, start)));
+        };
+        let span = Span::new(i, end + 2);
+        let inner: String = chars[start..end].iter().collect();
+        i = end + 2;
+        if close == "}}" {
+            pieces.push(Piece::Text(std::mem::take(&mut pending)));
+            pieces.push(Piece::Output(inner, start, span));
+            continue;
+        }
+        // Alone on its line: the indentation before it and the line break
+        // after it go too
+        let before = chars[..span.start].iter().rev().take_while(|c| **c != '\n').all(|c| c.is_whitespace());
+        let rest = chars[i..].iter().take_while(|c| **c != '\n').count();
+        if before && chars[i..i + rest].iter().all(|c| c.is_whitespace()) {
+            let indent = pending.len() - pending.trim_end_matches([' ', '\t', '\r']).len();
+            pending.truncate(pending.len() - indent);
+            i = (i + rest + 1).min(chars.len());
+        }
+        if close == "%}" {
+            pieces.push(Piece::Text(std::mem::take(&mut pending)));
+            let offset = start + inner.chars().take_while(|c| c.is_whitespace()).count();
+            pieces.push(Piece::Tag(inner.trim().to_string(), offset, span));
+        }
+    }
+    pieces.push(Piece::Text(pending));
+    Ok(pieces)
+}
+
+fn find(chars: &[char], from: usize, pattern: &str) -> Option<usize> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    (from..chars.len().saturating_sub(1)).find(|&i| chars[i..].starts_with(&pattern))
+}
+
+// A tag that ends a block (else or end), as Piece::Tag has it
+type End = (String, usize, Span);
+
+// Nodes up to the tag that ends the block, if any
+fn block(pieces: &mut impl Iterator<Item = Piece>, operators: &Operators) -> Result<(Vec<Node>, Option<End>), Diagnostic> {
+    let mut nodes = vec![];
+    while let Some(piece) = pieces.next() {
+        match piece {
+            Piece::Text(text) if text.is_empty() => {},
+            Piece::Text(text) => nodes.push(Node::Text(text)),
+            Piece::Output(code, offset, span) => nodes.push(Node::Output(program(&code, offset, span, operators)?, span)),
+            Piece::Tag(tag, offset, span) => {
+                if tag == "end" || tag == "else" || tag.starts_with("else ") {
+                    return Ok((nodes, Some((tag, offset, span))));
+                }
+                if let Some(condition) = keyword(&tag, "if") {
+                    nodes.push(if_block(condition, offset + skipped(&tag, condition), span, pieces, operators)?);
+                } else if let Some(head) = keyword(&tag, "for") {
+                    let head = for_head(head, offset + skipped(&tag, head), span, operators)?;
+                    let (body, (tag, _, end)) = closed(block(pieces, operators)?, span, "for")?;
+                    if tag != "end" {
+                        return Err(Diagnostic::error(format!("{{% {} %}} in a {{% for %}}", tag), end));
+                    }
+                    nodes.push(Node::For(head, span, body));
+                } else {
+                    return Err(Diagnostic::error(format!("Unknown tag {{% {} %}} (expected if, else, for or end)", tag), span));
+                }
+            },
+        }
+    }
+    Ok((nodes, None))
+}
+
+fn if_block(condition: &str, offset: usize, span: Span, pieces: &mut impl Iterator<Item = Piece>, operators: &Operators) -> Result<Node, Diagnostic> {
+    let mut branches = vec![];
+    let mut condition = (program(condition, offset, span, operators)?, span);
+    loop {
+        let (body, (tag, offset, end)) = closed(block(pieces, operators)?, span, "if")?;
+        branches.push((condition.0, condition.1, body));
+        if tag == "end" {
+            return Ok(Node::If(branches, vec![]));
+        }
+        if tag == "else" {
+            let (body, (tag, _, end)) = closed(block(pieces, operators)?, end, "else")?;
+            if tag != "end" {
+                return Err(Diagnostic::error(format!("{{% {} %}} after {{% else %}}", tag), end));
+            }
+            return Ok(Node::If(branches, body));
+        }
+        match tag.strip_prefix("else").map(str::trim_start).and_then(|t| keyword(t, "if")) {
+            Some(next) => condition = (program(next, offset + skipped(&tag, next), end, operators)?, end),
+            None => return Err(Diagnostic::error(format!("Unknown tag {{% {} %}} (expected else, else if or end)", tag), end)),
+        }
+    }
+}
+
+// The tag that ended a block, which has to be there
+fn closed(result: (Vec<Node>, Option<End>), open: Span, what: &str) -> Result<(Vec<Node>, End), Diagnostic> {
+    match result {
+        (nodes, Some(end)) => Ok((nodes, end)),
+        (_, None) => Err(Diagnostic::error(format!("{{% {} %}} is never closed with {{% end %}}", what), open)),
+    }
+}
+
+// What follows `word` and a space
+fn keyword<'a>(tag: &'a str, word: &str) -> Option<&'a str> {
+    tag.strip_prefix(word).filter(|rest| rest.starts_with(char::is_whitespace)).map(str::trim_start)
+}
+
+// Chars of `tag` before `rest`, which is the end of it
+fn skipped(tag: &str, rest: &str) -> usize {
+    tag.chars().count() - rest.chars().count()
+}
+
+// Parsed, with spans moved to where the code sits in the template
+fn program(code: &str, offset: usize, tag: Span, operators: &Operators) -> Result<Program, Diagnostic> {
+    if code.trim().is_empty() {
+        return Err(Diagnostic::error("Empty tag", tag));
+    }
+    let mut p = Parser::with_operators(Lexer::new(code.to_string()), operators.clone());
+    let mut program = p.parse_program();
+    if let Some(mut error) = p.errors.into_iter().next() {
+        error.span = Span::new(error.span.start + offset, error.span.end + offset);
+        return Err(error);
+    }
+    for statement in &mut program.statements {
+        statement.shift_spans(offset as isize);
+    }
+    Ok(program)
+}
+
+// `x in items`, read as the head of a for loop
+fn for_head(head: &str, offset: usize, tag: Span, operators: &Operators) -> Result<Box<ForExpression>, Diagnostic> {
+    let code = format!("for {} {{}}", head);
+    let mut program = program(&code, offset - 4, tag, operators)?;
+    match program.statements.pop() {
+        Some(Statement::Expression(s)) if program.statements.is_empty() => match s.expression {
+            Expression::For(fe) => Ok(Box::new(fe)),
+            _ => Err(Diagnostic::error("Expected {% for name in value %}", tag)),
+        },
+        _ => Err(Diagnostic::error("Expected {% for name in value %}", tag)),
+    }
+}
+
+fn render_nodes(nodes: &[Node], env: &mut Environment, out: &mut String) -> Result<(), Diagnostic> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Output(program, span) => match evaluate(program, *span, env)? {
+                Object::Null => {},
+                value => out.push_str(&value.to_string()),
+            },
+            Node::If(branches, otherwise) => {
+                let mut chosen = otherwise;
+                for (condition, span, body) in branches {
+                    if is_truthy(&evaluate(condition, *span, env)?, env) {
+                        chosen = body;
+                        break;
+                    }
+                }
+                render_nodes(chosen, env, out)?;
+            },
+            Node::For(fe, span, body) => {
+                let items = iterate(eval_expression(&fe.iterable, env)).map_err(|e| failure(e, *span, env))?;
+                for item in items {
+                    let item = item.map_err(|e| failure(e, *span, env))?;
+                    let bound = bind(env, &fe.variable, item);
+                    if bound.is_error() {
+                        return Err(failure(bound, *span, env));
+                    }
+                    render_nodes(body, env, out)?;
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+fn evaluate(program: &Program, tag: Span, env: &mut Environment) -> Result<Object, Diagnostic> {
+    match eval_program(program, env) {
+        Object::Error(msg) => Err(failure(Object::Error(msg), tag, env)),
+        value => Ok(value),
+    }
+}
+
+// At the statement the error came from, or else the tag
+fn failure(error: Object, tag: Span, env: &Environment) -> Diagnostic {
+    let span = env.runtime().borrow_mut().error_span.take().unwrap_or(tag);
+    let frame = env.runtime().borrow_mut().error_frame.take();
+    let message = match error {
+        Object::Error(msg) => msg,
+        other => other.to_string(),
+    };
+    let diagnostic = Diagnostic::error(message, span);
+    match frame {
+        Some(frame) => diagnostic.with_note(frame.to_string()),
+        None => diagnostic,
+    }
+}
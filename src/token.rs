@@ -1,5 +1,7 @@
 use std::fmt;
+use crate::decimal::Decimal;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenType {
     Illegal,
@@ -8,6 +10,9 @@ pub enum TokenType {
     // Identifiers + Literals
     Ident(String),
     Int(i64),
+    Str(String),
+    Char(char),
+    Decimal(Decimal), // 1.50d
 
     // Operators
     Assign,   // =
@@ -25,12 +30,20 @@ pub enum TokenType {
     
     // Delimiters
     Comma,
+    Colon,
     Semicolon,
     LParen,
     RParen,
     LBrace,
     RBrace,
+    LBracket,
+    RBracket,
     Arrow,  // ->
+    Pipe,   // |  (around lambda parameters)
+    And,    // &&
+    Or,     // ||
+    DotDot, // .. (the rest of an array pattern)
+    Operator(String), // Registered by the embedder (operators.rs)
 
     // Keywords
     Function,
@@ -43,6 +56,22 @@ pub enum TokenType {
     Return,
     Unsafe,
     Zone,
+    Yield,
+    Defer,
+    Loop,
+    Break,
+    For,
+    In,
+    Match,
+    Switch,
+    Case,
+    Default,
+    Do,
+    While,
+    Continue,
+    Async,
+    Await,
+    Macro,
 }
 
 pub fn lookup_ident(ident: &str) -> TokenType {
@@ -57,6 +86,22 @@ pub fn lookup_ident(ident: &str) -> TokenType {
         "return" => TokenType::Return,
         "unsafe" => TokenType::Unsafe,
         "zone" => TokenType::Zone,
+        "yield" => TokenType::Yield,
+        "defer" => TokenType::Defer,
+        "loop" => TokenType::Loop,
+        "break" => TokenType::Break,
+        "for" => TokenType::For,
+        "in" => TokenType::In,
+        "match" => TokenType::Match,
+        "switch" => TokenType::Switch,
+        "case" => TokenType::Case,
+        "default" => TokenType::Default,
+        "do" => TokenType::Do,
+        "while" => TokenType::While,
+        "continue" => TokenType::Continue,
+        "async" => TokenType::Async,
+        "await" => TokenType::Await,
+        "macro" => TokenType::Macro,
         _ => TokenType::Ident(ident.to_string()),
     }
 }
@@ -70,6 +115,10 @@ impl fmt::Display for TokenType {
             
             TokenType::Ident(s) => write!(f, "{}", s),
             TokenType::Int(i) => write!(f, "{}", i),
+            TokenType::Str(s) => write!(f, "{}", s),
+            TokenType::Char(c) => write!(f, "{}", c),
+            TokenType::Operator(s) => write!(f, "{}", s),
+            TokenType::Decimal(d) => write!(f, "{}d", d),
             
             TokenType::Assign => write!(f, "="),
             TokenType::Plus => write!(f, "+"),
@@ -84,12 +133,19 @@ impl fmt::Display for TokenType {
             TokenType::NotEq => write!(f, "!="),
             
             TokenType::Comma => write!(f, ","),
+            TokenType::Colon => write!(f, ":"),
             TokenType::Semicolon => write!(f, ";"),
             TokenType::LParen => write!(f, "("),
             TokenType::RParen => write!(f, ")"),
             TokenType::LBrace => write!(f, "{{"),
             TokenType::RBrace => write!(f, "}}"),
+            TokenType::LBracket => write!(f, "["),
+            TokenType::RBracket => write!(f, "]"),
             TokenType::Arrow => write!(f, "->"),
+            TokenType::Pipe => write!(f, "|"),
+            TokenType::And => write!(f, "&&"),
+            TokenType::Or => write!(f, "||"),
+            TokenType::DotDot => write!(f, ".."),
             
             TokenType::Function => write!(f, "fn"),
             TokenType::Let => write!(f, "let"),
@@ -101,6 +157,22 @@ impl fmt::Display for TokenType {
             TokenType::Return => write!(f, "return"),
             TokenType::Unsafe => write!(f, "unsafe"),
             TokenType::Zone => write!(f, "zone"),
+            TokenType::Yield => write!(f, "yield"),
+            TokenType::Defer => write!(f, "defer"),
+            TokenType::Loop => write!(f, "loop"),
+            TokenType::Break => write!(f, "break"),
+            TokenType::For => write!(f, "for"),
+            TokenType::In => write!(f, "in"),
+            TokenType::Match => write!(f, "match"),
+            TokenType::Switch => write!(f, "switch"),
+            TokenType::Case => write!(f, "case"),
+            TokenType::Default => write!(f, "default"),
+            TokenType::Do => write!(f, "do"),
+            TokenType::While => write!(f, "while"),
+            TokenType::Continue => write!(f, "continue"),
+            TokenType::Async => write!(f, "async"),
+            TokenType::Await => write!(f, "await"),
+            TokenType::Macro => write!(f, "macro"),
         }
     }
 }
\ No newline at end of file
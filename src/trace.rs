@@ -0,0 +1,80 @@
+// Record and replay of the inputs that make a run non-deterministic, so a
+// bug report can come with a trace that reproduces it exactly:
+//
+//   nova run script.nv --record trace.txt    // runs normally, logs inputs
+//   nova run script.nv --replay trace.txt    // same inputs, same output
+//
+// Logged inputs are the clock (time, clock_ms) and the seed rand() starts
+// from when the script doesn't call rand_seed. A trace is plain text, one
+// input per line:
+//
+//   # nova trace
+//   seed 8417740158922731043
+//   time 1760000000
+//
+// Replay hands the values back in order and fails if the script asks for
+// something else than was recorded, which means it isn't the same script.
+// Tasks run on their own runtimes and aren't traced.
+use std::collections::VecDeque;
+
+pub const HEADER: &str = "# nova trace";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Trace {
+    #[default]
+    Off,
+    Record(Vec<(String, i64)>),
+    Replay(VecDeque<(String, i64)>),
+}
+
+impl Trace {
+    pub fn record() -> Self {
+        Trace::Record(vec![])
+    }
+
+    // From the text `to_text` wrote
+    pub fn replay(text: &str) -> Result<Self, String> {
+        let mut inputs = VecDeque::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parsed = line.split_once(' ').and_then(|(kind, value)| Some((kind.to_string(), value.parse().ok()?)));
+            match parsed {
+                Some(input) => inputs.push_back(input),
+                None => return Err(format!("trace line {}: expected '<input> <int>', got {:?}", i + 1, line)),
+            }
+        }
+        Ok(Trace::Replay(inputs))
+    }
+
+    // The recorded inputs, in the format `replay` reads
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\n", HEADER);
+        if let Trace::Record(inputs) = self {
+            for (kind, value) in inputs {
+                text.push_str(&format!("{} {}\n", kind, value));
+            }
+        }
+        text
+    }
+
+    // One input: read live (and logged when recording), or the next
+    // recorded value when replaying
+    pub fn input(&mut self, kind: &str, live: impl FnOnce() -> Result<i64, String>) -> Result<i64, String> {
+        match self {
+            Trace::Off => live(),
+            Trace::Record(inputs) => {
+                let value = live()?;
+                inputs.push((kind.to_string(), value));
+                Ok(value)
+            },
+            Trace::Replay(inputs) => match inputs.pop_front() {
+                Some((recorded, value)) if recorded == kind => Ok(value),
+                Some((recorded, _)) => Err(format!("replay: script asked for {} but the trace has {} next", kind, recorded)),
+                None => Err(format!("replay: script asked for {} after the end of the trace", kind)),
+            },
+        }
+    }
+}
@@ -0,0 +1,596 @@
+// Static checking of type annotations.
+//
+//   let xs: array<int> = [1, 2];
+//   let id = fn<T>(x: T) -> T { x };
+//   let s: string = id(1);      // error: let s: expected string, found int
+//
+// Checking is gradual: anything without an annotation whose type can't be
+// worked out from literals is `any`, which matches everything, so
+// unannotated scripts never produce errors here beyond calling a known
+// function with the wrong number of arguments. Generic functions are
+// instantiated with fresh type variables at every use and the arguments
+// are unified against the parameters; inside the body a type parameter is
+// opaque and only matches itself.
+use std::collections::HashMap;
+use std::fmt;
+use crate::ast::{BlockStatement, Expression, FunctionLiteral, Program, Statement, TypeExpr};
+use crate::diagnostics::Diagnostic;
+use crate::macros;
+use crate::runtime;
+use crate::span::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Int,
+    Bool,
+    String,
+    Char,
+    Bytes,
+    Decimal,
+    DateTime,
+    Null,
+    Any,
+    Array(Box<Type>),
+    Set(Box<Type>),
+    Hash(Box<Type>, Box<Type>),
+    Tuple(Vec<Type>),
+    Function(Vec<Type>, Box<Type>),
+    Param(String), // A generic's type parameter, seen from inside its body
+    Var(usize),    // Filled in by unification
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |types: &[Type]| types.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", ");
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Bool => write!(f, "bool"),
+            Type::String => write!(f, "string"),
+            Type::Char => write!(f, "char"),
+            Type::Bytes => write!(f, "bytes"),
+            Type::Decimal => write!(f, "decimal"),
+            Type::DateTime => write!(f, "datetime"),
+            Type::Null => write!(f, "null"),
+            Type::Any => write!(f, "any"),
+            Type::Array(t) => write!(f, "array<{}>", t),
+            Type::Set(t) => write!(f, "set<{}>", t),
+            Type::Hash(k, v) => write!(f, "hash<{}, {}>", k, v),
+            Type::Tuple(ts) => write!(f, "tuple<{}>", list(ts)),
+            Type::Function(params, ret) => write!(f, "fn({}) -> {}", list(params), ret),
+            Type::Param(name) => write!(f, "{}", name),
+            Type::Var(_) => write!(f, "_"), // Nothing has pinned it down
+        }
+    }
+}
+
+// A binding's type; generic functions list their type parameters
+#[derive(Debug, Clone)]
+struct Scheme {
+    params: Vec<String>,
+    ty: Type,
+}
+
+pub fn check(program: &Program) -> Vec<Diagnostic> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("typecheck").entered();
+    let mut c = Checker {
+        scopes: vec![HashMap::new()],
+        vars: vec![],
+        returns: vec![],
+        type_params: vec![],
+        span: Span::default(),
+        diagnostics: vec![],
+    };
+    for stmt in &program.statements {
+        c.statement(stmt);
+    }
+    c.diagnostics
+}
+
+struct Checker {
+    scopes: Vec<HashMap<String, Scheme>>,
+    vars: Vec<Option<Type>>,
+    // Declared return type of each function we're inside (None: unannotated)
+    returns: Vec<Option<Type>>,
+    type_params: Vec<String>,
+    // Statement being checked; expressions have no spans of their own
+    span: Span,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn error(&mut self, msg: String) {
+        self.diagnostics.push(Diagnostic::error(msg, self.span));
+    }
+
+    fn bind(&mut self, name: &str, scheme: Scheme) {
+        self.scopes.last_mut().expect("scope").insert(name.to_string(), scheme);
+    }
+
+    fn lookup(&mut self, name: &str) -> Type {
+        let scheme = self.scopes.iter().rev().find_map(|s| s.get(name)).cloned();
+        match scheme {
+            Some(scheme) => self.instantiate(&scheme),
+            None => Type::Any,
+        }
+    }
+
+    // --- UNIFICATION ---
+
+    fn fresh(&mut self) -> Type {
+        self.vars.push(None);
+        Type::Var(self.vars.len() - 1)
+    }
+
+    fn instantiate(&mut self, scheme: &Scheme) -> Type {
+        let mapping: HashMap<String, Type> = scheme.params.iter().map(|p| (p.clone(), self.fresh())).collect();
+        substitute(&scheme.ty, &mapping)
+    }
+
+    // Follows variable bindings all the way down
+    fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Var(i) => match &self.vars[*i] {
+                Some(t) => self.resolve(t),
+                None => ty.clone(),
+            },
+            Type::Array(t) => Type::Array(Box::new(self.resolve(t))),
+            Type::Set(t) => Type::Set(Box::new(self.resolve(t))),
+            Type::Hash(k, v) => Type::Hash(Box::new(self.resolve(k)), Box::new(self.resolve(v))),
+            Type::Tuple(ts) => Type::Tuple(ts.iter().map(|t| self.resolve(t)).collect()),
+            Type::Function(ps, r) => Type::Function(ps.iter().map(|p| self.resolve(p)).collect(), Box::new(self.resolve(r))),
+            _ => ty.clone(),
+        }
+    }
+
+    fn unify(&mut self, a: &Type, b: &Type) -> bool {
+        let (a, b) = (self.resolve(a), self.resolve(b));
+        match (&a, &b) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Var(i), Type::Var(j)) if i == j => true,
+            (Type::Var(i), t) | (t, Type::Var(i)) => {
+                if occurs(*i, t) {
+                    return false;
+                }
+                self.vars[*i] = Some(t.clone());
+                true
+            },
+            (Type::Array(x), Type::Array(y)) | (Type::Set(x), Type::Set(y)) => self.unify(x, y),
+            (Type::Hash(k1, v1), Type::Hash(k2, v2)) => self.unify(k1, k2) && self.unify(v1, v2),
+            (Type::Tuple(xs), Type::Tuple(ys)) => {
+                xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| self.unify(x, y))
+            },
+            (Type::Function(p1, r1), Type::Function(p2, r2)) => {
+                p1.len() == p2.len() && p1.iter().zip(p2).all(|(x, y)| self.unify(x, y)) && self.unify(r1, r2)
+            },
+            _ => a == b,
+        }
+    }
+
+    // --- ANNOTATIONS ---
+
+    fn lower(&mut self, ty: &TypeExpr) -> Type {
+        match ty {
+            TypeExpr::Function { params, ret } => {
+                let params = params.iter().map(|p| self.lower(p)).collect();
+                Type::Function(params, Box::new(self.lower(ret)))
+            },
+            TypeExpr::Named { name, args } => {
+                let expected = match name.as_str() {
+                    "int" | "bool" | "string" | "char" | "bytes" | "decimal" | "datetime" | "null" | "any" => 0,
+                    "array" | "set" => 1,
+                    "hash" => 2,
+                    "tuple" => args.len(),
+                    _ if self.type_params.contains(name) => 0,
+                    _ => {
+                        self.error(format!("unknown type '{}'", name));
+                        return Type::Any;
+                    },
+                };
+                if args.len() != expected {
+                    self.error(format!("{} takes {} type argument(s), found {}", name, expected, args.len()));
+                    return Type::Any;
+                }
+                let mut args: Vec<Type> = args.iter().map(|a| self.lower(a)).collect();
+                match name.as_str() {
+                    "int" => Type::Int,
+                    "bool" => Type::Bool,
+                    "string" => Type::String,
+                    "char" => Type::Char,
+                    "bytes" => Type::Bytes,
+                    "decimal" => Type::Decimal,
+                    "datetime" => Type::DateTime,
+                    "null" => Type::Null,
+                    "any" => Type::Any,
+                    "array" => Type::Array(Box::new(args.remove(0))),
+                    "set" => Type::Set(Box::new(args.remove(0))),
+                    "hash" => {
+                        let key = args.remove(0);
+                        Type::Hash(Box::new(key), Box::new(args.remove(0)))
+                    },
+                    "tuple" => Type::Tuple(args),
+                    _ => Type::Param(name.clone()),
+                }
+            },
+        }
+    }
+
+    // --- STATEMENTS ---
+
+    fn statement(&mut self, stmt: &Statement) {
+        let outer = std::mem::replace(&mut self.span, stmt.span());
+        match stmt {
+            Statement::Let(s) => {
+                let (value, params) = match &s.value {
+                    Expression::Function(fl) => (self.function(fl), names(&fl.type_params)),
+                    value => (self.expression(value), vec![]),
+                };
+                let ty = match &s.annotation {
+                    Some(annotation) => {
+                        let declared = self.lower(annotation);
+                        if !self.unify(&declared, &value) {
+                            let found = self.resolve(&value);
+                            self.error(format!("let {}: expected {}, found {}", s.name.value, declared, found));
+                        }
+                        declared
+                    },
+                    None => value,
+                };
+                self.bind(&s.name.value, Scheme { params, ty });
+            },
+            Statement::LetTuple(s) => {
+                let value = self.expression(&s.value);
+                let elements = match self.resolve(&value) {
+                    Type::Tuple(ts) if ts.len() == s.names.len() => ts,
+                    _ => vec![Type::Any; s.names.len()],
+                };
+                for (name, ty) in s.names.iter().zip(elements) {
+                    self.bind(&name.value, Scheme { params: vec![], ty });
+                }
+            },
+            Statement::Return(s) => {
+                let value = self.expression(&s.return_value);
+                if let Some(Some(declared)) = self.returns.last().cloned() && !self.unify(&declared, &value) {
+                    let found = self.resolve(&value);
+                    self.error(format!("return: expected {}, found {}", declared, found));
+                }
+            },
+            Statement::Yield(s) => {
+                self.expression(&s.value);
+            },
+            Statement::Defer(s) => {
+                self.expression(&s.value);
+            },
+            Statement::Break(s) => {
+                if let Some(value) = &s.value {
+                    self.expression(value);
+                }
+            },
+            Statement::Continue(_) => {},
+            Statement::Expression(s) => {
+                self.expression(&s.expression);
+            },
+        }
+        self.span = outer;
+    }
+
+    // Type of the last expression; `any` when the block ends otherwise
+    fn block(&mut self, block: &BlockStatement) -> Type {
+        let mut ty = Type::Null;
+        for stmt in &block.statements {
+            ty = match stmt {
+                Statement::Expression(s) => {
+                    let outer = std::mem::replace(&mut self.span, s.span);
+                    let ty = self.expression(&s.expression);
+                    self.span = outer;
+                    ty
+                },
+                _ => {
+                    self.statement(stmt);
+                    Type::Any
+                },
+            };
+        }
+        ty
+    }
+
+    // --- EXPRESSIONS ---
+
+    fn expression(&mut self, exp: &Expression) -> Type {
+        runtime::with_stack(|| self.expression_unguarded(exp))
+    }
+
+    fn expression_unguarded(&mut self, exp: &Expression) -> Type {
+        match exp {
+            Expression::IntegerLiteral(_) => Type::Int,
+            Expression::StringLiteral(_) => Type::String,
+            Expression::CharLiteral(_) => Type::Char,
+            Expression::DecimalLiteral(_) => Type::Decimal,
+            Expression::Boolean(_) => Type::Bool,
+            Expression::Identifier(ident) => self.lookup(&ident.value),
+            Expression::Prefix(p) => {
+                let right = self.expression(&p.right);
+                match (p.operator.as_str(), self.resolve(&right)) {
+                    ("!", _) => Type::Bool,
+                    ("-", Type::Int) => Type::Int,
+                    ("-", Type::Decimal) => Type::Decimal,
+                    _ => Type::Any,
+                }
+            },
+            Expression::Infix(i) if i.operator == "&&" || i.operator == "||" => {
+                // Either operand can be the result
+                let left = self.expression(&i.left);
+                let right = self.expression(&i.right);
+                if self.unify(&left, &right) { self.resolve(&left) } else { Type::Any }
+            },
+            Expression::Infix(i) => {
+                let left = self.expression(&i.left);
+                let right = self.expression(&i.right);
+                match (i.operator.as_str(), self.resolve(&left), self.resolve(&right)) {
+                    ("<" | ">" | "==" | "!=", _, _) => Type::Bool,
+                    ("+" | "-" | "*" | "/", Type::Int, Type::Int) => Type::Int,
+                    ("+" | "-" | "*" | "/", Type::Decimal, Type::Decimal | Type::Int) => Type::Decimal,
+                    ("+" | "-" | "*" | "/", Type::Int, Type::Decimal) => Type::Decimal,
+                    ("+", Type::String, Type::String | Type::Char) => Type::String,
+                    ("+", Type::Char, Type::String) => Type::String,
+                    ("+" | "-", Type::DateTime, Type::Int) | ("+", Type::Int, Type::DateTime) => Type::DateTime,
+                    ("-", Type::DateTime, Type::DateTime) => Type::Int,
+                    _ => Type::Any,
+                }
+            },
+            Expression::Comparison(c) => {
+                for operand in &c.operands {
+                    self.expression(operand);
+                }
+                Type::Bool
+            },
+            Expression::If(ie) => {
+                self.expression(&ie.condition);
+                let then = self.block(&ie.consequence);
+                match &ie.alternative {
+                    Some(alt) => {
+                        let otherwise = self.block(alt);
+                        if self.unify(&then, &otherwise) { self.resolve(&then) } else { Type::Any }
+                    },
+                    None => Type::Any,
+                }
+            },
+            // Not bound by a let, so there's no name to be generic under
+            Expression::Function(fl) => {
+                let ty = self.function(fl);
+                let scheme = Scheme { params: names(&fl.type_params), ty };
+                self.instantiate(&scheme)
+            },
+            Expression::Await(a) => {
+                self.expression(&a.value);
+                Type::Any
+            },
+            Expression::Call(c) if c.is_quote() => {
+                for e in macros::unquoted(c) {
+                    self.expression(&e);
+                }
+                Type::Any
+            },
+            Expression::Call(c) => {
+                let callee = self.expression(&c.function);
+                let args: Vec<Type> = c.arguments.iter().map(|a| self.expression(a)).collect();
+                for (_, value) in &c.keywords {
+                    self.expression(value);
+                }
+                // Function types don't carry parameter names to match keywords against
+                let Type::Function(params, ret) = self.resolve(&callee) else {
+                    return Type::Any;
+                };
+                if !c.keywords.is_empty() {
+                    return Type::Any;
+                }
+                if params.len() != args.len() {
+                    self.error(format!("expected {} argument(s), found {}", params.len(), args.len()));
+                    return Type::Any;
+                }
+                for (i, (param, arg)) in params.iter().zip(&args).enumerate() {
+                    if !self.unify(param, arg) {
+                        let (param, arg) = (self.resolve(param), self.resolve(arg));
+                        self.error(format!("argument {}: expected {}, found {}", i + 1, param, arg));
+                    }
+                }
+                self.resolve(&ret)
+            },
+            Expression::Array(a) => {
+                let types: Vec<Type> = a.elements.iter().map(|e| self.expression(e)).collect();
+                let element = self.common(types);
+                Type::Array(Box::new(element))
+            },
+            Expression::Tuple(t) => Type::Tuple(t.elements.iter().map(|e| self.expression(e)).collect()),
+            Expression::Hash(h) => {
+                let (mut keys, mut values) = (vec![], vec![]);
+                for (k, v) in &h.pairs {
+                    keys.push(self.expression(k));
+                    values.push(self.expression(v));
+                }
+                Type::Hash(Box::new(self.common(keys)), Box::new(self.common(values)))
+            },
+            Expression::Index(ie) => {
+                let left = self.expression(&ie.left);
+                self.expression(&ie.index);
+                match (self.resolve(&left), &*ie.index) {
+                    (Type::Array(t), _) => *t,
+                    (Type::Hash(_, v), _) => *v,
+                    (Type::String, _) => Type::Char,
+                    (Type::Bytes, _) => Type::Int,
+                    (Type::Tuple(ts), Expression::IntegerLiteral(i)) => {
+                        usize::try_from(i.value).ok().and_then(|i| ts.get(i).cloned()).unwrap_or(Type::Any)
+                    },
+                    _ => Type::Any,
+                }
+            },
+            Expression::Slice(se) => {
+                let left = self.expression(&se.left);
+                for bound in [&se.start, &se.end].into_iter().flatten() {
+                    self.expression(bound);
+                }
+                match self.resolve(&left) {
+                    ty @ (Type::Array(_) | Type::String | Type::Bytes) => ty,
+                    _ => Type::Any,
+                }
+            },
+            Expression::Unsafe(u) => self.block(&u.body),
+            // Whatever a break hands back
+            Expression::Loop(l) => {
+                self.block(&l.body);
+                Type::Any
+            },
+            Expression::DoWhile(d) => {
+                self.block(&d.body);
+                self.expression(&d.condition);
+                Type::Any
+            },
+            Expression::For(fe) => {
+                let iterable = self.expression(&fe.iterable);
+                let element = match self.resolve(&iterable) {
+                    Type::Array(t) | Type::Set(t) | Type::Hash(t, _) => *t,
+                    Type::String => Type::Char,
+                    Type::Bytes => Type::Int,
+                    _ => Type::Any,
+                };
+                self.scopes.push(HashMap::new());
+                self.bind(&fe.variable.value, Scheme { params: vec![], ty: element });
+                self.block(&fe.body);
+                self.scopes.pop();
+                Type::Any
+            },
+            // Pattern names are Any; the arms have to agree, like an if's
+            Expression::Match(me) => {
+                self.expression(&me.subject);
+                let mut result: Option<Type> = None;
+                let mut agree = true;
+                for arm in &me.arms {
+                    self.scopes.push(HashMap::new());
+                    for name in arm.pattern.bindings() {
+                        self.bind(&name.value, Scheme { params: vec![], ty: Type::Any });
+                    }
+                    if let Some(guard) = &arm.guard {
+                        self.expression(guard);
+                    }
+                    let ty = self.block(&arm.body);
+                    self.scopes.pop();
+                    match &result {
+                        Some(first) => agree &= self.unify(first, &ty),
+                        None => result = Some(ty),
+                    }
+                }
+                match result {
+                    Some(ty) if agree => self.resolve(&ty),
+                    _ => Type::Any,
+                }
+            },
+            Expression::Switch(se) => {
+                self.expression(&se.subject);
+                let mut result: Option<Type> = None;
+                let mut agree = true;
+                for body in se.bodies() {
+                    let ty = self.block(body);
+                    match &result {
+                        Some(first) => agree &= self.unify(first, &ty),
+                        None => result = Some(ty),
+                    }
+                }
+                match result {
+                    Some(ty) if agree => self.resolve(&ty),
+                    _ => Type::Any,
+                }
+            },
+            Expression::Zone(z) => {
+                for (_, limit) in &z.limits {
+                    self.expression(limit);
+                }
+                self.scopes.push(HashMap::new());
+                let ty = self.block(&z.body);
+                self.scopes.pop();
+                ty
+            },
+        }
+    }
+
+    // Type of a function literal. Type parameters stay as Param inside.
+    fn function(&mut self, fl: &FunctionLiteral) -> Type {
+        let outer_params = self.type_params.len();
+        self.type_params.extend(names(&fl.type_params));
+
+        let params: Vec<Type> = fl.param_types.iter()
+            .map(|ty| ty.as_ref().map(|t| self.lower(t)).unwrap_or(Type::Any))
+            .collect();
+        let declared = fl.return_type.as_ref().map(|t| self.lower(t));
+
+        self.scopes.push(HashMap::new());
+        for (name, ty) in fl.parameters.iter().zip(&params) {
+            self.bind(&name.value, Scheme { params: vec![], ty: ty.clone() });
+        }
+        self.returns.push(declared.clone());
+        let body = self.block(&fl.body);
+        self.returns.pop();
+        self.scopes.pop();
+        self.type_params.truncate(outer_params);
+
+        // Calling a generator hands back the generator, not the body's value
+        let body = if fl.body.contains_yield() { Type::Any } else { body };
+        let ret = match declared {
+            Some(declared) => {
+                if !self.unify(&declared, &body) {
+                    let found = self.resolve(&body);
+                    self.error(format!("function body: expected {}, found {}", declared, found));
+                }
+                declared
+            },
+            None => body,
+        };
+        // Calling an async fn hands back a future; `-> T` is what awaiting
+        // it gives
+        let ret = if fl.is_async { Type::Any } else { ret };
+        Type::Function(params, Box::new(ret))
+    }
+
+    // Element type for a collection literal: the shared type, or any
+    fn common(&mut self, types: Vec<Type>) -> Type {
+        let mut iter = types.into_iter();
+        let Some(first) = iter.next() else {
+            return self.fresh();
+        };
+        let first = self.resolve(&first);
+        for ty in iter {
+            if self.resolve(&ty) != first {
+                return Type::Any;
+            }
+        }
+        first
+    }
+}
+
+fn names(idents: &[crate::ast::Identifier]) -> Vec<String> {
+    idents.iter().map(|i| i.value.clone()).collect()
+}
+
+fn substitute(ty: &Type, mapping: &HashMap<String, Type>) -> Type {
+    match ty {
+        Type::Param(name) => mapping.get(name).cloned().unwrap_or_else(|| ty.clone()),
+        Type::Array(t) => Type::Array(Box::new(substitute(t, mapping))),
+        Type::Set(t) => Type::Set(Box::new(substitute(t, mapping))),
+        Type::Hash(k, v) => Type::Hash(Box::new(substitute(k, mapping)), Box::new(substitute(v, mapping))),
+        Type::Tuple(ts) => Type::Tuple(ts.iter().map(|t| substitute(t, mapping)).collect()),
+        Type::Function(ps, r) => {
+            Type::Function(ps.iter().map(|p| substitute(p, mapping)).collect(), Box::new(substitute(r, mapping)))
+        },
+        _ => ty.clone(),
+    }
+}
+
+fn occurs(var: usize, ty: &Type) -> bool {
+    match ty {
+        Type::Var(i) => *i == var,
+        Type::Array(t) | Type::Set(t) => occurs(var, t),
+        Type::Hash(k, v) => occurs(var, k) || occurs(var, v),
+        Type::Tuple(ts) => ts.iter().any(|t| occurs(var, t)),
+        Type::Function(ps, r) => ps.iter().any(|p| occurs(var, p)) || occurs(var, r),
+        _ => false,
+    }
+}
@@ -0,0 +1,45 @@
+// Browser bindings, built with:
+//   cargo build --lib --target wasm32-unknown-unknown --no-default-features --features wasm
+//
+// Results and parse errors come back as plain strings so the JS side
+// doesn't need to know anything about the Object enum.
+use wasm_bindgen::prelude::*;
+use crate::interpreter::{Error, Interpreter};
+
+// One-shot evaluation in a fresh environment
+#[wasm_bindgen]
+pub fn eval(source: &str) -> String {
+    Session::new().eval(source)
+}
+
+// Keeps bindings alive between calls, like the REPL does
+#[wasm_bindgen]
+pub struct Session {
+    interp: Interpreter,
+}
+
+#[wasm_bindgen]
+impl Session {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Session {
+        Session { interp: Interpreter::new() }
+    }
+
+    pub fn eval(&mut self, source: &str) -> String {
+        match self.interp.eval_source(source) {
+            Ok(value) => value.to_string(),
+            Err(Error::Runtime(msg)) => crate::object::Object::Error(msg).to_string(),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.interp.reset();
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
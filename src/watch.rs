@@ -0,0 +1,60 @@
+// Noticing when files change, for `nova run --watch`. Polls modification
+// times instead of asking the OS, which works the same everywhere and is
+// plenty for a handful of files.
+//
+//   let mut watcher = Watcher::new(["script.nv"]);
+//   loop {
+//       run_it();
+//       watcher.wait();   // blocks until script.nv is saved again
+//   }
+//
+// The CLI watches the script, or every file of a project (see manifest.rs).
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+pub struct Watcher {
+    // None while the file can't be read, so deleting and recreating it
+    // counts as two changes
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl Watcher {
+    pub fn new(paths: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        let files = paths.into_iter().map(|p| {
+            let path = p.into();
+            let modified = modified(&path);
+            (path, modified)
+        });
+        Watcher { files: files.collect() }
+    }
+
+    // Files modified since the watcher was made or last asked
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let mut changed = vec![];
+        for (path, seen) in &mut self.files {
+            let now = modified(path);
+            if now != *seen {
+                *seen = now;
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+
+    // Blocks until something changes
+    pub fn wait(&mut self) -> Vec<PathBuf> {
+        loop {
+            let changed = self.changed();
+            if !changed.is_empty() {
+                return changed;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,2489 @@
+use nova_lang::environment::Environment;
+use nova_lang::evaluator::eval_program;
+use nova_lang::lexer::Lexer;
+use nova_lang::object::Object;
+use nova_lang::parser::Parser;
+use nova_lang::runtime::Capabilities;
+
+fn eval(source: &str) -> Object {
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    assert!(p.errors.is_empty(), "parse errors in {:?}: {:?}", source, p.errors);
+    eval_program(&program, &mut Environment::new())
+}
+
+// With the host having granted `capabilities`
+fn eval_granted(source: &str, capabilities: Capabilities) -> Object {
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    assert!(p.errors.is_empty(), "parse errors in {:?}: {:?}", source, p.errors);
+    eval_program(&program, &mut Environment::with_capabilities(capabilities))
+}
+
+fn assert_int(source: &str, expected: i64) {
+    assert_eq!(eval(source), Object::Integer(expected), "{}", source);
+}
+
+fn assert_error(source: &str, expected: &str) {
+    match eval(source) {
+        Object::Error(msg) => assert_eq!(msg, expected, "{}", source),
+        other => panic!("{}: expected error {:?}, got {}", source, expected, other),
+    }
+}
+
+// --- MATH ---
+
+#[test]
+fn abs() {
+    assert_int("abs(-5)", 5);
+    assert_int("abs(7)", 7);
+    assert_int("abs(0)", 0);
+    assert_error("abs(-9223372036854775807 - 1)", "abs: integer overflow");
+    assert_error("abs(\"x\")", "abs: argument 1: expected int, found string");
+}
+
+#[test]
+fn min_and_max() {
+    assert_int("min(3, 1, 2)", 1);
+    assert_int("max(3, 1, 2)", 3);
+    assert_int("min([4, -2, 9])", -2);
+    assert_int("max([4, -2, 9])", 9);
+    assert_int("max(5)", 5);
+    assert_error("min()", "min: needs at least one number");
+    assert_error("max([])", "max: needs at least one number");
+    assert_error("max(1, true)", "max: argument 2: expected int, found bool");
+}
+
+#[test]
+fn pow() {
+    assert_int("pow(2, 10)", 1024);
+    assert_int("pow(-3, 3)", -27);
+    assert_int("pow(7, 0)", 1);
+    assert_error("pow(2, -1)", "pow: exponent must be non-negative, found -1");
+    assert_error("pow(3, 9999999999)", "pow: exponent too large, found 9999999999");
+    #[cfg(not(feature = "bigint"))]
+    assert_error("pow(2, 63)", "pow: integer overflow");
+    #[cfg(feature = "bigint")]
+    {
+        assert_eq!(eval("pow(2, 100)").to_string(), "1267650600228229401496703205376");
+        assert_error("pow(3, 4000000000)", "pow: result too large (about 8000000000 bits)");
+        let mut env = Environment::new();
+        env.set_memory_limit(Some(10_000));
+        let program = Parser::new(Lexer::new("pow(7, 100000)".to_string())).parse_program();
+        assert_eq!(eval_program(&program, &mut env), Object::error("pow: result too large (about 300000 bits)"));
+    }
+}
+
+#[test]
+fn sqrt() {
+    assert_int("sqrt(16)", 4);
+    assert_int("sqrt(17)", 4);
+    assert_int("sqrt(0)", 0);
+    assert_int("sqrt(9223372036854775807)", 3037000499);
+    assert_error("sqrt(-4)", "sqrt: negative argument -4");
+}
+
+#[test]
+fn floor_and_ceil_on_ints() {
+    assert_int("floor(3)", 3);
+    assert_int("ceil(-3)", -3);
+    assert_error("floor(1, 2)", "floor: wrong number of arguments: expected 1, found 2");
+}
+
+#[test]
+fn math_builtins_can_be_shadowed() {
+    assert_int("let max = fn(a, b) { a }; max(1, 2)", 1);
+}
+
+// --- COMPARISON ---
+
+#[test]
+fn deep_equality() {
+    assert_eq!(eval("[1, [2, \"a\"]] == [1, [2, \"a\"]]"), Object::Boolean(true));
+    assert_eq!(eval("[1, 2] == [1, 2, 3]"), Object::Boolean(false));
+    assert_eq!(eval("[1, 2] != [2, 1]"), Object::Boolean(true));
+    assert_eq!(eval("true == true"), Object::Boolean(true));
+    assert_eq!(eval("1 == \"1\""), Object::Boolean(false));
+    assert_eq!(eval("[] == []"), Object::Boolean(true));
+}
+
+#[test]
+fn ordering() {
+    assert_eq!(eval("\"apple\" < \"banana\""), Object::Boolean(true));
+    assert_eq!(eval("[1, 2, 3] < [1, 3]"), Object::Boolean(true));
+    assert_eq!(eval("[1, 2] > [1]"), Object::Boolean(true));
+    assert_error("1 < \"2\"", "can't compare int with string");
+    assert_error("true > false", "bool values can't be ordered");
+    assert_error("[1] < [\"a\"]", "can't compare int with string");
+}
+
+// --- CHAINED COMPARISONS ---
+
+#[test]
+fn chained_comparisons() {
+    assert_eq!(eval("let x = 5; 1 < x < 10"), Object::Boolean(true));
+    assert_eq!(eval("let x = 5; 1 < x < 3"), Object::Boolean(false));
+    assert_eq!(eval("let x = 5; 10 > x > 1"), Object::Boolean(true));
+    assert_eq!(eval("\"a\" < \"b\" < \"c\" > \"a\""), Object::Boolean(true));
+    // Stops at the first false link, so the rest is never evaluated
+    assert_eq!(eval("5 < 1 < undefined_thing()"), Object::Boolean(false));
+    // Grouping makes it an ordinary comparison of a bool
+    assert_error("let x = 5; (1 < x) < 10", "can't compare bool with int");
+}
+
+// --- HASHES ---
+
+#[test]
+fn hash_literals() {
+    assert_eq!(eval(r#"let k = "b"; {"a": 1, k: 2}["b"]"#), Object::Integer(2));
+    assert_eq!(eval(r#"{1: "one", true: "yes"}[true]"#), Object::String("yes".to_string()));
+    assert_eq!(eval(r#"{1: "one"}[2]"#), Object::Null);
+    assert_eq!(eval("{1: 2, 1: 3}[1]"), Object::Integer(3));
+    assert_eq!(eval(r#"{"a": [1]} == {"a": [1]}"#), Object::Boolean(true));
+}
+
+#[test]
+fn unhashable_keys() {
+    assert_error("{[1]: 2}", "unusable as hash key: array");
+    assert_error("{1: 2}[{}]", "unusable as hash key: hash");
+}
+
+// --- SETS ---
+
+#[test]
+fn set_operations() {
+    assert_eq!(eval("set([3, 1, 3, 2])").to_string(), "set([1, 2, 3])");
+    assert_eq!(eval("union(set([1, 2]), set([2, 3])) == set([1, 2, 3])"), Object::Boolean(true));
+    assert_eq!(eval("intersection(set([1, 2]), set([2, 3]))").to_string(), "set([2])");
+    assert_eq!(eval("difference(set([1, 2]), set([2, 3]))").to_string(), "set([1])");
+    assert_eq!(eval("set()").to_string(), "set([])");
+    assert_error("set([[1]])", "set: unusable as set element: array");
+    assert_error("union(set([1]), [2])", "union: argument 2: expected set, found array");
+}
+
+#[test]
+fn contains() {
+    assert_eq!(eval("contains(set([1, 2]), 2)"), Object::Boolean(true));
+    assert_eq!(eval("contains(set([1, 2]), [2])"), Object::Boolean(false));
+    assert_eq!(eval("contains([1, [2]], [2])"), Object::Boolean(true));
+    assert_eq!(eval(r#"contains({"a": 1}, "a")"#), Object::Boolean(true));
+    assert_eq!(eval(r#"contains("nova", "ov")"#), Object::Boolean(true));
+    assert_error("contains(1, 1)", "contains: expected set, array, hash or string, found int");
+}
+
+// --- HASH METHODS ---
+
+#[test]
+fn hash_operations() {
+    assert_eq!(eval(r#"keys({"b": 1, "a": 2, 3: 0})"#).to_string(), r#"["b", "a", 3]"#);
+    assert_eq!(eval(r#"sort_keys({"b": 1, "a": 2, 3: 0})"#).to_string(), r#"{3: 0, a: 2, b: 1}"#);
+    assert_eq!(eval(r#"let h = {"b": 1, "a": 2}; let h = merge(h, {"b": 3, "c": 4}); [h, sort_keys(h) == h]"#).to_string(), "[{b: 3, a: 2, c: 4}, true]");
+    assert_eq!(eval(r#"values({"b": 1, "a": 2})"#).to_string(), "[1, 2]");
+    assert_eq!(eval(r#"has_key({"a": 1}, "a")"#), Object::Boolean(true));
+    assert_eq!(eval(r#"has_key({"a": 1}, [1])"#), Object::Boolean(false));
+    assert_eq!(eval(r#"let h = {"a": 1, "b": 2}; [delete(h, "a"), delete(h, "z"), h]"#).to_string(), "[{b: 2}, {a: 1, b: 2}, {a: 1, b: 2}]");
+    assert_eq!(eval(r#"merge({"port": 80, "tls": false}, {"port": 8080}, {"host": "x"})"#).to_string(), r#"{port: 8080, tls: false, host: "x"}"#);
+    assert_error(r#"merge({}, [1])"#, "merge: argument 2: expected hash, found array");
+    assert_error(r#"keys([1])"#, "keys: argument 1: expected hash, found array");
+}
+
+// --- TUPLES ---
+
+#[test]
+fn tuples() {
+    assert_eq!(eval(r#"(1, "a", true)[1]"#), Object::String("a".to_string()));
+    assert_eq!(eval("(1 + 2)"), Object::Integer(3));
+    assert_eq!(eval("(7,)").to_string(), "(7,)");
+    assert_eq!(eval("()").to_string(), "()");
+    assert_eq!(eval("(1, [2]) == (1, [2])"), Object::Boolean(true));
+}
+
+#[test]
+fn tuple_destructuring() {
+    assert_int("let pair = fn(a) { return (a, a * 2); }; let (x, y) = pair(4); x + y", 12);
+    assert_error("let (a, b) = (1, 2, 3)", "let: can't unpack a tuple of 3 into 2 names");
+    assert_error("let (a, b) = [1, 2]", "let: can't unpack array, expected a tuple");
+}
+
+// --- INDEXING AND SLICES ---
+
+#[test]
+fn negative_indexes() {
+    assert_int("[1, 2, 3][-1]", 3);
+    assert_int("[1, 2, 3][-3]", 1);
+    assert_eq!(eval("[1, 2, 3][-4]"), Object::Null);
+}
+
+#[test]
+fn slices() {
+    assert_eq!(eval("[1, 2, 3, 4, 5][1:4]").to_string(), "[2, 3, 4]");
+    assert_eq!(eval("[1, 2, 3][:2]").to_string(), "[1, 2]");
+    assert_eq!(eval("[1, 2, 3][-2:]").to_string(), "[2, 3]");
+    assert_eq!(eval("[1, 2, 3][1:100]").to_string(), "[2, 3]");
+    assert_eq!(eval(r#""nova"[0:3]"#), Object::String("nov".to_string()));
+    assert_eq!(eval("(1, 2, 3)[1:]").to_string(), "(2, 3)");
+    assert_error("[1, 2][2:1]", "slice start 2 is after end 1");
+    assert_error(r#"[1]["a":]"#, "slice bounds must be ints, found string");
+    assert_error("5[0:1]", "can't slice int");
+}
+
+// --- GENERATORS ---
+
+#[test]
+fn generators_resume_where_they_left_off() {
+    let source = r#"
+        let count = fn(n) {
+            yield n;
+            if (n > 0) { yield n + 1; } else { yield 0; }
+            yield "end";
+        };
+        let g = count(1);
+        [next(g), next(g), next(g), next(g), collect(count(0))]
+    "#;
+    assert_eq!(eval(source).to_string(), r#"[1, 2, "end", null, [0, 0, "end"]]"#);
+}
+
+#[test]
+fn generator_errors() {
+    assert_error("let f = fn() { unsafe { yield 1; } }; next(f())", "yield is only allowed in a function body or its if/else branches");
+    assert_error("next([1])", "next: expected generator, found array");
+}
+
+// --- DEFER AND RESOURCES ---
+
+#[test]
+fn defer_runs_when_the_function_ends() {
+    // Last deferred first, whether the body returns or fails
+    let mut env = Environment::new();
+    let mut run = |source: &str| eval_program(&Parser::new(Lexer::new(source.to_string())).parse_program(), &mut env);
+    let source = r#"
+        let ch = channel();
+        let f = fn(x) {
+            defer send(ch, "first");
+            defer send(ch, "second");
+            if (x > 0) { return x; }
+            10 / x
+        };
+        [f(1), recv(ch), recv(ch)]
+    "#;
+    assert_eq!(run(source).to_string(), r#"[1, "second", "first"]"#);
+    assert_eq!(run("f(0)"), Object::error("division by zero: 10 / 0"));
+    assert_eq!(run("[recv(ch), recv(ch)]").to_string(), r#"["second", "first"]"#);
+    assert_eq!(eval("let ch = channel(); let f = fn() { defer send(ch, 2); send(ch, 1); 3 }; [f(), recv(ch), recv(ch)]").to_string(), "[3, 1, 2]");
+    assert_eq!(eval("let ch = channel(); zone { defer send(ch, 2); send(ch, 1); }; [recv(ch), recv(ch)]").to_string(), "[1, 2]");
+    assert_error("let f = fn() { defer 1 / 0; 5 }; f()", "division by zero: 1 / 0");
+    // The body's error wins over a deferred one
+    assert_error("let f = fn() { defer 1 / 0; 10 / 0 }; f()", "division by zero: 10 / 0");
+}
+
+#[test]
+fn file_resources() {
+    let path = std::env::temp_dir().join(format!("nova-file-{}.txt", std::process::id()));
+    let source = format!(r#"
+        let append = fn(line) {{
+            let f = unsafe {{ open_file({0:?}, "a") }};
+            defer close(f);
+            unsafe {{ write(f, line) }}
+        }};
+        append("one,");
+        append("two");
+        let f = unsafe {{ open_file({0:?}, "r") }};
+        let text = unsafe {{ read(f) }};
+        close(f);
+        close(f);
+        [text, unsafe {{ read(f) }}]
+    "#, path.display().to_string());
+    let mut p = Parser::new(Lexer::new(source));
+    let program = p.parse_program();
+    let mut env = Environment::with_capabilities(Capabilities { fs: true, ..Capabilities::none() });
+    let result = eval_program(&program, &mut env);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "one,two");
+    let _ = std::fs::remove_file(&path);
+    assert!(matches!(&result, Object::Error(msg) if msg.starts_with("read: file #") && msg.ends_with(" is closed")), "{}", result);
+    assert_error("unsafe { open_file(\"x\", \"r\") }", "open_file: not permitted (needs --allow-fs)");
+    assert_error("open_file(\"x\", \"rw\")", "open_file: unknown mode \"rw\" (expected \"r\", \"w\" or \"a\")");
+    assert_error("close(1)", "close: argument 1: expected resource, found int");
+}
+
+// --- TASKS ---
+
+#[test]
+fn tasks_and_channels() {
+    let source = r#"
+        let worker = fn(ch, n) { send(ch, [n, n * 2]); n + 1 };
+        let ch = channel();
+        let task = spawn(worker, ch, 20);
+        let got = recv(ch);
+        [got[1], join(task)]
+    "#;
+    assert_eq!(eval(source).to_string(), "[40, 21]");
+}
+
+#[test]
+fn task_errors() {
+    assert_error("let t = spawn(abs, 1); join(t); join(t)", "join: task was already joined");
+    assert_error("let g = fn() { yield 1; }; spawn(abs, g())", "spawn: a generator can't be sent to another thread");
+    assert_error("recv([])", "recv: expected channel, found array");
+}
+
+#[test]
+fn recv_gives_up_when_the_zone_or_evaluation_ends() {
+    use nova_lang::interpreter::{Error, Interpreter};
+    assert_error("zone(steps: 20) { recv(channel()) }", "recv: zone: step limit exceeded");
+
+    let mut nova = Interpreter::new();
+    let cancel = nova.cancel_handle();
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        cancel.cancel();
+    });
+    assert_eq!(nova.eval_source("recv(channel())"), Err(Error::Runtime("recv: cancelled".into())));
+    canceller.join().unwrap();
+}
+
+#[test]
+fn tasks_inherit_limits_and_settings() {
+    use nova_lang::interpreter::{Config, Error, Interpreter};
+    use nova_lang::runtime::Truthiness;
+    assert_error("zone(steps: 1000) { join(spawn(fn() { loop {} })) }", "zone: step limit exceeded");
+    assert_error("zone(steps: 1000) { pmap([1, 2], fn(x) { loop {} }) }", "zone: step limit exceeded");
+    assert_error("let down = fn(f, n) { f(f, n + 1) }; zone(depth: 50) { join(spawn(down, down, 0)) }", "zone: call depth limit exceeded");
+
+    let mut nova = Interpreter::with_config(Config { memory_limit: Some(4000), truthiness: Truthiness::PYTHON, ..Config::default() });
+    assert_eq!(nova.eval_source("join(spawn(fn() { 0 || \"empty\" }))"), Ok(Object::String("empty".into())));
+    let grow = r#"join(spawn(fn() { let grow = fn(g, s) { g(g, s + "xxxxxxxxxxxxxxxxxxxxxxxx") }; grow(grow, "") }))"#;
+    assert!(matches!(nova.eval_source(grow), Err(Error::Runtime(msg)) if msg.starts_with("out of memory: ")));
+
+    let cancel = nova.cancel_handle();
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        cancel.cancel();
+    });
+    assert_eq!(nova.eval_source("join(spawn(fn() { loop {} }))"), Err(Error::Runtime("cancelled".into())));
+    canceller.join().unwrap();
+}
+
+#[test]
+fn tasks_share_the_limits_of_the_code_that_starts_them() {
+    use nova_lang::interpreter::{Config, Error, Interpreter};
+    // Each call fits in the zone on its own, but not both together
+    let work = "let work = fn() { let i = 0; do { let i = i + 1; } while (i < 400); i };";
+    assert_int(&format!("{} zone(steps: 1000) {{ join(spawn(work)) }}", work), 400);
+    assert_error(&format!("{} zone(steps: 1000) {{ [join(spawn(work)), join(spawn(work))] }}", work), "zone: step limit exceeded");
+    assert_error(&format!("{} zone(steps: 1000) {{ pmap([work, work], fn(w) {{ w() }}) }}", work), "zone: step limit exceeded");
+
+    // The task's bindings count along with what the caller binds meanwhile
+    let mut nova = Interpreter::with_config(Config { memory_limit: Some(4000), ..Config::default() });
+    let source = format!(r#"
+        let ch = channel();
+        let t = spawn(fn(ch) {{ recv(ch); let s = "{}"; 1 }}, ch);
+        let big = "{}";
+        send(ch, 0);
+        join(t)
+    "#, "y".repeat(2000), "x".repeat(2500));
+    assert!(matches!(nova.eval_source(&source), Err(Error::Runtime(msg)) if msg.starts_with("out of memory: ")));
+
+    let mut nova = Interpreter::new();
+    let source = format!(r#"
+        let ch = channel();
+        let wait = fn(ch) {{ recv(ch) }};
+        let n = 0;
+        do {{ spawn(wait, ch); let n = n + 1; }} while (n < {});
+        spawn(wait, ch)
+    "#, nova_lang::runtime::MAX_TASKS);
+    assert_eq!(nova.eval_source(&source), Err(Error::Runtime("spawn: too many tasks running (at most 64)".into())));
+    assert_eq!(nova.eval_source("do { send(ch, n); let n = n - 1; } while (n > 0); n"), Ok(Object::Integer(0)));
+}
+
+// --- PARALLEL MAP ---
+
+#[test]
+fn parallel_map() {
+    assert_eq!(eval("pmap([1, 2, 3, 4, 5, 6, 7, 8, 9], fn(x) { x * x })").to_string(), "[1, 4, 9, 16, 25, 36, 49, 64, 81]");
+    assert_eq!(eval("pmap([], abs)").to_string(), "[]");
+    assert_eq!(eval("let times = fn(k) { fn(x) { x * k } }; pmap([1, 2], times(3))").to_string(), "[3, 6]");
+    // The first failing element by position, wherever it ran
+    assert_error("pmap([1, 0, 2, 0], fn(x) { 10 / x })", "division by zero: 10 / 0");
+    assert_error("pmap([1], fn(x) { fn() { yield x; }() })", "pmap result: a generator can't be sent to another thread");
+    assert_error("pmap(1, abs)", "pmap: argument 1: expected array, found int");
+    assert_error("pmap([1], 2)", "pmap: argument 2: expected function, found int");
+}
+
+// --- ASYNC ---
+
+#[test]
+fn async_functions_and_await() {
+    let timed = |source: &str| {
+        let mut p = Parser::new(Lexer::new(source.to_string()));
+        let program = p.parse_program();
+        assert!(p.errors.is_empty(), "{:?}", p.errors);
+        let mut env = Environment::with_capabilities(Capabilities { time: true, ..Capabilities::none() });
+        eval_program(&program, &mut env)
+    };
+    // Both sleeps are under way at once, and each body picks up after its
+    // awaits in order
+    let source = r#"
+        let worker = async fn(name, ms) {
+            await sleep_async(ms);
+            let done = await sleep_async(ms);
+            if (done == null) { await sleep_async(1); }
+            return name;
+        };
+        let start = clock_ms();
+        let a = worker("a", 40);
+        let b = worker("b", 40);
+        [await a, await b, clock_ms() - start < 160, await gather([worker("c", 1), spawn(fn(x) { x * 2 }, 21), 3])]
+    "#;
+    assert_eq!(timed(source).to_string(), r#"["a", "b", true, ["c", 42, 3]]"#);
+    assert_eq!(timed("let f = async fn(x) { x * 2 }; let fut = f(4); [fut, await fut, await 5]").to_string(), "[future, 8, 5]");
+    assert_eq!(timed("let f = async fn() { await sleep_async(1); 1 + await sleep_async(1) }; await f()").to_string(), "null");
+    assert!(matches!(timed("let f = async fn() { await sleep_async(1); 1 / 0 }; let x = f(); await x"), Object::Error(m) if m == "division by zero: 1 / 0"));
+    assert!(matches!(timed("let f = async fn() { await sleep_async(1); await me; }; let me = f(); await me"), Object::Error(m) if m == "await: a future can't wait for itself"));
+    assert_error("let f = async fn() { yield 1; }; await f()", "yield is not allowed in an async function");
+    assert_error("sleep_async(1)", "sleep_async: not permitted (needs the time capability)");
+}
+
+// --- OPERATOR OVERLOADING ---
+
+#[test]
+fn hashes_can_overload_operators() {
+    let source = r#"
+        let add = fn(a, b) { {"x": a["x"] + b["x"]} };
+        let eq = fn(a, b) { a["x"] == b["x"] };
+        let one = {"x": 1, "__add": add, "__eq": eq};
+        let two = {"x": 2, "__add": add, "__eq": eq};
+        [(one + two)["x"], one == one, one != two, 10 * {"__mul": fn(a, b) { a }}]
+    "#;
+    assert_eq!(eval(source).to_string(), "[3, true, true, 10]");
+}
+
+#[test]
+fn index_fallback() {
+    let source = r#"
+        let h = {"a": 1, "__index": fn(self, key) { "no " + key }};
+        [h["a"], h["b"]]
+    "#;
+    assert_eq!(eval(source).to_string(), r#"[1, "no b"]"#);
+}
+
+// --- REFLECTION ---
+
+#[test]
+fn eval_builtin() {
+    assert_int(r#"eval("1 + 2")"#, 3);
+    assert_int(r#"eval("let x = 5;"); x * 2"#, 10);
+    assert_eq!(eval(r#"let x = 1; eval("let x = 2; x", true) + x"#), Object::Integer(3));
+    assert_error(r#"eval("(1")"#, "eval: 1:3: error: Expected RParen, got EOF");
+}
+
+// --- INTROSPECTION ---
+
+#[test]
+fn introspection() {
+    let source = "let f = fn(a, b) { a + b }; [names(), params(f), arity(f), arity(abs)]";
+    assert_eq!(eval(source).to_string(), r#"[["f"], ["a", "b"], 2, null]"#);
+    assert_eq!(eval("source(fn(a) { let b = a; b * 2 })").to_string(), "fn(a) {\n    let b = a;\n    (b * 2)\n}");
+    assert_error("params(1)", "params: expected function, found int");
+}
+
+// --- TYPE ANNOTATIONS ---
+
+fn type_errors(source: &str) -> Vec<String> {
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    assert!(p.errors.is_empty(), "parse errors in {:?}: {:?}", source, p.errors);
+    nova_lang::typecheck::check(&program).into_iter().map(|d| d.message).collect()
+}
+
+#[test]
+fn annotations_are_ignored_at_runtime() {
+    assert_int("let id = fn<T>(x: T) -> T { x }; let n: int = id(4); n", 4);
+    assert_eq!(eval("source(fn<T>(xs: array<T>, f: fn(T) -> T) -> T { f(xs[0]) })").to_string(),
+        "fn(xs, f) {\n    f((xs[0]))\n}");
+}
+
+#[test]
+fn generic_annotations_are_unified() {
+    let source = r#"
+        let id = fn<T>(x: T) -> T { x };
+        let first = fn<T>(xs: array<T>) -> T { xs[0] };
+        let a: int = id(1);
+        let b: string = first([1, 2]);
+        let c: array<string> = [1];
+        let apply = fn(f: fn(int) -> bool, n: int) -> bool { f(n) };
+        apply(fn(x) { x > 1 }, 3);
+        apply(1, 2);
+        let d: thing = 1;
+        id(1, 2);
+    "#;
+    assert_eq!(type_errors(source), vec![
+        "let b: expected string, found int",
+        "let c: expected array<string>, found array<int>",
+        "argument 1: expected fn(int) -> bool, found int",
+        "unknown type 'thing'",
+        "expected 1 argument(s), found 2",
+    ]);
+    assert!(type_errors("let f = fn(a) { a + 1 }; f(\"x\"); f(f)").is_empty());
+}
+
+// --- DOC COMMENTS ---
+
+#[test]
+fn doc_comments_attach_to_lets() {
+    let source = "/// Adds.\n/// Ints only.\nlet add = fn(a: int, b) -> int { a + b };\n/// Lost.\n// plain\nlet x = 1;\nlet y = 2;";
+    let program = Parser::new(Lexer::new(source.to_string())).parse_program();
+    let entries = nova_lang::doc::entries(&program);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].signature, "add(a: int, b) -> int");
+    assert_eq!(entries[0].doc, "Adds.\nInts only.");
+    assert_eq!(nova_lang::doc::markdown("m", &program), "# m\n\n## `add(a: int, b) -> int`\n\nAdds.\nInts only.\n");
+}
+
+// --- BENCH ---
+
+#[test]
+fn bench_discovers_and_runs_benchmarks() {
+    use nova_lang::bench;
+    let program = Parser::new(Lexer::new("let bench_a = fn() { 1 }; let bench_b = fn(x) { x }; let other = fn() { 2 }; let bench_c = fn() { let (a, b) = 1; a };".to_string())).parse_program();
+    let mut env = Environment::new();
+    eval_program(&program, &mut env);
+    assert_eq!(bench::benchmarks(&env), vec!["bench_a", "bench_c"]);
+
+    let config = bench::Config { warmup: std::time::Duration::ZERO, time: std::time::Duration::ZERO };
+    assert_eq!(bench::run("bench_a", &mut env, &config).unwrap().iterations, 1);
+    assert_eq!(bench::run("bench_c", &mut env, &config).err().unwrap(), "bench_c: let: can't unpack int, expected a tuple");
+}
+
+// --- MEMORY ---
+
+#[test]
+fn memory_is_counted_and_released() {
+    let mut env = Environment::new();
+    let program = Parser::new(Lexer::new(r#"let s = "0123456789"; let f = fn(x) { memory_used() };"#.to_string())).parse_program();
+    eval_program(&program, &mut env);
+    let before = env.memory_used();
+    assert!(before > 10);
+
+    // The call's scope counts while it runs and is gone afterwards
+    let program = Parser::new(Lexer::new("f(s)".to_string())).parse_program();
+    match eval_program(&program, &mut env) {
+        Object::Integer(during) => assert!(during as usize > before),
+        other => panic!("expected int, got {}", other),
+    }
+    assert_eq!(env.memory_used(), before);
+}
+
+#[test]
+fn memory_limit_stops_evaluation() {
+    let mut env = Environment::new();
+    env.set_memory_limit(Some(1000));
+    let source = r#"let grow = fn(g, s) { g(g, s + "xxxxxxxxxxxxxxxxxxxxxxxx") }; grow(grow, "")"#;
+    let program = Parser::new(Lexer::new(source.to_string())).parse_program();
+    match eval_program(&program, &mut env) {
+        Object::Error(msg) => assert!(msg.starts_with("out of memory: "), "{}", msg),
+        other => panic!("expected an error, got {}", other),
+    }
+}
+
+// --- INTERPRETER ---
+
+#[test]
+fn interpreter_keeps_bindings_until_reset() {
+    use nova_lang::interpreter::{Error, Interpreter};
+    use nova_lang::object::NativeFunction;
+    let mut nova = Interpreter::new();
+    nova.register(NativeFunction::new("twice", |args| match args.first() {
+        Some(Object::Integer(n)) => Object::Integer(n * 2),
+        _ => Object::Null,
+    }));
+    assert_eq!(nova.eval_source("let x = 20;"), Ok(Object::Integer(20)));
+    assert_eq!(nova.eval_source("twice(x) + 2"), Ok(Object::Integer(42)));
+
+    assert_eq!(nova.eval_source("(1"), Err(Error::Parse(vec!["1:3: error: Expected RParen, got EOF".to_string()])));
+    assert_eq!(nova.diagnostics().len(), 1);
+    assert_eq!(nova.eval_source("let s: string = 1;"), Err(Error::Type(vec!["1:1: error: let s: expected string, found int".to_string()])));
+    assert_eq!(nova.eval_source("let (a, b) = 1;"), Err(Error::Runtime("let: can't unpack int, expected a tuple".to_string())));
+
+    nova.reset();
+    assert_eq!(nova.env().names(), vec!["twice"]);
+    assert!(matches!(nova.eval_file("/nonexistent.nv"), Err(Error::Io(_))));
+}
+
+// --- PANICS ---
+
+#[test]
+fn interpreter_recovers_from_a_panic() {
+    use nova_lang::interpreter::{Config, Error, Interpreter};
+    use nova_lang::object::NativeFunction;
+    let capabilities = Capabilities { fs: true, ..Capabilities::none() };
+    let mut nova = Interpreter::with_config(Config { capabilities, ..Config::default() });
+    nova.register(NativeFunction::new("boom", |_| panic!("boom")));
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let panicked = nova.eval_source("let f = fn() { zone(steps: 5) { unsafe { boom() } } }; f()");
+    std::panic::set_hook(hook);
+    assert_eq!(panicked, Err(Error::Runtime("internal error: boom".to_string())));
+
+    // Not still inside the unsafe block, the zone or the call
+    assert_eq!(nova.eval_source("read_bytes(\"x\")"), Err(Error::Runtime("read_bytes: only allowed inside an unsafe block".to_string())));
+    assert_eq!(nova.eval_source("let n = 0; loop { let n = n + 1; if (n == 10) { break n; } }"), Ok(Object::Integer(10)));
+    assert_eq!(nova.eval_source("let g = fn(n) { if (n == 0) { 0 } else { g(n - 1) } }; g(9990)"), Ok(Object::Integer(0)));
+}
+
+// --- CANCELLATION ---
+
+#[test]
+fn interpreter_can_be_cancelled_from_another_thread() {
+    use nova_lang::interpreter::{Error, Interpreter};
+    let mut nova = Interpreter::new();
+    let handle = nova.cancel_handle();
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        handle.cancel();
+    });
+    assert_eq!(nova.eval_source("let spin = fn() { loop { } }; spin()"), Err(Error::Runtime("cancelled".to_string())));
+    canceller.join().unwrap();
+
+    // Only that evaluation; the handle still works after reset()
+    assert_eq!(nova.eval_source("1 + 1"), Ok(Object::Integer(2)));
+    nova.reset();
+    nova.cancel_handle().cancel();
+    assert_eq!(nova.eval_source("1 + 1"), Err(Error::Runtime("cancelled".to_string())));
+    assert_eq!(nova.eval_source("1 + 1"), Ok(Object::Integer(2)));
+}
+
+// --- HOOKS ---
+
+#[test]
+fn interpreter_hooks_observe_evaluation() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use nova_lang::ast::Statement;
+    use nova_lang::hooks::Hooks;
+    use nova_lang::interpreter::Interpreter;
+    use nova_lang::span::Span;
+
+    struct Log(Rc<RefCell<Vec<String>>>);
+    impl Hooks for Log {
+        fn on_statement(&mut self, statement: &Statement) {
+            self.0.borrow_mut().push(format!("statement {}", nova_lang::ast::Node::string(statement)));
+        }
+        fn on_call(&mut self, function: &Object, args: &[Object]) {
+            self.0.borrow_mut().push(format!("call {} with {}", function.type_name(), args.len()));
+        }
+        fn on_error(&mut self, message: &str, span: Span) {
+            self.0.borrow_mut().push(format!("error {} at {}", message, span.start));
+        }
+        fn on_binding(&mut self, name: &str, value: &Object) {
+            self.0.borrow_mut().push(format!("bind {} = {}", name, value));
+        }
+    }
+
+    let log = Rc::new(RefCell::new(vec![]));
+    let mut nova = Interpreter::new();
+    nova.add_hooks(Log(log.clone()));
+    assert_eq!(nova.eval_source("let f = fn(n) { n + 1 };\nf(2)"), Ok(Object::Integer(3)));
+    assert_eq!(*log.borrow(), vec![
+        "statement let f = fn(n) { (n + 1) };",
+        "bind f = fn(n) { (n + 1) }",
+        "statement f(2)",
+        "call function with 1",
+        "bind n = 2",
+        "statement (n + 1)",
+    ]);
+
+    // Reported once, where it happened, and the hooks outlive reset()
+    log.borrow_mut().clear();
+    nova.reset();
+    assert!(nova.eval_source("let g = fn() { 1 / 0 };\ng()").is_err());
+    assert_eq!(log.borrow().iter().filter(|e| e.starts_with("error")).collect::<Vec<_>>(), vec!["error division by zero: 1 / 0 at 15"]);
+}
+
+// --- GOLDEN FILES ---
+
+#[test]
+fn golden_fixtures() {
+    use nova_lang::golden;
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let update = std::env::var_os("NOVA_UPDATE_GOLDEN").is_some();
+    let mismatches = golden::check_dir(&dir, update).expect("reading tests/fixtures");
+    let report: Vec<String> = mismatches.iter().map(|m| m.to_string()).collect();
+    assert!(report.is_empty(), "golden mismatches (NOVA_UPDATE_GOLDEN=1 to accept):\n{}", report.join("\n"));
+    assert_eq!(golden::run("print(1, \"a\"); 1 / 0"), "1 a\n1:16: error: division by zero: 1 / 0\n");
+    assert_eq!(golden::run("print(1, \"a\"); [2]"), "1 a\n=> [2]\n");
+}
+
+// --- TRACING ---
+
+#[test]
+#[cfg(feature = "tracing")]
+fn tracing_spans() {
+    use std::sync::{Arc, Mutex};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata};
+    use nova_lang::interpreter::Interpreter;
+
+    // The names of the spans opened and events sent, in order
+    struct Names(Arc<Mutex<Vec<String>>>);
+    impl tracing::Subscriber for Names {
+        fn enabled(&self, _: &Metadata<'_>) -> bool { true }
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut names = self.0.lock().unwrap();
+            names.push(span.metadata().name().to_string());
+            Id::from_u64(names.len() as u64)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &Event<'_>) {
+            self.0.lock().unwrap().push(format!("event: {}", event.metadata().level()));
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    let names = Arc::new(Mutex::new(vec![]));
+    tracing::subscriber::with_default(Names(names.clone()), || {
+        let mut nova = Interpreter::new();
+        nova.eval_source("let double = fn(x) { x * 2 }; double(double(1))").unwrap();
+        nova.eval_source("1 / 0").unwrap_err();
+    });
+    assert_eq!(*names.lock().unwrap(), [
+        "eval_source", "parse", "typecheck", "resolve", "eval", "call", "call",
+        "eval_source", "parse", "typecheck", "resolve", "eval", "event: DEBUG",
+    ]);
+}
+
+// --- PANIC-FREE ---
+
+#[test]
+fn arithmetic_errors_instead_of_panicking() {
+    assert_error("1 / 0", "division by zero: 1 / 0");
+    #[cfg(not(feature = "bigint"))]
+    {
+        assert_error("9223372036854775807 + 1", "integer overflow: 9223372036854775807 + 1");
+        assert_error("let m = -9223372036854775807 - 1; -m", "integer overflow: -(-9223372036854775808)");
+        assert_error("let m = -9223372036854775807 - 1; m / -1", "integer overflow: -9223372036854775808 / -1");
+    }
+    assert_error("let f = fn(a, b) { a }; f(1)", "wrong number of arguments: expected 2, found 1");
+}
+
+#[test]
+fn hostile_sources_are_errors() {
+    use nova_lang::interpreter::{Error, Interpreter};
+    let mut nova = Interpreter::new();
+    assert_eq!(nova.eval_source("99999999999999999999"),
+        Err(Error::Parse(vec!["1:1: error: Integer literal 99999999999999999999 is too large".to_string()])));
+
+    let nested = format!("{}1{}", "[".repeat(5000), "]".repeat(5000));
+    assert_eq!(nova.eval_source(&nested),
+        Err(Error::Parse(vec!["1:501: error: Nested too deeply (more than 500 levels)".to_string()])));
+
+    let source = "let f = fn(g, n) { if (n == 0) { 0 } else { g(g, n - 1) } }; f(f, 100000)";
+    assert_eq!(nova.eval_source(source), Err(Error::Runtime("stack overflow: more than 10000 nested calls".to_string())));
+    assert_eq!(nova.eval_source("f(f, 9000)"), Ok(Object::Integer(0)));
+}
+
+// --- DISPLAY ---
+
+#[test]
+fn composites_display_like_literals() {
+    assert_eq!(eval(r#"[1, "a", {"x": 2, "a b": "c"}, ("t",), set(["s"])]"#).to_string(),
+        r#"[1, "a", {x: 2, "a b": "c"}, ("t",), set(["s"])]"#);
+    assert_eq!(eval(r#""top level""#).to_string(), "top level");
+}
+
+#[test]
+fn display_truncates_big_values() {
+    use nova_lang::object::Truncation;
+    let value = eval("[1, 2, 3, 4, [5, [6, [7]]]]");
+    let limits = Truncation { depth: Some(2), items: Some(3) };
+    assert_eq!(value.display(limits).to_string(), "[1, 2, 3, ... 2 more]");
+    let limits = Truncation { depth: Some(2), items: None };
+    assert_eq!(value.display(limits).to_string(), "[1, 2, 3, 4, [5, [...]]]");
+    assert_eq!(value.display(Truncation::NONE).to_string(), value.to_string());
+}
+
+// --- REPL RESULTS ---
+
+#[test]
+fn identifiers_may_contain_digits() {
+    assert_int("let _1 = 4; let utf8 = 2; _1 * utf8", 8);
+}
+
+#[test]
+#[cfg(feature = "repl")]
+fn repl_binds_results_to_underscores() {
+    use nova_lang::interpreter::Interpreter;
+    use nova_lang::repl::Repl;
+    let mut repl = Repl::new(Interpreter::new());
+    let mut out = Vec::new();
+    // Null and errors aren't results, so they don't use up a number
+    let input = "6 * 7\n_ + 1\nnull\n1 / 0\n\"s\"\n[_1, _2, _3, _]\n_9\n";
+    repl.run(input.as_bytes(), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+        ">> 42\n>> 43\n>> null\n>> ERROR: division by zero: 1 / 0\n>> s\n>> [42, 43, \"s\", \"s\"]\n>> null\n>> ");
+
+    // Hiding null only stops echoing it
+    repl.show_null(false);
+    let mut out = Vec::new();
+    repl.eval_line("let x = _4;", &mut out).unwrap();
+    repl.eval_line("null", &mut out).unwrap();
+    repl.eval_line("x", &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "[42, 43, \"s\", \"s\"]\n[42, 43, \"s\", \"s\"]\n");
+}
+
+// --- SHEBANG ---
+
+#[test]
+fn shebang_line_is_skipped() {
+    assert_int("#!/usr/bin/env nova\nlet x = 2;\nx * 3", 6);
+    let mut p = Parser::new(Lexer::new("#!/usr/bin/env nova\n(1".to_string()));
+    p.parse_program();
+    assert_eq!(p.errors[0].render("#!/usr/bin/env nova\n(1"), "2:3: error: Expected RParen, got EOF");
+}
+
+// --- KEYWORD ARGUMENTS ---
+
+#[test]
+fn keyword_arguments() {
+    assert_int("let f = fn(x, y, z) { x * 100 + y * 10 + z }; f(1, z: 3, y: 2)", 123);
+    assert_int("let f = fn(x, y) { x - y }; f(y: 1, x: 5)", 4);
+    assert_error("let f = fn(x, y) { x }; f(1, z: 2)", "unknown keyword argument 'z'");
+    assert_error("let f = fn(x, y) { x }; f(1, x: 2)", "argument 'x' given twice");
+    assert_error("let f = fn(x, y) { x }; f(x: 1)", "missing argument 'y'");
+
+    let mut p = Parser::new(Lexer::new("f(x: 1, 2)".to_string()));
+    p.parse_program();
+    assert_eq!(p.errors[0].message, "Positional argument after keyword arguments");
+}
+
+#[test]
+fn keyword_arguments_compile_to_positional_js() {
+    let program = Parser::new(Lexer::new("let f = fn(x, y) { x - y }; f(y: 1, x: 5)".to_string())).parse_program();
+    assert!(nova_lang::codegen::js::compile(&program).contains("f(5, 1)"));
+}
+
+// --- PARTIAL APPLICATION ---
+
+#[test]
+fn partial_application() {
+    assert_int("let sub = fn(a, b) { a - b }; let from10 = partial(sub, 10); from10(3)", 7);
+    assert_int("let add = fn(a, b, c) { a + b + c }; curry(add)(1)(2)(3)", 6);
+    assert_int("let add = fn(a, b, c) { a + b + c }; curry(add)(1, 2)(3)", 6);
+    assert_int("partial(max, 4)(9, 2)", 9);
+    assert_eq!(eval("partial(fn(a, b) { a }, \"x\")").to_string(), "partial(fn(a, b) { a }, \"x\")");
+    assert_error("curry(max)", "curry: can't tell how many arguments builtin fn max takes");
+    assert_error("partial(1, 2)", "partial: expected function, found int");
+}
+
+// --- SHORT LAMBDAS ---
+
+#[test]
+fn short_lambdas() {
+    assert_int("let double = |x| x * 2; double(21)", 42);
+    assert_int("let f = |a, b: int| a - b; f(5, 3)", 2);
+    assert_int("let k = || 7; k()", 7);
+    assert_int("partial(|a, b| a * b, 6)(7)", 42);
+    assert_eq!(eval("source(|x| x + 1)").to_string(), "fn(x) {\n    (x + 1)\n}");
+}
+
+// --- FUNCTION SCOPES ---
+
+#[test]
+fn function_bodies_see_globals() {
+    assert_int("let fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } }; fact(5)", 120);
+    assert_int("let scale = 3; let triple = fn(x) { x * scale }; triple(4)", 12);
+    assert_int("let is_even = fn(n) { if (n == 0) { true } else { is_odd(n - 1) } }; \
+                let is_odd = fn(n) { if (n == 0) { false } else { is_even(n - 1) } }; \
+                if (is_even(10)) { 1 } else { 0 }", 1);
+    // An enclosing call's locals come along as captured copies
+    assert_int("let outer = fn(a) { let inner = fn() { a }; inner() }; outer(1)", 1);
+}
+
+// --- LOOPS ---
+
+#[test]
+fn loop_and_break() {
+    assert_int("let i = 0; let found = loop { let i = i + 1; if (i * i > 50) { break i; } }; found", 8);
+    assert_eq!(eval("loop { break; }"), Object::Null);
+    assert_int("let f = fn() { let n = 0; loop { let n = n + 1; if (n == 3) { return n * 10; } } }; f()", 30);
+    assert_error("zone(steps: 100) { loop {} }", "zone: step limit exceeded");
+
+    let mut p = Parser::new(Lexer::new("loop { let f = fn() { break; }; }".to_string()));
+    p.parse_program();
+    assert_eq!(p.errors[0].message, "'break' outside of a loop");
+}
+
+#[test]
+fn generators_yield_from_loops() {
+    assert_eq!(eval("let count = fn(n) { let i = 0; loop { if (i == n) { break; } yield i; let i = i + 1; } }; collect(count(3))").to_string(),
+        "[0, 1, 2]");
+    assert_int("let nat = fn() { let i = 0; loop { yield i; let i = i + 1; } }; let g = nat(); next(g); next(g); next(g)", 2);
+}
+
+// --- MATCH ---
+
+#[test]
+fn match_expressions() {
+    let describe = r#"let describe = fn(v) {
+        match (v) {
+            0 -> "zero",
+            -1 -> "minus one",
+            [] -> "empty",
+            [first, ..rest] -> first + len(rest),
+            (a, b) if a == b -> "twins",
+            (a, _) -> a,
+            {"name": name} -> { "named " + name },
+            null -> "nothing",
+            n if n > 100 -> "big",
+            _ -> "other",
+        }
+    };"#;
+    let cases = [
+        ("0", "zero"), ("-1", "minus one"), ("[]", "empty"), ("[10, 2, 3]", "12"),
+        ("(4, 4)", "twins"), ("(4, 5)", "4"), (r#"{"age": 3, "name": "ada"}"#, "named ada"),
+        ("null", "nothing"), ("500", "big"), ("7", "other"),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(eval(&format!("{} describe({})", describe, input)).to_string(), expected, "describe({})", input);
+    }
+    // Bindings outlive the match, like a let
+    assert_int("match ([1, [2, 3]]) { [a, [_, b]] -> 0 }; a + b", 4);
+    // ...but only the chosen arm's: a failed guard leaves things as they were
+    assert_int("let n = 5; match (1) { n if false -> 0, _ -> 1 }; n", 5);
+    assert_eq!(eval(r#"let a = "outer"; match ((1, 2)) { (a, b) if a == b -> 0, _ -> 1 }; [a, b]"#).to_string(), r#"["outer", null]"#);
+    assert_int("let f = fn() { let n = 5; match (1) { n if false -> 0, _ -> 1 }; n }; f()", 5);
+    assert_error("match (3) { 1 -> true, 2 -> false }", "match: no arm matches 3");
+    assert_eq!(eval(r#"match ("x") { "x" if false -> 1, s -> s }"#), Object::String("x".to_string()));
+}
+
+// --- SWITCH ---
+
+#[test]
+fn switch_statements() {
+    let status = r#"let status = fn(code) {
+        switch (code) {
+            case 200, 204: "ok"
+            case -1:
+                let reason = "unknown";
+                reason
+            case 404: "missing"
+            default: "error"
+        }
+    };"#;
+    for (input, expected) in [("200", "ok"), ("204", "ok"), ("-1", "unknown"), ("404", "missing"), ("500", "error"), (r#""200""#, "error")] {
+        assert_eq!(eval(&format!("{} status({})", status, input)), Object::String(expected.to_string()), "status({})", input);
+    }
+    assert_int(r#"switch ("b") { case "a": 1 case "b": 2 }"#, 2);
+    assert_error(r#"switch ("c") { case "a": 1 case "b": 2 }"#, "switch: no case for c");
+
+    let source = "switch (1) { case 1: case 2: 3 }; switch (1) { case 1: 1 case 1: 2 }";
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    p.parse_program();
+    let errors: Vec<&str> = p.errors.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(errors, ["Empty case: cases don't fall through, so list the values together (case 1, 2:)", "Duplicate case 1"]);
+}
+
+// --- DO-WHILE ---
+
+#[test]
+fn do_while_loops() {
+    assert_int("let n = 0; do { let n = n + 1; } while (n < 5); n", 5);
+    // The body runs once even when the condition never holds
+    assert_int("let n = 10; do { let n = n + 1; } while (n < 5); n", 11);
+    assert_eq!(eval("do { 1 } while (false)"), Object::Null);
+    assert_int("let i = 0; do { let i = i + 1; if (i == 3) { break i * 10; } } while (true)", 30);
+    assert_error("zone(steps: 100) { do {} while (true) }", "zone: step limit exceeded");
+}
+
+// --- LABELLED LOOPS ---
+
+#[test]
+fn labelled_break_and_continue() {
+    let grid = "let grid = [[1, 2, 3], [4, 50, 6], [7, 8, 9]];";
+    assert_int(&format!("{} let found = 0; outer: for row in grid {{ for x in row {{ if (x > 10) {{ let found = x; break outer; }} }} }}; found", grid), 50);
+    // Only the lower triangle: `continue rows` skips the rest of the row
+    assert_int("let n = 0; rows: for i in [1, 2, 3] { for j in [1, 2, 3] { if (j > i) { continue rows; } let n = n + 1; } }; n", 6);
+    assert_int("let sum = 0; for x in [1, 2, 3, 4] { if (x == 2) { continue; } let sum = sum + x; }; sum", 8);
+    assert_int("let f = fn() { search: loop { loop { break search 42; } } }; f()", 42);
+    // A name that isn't a label is a break value
+    assert_int("let v = 7; loop { break v; }", 7);
+    // Like in C, continuing a do-while still checks its condition
+    assert_int("let i = 0; do { let i = i + 1; if (i < 5) { continue; } } while (i < 3); i", 3);
+    assert_eq!(eval("let g = fn() { let i = 0; outer: loop { let i = i + 1; if (i == 2) { continue outer; } if (i > 3) { break; } yield i; } }; collect(g())").to_string(), "[1, 3]");
+
+    let source = "continue; loop { continue nowhere; }; a: loop { a: loop { break; } }; b: 1";
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    p.parse_program();
+    let errors: Vec<&str> = p.errors.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(errors, [
+        "'continue' outside of a loop",
+        "No loop labelled 'nowhere' around this",
+        "The label 'a' is already used by an enclosing loop",
+        "Expected a loop after the label 'b', got Int(1)",
+    ]);
+}
+
+// --- STRING BUFFERS ---
+
+#[test]
+fn string_buffers() {
+    let source = r#"
+        let out = buffer("<");
+        let same = out;
+        for n in [1, 2, 3] { push(out, n, ","); }
+        push(same, 'x', [1, "a"], ">");
+        [to_string(out), len(out), same == out, out == buffer(), to_string(1.5d)]
+    "#;
+    assert_eq!(eval(source).to_string(), r#"["<1,2,3,x[1, \"a\"]>", 17, true, false, "1.5"]"#);
+    assert_eq!(eval("let b = buffer(\"ab\"); push(b, b); b").to_string(), r#"buffer("abab")"#);
+    assert_error("push(\"s\", 1)", "push: argument 1: expected buffer, found string");
+    assert_error("buffer(1)", "buffer: argument 1: expected string, found int");
+
+    // Rebinding a name to a buffer that grew after it was bound
+    let grown = format!(r#"let b = buffer(); push(b, "{}"); let b = 1; b"#, "x".repeat(200));
+    assert_int(&grown, 1);
+    assert_int(&format!("let f = fn() {{ {} }}; f()", grown), 1);
+    let mut env = Environment::new();
+    env.set_memory_limit(Some(1000));
+    let source = r#"let a = buffer(); let b = buffer(); let piece = "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx";
+        for i in [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12] { push(a, piece); push(b, piece); }"#;
+    let program = Parser::new(Lexer::new(source.to_string())).parse_program();
+    match eval_program(&program, &mut env) {
+        Object::Error(msg) => assert!(msg.starts_with("out of memory: "), "{}", msg),
+        other => panic!("expected an error, got {}", other),
+    }
+}
+
+// --- PARSE CACHE ---
+
+#[test]
+fn parse_cache() {
+    use nova_lang::operators::Operators;
+    let mut cache = nova_lang::cache::ParseCache::new(2);
+    let none = Operators::new();
+    let (first, _) = cache.parse("1 + 2", &none);
+    let (again, errors) = cache.parse("1 + 2", &none);
+    assert_eq!((first == again, errors.len(), cache.hits, cache.misses), (true, 0, 1, 1));
+    assert_eq!(cache.parse("let;", &none).1.len(), cache.parse("let;", &none).1.len());
+    cache.parse("3", &none);
+    assert_eq!((cache.len(), cache.hits, cache.misses), (2, 2, 3));
+
+    let mut env = Environment::new();
+    let source = "let total = 0; for n in [1, 2, 3] { eval(\"let total = total + n;\"); } total";
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    assert_eq!(eval_program(&p.parse_program(), &mut env), Object::Integer(6));
+    let cache = &env.runtime().borrow().parse_cache;
+    assert_eq!((cache.hits, cache.misses), (2, 1));
+    assert_eq!(nova_lang::cache::hash("1 + 2"), 0x82ad_effc_df82_1d7f);
+
+    // Entries count as memory, and the cache lets them go rather than
+    // push a script over the limit
+    let mut env = Environment::new();
+    let before = env.memory_used();
+    let mut p = Parser::new(Lexer::new("eval(\"1 + 2\")".to_string()));
+    assert_eq!(eval_program(&p.parse_program(), &mut env), Object::Integer(3));
+    assert_eq!(env.memory_used() - before, env.runtime().borrow().parse_cache.bytes());
+    env.set_memory_limit(Some(env.memory_used() + 100));
+    let mut p = Parser::new(Lexer::new(format!("eval(\"{}1\")", " ".repeat(100))));
+    assert_eq!(eval_program(&p.parse_program(), &mut env), Object::Integer(1));
+    assert!(env.runtime().borrow().parse_cache.is_empty());
+    assert_eq!(env.memory_used(), before);
+}
+
+// --- PROJECTS ---
+
+#[test]
+fn project_manifest() {
+    use nova_lang::manifest::Manifest;
+    use std::path::{Path, PathBuf};
+    let text = r#"
+        [project]
+        entry = "main.nv"   # runs last
+        sources = ["src", "lib/#1.nv"]
+
+        [settings]
+        strict = true
+        allow = ["fs"]
+        max_memory = 1_000
+    "#;
+    let m = Manifest::parse(text, Path::new("app")).unwrap();
+    assert_eq!(m.entry, PathBuf::from("app/main.nv"));
+    assert_eq!(m.sources, [PathBuf::from("app/src"), PathBuf::from("app/lib/#1.nv")]);
+    assert_eq!((m.strict, m.capabilities.fs, m.capabilities.time, m.capabilities.net, m.max_memory), (true, true, true, false, Some(1000)));
+
+    let error = |text: &str| Manifest::parse(text, Path::new(".")).unwrap_err();
+    assert_eq!(error("[project]\nentry = 1"), "line 2: entry must be a string");
+    assert_eq!(error("[settings]\nallow = [\"gpu\"]"), "line 2: unknown capability 'gpu' (expected ffi, process, time, fs or net)");
+    assert_eq!(error("strict = true"), "line 1: 'strict' must be in [project] or [settings]");
+    assert_eq!(error("[project]\nsources = []"), "[project] needs an entry");
+
+    // Names the files before it bound aren't undefined
+    let program = Parser::new(Lexer::new("helper(x)".to_string())).parse_program();
+    let globals = ["helper".to_string()];
+    let warnings = nova_lang::resolver::resolve_with(&program, &globals).diagnostics;
+    assert_eq!(warnings.iter().map(|w| w.message.as_str()).collect::<Vec<_>>(), ["undefined variable 'x'"]);
+}
+
+// --- TEMPLATES ---
+
+#[test]
+fn text_templates() {
+    use nova_lang::template::{render, Template};
+    let mut env = Environment::new();
+    let program = Parser::new(Lexer::new("let items = [\"a\", \"b\"];".to_string())).parse_program();
+    eval_program(&program, &mut env);
+    let text = "Items:\n{% for item in items %}\n  - {{ item }}{# not shown #}\n{% end %}\n{% if len(items) > 5 %}many{% else if len(items) > 1 %}some{% else %}one{% end %} {{ null }}{{ 6 * 7 }}";
+    assert_eq!(render(text, &mut env).unwrap(), "Items:\n  - a\n  - b\nsome 42");
+
+    // Parsed once, rendered against whatever the environment holds then
+    let template = Template::parse("{{ items }}").unwrap();
+    eval_program(&Parser::new(Lexer::new("let items = 3;".to_string())).parse_program(), &mut env);
+    assert_eq!(template.render(&mut env).unwrap(), "3");
+
+    let error = |text: &str| {
+        let result = Template::parse(text).and_then(|t| t.render(&mut Environment::new()));
+        result.unwrap_err().render(text)
+    };
+    assert_eq!(error("a\n{{ 1 / 0 }}"), "2:4: error: division by zero: 1 / 0");
+    assert_eq!(error("{% for x in 1 %}{% end %}"), "1:1: error: for: can't iterate over int");
+    assert_eq!(error("{% if true %}x"), "1:1: error: {% if %} is never closed with {% end %}");
+    assert_eq!(error("x {{ 1"), "1:3: error: {{ is never closed with }}");
+    assert_eq!(error("{% end %}"), "1:1: error: {% end %} without a block to close");
+    assert_eq!(error("{% while x %}"), "1:1: error: Unknown tag {% while x %} (expected if, else, for or end)");
+}
+
+// --- COST ESTIMATES ---
+
+#[test]
+fn cost_estimates() {
+    use nova_lang::cost::{estimate, Cost};
+    use nova_lang::interpreter::Interpreter;
+    let cost = |source: &str| estimate(&Parser::new(Lexer::new(source.to_string())).parse_program());
+    let nested = cost("let f = fn(n) { f(n - 1) }; for x in [1, 2, 3] { for y in items { x * y } }");
+    assert_eq!(nested, Cost { nodes: 21, loop_depth: 2, calls: 1, fan_out: 1, recursive: true, work: 143 });
+    let flat = cost("let g = fn(a) { len(a) + len(a) }; g(1)");
+    assert_eq!((flat.loop_depth, flat.calls, flat.fan_out, flat.recursive), (0, 3, 2, false));
+    assert!(cost("loop { loop { loop { 1 } } }").work > cost("1 + 2 + 3 + 4 + 5 + 6").work * 100);
+
+    let nova = Interpreter::new();
+    assert_eq!(nova.estimate("1 + 2").map(|c| c.work), Ok(4));
+    assert!(nova.estimate("1 +").is_err());
+}
+
+// --- AST DIFF ---
+
+#[test]
+fn ast_diff() {
+    use nova_lang::ast::{diff, Change};
+    use nova_lang::span::Span;
+    let parse = |source: &str| Parser::new(Lexer::new(source.to_string())).parse_program();
+    let old = parse("let a = 1;\nlet b = 2;\nlet c = 3;\nprint(c);");
+    // a reformatted, b changed, c removed, a call added
+    let new = parse("let  a=1;\nlet b = 20;\nprint(c);\nprint(b);");
+    assert_eq!(diff(&old, &new), [
+        Change::Modified { old: 1, new: 1, old_span: Span::new(11, 21), new_span: Span::new(10, 21) },
+        Change::Removed { old: 2, span: Span::new(22, 32) },
+        Change::Added { new: 3, span: Span::new(32, 41) },
+    ]);
+    assert_eq!(diff(&new, &new), []);
+    assert_eq!(diff(&parse(""), &parse("1; 2")).len(), 2);
+}
+
+// --- RENAMING ---
+
+#[test]
+fn rename_refactor() {
+    use nova_lang::refactor::rename;
+    use nova_lang::span::Span;
+    let apply = |source: &str, at: usize, new_name: &str| {
+        let program = Parser::new(Lexer::new(source.to_string())).parse_program();
+        let edits = rename(&program, Span::new(at, at), new_name)?;
+        let mut chars: Vec<char> = source.chars().collect();
+        for edit in edits.iter().rev() {
+            chars.splice(edit.span.start..edit.span.end, edit.new_text.chars());
+        }
+        Ok::<String, String>(chars.into_iter().collect())
+    };
+    // The parameter shadows the outer x, so it keeps its name
+    let source = "let x = 1; let x = x + 1; let f = fn(x) { x * 2 }; f(x)";
+    assert_eq!(apply(source, 4, "count"), Ok("let count = 1; let count = count + 1; let f = fn(x) { x * 2 }; f(count)".to_string()));
+    assert_eq!(apply(source, 42, "n"), Ok("let x = 1; let x = x + 1; let f = fn(n) { n * 2 }; f(x)".to_string()));
+
+    assert_eq!(apply(source, 4, "f"), Err("'f' is already bound in the same scope".to_string()));
+    assert_eq!(apply("let a = 1; let g = fn(b) { a + b };", 4, "b"),
+        Err("'b' is bound where 'a' is used, so that use would refer to it instead".to_string()));
+    assert_eq!(apply("let a = 1; let g = fn(b) { len(b) };", 22, "len"),
+        Err("'len' already means something else where 'b' is visible".to_string()));
+    assert_eq!(apply(source, 4, "let"), Err("'let' isn't a valid name".to_string()));
+    assert_eq!(apply(source, 3, "y"), Err("Nothing to rename here".to_string()));
+}
+
+// --- MACROS ---
+
+#[test]
+fn macros() {
+    let unless = "macro unless(cond, body) { quote(if (!unquote(cond)) { unquote(body) }) } ";
+    assert_int(&format!("{}let x = 1; unless(x > 5) {{ let x = x + 10; }} x", unless), 11);
+    // Expanded inside a function, with its locals
+    assert_int(&format!("{}let f = fn(n) {{ unless(n < 0) {{ let n = n * 2; }} n }}; f(4) + f(-1)", unless), 7);
+    assert_int("macro twice(e) { quote(unquote(e) + unquote(e)) } let i = 2; twice(i * 3)", 12);
+    assert_eq!(eval("let n = 5; quote(n + unquote(n))").to_string(), "quote((n + 5))");
+
+    let errors = |source: &str| {
+        let mut p = Parser::new(Lexer::new(source.to_string()));
+        p.parse_program();
+        p.errors.iter().map(|d| d.render(source)).collect::<Vec<_>>()
+    };
+    assert_eq!(errors("macro m() { 1 } m()"), ["1:17: error: Macro 'm' must return code built with quote(), not int"]);
+    assert_eq!(errors("macro m(e) { quote(m(unquote(e))) } m(1)"), ["1:37: error: Macro 'm' expands more than 64 levels deep"]);
+    let doubling = format!("macro m(e) {{ quote(unquote(e) + unquote(e)) }} {}1{}", "m(".repeat(26), ")".repeat(26));
+    assert_eq!(errors(&doubling), ["1:47: error: Macros expand more than 10000 times in one file"]);
+    assert_eq!(errors("fn() { macro m() { quote(1) } }"), ["1:8: error: Macros can only be defined at the top level"]);
+    assert_error("partial(quote, 1)()", "quote() has to be called by name, with the code to quote: quote(x + 1)");
+}
+
+// --- QUOTING ---
+
+#[test]
+fn quoting() {
+    assert_eq!(eval("let n = 3; quote { let y = unquote(n) * 2; y + 1 }").to_string(), "quote { let y = (3 * 2); (y + 1) }");
+    assert_eq!(eval(r#"quote([unquote([1, "a"]), unquote({"k": (true, 'c')})])"#).to_string(), r#"quote([[1, "a"], {"k": (true, 'c')}])"#);
+    // Evaluated where eval() is called, by name
+    assert_int("let q = quote(a * 10); let f = fn(a) { eval(q) }; f(4)", 40);
+    assert_int("let q = quote { let z = 5; }; eval(q); z", 5);
+    assert_eq!(eval("let q = quote { let w = 5; }; eval(q, true); w"), Object::Null);
+    assert_error("quote(unquote(len))", "unquote() can't turn a function into code");
+    assert_error("eval(1)", "eval: argument 1: expected string or quote, found int");
+}
+
+// --- STRING ESCAPES ---
+
+#[test]
+fn string_escapes() {
+    assert_eq!(eval(r#""a\tb\n\"q\" \\ \u{e9}\u{1F600}""#), Object::String("a\tb\n\"q\" \\ é😀".to_string()));
+    assert_eq!(eval(r#"r"C:\dir\n""#), Object::String(r"C:\dir\n".to_string()));
+    assert_eq!(eval(r#"source(fn() { "tab\there" })"#).to_string(), "fn() {\n    \"tab\\there\"\n}");
+
+    let source = r#"let s = "bad \q escape"; let t = "\u{110000}";"#;
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    p.parse_program();
+    let errors: Vec<String> = p.errors.iter().map(|d| d.render(source)).collect();
+    assert_eq!(errors, [
+        "1:14: error: Unknown escape sequence \\q",
+        "1:35: error: Invalid unicode escape: \"110000\" is not a character",
+    ]);
+}
+
+// --- REGEX ---
+
+#[test]
+#[cfg(feature = "regex")]
+fn regex_builtins() {
+    assert_eq!(eval(r#"regex_match(r"^\d+$", "123")"#), Object::Boolean(true));
+    assert_eq!(eval(r#"regex_find_all(r"\d+", "a1b22c")"#).to_string(), r#"["1", "22"]"#);
+    assert_eq!(eval(r#"regex_captures(r"(?<key>\w+)=(\d+)?", "x=")"#).to_string(), r#"{0: "x=", 1: "x", 2: null, key: "x"}"#);
+    assert_eq!(eval(r#"regex_captures("z", "abc")"#), Object::Null);
+    assert_eq!(eval(r#"regex_replace(r"(\w+)@(\w+)", "me@host", "$2:$1")"#), Object::String("host:me".to_string()));
+    assert_error(r#"regex_match("(", "x")"#, "regex_match: invalid regex: unclosed group");
+}
+
+// --- CHARS ---
+
+#[test]
+fn chars_and_for_in() {
+    assert_eq!(eval("\"héllo\"[1]"), Object::Char('é'));
+    assert_eq!(eval("\"héllo\"[-1]"), Object::Char('o'));
+    assert_eq!(eval("\"héllo\"[9]"), Object::Null);
+    assert_eq!(eval("['\\n', 'x']").to_string(), "['\\n', 'x']");
+    assert_eq!(eval("\"ab\" + 'c'"), Object::String("abc".to_string()));
+    assert_eq!(eval("'a' == \"héllo\"[0]"), Object::Boolean(false));
+
+    assert_int("let total = 0; for x in [1, 2, 3] { let total = total + x; }; total", 6);
+    assert_eq!(eval("let out = \"\"; for c in \"naïve\" { let out = out + c + \"-\"; }; out"),
+        Object::String("n-a-ï-v-e-".to_string()));
+    assert_int("let g = fn() { yield 1; yield 2; }; let n = 0; for x in g() { let n = n * 10 + x; }; n", 12);
+    assert_eq!(eval("for c in \"abc\" { if (c == 'b') { break c; } }"), Object::Char('b'));
+    assert_eq!(eval("for x in [] { break 1; }"), Object::Null);
+    assert_error("for x in 5 {}", "for: can't iterate over int");
+
+    let mut p = Parser::new(Lexer::new("let c = '';".to_string()));
+    p.parse_program();
+    assert_eq!(p.errors[0].message, "Empty character literal");
+}
+
+// --- BYTES ---
+
+#[test]
+fn bytes_and_encodings() {
+    assert_eq!(eval("encode(\"hé\")").to_string(), "bytes([104, 195, 169])");
+    assert_int("encode(\"hé\")[-1]", 169);
+    assert_int("len(encode(\"hé\"))", 3);
+    assert_int("len(\"hé\")", 2);
+    assert_eq!(eval("decode(bytes([104, 233]), \"latin-1\")"), Object::String("hé".to_string()));
+    assert_eq!(eval("decode(encode(\"hé\", \"utf-16be\"), \"utf-16be\")"), Object::String("hé".to_string()));
+    assert_eq!(eval("(bytes([1, 2]) + bytes([3]))[1:]").to_string(), "bytes([2, 3])");
+    assert_error("decode(bytes([104, 233]))", "decode: invalid utf-8 at byte 1");
+    assert_error("encode(\"€\", \"latin-1\")", "encode: '€' can't be encoded as latin-1");
+    assert_error("bytes([256])", "bytes: 256 is not a byte (0-255)");
+    assert_error("unsafe { read_bytes(\"x\") }", "read_bytes: not permitted (needs --allow-fs)");
+
+    let path = std::env::temp_dir().join(format!("nova-bytes-{}.bin", std::process::id()));
+    let source = format!("unsafe {{ write_bytes({0:?}, bytes([0, 255])); read_bytes({0:?}) }}", path.display().to_string());
+    let mut p = Parser::new(Lexer::new(source));
+    let program = p.parse_program();
+    let mut env = Environment::with_capabilities(Capabilities { fs: true, ..Capabilities::none() });
+    assert_eq!(eval_program(&program, &mut env), Object::Bytes(vec![0, 255]));
+    let _ = std::fs::remove_file(path);
+}
+
+// --- PATHS ---
+
+#[test]
+fn paths_and_globs() {
+    let joined = ["src", "stdlib", "mod.rs"].iter().collect::<std::path::PathBuf>();
+    assert_eq!(eval(r#"path_join("src", "stdlib", "mod.rs")"#), Object::String(joined.display().to_string()));
+    assert_eq!(eval(r#"path_basename("src/main.rs")"#), Object::String("main.rs".to_string()));
+    assert_eq!(eval(r#"path_dirname("src/main.rs")"#), Object::String("src".to_string()));
+    assert_eq!(eval(r#"path_dirname("main.rs")"#), Object::String(String::new()));
+    assert_eq!(eval(r#"path_ext("archive.tar.gz")"#), Object::String("gz".to_string()));
+    assert_eq!(eval(r#"path_ext(".bashrc")"#), Object::String(String::new()));
+    assert_error("unsafe { glob(\"*\") }", "glob: not permitted (needs --allow-fs)");
+
+    let dir = std::env::temp_dir().join(format!("nova-glob-{}", std::process::id()));
+    for file in ["a.rs", "b.txt", "sub/c.rs", "sub/deep/d.rs", ".hidden/e.rs"] {
+        let path = dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+    let found = |pattern: &str| {
+        let source = format!("unsafe {{ glob({:?}) }}", dir.join(pattern).display().to_string());
+        let mut p = Parser::new(Lexer::new(source));
+        let program = p.parse_program();
+        let mut env = Environment::with_capabilities(Capabilities { fs: true, ..Capabilities::none() });
+        match eval_program(&program, &mut env) {
+            Object::Array(paths) => paths.iter().map(|p| p.to_string().replace(&dir.display().to_string(), "").replace('\\', "/")).collect::<Vec<_>>(),
+            other => panic!("{}: {}", pattern, other),
+        }
+    };
+    assert_eq!(found("*.rs"), ["/a.rs"]);
+    assert_eq!(found("**/*.rs"), ["/a.rs", "/sub/c.rs", "/sub/deep/d.rs"]);
+    assert_eq!(found("[!a]*"), ["/b.txt", "/sub"]);
+    assert_eq!(found("sub/?.rs"), ["/sub/c.rs"]);
+    assert!(found("nothing/*").is_empty());
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+// --- BIG INTEGERS ---
+
+#[test]
+#[cfg(feature = "bigint")]
+fn integers_promote_instead_of_overflowing() {
+    assert_eq!(eval("9223372036854775807 + 1").to_string(), "9223372036854775808");
+    assert_eq!(eval("pow(2, 100) * 3").to_string(), "3802951800684688204490109616128");
+    assert_eq!(eval("let m = -9223372036854775807 - 1; -m").type_name(), "bigint");
+    // Back to a plain int once it fits again
+    assert_int("(9223372036854775807 + 10) - 20", 9223372036854775797);
+    assert_int("pow(2, 64) / pow(2, 60)", 16);
+    assert_eq!(eval("pow(2, 64) > 5"), Object::Boolean(true));
+    assert_eq!(eval("pow(2, 64) == pow(2, 64)"), Object::Boolean(true));
+    assert_error("pow(2, 64) / 0", "division by zero: 18446744073709551616 / 0");
+}
+
+// --- DECIMALS ---
+
+#[test]
+fn decimals_are_exact() {
+    assert_eq!(eval("0.1d + 0.2d == 0.3d"), Object::Boolean(true));
+    assert_eq!(eval("1.50d + 2").to_string(), "3.50");
+    assert_eq!(eval("19.99d * 3").to_string(), "59.97");
+    assert_eq!(eval("1.5d * 1.5d").to_string(), "2.25");
+    assert_eq!(eval("-0.05d - 1").to_string(), "-1.05");
+    // Division keeps the larger scale, rounding half to even
+    assert_eq!(eval("10.00d / 3").to_string(), "3.33");
+    assert_eq!(eval("0.5d / 2").to_string(), "0.2");
+    assert_eq!(eval("0.7d / 2").to_string(), "0.4");
+    assert_eq!(eval("round(2.345d, 2)").to_string(), "2.34");
+    assert_eq!(eval("round(-2.5d)").to_string(), "-2");
+    assert_eq!(eval("decimal(\"12.340\") == 12.34d"), Object::Boolean(true));
+    assert_eq!(eval("[1.25d, 2.0d]").to_string(), "[1.25, 2.0]");
+    assert_eq!(eval("source(fn() { 1.50d })").to_string(), "fn() {\n    1.50d\n}");
+    assert_eq!(eval("2d < 1.99d"), Object::Boolean(false));
+    assert_error("1.0d / 0", "division by zero: 1.0 / 0");
+    assert_error("decimal(\"1.2.3\")", "decimal: not a decimal number: \"1.2.3\"");
+
+    let mut p = Parser::new(Lexer::new("let x = 1.5;".to_string()));
+    p.parse_program();
+    assert_eq!(p.errors[0].message, "Nova has no floats; write 1.5d for a decimal");
+}
+
+// --- NUMBER FORMATTING ---
+
+#[test]
+fn numbers_round_trip_through_text() {
+    use nova_lang::json::{self, Json};
+    use nova_lang::numbers;
+    // What to_string() writes, int() and decimal() read back as the same value
+    for n in ["0", "-7", "9223372036854775807", "-9223372036854775807 - 1"] {
+        assert_eq!(eval(&format!("int(to_string({})) == {}", n, n)), Object::Boolean(true), "{}", n);
+    }
+    for d in ["0.0d", "-0.50d", "12.340d", "79228162514264337593543950335d"] {
+        assert_eq!(eval(&format!("let d = {}; let s = to_string(d); decimal(s) == d && to_string(decimal(s)) == s", d)), Object::Boolean(true), "{}", d);
+    }
+    assert_int("int(\" 42 \") + int(2.75d) + int(-2.75d)", 42);
+    assert_error("int(\"+1\")", "int: not an int: \"+1\"");
+    assert_error("int(\"1_000\")", "int: not an int: \"1_000\"");
+    assert_error("int(99999999999999999999.5d)", "int: integer overflow");
+    assert_eq!(numbers::parse_decimal("1."), None);
+    assert_eq!(numbers::parse_int("٣"), None); // Arabic-Indic three: digits are ASCII only
+
+    // JSON numbers: the shortest digits that read back as the same f64
+    for f in [0.1, 1.0 / 3.0, -2.5e-8, 1e300, 123456789.125] {
+        let text = Json::Number(f).to_string();
+        assert_eq!(json::parse(&text), Ok(Json::Number(f)), "{}", text);
+    }
+    assert_eq!(Json::Number(0.1).to_string(), "0.1");
+    assert_eq!(Json::Number(f64::NAN).to_string(), "null");
+    assert!(json::parse("1e999").is_err());
+    assert_eq!(numbers::decimal_from_f64(0.1).map(|d| d.to_string()), Some("0.1".to_string()));
+}
+
+// --- UNICODE IDENTIFIERS ---
+
+#[test]
+fn unicode_identifiers() {
+    assert_int("let café = 3; café", 3);
+    // The same name typed with a combining accent is its own, single identifier
+    assert_int("let cafe\u{301} = 4; cafe\u{301}", 4);
+    assert_int("let x2 = 5; let _private = x2 + 1; _private", 6);
+    assert_int("let 変数 = 7; 変数", 7);
+    // Keywords only match whole words
+    assert_int("let iffy = 1; let fn2 = 2; let loops = 3; iffy + fn2 + loops", 6);
+    assert_eq!(eval("{\"naïve\": 1, \"2x\": 2}").to_string(), "{naïve: 1, \"2x\": 2}");
+}
+
+// --- CUSTOM OPERATORS ---
+
+#[test]
+fn custom_operators() {
+    use nova_lang::interpreter::Interpreter;
+    use nova_lang::object::NativeFunction;
+    use nova_lang::operators::{Associativity, Precedence};
+    let mut nova = Interpreter::new();
+    nova.register(NativeFunction::new("approx", |args| match args.as_slice() {
+        [Object::Integer(a), Object::Integer(b)] => Object::Boolean((a - b).abs() <= 1),
+        _ => Object::Null,
+    }));
+    assert!(nova.eval_source("eval(\"2 ** 3\")").is_err());
+    nova.define_operator("~=", Precedence::Equals, Associativity::Left, "approx").unwrap();
+    nova.define_operator("**", Precedence::Product, Associativity::Right, "pow").unwrap();
+    nova.define_operator("<->", Precedence::Sum, Associativity::Left, "sub").unwrap();
+
+    assert_eq!(nova.eval_source("10 ~= 9 + 2"), Ok(Object::Boolean(true)));
+    assert_eq!(nova.eval_source("2 ** 3 ** 2"), Ok(Object::Integer(512)));
+    assert_eq!(nova.eval_source("1 + 2 ** 2"), Ok(Object::Integer(5)));
+    assert_eq!(nova.eval_source("let sub = fn(a, b) { a - b }; 10 <-> 3 <-> 2"), Ok(Object::Integer(5)));
+    assert_eq!(nova.eval_source("source(fn() { 2 ** 3 })"), Ok(Object::String("fn() {\n    pow(2, 3)\n}".to_string())));
+
+    // So does code parsed while running, in tasks and after a reset too
+    assert_eq!(nova.eval_source("eval(\"2 ** 3\")"), Ok(Object::Integer(8)));
+    assert_eq!(nova.eval_source("join(spawn(fn() { eval(\"2 ** 4\") }))"), Ok(Object::Integer(16)));
+    assert_eq!(nova.guarded(|env| nova_lang::template::render("{{ 2 ** 5 }}", env)), Ok(Ok("32".to_string())));
+    nova.reset();
+    assert_eq!(nova.eval_source("eval(\"2 ** 3\")"), Ok(Object::Integer(8)));
+
+    assert_eq!(nova.define_operator("+", Precedence::Sum, Associativity::Left, "add"), Err("\"+\" is a built-in operator".to_string()));
+    assert!(nova.define_operator("a+", Precedence::Sum, Associativity::Left, "add").is_err());
+}
+
+// --- REPL ---
+
+#[test]
+#[cfg(feature = "repl")]
+fn repl_over_any_stream() {
+    use nova_lang::interpreter::Interpreter;
+    use nova_lang::repl::Repl;
+    let mut repl = Repl::new(Interpreter::new());
+    let mut out = Vec::new();
+    repl.run("let x = 2;\nx * 21\n:null hide\nlet f = fn() { print(_) };\nf()\n(1\n".as_bytes(), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), ">> 2\n>> 42\n>> >> fn() { print(_) }\n>> >> \tExpected RParen, got EOF\n>> ");
+
+    // An error doesn't end the session or lose its bindings
+    let mut out = Vec::new();
+    repl.run("x / 0\nx + 1\n".as_bytes(), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), ">> ERROR: division by zero: 2 / 0\n>> 3\n>> ");
+}
+
+// --- SERVE ---
+
+#[cfg(feature = "repl")]
+#[test]
+fn serve_handles_plain_and_json_requests() {
+    use nova_lang::serve::{handle, Config};
+    let mut out = Vec::new();
+    handle("let x = 20;\n\nx * 2\nx / 0\n".as_bytes(), &mut out, Config::default()).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "20\n40\nERROR: division by zero: 20 / 0\n");
+
+    let config = Config { json: true, max_steps: Some(1000), ..Config::default() };
+    let mut out = Vec::new();
+    let input = "{\"id\": 1, \"code\": \"let x = 20; x * 2\"}\n{\"id\": 2, \"code\": \"loop {}\"}\n{\"id\": 3}\n";
+    handle(input.as_bytes(), &mut out, config).unwrap();
+    let replies = String::from_utf8(out).unwrap();
+    let replies: Vec<&str> = replies.lines().collect();
+    assert_eq!(replies[0], r#"{"id":1,"value":"40","type":"int"}"#);
+    assert_eq!(replies[1], r#"{"id":2,"error":"zone: step limit exceeded"}"#);
+    assert_eq!(replies[2], r#"{"id":3,"error":"invalid request: missing \"code\""}"#);
+
+    // A task a request leaves running stops at the same budget
+    let config = Config { max_steps: Some(1000), ..Config::default() };
+    let mut out = Vec::new();
+    handle("let ch = channel(); let t = spawn(fn(ch) { recv(ch); loop {} }, ch); 1\nsend(ch, 0); join(t)\n".as_bytes(), &mut out, config).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "1\nERROR: zone: step limit exceeded\n");
+}
+
+// --- JSON OUTPUT ---
+
+#[test]
+fn diagnostics_as_json() {
+    let source = "let x = 1;\nlet y = (x;";
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    p.parse_program();
+    assert_eq!(
+        p.errors[0].to_json("parse", source).to_string(),
+        r#"{"code":"parse","severity":"error","message":"Expected RParen, got Semicolon","span":{"start":21,"end":22,"line":2,"column":11}}"#,
+    );
+}
+
+// --- RECORD AND REPLAY ---
+
+#[test]
+fn record_and_replay_inputs() {
+    use nova_lang::trace::Trace;
+    let run = |trace: Trace| {
+        let mut p = Parser::new(Lexer::new("[time(), rand(), rand_int(1, 6)]".to_string()));
+        let program = p.parse_program();
+        let mut env = Environment::with_capabilities(Capabilities { time: true, ..Capabilities::none() });
+        env.runtime().borrow_mut().trace = trace;
+        let result = eval_program(&program, &mut env);
+        let trace = env.runtime().borrow().trace.clone();
+        (result, trace)
+    };
+
+    let (recorded, trace) = run(Trace::record());
+    let text = trace.to_text();
+    assert!(text.starts_with("# nova trace\ntime "), "{}", text);
+    assert_eq!(run(Trace::replay(&text).unwrap()).0, recorded);
+
+    let (result, _) = run(Trace::replay("seed 1\n").unwrap());
+    assert_eq!(result, Object::error("time: replay: script asked for time but the trace has seed next"));
+    assert!(Trace::replay("time soon").is_err());
+}
+
+// --- WATCH MODE ---
+
+#[test]
+fn watcher_notices_modified_files() {
+    use nova_lang::watch::Watcher;
+    use std::time::{Duration, SystemTime};
+    let path = std::env::temp_dir().join(format!("nova-watch-{}.nv", std::process::id()));
+    std::fs::write(&path, "1").unwrap();
+    let mut watcher = Watcher::new([&path]);
+    assert!(watcher.changed().is_empty());
+
+    // Bump the time explicitly; some filesystems only keep whole seconds
+    let file = std::fs::File::options().write(true).open(&path).unwrap();
+    file.set_modified(SystemTime::now() + Duration::from_secs(5)).unwrap();
+    assert_eq!(watcher.changed(), vec![path.clone()]);
+    assert!(watcher.changed().is_empty());
+
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(watcher.changed(), vec![path]);
+}
+
+// --- ERROR POSITIONS ---
+
+#[test]
+fn runtime_errors_point_at_their_statement() {
+    use nova_lang::interpreter::Interpreter;
+    let source = "let f = fn(x) {\n  let y = x + 1;\n  y / 0\n};\nlet b = f(1) + 2;";
+    let mut nova = Interpreter::new();
+    assert!(nova.eval_source(source).is_err());
+    assert_eq!(nova.diagnostics()[0].render(source), "3:3: error: division by zero: 2 / 0\n  note: in f(x = 1), with y = 2");
+
+    // Once the callee has returned, errors belong to the caller's statement
+    let source = "let f = fn(x) { x + 1 };\nlet b = f(1) / 0;";
+    assert!(nova.eval_source(source).is_err());
+    assert_eq!(nova.diagnostics()[0].render(source), "2:1: error: division by zero: 2 / 0");
+}
+
+// --- ERROR FRAMES ---
+
+#[test]
+fn runtime_errors_show_their_frame() {
+    use nova_lang::interpreter::Interpreter;
+    use nova_lang::runtime::FrameDump;
+    let mut nova = Interpreter::new();
+    let frame = |nova: &mut Interpreter, source: &str| {
+        assert!(nova.eval_source(source).is_err());
+        nova.diagnostics()[0].notes.clone()
+    };
+    // Values are shortened, and only the locals bound so far are there
+    let notes = frame(&mut nova, r#"let f = fn(s, xs) { let n = len(xs); n / 0; let later = 1; }; f("abcdefghijabcdefghijabcdefghijabcdefghijabcdefghij", [1, 2, 3, 4, 5, 6, 7])"#);
+    assert_eq!(notes, [r#"in f(s = "abcdefghijabcdefghijabcdefghijabcdefghi..., xs = [1, 2, 3, 4, 5, ... 2 more]), with n = 7"#]);
+    // The innermost call; one that isn't called by a name shows as fn
+    assert_eq!(frame(&mut nova, "let g = fn(a) { let hs = [|x| x / 0]; hs[0](a) }; g(3)"), ["in fn(x = 3)"]);
+    // An error outside any call has none
+    assert!(frame(&mut nova, "1 / 0").is_empty());
+
+    let dump = FrameDump { name: None, parameters: vec![], locals: vec![("k".to_string(), "1".to_string())] };
+    assert_eq!(dump.to_string(), "in fn(), with k = 1");
+}
+
+// --- LOCAL SLOTS ---
+
+#[test]
+fn function_locals_live_in_slots() {
+    use nova_lang::ast::{Expression, Statement};
+    let mut p = Parser::new(Lexer::new("let f = fn(a, b) { let c = a + b; for x in [c] { x } };".to_string()));
+    let program = p.parse_program();
+    let Statement::Let(s) = &program.statements[0] else { panic!() };
+    let Expression::Function(f) = &s.value else { panic!() };
+    assert_eq!(f.locals.to_vec(), vec!["a", "b", "c", "x"]);
+
+    // Before its let, a local's name still means the global
+    assert_int("let x = 1; let f = fn() { let y = x; let x = 10; x + y }; f()", 11);
+    assert_int("let f = fn(n) { let total = 0; for i in [1, 2, 3] { let total = total + i * n; }; total }; f(2)", 12);
+    assert_int("let f = fn(n) { zone(steps: 100) { let n = n + 1; }; n }; f(1)", 1);
+    assert_int("let f = fn(a, b) { a - b }; f(b: 1, a: 10)", 9);
+    assert_int("let g = fn(n) { yield n; let m = n * 2; yield m; }; let it = g(4); next(it) + next(it)", 12);
+}
+
+// --- DEAD CODE ---
+
+#[test]
+fn dead_code_is_dropped_with_a_warning() {
+    use nova_lang::interpreter::Interpreter;
+    let source = "let f = fn(x) { return x; print(\"never\"); };\nif (true) { f(1) } else { print(\"no\") }";
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    let mut program = p.parse_program();
+    let warnings: Vec<String> = nova_lang::deadcode::eliminate(&mut program).iter().map(|w| w.render(source)).collect();
+    assert_eq!(warnings, vec![
+        "1:27: warning: unreachable code",
+        "2:27: warning: this branch never runs: the condition is always true",
+    ]);
+    assert_eq!(nova_lang::ast::Node::string(&program), "let f = fn(x) { return x; };if true { f(1) }");
+
+    let mut nova = Interpreter::new();
+    assert_eq!(nova.eval_source(source), Ok(Object::Integer(1)));
+    assert_eq!(nova.diagnostics().len(), 2);
+}
+
+// --- WARNINGS ---
+
+#[test]
+fn warning_policy() {
+    use nova_lang::diagnostics::{Level, Severity, WarningPolicy};
+    let source = "let f = fn() { return 1; 2 };\nif (false) { missing }";
+    let mut program = Parser::new(Lexer::new(source.to_string())).parse_program();
+    let warnings = nova_lang::deadcode::eliminate(&mut program);
+    let rules: Vec<_> = warnings.iter().map(|w| w.rule).collect();
+    assert_eq!(rules, vec![Some("unreachable-code"), Some("constant-condition")]);
+
+    let apply = |policy: &WarningPolicy| -> Vec<Severity> {
+        warnings.iter().cloned().filter_map(|w| policy.apply(w)).map(|w| w.severity).collect()
+    };
+    let mut policy = WarningPolicy::default();
+    assert_eq!(apply(&policy), vec![Severity::Warning, Severity::Warning]);
+    policy.deny_warnings = true;
+    assert_eq!(apply(&policy), vec![Severity::Error, Severity::Error]);
+    // A rule's own level wins over --deny-warnings
+    policy.set("constant-condition", Level::Allow).unwrap();
+    assert_eq!(apply(&policy), vec![Severity::Error]);
+    policy.deny_warnings = false;
+    policy.set("unreachable-code", Level::Deny).unwrap();
+    assert_eq!(apply(&policy), vec![Severity::Error]);
+    assert!(policy.set("unused", Level::Allow).unwrap_err().contains("unknown rule 'unused'"));
+    // Errors aren't warnings and go through untouched
+    let error = nova_lang::diagnostics::Diagnostic::error("boom", Default::default());
+    assert_eq!(WarningPolicy::default().apply(error.clone()), Some(error));
+}
+
+// --- STRICT MODE ---
+
+#[test]
+fn strict_mode() {
+    use nova_lang::interpreter::{Config, Error, Interpreter};
+    // Quietly null by default
+    assert_eq!(eval("missing"), Object::Null);
+    assert_eq!(eval("1 + \"a\""), Object::Null);
+
+    assert_error("#pragma strict\nmissing", "undefined variable 'missing'");
+    assert_error("#pragma strict\nlet a = 1; a(2)", "can't call int");
+    assert_error("#pragma strict\n1 + \"a\"", "can't apply + to int and string");
+    assert_error("#pragma strict\n-\"a\"", "can't apply - to string");
+    assert_int("// comments first are fine\n#pragma strict\nlen([1, 2]) + 1", 3);
+
+    let mut nova = Interpreter::with_config(Config { strict: true, ..Config::default() });
+    assert_eq!(nova.eval_source("[1] + [2]"), Err(Error::Runtime("can't apply + to array and array".to_string())));
+
+    let mut p = Parser::new(Lexer::new("let a = 1;\n#pragma strict\n#pragma loose".to_string()));
+    p.parse_program();
+    let errors: Vec<String> = p.errors.iter().map(|e| e.message.clone()).collect();
+    assert_eq!(errors, vec!["#pragma must come before any code", "#pragma must come before any code"]);
+    let mut p = Parser::new(Lexer::new("#pragma loose".to_string()));
+    p.parse_program();
+    assert_eq!(p.errors[0].message, "Unknown pragma 'loose' (expected one of: strict, feature)");
+}
+
+// --- PRAGMAS ---
+
+#[test]
+fn feature_pragmas() {
+    use nova_lang::features;
+    let warnings = |source: &str| {
+        let mut p = Parser::new(Lexer::new(source.to_string()));
+        let program = p.parse_program();
+        assert!(p.errors.is_empty(), "{:?}", p.errors);
+        features::check(&program, |_| false).iter().map(|w| (w.rule, w.render(source))).collect::<Vec<_>>()
+    };
+    let source = "let g = fn() {\n  yield 1;\n  yield 2;\n};\nlet f = async fn() { await g };\nhas_key({}, 1)";
+    assert_eq!(warnings(source), [
+        (Some("experimental-feature"), "2:3: warning: generators are experimental; turn them on with `#pragma feature(generators)` at the top of the file".to_string()),
+        (Some("experimental-feature"), "5:1: warning: async functions are experimental; turn them on with `#pragma feature(async)` at the top of the file".to_string()),
+        (Some("deprecated"), "6:1: warning: has_key is deprecated; use contains(hash, key) instead".to_string()),
+    ]);
+    // Turned on, or a name of the script's own, there's nothing to say
+    let source = "#pragma feature(async, generators)\n#pragma feature(macros)\nmacro m(x) { x }\nlet has_key = fn(h, k) { k };\nlet f = async fn() { yield m(1) }; has_key(1, 2)";
+    assert!(warnings(source).is_empty());
+    let program = Parser::new(Lexer::new("has_key(1, 2)".to_string())).parse_program();
+    assert!(features::check(&program, |name| name == "has_key").is_empty());
+
+    let mut p = Parser::new(Lexer::new("#pragma feature(threads)\n#pragma feature".to_string()));
+    p.parse_program();
+    let errors: Vec<String> = p.errors.iter().map(|e| e.message.clone()).collect();
+    assert_eq!(errors, ["Unknown feature 'threads' (expected one of: generators, async, macros)", "Expected #pragma feature(name, ...)"]);
+    // Gated or not, the code runs
+    assert_int("let g = fn() { yield 4; }; next(g())", 4);
+}
+
+// --- TRUTHINESS ---
+
+#[test]
+fn truthiness() {
+    use nova_lang::interpreter::{Config, Interpreter};
+    use nova_lang::runtime::Truthiness;
+    // The deciding operand is the result, and the right side only runs
+    // when it has to
+    assert_eq!(eval(r#"let name = null; name || "anonymous""#), Object::String("anonymous".to_string()));
+    assert_eq!(eval("[false && 1 / 0, true || 1 / 0]").to_string(), "[false, true]");
+    assert_int("0 || 5", 0);
+    assert_eq!(eval("1 < 2 && 2 < 1 || !null"), Object::Boolean(true));
+    assert_eq!(eval(r#"!"" == false"#), Object::Boolean(true));
+    // `||` at the start of an expression is still a lambda with no parameters
+    assert_int("let k = || 7; k()", 7);
+    assert_int("[|| 3][0]() || 4", 3);
+
+    let mut nova = Interpreter::with_config(Config { truthiness: Truthiness::PYTHON, ..Config::default() });
+    assert_eq!(nova.eval_source("0 || 5"), Ok(Object::Integer(5)));
+    assert_eq!(nova.eval_source(r#"if ("") { 1 } else { 2 }"#), Ok(Object::Integer(2)));
+    assert_eq!(nova.eval_source("[!0, ![], !{}, ![0], !0.0d]").map(|v| v.to_string()), Ok("[true, true, true, false, true]".to_string()));
+    assert_eq!(nova.eval_source("let n = 3; do { let n = n - 1; } while (n); n"), Ok(Object::Integer(0)));
+}
+
+// --- AST TREE ---
+
+#[test]
+fn ast_tree_dump() {
+    let source = "let x = 1 + y;\nif (x) { f(2, by: \"a\") }";
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    assert_eq!(nova_lang::ast_tree::render(&program, source), "\
+Program
+  Let x @1:1
+    Infix +
+      Int 1
+      Identifier y @1:13
+  Expression @2:1
+    If
+      condition
+        Identifier x @2:5
+      then
+        Expression @2:10
+          Call
+            Identifier f @2:10
+            args
+              Int 2
+            by:
+              String \"a\"
+");
+}
+
+// --- TRAILING COMMAS ---
+
+#[test]
+fn trailing_commas() {
+    assert_int("let f = fn(a, b,) { a - b }; f(10, 3,)", 7);
+    assert_int("let f = fn(a: int, b: int,) { a - b }; f(\n    10,\n    b: 4,\n)", 6);
+    assert_int("len([1, 2, 3,]) + len({\"a\": 1,}) + len((1, 2,))", 6);
+    assert_int("let g = |a, b,| a * b; g(6, 7,)", 42);
+
+    // One comma, not two
+    let mut p = Parser::new(Lexer::new("f(1,,)".to_string()));
+    p.parse_program();
+    assert!(!p.errors.is_empty());
+}
+
+// --- CALL CHAINS ---
+
+#[test]
+fn calls_on_returned_functions() {
+    assert_int("let adder = fn(x) { fn(y) { x + y } }; adder(1)(2)", 3);
+    assert_int("(fn(x) { fn(y) { x + y } })(1)(2)", 3);
+    assert_int("let add3 = fn(a) { fn(b) { fn(c) { a * 100 + b * 10 + c } } }; add3(1)(2)(3)", 123);
+    assert_int("let make = fn(n) { let k = n * 2; |x| x + k }; let add10 = make(5); add10(1) + add10(2)", 23);
+
+    // Captured when made, so later changes to the local don't show
+    assert_int("let f = fn() { let n = 1; let get = fn() { n }; let n = 2; get() }; f()", 1);
+    // Globals are still looked up when the function runs
+    assert_int("let g = fn() { fn() { late } }; let h = g(); let late = 7; h()", 7);
+    assert_int("let mk = fn(x) { fn() { x * 2 } }; join(spawn(mk(21)))", 42);
+}
+
+// --- FUNCTION DISPLAY ---
+
+#[test]
+fn functions_display_their_body() {
+    use nova_lang::object::Truncation;
+    assert_eq!(eval("fn(x, y) { let z = x * y; z + 1 }").to_string(), "fn(x, y) { let z = (x * y); (z + 1) }");
+    assert_eq!(eval("|x| x").to_string(), "fn(x) { x }");
+
+    let long = eval("fn(n) { let a = n + 1; let b = a * 2; let c = b - 3; let d = c / 4; let e = d - 5; a + b + c + d + e }");
+    let shown = long.display(Truncation::default()).to_string();
+    assert!(shown.starts_with("fn(n) { let a = (n + 1);") && shown.ends_with(" ... }"), "{}", shown);
+    assert!(!long.to_string().contains("..."));
+    assert_eq!(eval("[[fn(a) { a }]]").display(Truncation { depth: Some(1), items: None }).to_string(), "[[...]]");
+    assert_eq!(eval("[fn(a) { a }]").display(Truncation { depth: Some(1), items: None }).to_string(), "[fn(a) { ... }]");
+}
+
+// --- HELP ---
+
+#[test]
+fn builtins_have_help() {
+    assert_eq!(eval("help(\"len\")"), Object::String("len(value): Elements, keys, characters of a string or bytes".to_string()));
+    assert_eq!(eval("help(len)"), eval("help(\"len\")"));
+    assert_eq!(eval("help(\"nope\")"), Object::Error("help: no builtin named 'nope'".to_string()));
+    for builtin in nova_lang::stdlib::all() {
+        assert!(builtin.signature.starts_with(&format!("{}(", builtin.name)), "{}", builtin.signature);
+        assert!(!builtin.doc.is_empty(), "{} has no doc", builtin.name);
+    }
+    let Object::String(all) = eval("help()") else { panic!() };
+    assert_eq!(all.lines().count(), nova_lang::stdlib::all().len());
+}
+
+// --- HTTP ---
+
+#[test]
+fn http_builtins_need_the_net_capability() {
+    assert_error("unsafe { http_get(\"http://127.0.0.1:1/\") }", "http_get: not permitted (needs --allow-net)");
+    assert_error("http_post(\"http://x/\", 5)", "http_post: argument 2: expected string or bytes, found int");
+    assert_error("http_get(\"http://x/\", {\"a\": 1})", "http_get: headers must map strings to strings, found a: int");
+}
+
+#[test]
+#[cfg(feature = "net")]
+fn http_get_and_post_against_a_local_server() {
+    use std::io::{BufRead, BufReader, Read, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    // Answers two requests, echoing the method, the X-Test header and the body
+    let server = std::thread::spawn(move || {
+        for _ in 0..2 {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let (mut length, mut test) = (0, String::new());
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end().to_lowercase();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some(v) = line.strip_prefix("content-length: ") {
+                    length = v.parse().unwrap();
+                }
+                if let Some(v) = line.strip_prefix("x-test: ") {
+                    test = v.to_string();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let method = request_line.split(' ').next().unwrap().to_string();
+            let reply = format!("{} {} {}", method, test, String::from_utf8(body).unwrap());
+            let status = if method == "GET" { "200 OK" } else { "201 Created" };
+            write!(&stream, "HTTP/1.1 {}\r\nContent-Length: {}\r\nX-Reply: yes\r\nConnection: close\r\n\r\n{}", status, reply.len(), reply).unwrap();
+        }
+    });
+
+    let source = format!("unsafe {{ let url = \"http://127.0.0.1:{}/\"; \
+        let a = http_get(url, {{\"X-Test\": \"one\"}}); \
+        let b = http_post(url, \"hi\", {{\"X-Test\": \"two\"}}); \
+        [a[\"status\"], a[\"body\"], a[\"headers\"][\"x-reply\"], b[\"status\"], b[\"body\"]] }}", port);
+    let mut p = Parser::new(Lexer::new(source));
+    let program = p.parse_program();
+    let mut env = Environment::with_capabilities(Capabilities { net: true, ..Capabilities::none() });
+    assert_eq!(eval_program(&program, &mut env).to_string(), r#"[200, "GET one ", "yes", 201, "POST two hi"]"#);
+    server.join().unwrap();
+}
+
+// --- TCP ---
+
+#[test]
+fn tcp_server_and_client() {
+    assert_error("unsafe { tcp_listen(0) }", "tcp_listen: not permitted (needs --allow-net)");
+    let source = "
+        let serve_one = fn(server) { unsafe {
+            let conn = accept(server);
+            let got = read(conn);
+            write(conn, \"echo: \" + got);
+            close(conn);
+            got
+        } };
+        unsafe {
+            let server = tcp_listen(0);
+            let task = spawn(serve_one, server);
+            let conn = tcp_connect(\"127.0.0.1\", tcp_port(server));
+            let sent = write(conn, \"hi\");
+            let reply = read(conn);
+            let after = read(conn);
+            close(conn);
+            close(server);
+            [sent, reply, after, join(task)]
+        }";
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    let mut env = Environment::with_capabilities(Capabilities { net: true, ..Capabilities::none() });
+    assert_eq!(eval_program(&program, &mut env).to_string(), r#"[2, "echo: hi", null, "hi"]"#);
+    let mut env = Environment::with_capabilities(Capabilities { net: true, ..Capabilities::none() });
+    assert_eq!(eval_program(&Parser::new(Lexer::new("unsafe { read(999999) }".to_string())).parse_program(), &mut env), Object::Error("read: argument 1: expected resource, found int".to_string()));
+}
+
+// --- CSV AND TOML ---
+
+#[test]
+fn csv_parsing() {
+    assert_eq!(eval(r#"csv_parse("a,b\n1,\"x, \"\"y\"\"\"\r\n,\n")"#).to_string(), r#"[["a", "b"], ["1", "x, \"y\""], ["", ""]]"#);
+    assert_eq!(eval(r#"csv_parse("name,note\nann,\"two\nlines\"")[1][1]"#), Object::String("two\nlines".to_string()));
+    assert_eq!(eval(r#"csv_parse("a,b\n1,2\n3,4\n", true)"#).to_string(), r#"[{a: "1", b: "2"}, {a: "3", b: "4"}]"#);
+    assert_eq!(eval(r#"csv_parse("")"#).to_string(), "[]");
+    assert_error(r#"csv_parse("a,b\n1\n", true)"#, "csv_parse: row 2 has 1 fields, the header has 2");
+    assert_error(r#"csv_parse("a,\"b")"#, "csv_parse: line 1: unclosed quote");
+    assert_error(r#"csv_parse("\"a\"b")"#, "csv_parse: line 1: text after a closing quote");
+}
+
+#[test]
+#[cfg(feature = "toml")]
+fn toml_parsing() {
+    let source = r#"toml_parse("title = \"x\"\n[server]\nport = 8080\nratio = 0.25\nhosts = [\"a\", \"b\"]\nstarted = 1979-05-27\n")"#;
+    assert_eq!(eval(source).to_string(), r#"{title: "x", server: {port: 8080, ratio: 0.25, hosts: ["a", "b"], started: "1979-05-27"}}"#);
+    assert_int(r#"toml_parse("[a.b]\nc = 3")["a"]["b"]["c"]"#, 3);
+    assert!(matches!(eval(r#"toml_parse("x = ")"#), Object::Error(msg) if msg.starts_with("toml_parse: ")));
+}
+
+// --- DATES AND TIMES ---
+
+#[test]
+fn dates_and_times() {
+    assert_eq!(eval(r#"date_add(date_parse("2024-01-31"), 1, "months")"#).to_string(), "2024-02-29T00:00:00Z");
+    assert_eq!(eval(r#"date_parse("2024-03-10T08:15:30.5+02:00")"#).to_string(), "2024-03-10T06:15:30.500Z");
+    assert_eq!(eval(r#"date_format(date(2024, 3, 1, 9, 30) + 90, "%a %d %b %Y, %H:%M:%S")"#).to_string(), "Fri 01 Mar 2024, 09:31:30");
+    assert_eq!(eval(r#"date_parse("29/02/24 07h", "%d/%m/%y %Hh")"#).to_string(), "2024-02-29T07:00:00Z");
+    assert_int(r#"date(2024, 3, 1) - date(2024, 2, 1)"#, 29 * 86400);
+    assert_int(r#"timestamp(date(2001, 9, 9, 1, 46, 40))"#, 1_000_000_000);
+    assert_int(r#"date_parts(date(0))["weekday"]"#, 4);
+    assert_eq!(eval(r#"date(1969, 12, 31) < date(1970, 1, 1)"#), Object::Boolean(true));
+    assert_error(r#"date(2023, 2, 29)"#, "date: no such time: 2023-02-29 00:00:00");
+    assert_error(r#"date_parse("2024-01-01", "%d.%m.%Y")"#, "date_parse: expected '.' at position 2, found '2'");
+    assert_error(r#"date_add(date(0), 1, "fortnights")"#, r#"date_add: unknown unit "fortnights" (ms, seconds, minutes, hours, days, weeks, months, years)"#);
+}
+
+// --- INCREMENTAL PARSING ---
+
+// Replaces `old` in `text` with `new` (its first occurrence), parses the
+// text both ways and checks they agree; the tree before and the reparse
+fn reparse_edit(text: &str, old: &str, new: &str) -> (nova_lang::ast::Program, nova_lang::incremental::Reparse) {
+    use nova_lang::ast::Node;
+    use nova_lang::incremental::{full_parse, reparse, Edit};
+    let before = full_parse(text);
+    assert!(before.errors.is_empty(), "{:?}", before.errors);
+    let start = text.find(old).unwrap();
+    let edit = Edit { start, end: start + old.len(), new_len: new.len() };
+    let new_text = text.replacen(old, new, 1);
+    let after = reparse(&before.program, &edit, &new_text);
+    let full = full_parse(&new_text);
+    assert_eq!(after.program.string(), full.program.string(), "{:?}", new_text);
+    let spans = |p: &nova_lang::ast::Program| p.statements.iter().map(|s| s.span()).collect::<Vec<_>>();
+    assert_eq!(spans(&after.program), spans(&full.program), "{:?}", new_text);
+    assert_eq!(after.errors, full.errors);
+    (before.program, after)
+}
+
+#[test]
+fn incremental_reparse() {
+    let text = "let a = 1;\nlet b = 2;\nlet c = 3;\nlet d = 4;\nlet e = 5;\n";
+    let ids = |p: &nova_lang::ast::Program| p.statements.iter().map(|s| s.id()).collect::<Vec<_>>();
+
+    // One statement edited in place: it and its neighbours are parsed
+    // again, and every statement keeps its id
+    let (before, after) = reparse_edit(text, "2", "20");
+    assert_eq!(after.reparsed, Some(0..3));
+    assert_eq!(ids(&after.program), ids(&before));
+
+    // Further in, the statements before the neighbour aren't touched
+    let (before, after) = reparse_edit(text, "4", "40 + 2");
+    assert_eq!(after.reparsed, Some(2..5));
+    assert_eq!(ids(&after.program), ids(&before));
+
+    // A statement rewritten as two: both get new ids, the rest keep theirs
+    let (before, after) = reparse_edit(text, "let c = 3;", "let c = 3; let x = 9;");
+    let (old, new) = (ids(&before), ids(&after.program));
+    assert_eq!(new.len(), 6);
+    assert_eq!([new[0], new[1], new[4], new[5]], [old[0], old[1], old[3], old[4]]);
+    assert!(new[2] > before.max_id() && new[3] > before.max_id());
+
+    // The edit joins a statement to its neighbour, which the widening
+    // by one picks up
+    let (_, after) = reparse_edit("let a = 1;\nlet f = fn(x) { x };\nf;\n(3);\nlet e = 5;\n", "f;\n(3)", "f(3)");
+    assert_eq!(after.program.statements.len(), 4);
+    assert!(after.reparsed.is_some());
+
+    // Text that doesn't parse, or has macros, gets a full parse
+    let (_, after) = reparse_edit(text, "let c = 3;", "let c = (3;");
+    assert_eq!(after.reparsed, None);
+    assert!(!after.errors.is_empty());
+    let (_, after) = reparse_edit(text, "3", "m(3)");
+    assert!(after.reparsed.is_some());
+    let (_, after) = reparse_edit(text, "let a = 1;", "macro m(e) { quote(unquote(e)) } let a = 1;");
+    assert_eq!(after.reparsed, None);
+}
+
+// --- JIT ---
+
+#[test]
+#[cfg(feature = "jit")]
+fn jit_matches_the_interpreter() {
+    let parse = |source: &str| Parser::new(Lexer::new(source.to_string())).parse_program();
+    let source = "
+        let fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } };
+        let add = fn(a, b) { let c = a + b; return c; };
+        let big = fn(a) { a < 10 };
+        let clamp = fn(x, lo, hi) { if (x < lo) { lo } else { if (x > hi) { hi } else { x } } };
+        let greet = fn(name) { \"hi \" + name };
+        let twice = fn(f, x) { f(f(x)) };
+        let again = fn(x) { x };
+        let again = fn(x) { x + 1 };
+    ";
+    let mut names: Vec<String> = nova_lang::jit::compile(&parse(source)).into_keys().collect();
+    names.sort();
+    // Calls, strings, function parameters and rebound names stay interpreted
+    assert_eq!(names, ["add", "big", "clamp"]);
+
+    // Same results either way, including where compiled code bails out
+    // (overflow, division by zero) and the interpreter re-runs the call
+    let checks = [
+        "add(40, 2)",
+        "fact(10)",
+        "[big(3), big(30)]",
+        "[clamp(-5, 0, 9), clamp(5, 0, 9), clamp(50, 0, 9)]",
+        "greet(\"you\")",
+        "twice(fn(x) { x * 3 }, 2)",
+        "again(1)",
+        "add(9223372036854775807, 1)",
+        "let div = fn(a, b) { a / b }; div(7, 0)",
+        // Compiled functions are still functions
+        "add(b: 2, a: 40)",
+        "join(spawn(add, 40, 2))",
+        "[source(big), to_string(big)]",
+        "zone(depth: 0) { add(40, 2) }",
+    ];
+    for check in checks {
+        let program = parse(&format!("{}\n{}", source, check));
+        let jit = nova_lang::jit::run(&program, &mut Environment::new());
+        assert_eq!(jit, eval_program(&program, &mut Environment::new()), "{}", check);
+    }
+
+    // A call compiled code bails out of goes on in the caller's runtime
+    use nova_lang::interpreter::{Error, Interpreter};
+    let mut nova = Interpreter::new();
+    let source = "let div = fn(a, b) { a / b };\ndiv(7, 0)";
+    let program = parse(source);
+    assert_eq!(nova.eval_with(|env| nova_lang::jit::run(&program, env)), Err(Error::Runtime("division by zero: 7 / 0".into())));
+    assert_eq!(nova.diagnostics()[0].render(source), "1:22: error: division by zero: 7 / 0\n  note: in div(a = 7, b = 0)");
+}
+
+// --- JS CODEGEN ---
+
+#[test]
+fn compiled_js_runs_like_the_interpreter() {
+    // Runs where node is installed; the output is checked either way
+    let node = std::process::Command::new("node").arg("--version").output().is_ok_and(|o| o.status.success());
+    let programs = [
+        "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15)",
+        "let total = 0; for x in [1, 2, 3, 4] { let total = total + x * x; }; total",
+        "let n = 0; let r = loop { let n = n + 1; if (n == 5) { break n * 10; } }; r",
+        "let xs = [1, 2, 3]; let (a, b) = (xs[-1], xs[0:2]); a * 10 + b[1]",
+        "let f = fn(x, y) { x - y }; f(y: 1, x: 5) + 7 / 2",
+        "let kind = fn(v) { match (v) { 0 -> \"zero\", n if n < 0 -> \"negative\", _ -> \"positive\" } }; kind(-3) + \" \" + kind(0)",
+        "let day = fn(d) { switch (d) { case 6: \"weekend\" case 7: \"weekend\" default: \"weekday\" } }; day(7)",
+        "let c = 5; 1 < c < 10 && c != 6",
+    ];
+    for source in programs {
+        let program = Parser::new(Lexer::new(source.to_string())).parse_program();
+        let js = nova_lang::codegen::js::compile(&program);
+        assert!(js.contains("console.log("), "{}", js);
+        if !node {
+            continue;
+        }
+        let path = std::env::temp_dir().join(format!("nova_codegen_{}.js", std::process::id()));
+        std::fs::write(&path, &js).unwrap();
+        let out = std::process::Command::new("node").arg(&path).output().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(out.status.success(), "{}\n{}", js, String::from_utf8_lossy(&out.stderr));
+        let expected = eval_program(&program, &mut Environment::new()).to_string();
+        assert_eq!(String::from_utf8(out.stdout).unwrap().trim_end(), expected, "{}\n{}", source, js);
+    }
+}
+
+// --- C ABI ---
+
+#[test]
+#[cfg(feature = "capi")]
+fn c_abi() {
+    use std::ffi::{CStr, CString};
+    use std::ptr;
+    use nova_lang::capi::*;
+    // SAFETY: the result pointer is read before the next call on `nova`
+    let result = |nova| unsafe { CStr::from_ptr(nova_get_string_result(nova)) }.to_str().unwrap().to_string();
+    let source = |s: &str| CString::new(s).unwrap();
+
+    let nova = nova_new();
+    unsafe {
+        // Bindings last between calls
+        assert_eq!(nova_eval(nova, source("let x = 20;").as_ptr()), NOVA_OK);
+        assert_eq!(nova_eval(nova, source("x * 2").as_ptr()), NOVA_OK);
+        assert_eq!(result(nova), "40");
+        assert_eq!(nova_eval(nova, source("x / 0").as_ptr()), NOVA_ERR_RUNTIME);
+        assert_eq!(result(nova), "division by zero: 20 / 0");
+        // An error string is still a value
+        assert_eq!(nova_eval(nova, source("\"ERROR\"").as_ptr()), NOVA_OK);
+
+        // The result belongs to the interpreter until the next call
+        let kept = nova_get_string_result(nova);
+        assert_eq!(result(nova), "ERROR");
+        assert_eq!(CStr::from_ptr(kept).to_str(), Ok("ERROR"));
+        assert_eq!(nova_eval(nova, source("\"a\\0b\"").as_ptr()), NOVA_OK);
+        assert_eq!(result(nova), "a\\0b");
+
+        assert_eq!(nova_eval(nova, source("let = 1").as_ptr()), NOVA_ERR_PARSE);
+        assert!(result(nova).starts_with("1:5: error: "), "{}", result(nova));
+        assert_eq!(nova_eval(nova, source("let s: string = 1;").as_ptr()), NOVA_ERR_RUNTIME);
+        assert_eq!(result(nova), "1:1: error: let s: expected string, found int");
+
+        // NULL and bad input are refused, not crashed on
+        assert_eq!(nova_eval(ptr::null_mut(), source("1").as_ptr()), NOVA_ERR_ARGUMENT);
+        assert_eq!(nova_eval(nova, ptr::null()), NOVA_ERR_ARGUMENT);
+        assert_eq!(result(nova), "");
+        assert_eq!(nova_eval(nova, c"\xff".as_ptr()), NOVA_ERR_ARGUMENT);
+        assert_eq!(result(nova), "source is not valid UTF-8");
+        assert!(nova_get_string_result(ptr::null()).is_null());
+        nova_free(ptr::null_mut());
+        nova_free(nova);
+    }
+}
+
+// --- CONVERSIONS ---
+
+#[test]
+fn rust_values_round_trip_through_objects() {
+    use nova_lang::convert::{ArgError, ConversionError, FromNovaArgs, FromObject};
+    assert_eq!(i64::try_from(Object::from(42)), Ok(42));
+    assert_eq!(bool::try_from(Object::from(true)), Ok(true));
+    assert_eq!(String::try_from(Object::from("hi")), Ok("hi".to_string()));
+    assert_eq!(char::from_object(&Object::from('é')), Ok('é'));
+    assert_eq!(Vec::<i64>::try_from(Object::from(vec![1, 2, 3])), Ok(vec![1, 2, 3]));
+    assert_eq!(Option::<i64>::from_object(&Object::from(None::<i64>)), Ok(None));
+    assert_eq!(Option::<i64>::from_object(&Object::from(Some(7))), Ok(Some(7)));
+    assert_eq!(Object::from(()), Object::Null);
+    assert_eq!(Object::from(vec!["a", "b"]).to_string(), r#"["a", "b"]"#);
+
+    // What came out of a script converts the same way
+    assert_eq!(Vec::<String>::try_from(eval(r#"["a", "b"]"#)), Ok(vec!["a".to_string(), "b".to_string()]));
+    assert_eq!(i64::try_from(eval("\"7\"")), Err(ConversionError { expected: "int", found: "string" }));
+    assert_eq!(Vec::<i64>::try_from(eval("[1, true]")), Err(ConversionError { expected: "int", found: "bool" }));
+
+    let args = [Object::from("n"), Object::from(3)];
+    assert_eq!(<(String, i64)>::from_args(&args), Ok(("n".to_string(), 3)));
+    assert_eq!(<(String,)>::from_args(&args), Err(ArgError::WrongCount { expected: 1, found: 2 }));
+    let wrong = <(i64, i64)>::from_args(&args).unwrap_err();
+    assert_eq!(wrong.to_string(), "argument 1: expected int, found string");
+}
+
+// --- WASM ---
+
+#[test]
+#[cfg(feature = "wasm")]
+fn wasm_sessions_keep_bindings() {
+    use nova_lang::wasm::{eval, Session};
+    let mut session = Session::new();
+    assert_eq!(session.eval("let x = 20;"), "20");
+    assert_eq!(session.eval("x * 2"), "40");
+    assert_eq!(session.eval("x / 0"), "ERROR: division by zero: 20 / 0");
+    assert_eq!(session.eval("(1"), "1:3: error: Expected RParen, got EOF");
+    session.reset();
+    assert_eq!(session.eval("x"), "null");
+
+    // One-shot calls don't share anything
+    assert_eq!(eval("let y = 1; y + 1"), "2");
+    assert_eq!(eval("y"), "null");
+}
+
+// --- LANGUAGE SERVER ---
+
+// Frames `messages` the way a client sends them, runs a server over them
+// and returns what it wrote back, one JSON value per message
+fn lsp_session(messages: &[&str]) -> Vec<nova_lang::json::Json> {
+    let input: String = messages.iter().map(|m| format!("Content-Length: {}\r\n\r\n{}", m.len(), m)).collect();
+    let mut out = Vec::new();
+    nova_lang::lsp::run(input.as_bytes(), &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    out.split("Content-Length: ").skip(1)
+        .map(|m| nova_lang::json::parse(m.split_once("\r\n\r\n").unwrap().1).unwrap())
+        .collect()
+}
+
+#[test]
+fn language_server_replies() {
+    let uri = r#""textDocument": {"uri": "file:///a.nv""#;
+    let replies = lsp_session(&[
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}"#,
+        &format!(r#"{{"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {{{}, "text": "let x = 1;\nlet y = x + ;"}}}}}}"#, uri),
+        &format!(r#"{{"jsonrpc": "2.0", "method": "textDocument/didChange", "params": {{{}}}, "contentChanges": [{{"range": {{"start": {{"line": 1, "character": 12}}, "end": {{"line": 1, "character": 12}}}}, "text": "2"}}]}}}}"#, uri),
+        &format!(r#"{{"jsonrpc": "2.0", "id": 2, "method": "textDocument/definition", "params": {{{}}}, "position": {{"line": 1, "character": 8}}}}}}"#, uri),
+        &format!(r#"{{"jsonrpc": "2.0", "id": 3, "method": "textDocument/hover", "params": {{{}}}, "position": {{"line": 1, "character": 8}}}}}}"#, uri),
+        &format!(r#"{{"jsonrpc": "2.0", "id": 4, "method": "textDocument/documentSymbol", "params": {{{}}}}}}}"#, uri),
+        &format!(r#"{{"jsonrpc": "2.0", "id": 5, "method": "textDocument/rename", "params": {{{}}}, "position": {{"line": 0, "character": 4}}, "newName": "z"}}}}"#, uri),
+        r#"{"jsonrpc": "2.0", "id": 6, "method": "workspace/unknown"}"#,
+        r#"{"jsonrpc": "2.0", "id": 7, "method": "shutdown"}"#,
+        r#"{"jsonrpc": "2.0", "method": "exit"}"#,
+        r#"{"jsonrpc": "2.0", "id": 8, "method": "shutdown"}"#,
+    ]);
+    let result = |i: usize| replies[i].get("result").map(|r| r.to_string()).unwrap_or_default();
+    let diagnostics = |i: usize| replies[i].get("params").and_then(|p| p.get("diagnostics")).map(|d| d.to_string()).unwrap_or_default();
+    // Nothing comes back after `exit`
+    assert_eq!(replies.len(), 9);
+    assert!(result(0).contains(r#""definitionProvider":true"#), "{}", result(0));
+
+    // Opening a broken file reports it, the edit fixing it clears it
+    assert_eq!(diagnostics(1), r#"[{"range":{"start":{"line":1,"character":12},"end":{"line":1,"character":13}},"severity":1,"source":"nova","message":"No prefix parse function for Semicolon"}]"#);
+    assert_eq!(diagnostics(2), "[]");
+
+    assert_eq!(result(3), r#"{"uri":"file:///a.nv","range":{"start":{"line":0,"character":4},"end":{"line":0,"character":5}}}"#);
+    assert_eq!(result(4), r#"{"contents":{"kind":"markdown","value":"```nova\nlet x: int\n```"}}"#);
+    let symbols = &replies[5].get("result").and_then(|r| r.as_array()).unwrap();
+    let names: Vec<_> = symbols.iter().map(|s| s.get("name").and_then(|n| n.as_str()).unwrap()).collect();
+    assert_eq!(names, ["x", "y"]);
+    assert_eq!(result(6), r#"{"changes":{"file:///a.nv":[{"range":{"start":{"line":0,"character":4},"end":{"line":0,"character":5}},"newText":"z"},{"range":{"start":{"line":1,"character":8},"end":{"line":1,"character":9}},"newText":"z"}]}}"#);
+    assert_eq!(replies[7].get("error").map(|e| e.to_string()).unwrap(), r#"{"code":-32601,"message":"method not found: workspace/unknown"}"#);
+    assert_eq!(result(8), "null");
+}
+
+// --- SEMANTIC TOKENS ---
+
+#[test]
+fn semantic_tokens() {
+    let source = "let add = fn(a) { a + 1 }; // sum\nadd(\"s\")";
+    let chars: Vec<char> = source.chars().collect();
+    let classes: Vec<String> = nova_lang::highlight::classify(source).iter()
+        .map(|t| format!("{}:{}", chars[t.span.start..t.span.end].iter().collect::<String>(), t.class.name()))
+        .collect();
+    assert_eq!(classes, [
+        "let:keyword", "add:function", "=:operator", "fn:keyword", "a:parameter", "a:parameter", "+:operator", "1:number",
+        "// sum:comment", "add:function", "\"s\":string",
+    ]);
+
+    // Relative to the token before, as the LSP spec has it
+    let replies = lsp_session(&[
+        &format!(r#"{{"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {{"textDocument": {{"uri": "file:///a.nv", "text": {}}}}}}}"#, nova_lang::json::Json::from(source)),
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "textDocument/semanticTokens/full", "params": {"textDocument": {"uri": "file:///a.nv"}}}"#,
+    ]);
+    let data = replies[1].get("result").and_then(|r| r.get("data")).unwrap().to_string();
+    assert_eq!(data, "[0,0,3,0,0,0,4,3,2,0,0,4,1,6,0,0,2,2,0,0,0,3,1,3,0,0,5,1,3,0,0,2,1,6,0,0,2,1,4,0,0,5,6,7,0,1,0,3,2,0,0,4,3,5,0]");
+}
+
+// --- FFI ---
+
+#[test]
+fn ffi_is_gated() {
+    let ffi = Capabilities { ffi: true, ..Capabilities::none() };
+    assert_error(r#"unsafe { ffi_load("libc.so.6") }"#, "ffi_load: not permitted (needs --allow-ffi)");
+    assert_error(r#"unsafe { ffi_call(0, "abs", [-5]) }"#, "ffi_call: not permitted (needs --allow-ffi)");
+    let outside = eval_granted(r#"ffi_load("libc.so.6")"#, ffi);
+    assert_eq!(outside, Object::error("ffi_load: only allowed inside an unsafe block"));
+    assert_error(r#"ffi_call(0, "abs", [-5], 1)"#, "ffi_call: argument 4: expected string, found int");
+
+    let loaded = eval_granted(r#"unsafe { let libc = ffi_load("libc.so.6"); [ffi_call(libc, "abs", [-5]), ffi_call(libc, "strlen", ["four"])] }"#, ffi);
+    if cfg!(all(feature = "ffi", target_os = "linux")) {
+        assert_eq!(loaded.to_string(), "[5, 4]");
+        let bad = eval_granted(r#"unsafe { ffi_call(ffi_load("libc.so.6"), "abs", [-5], "float") }"#, ffi);
+        assert_eq!(bad, Object::error("ffi_call: unknown return type 'float' (int, string or void)"));
+        let bad = eval_granted(r#"unsafe { ffi_call(3, "abs", [-5]) }"#, ffi);
+        assert_eq!(bad, Object::error("ffi_call: invalid library handle 3"));
+    } else if !cfg!(feature = "ffi") {
+        assert_eq!(loaded, Object::error("ffi_load: FFI support is not compiled in (build with the `ffi` feature)"));
+    }
+}
+
+// --- UNSAFE ---
+
+#[test]
+fn gated_builtins_need_an_unsafe_block() {
+    let dir = std::env::temp_dir().join(format!("nova_unsafe_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("a.txt");
+    std::fs::write(&path, "hi").unwrap();
+    let fs = Capabilities { fs: true, ..Capabilities::none() };
+    let run = |body: &str| eval_granted(&format!("let path = {:?};\n{}", path.display().to_string(), body), fs).to_string();
+
+    // Granting the capability isn't enough on its own
+    assert_eq!(run("read_bytes(path)"), "ERROR: read_bytes: only allowed inside an unsafe block");
+    assert_eq!(run("unsafe { len(read_bytes(path)) }"), "2");
+    // Functions called from the block count as inside it, wherever they
+    // were defined; once it ends, so does the permission
+    assert_eq!(run("let read = fn(p) { len(read_bytes(p)) }; unsafe { read(path) }"), "2");
+    assert_eq!(run("let read = fn(p) { len(read_bytes(p)) }; unsafe { read(path) }; read(path)"),
+        "ERROR: read_bytes: only allowed inside an unsafe block");
+    // An error leaving the block ends it too
+    let mut env = Environment::with_capabilities(fs);
+    for (source, expected) in [("unsafe { 1 / 0 }", "ERROR: division by zero: 1 / 0"), ("read_bytes(\"x\")", "ERROR: read_bytes: only allowed inside an unsafe block")] {
+        let program = Parser::new(Lexer::new(source.to_string())).parse_program();
+        assert_eq!(eval_program(&program, &mut env).to_string(), expected);
+    }
+    assert_eq!(run("let f = fn() { unsafe { return 1; } }; f(); read_bytes(path)"),
+        "ERROR: read_bytes: only allowed inside an unsafe block");
+    // An unsafe block opens no scope, and builtins that stay inside the
+    // interpreter don't need one
+    assert_eq!(run("unsafe { let n = 3; }; n + len(\"ab\")"), "5");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    for gated in ["ffi_load(\"x\")", "spawn(\"ls\", [])", "http_get(\"http://x\")", "tcp_connect(\"x\", 1)", "glob(\"*\")", "write_bytes(\"x\", bytes([]))"] {
+        let result = eval_granted(gated, Capabilities::all()).to_string();
+        assert!(result.ends_with(": only allowed inside an unsafe block"), "{}: {}", gated, result);
+    }
+}
+
+// --- ZONES ---
+
+#[test]
+fn zones_scope_and_limits() {
+    // Outer bindings are visible, nothing bound inside survives
+    assert_int("let x = 2; let y = zone { let x = x * 10; x + 1 }; x + y", 23);
+    assert_eq!(eval("zone { let inner = 1; }; inner"), Object::Null);
+
+    assert_int("zone(steps: 100, depth: 10) { let f = fn(n) { n * 2 }; f(21) }", 42);
+    assert_error("zone(steps: 5) { let a = 1; let b = 2; let c = 3; let d = 4; let e = 5; let f = 6; }", "zone: step limit exceeded");
+    assert_error("let down = fn(n) { down(n + 1) }; zone(depth: 20) { down(0) }", "zone: call depth limit exceeded");
+    // Limits count from where the zone starts, and end with it
+    assert_int("let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; zone(depth: 3) { f(2) }", 2);
+    assert_int("let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; zone(depth: 3) { f(1) }; f(30)", 30);
+    assert_int("let f = fn() { zone(depth: 1) { 1 } }; f()", 1);
+    // An inner zone can't get more than the one around it
+    assert_error("zone(steps: 50) { zone(steps: 1000) { loop {} } }", "zone: step limit exceeded");
+    assert_error("zone(depth: 2) { let f = fn(n) { zone(depth: 100) { f(n + 1) } }; f(0) }", "zone: call depth limit exceeded");
+
+    assert_error("zone(steps: -1) { 1 }", "zone: steps must be a non-negative int, found int");
+    assert_error("zone(depth: \"deep\") { 1 }", "zone: depth must be a non-negative int, found string");
+    let mut p = Parser::new(Lexer::new("zone(time: 10) { 1 }".to_string()));
+    p.parse_program();
+    assert_eq!(p.errors[0].message, "Unknown zone limit 'time' (expected one of: steps, depth)");
+}
+
+// --- PROCESSES ---
+
+#[test]
+#[cfg(unix)]
+fn spawned_programs_report_status_and_output() {
+    let process = Capabilities { process: true, ..Capabilities::none() };
+    let result = eval_granted(r#"unsafe { spawn("sh", ["-c", "echo out; echo err >&2; exit 3"]) }"#, process);
+    assert_eq!(result.to_string(), r#"{status: 3, stdout: "out\n", stderr: "err\n"}"#);
+    let result = eval_granted(r#"unsafe { spawn("true")["status"] }"#, process);
+    assert_eq!(result, Object::Integer(0));
+    // Killed by a signal: no exit code
+    let result = eval_granted(r#"unsafe { spawn("sh", ["-c", "kill -9 $$"])["status"] }"#, process);
+    assert_eq!(result, Object::Null);
+
+    let result = eval_granted(r#"unsafe { spawn("/no/such/program") }"#, process);
+    assert!(matches!(&result, Object::Error(msg) if msg.starts_with("spawn: /no/such/program: ")), "{}", result);
+    assert_error(r#"unsafe { spawn("true") }"#, "spawn: not permitted (needs --allow-process)");
+    assert_error(r#"unsafe { spawn("echo", [1]) }"#, "spawn: argument 2: expected string, found int");
+}
+
+// --- TIME ---
+
+#[test]
+fn clocks_need_the_time_capability() {
+    for call in ["time()", "clock_ms()", "now()", "sleep(1)", "sleep_async(1)"] {
+        let name = &call[..call.find('(').unwrap()];
+        assert_error(call, &format!("{}: not permitted (needs the time capability)", name));
+    }
+    // Dates someone else made don't read the clock
+    assert_int("timestamp(date(1970, 1, 2))", 86400);
+
+    let time = Capabilities { time: true, ..Capabilities::none() };
+    let result = eval_granted("let t = clock_ms(); sleep(5); [time() > 1700000000, clock_ms() - t > 4, sleep(0)]", time);
+    assert_eq!(result.to_string(), "[true, true, null]");
+    assert_eq!(eval_granted("sleep(-1)", time).to_string(), "ERROR: sleep: negative duration -1");
+}
+
+// --- RANDOM NUMBERS ---
+
+#[test]
+fn seeded_random_numbers_repeat() {
+    // Pinned, so a change to the generator shows up here
+    let rolls = "rand_seed(42); let a = [rand_int(1, 6), rand_int(1, 6), rand_int(1, 6), rand_int(1, 6), rand_int(1, 6)]; a";
+    assert_eq!(eval(rolls).to_string(), "[1, 1, 6, 6, 5]");
+    assert_int("rand_seed(42); rand()", 773499382201279371);
+    assert_int("rand_seed(-1); rand_int(-9223372036854775807, 9223372036854775807)", 1104825383502392585);
+
+    // Seeding again starts the sequence over; another seed gives another
+    assert_eq!(eval("rand_seed(7); let a = [rand(), rand()]; rand_seed(7); a == [rand(), rand()]"), Object::Boolean(true));
+    assert_eq!(eval("rand_seed(7); let a = rand(); rand_seed(8); a == rand()"), Object::Boolean(false));
+    // Unseeded runs don't repeat each other
+    assert_ne!(eval("rand()"), eval("rand()"));
+    assert_int("rand_int(3, 3)", 3);
+    assert_error("rand_int(2, 1)", "rand_int: empty range 2..1");
+}
+
+// --- LISTING BINDINGS ---
+
+#[test]
+fn bindings_are_listed_by_name() {
+    let program = Parser::new(Lexer::new("let b = 2; let a = \"x\"; let b = b + 1; zone { let hidden = 1; }".to_string())).parse_program();
+    let mut env = Environment::new();
+    eval_program(&program, &mut env);
+    let bindings: Vec<String> = env.iter().map(|(name, value)| format!("{} = {}", name, value)).collect();
+    assert_eq!(bindings, ["a = x", "b = 3"]);
+    assert_eq!(Environment::new().iter().count(), 0);
+}
+
+#[test]
+#[cfg(feature = "repl")]
+fn repl_lists_its_environment() {
+    use nova_lang::interpreter::Interpreter;
+    use nova_lang::repl::Repl;
+    let mut repl = Repl::new(Interpreter::new());
+    let mut out = Vec::new();
+    repl.run(":env\nlet f = fn(x) { x };\nlet n = f(4);\n:env\n".as_bytes(), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), ">> >> fn(x) { x }\n>> 4\n>> _ = 4\n_1 = fn(x) { x }\n_2 = 4\nf = fn(x) { x }\nn = 4\n>> ");
+    assert_eq!(repl.env().iter().map(|(name, _)| name).collect::<Vec<_>>(), ["_", "_1", "_2", "f", "n"]);
+}